@@ -0,0 +1,36 @@
+/*
+ * C API for php-switcher (build the library with the `capi` feature).
+ *
+ * Queries return a JSON document using the same envelope as `--json`
+ * output: {"schema_version": 1, "kind": "...", "data": {...}}.
+ * Returned strings are owned by the caller and must be released with
+ * php_switcher_free(). NULL means failure; see php_switcher_last_error().
+ */
+
+#ifndef PHP_SWITCHER_H
+#define PHP_SWITCHER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Tracked versions ("version_list") */
+char *php_switcher_list(void);
+
+/* Version a pattern such as "8.2" resolves to ("resolved_version"), without switching */
+char *php_switcher_resolve(const char *pattern);
+
+/* Active version ("current_state") */
+char *php_switcher_current(void);
+
+/* Error of the last failed call on this thread, or NULL */
+char *php_switcher_last_error(void);
+
+/* Release a string returned by any of the functions above */
+void php_switcher_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PHP_SWITCHER_H */
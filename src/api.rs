@@ -0,0 +1,400 @@
+// Long-running JSON-RPC API module
+//
+// `php-switcher api` reads one JSON-RPC 2.0 request per line on stdin and writes
+// one response per line on stdout. Editor extensions and GUIs keep the process
+// resident instead of spawning the CLI for every query. When the active version
+// changes (through this API or another terminal) a `state_changed` notification
+// is pushed without being asked for.
+
+use crate::config::Config;
+use crate::history::Trigger;
+use crate::output::{Envelope, VersionInfo, VersionList};
+use crate::{composer, config, switcher};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the background watcher checks for external switches
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    fn success(id: Value, result: Value) -> Self {
+        Self { jsonrpc: "2.0", id, result: Some(result), error: None }
+    }
+
+    fn failure(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(RpcError { code, message: message.into() }),
+        }
+    }
+}
+
+/// Server-initiated message without an id
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Notification {
+    pub jsonrpc: &'static str,
+    pub method: &'static str,
+    pub params: Value,
+}
+
+/// Output of the `current` method and `state_changed` notifications
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CurrentState {
+    pub version: Option<String>,
+    #[serde(default, with = "crate::paths::option")]
+    pub target: Option<PathBuf>,
+}
+
+/// Output of the `switch` method
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SwitchResult {
+    pub version: String,
+    #[serde(with = "crate::paths::path")]
+    pub primary_path: PathBuf,
+    pub links: Vec<String>,
+    pub shims: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PatternParams {
+    #[serde(alias = "version")]
+    pattern: String,
+}
+
+/// Run the API loop on stdin/stdout until EOF or a `shutdown` request
+pub fn run() -> Result<()> {
+    let bin_dir = switcher::get_bin_dir()?;
+    let stdout = Arc::new(Mutex::new(std::io::stdout()));
+    let last_state = Arc::new(Mutex::new(current_state(&config::load_config()?, &bin_dir)));
+
+    spawn_watcher(bin_dir.clone(), Arc::clone(&stdout), Arc::clone(&last_state));
+
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let response = Response::failure(Value::Null, PARSE_ERROR, format!("Invalid request: {}", e));
+                write_message(&stdout, &response)?;
+                continue;
+            }
+        };
+
+        if request.method == "shutdown" {
+            if let Some(id) = request.id {
+                write_message(&stdout, &Response::success(id, Value::Null))?;
+            }
+            break;
+        }
+
+        // Hold the state lock while switching so the watcher can't report our own change first
+        let mut last = last_state.lock().unwrap();
+        let response = handle_request(&request, &bin_dir);
+
+        // Requests without an id are notifications and get no response
+        if request.id.is_some() {
+            write_message(&stdout, &response)?;
+        }
+
+        // Push the new state immediately instead of waiting for the watcher
+        if request.method == "switch" && response.error.is_none() {
+            let state = current_state(&config::load_config()?, &bin_dir);
+            *last = state.clone();
+            write_message(&stdout, &state_changed(&state))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Dispatch a single request to its handler
+pub fn handle_request(request: &Request, bin_dir: &Path) -> Response {
+    let id = request.id.clone().unwrap_or(Value::Null);
+
+    let result = match request.method.as_str() {
+        "list" => config::load_config().map(|config| envelope("version_list", &list(&config, bin_dir))),
+        "current" => config::load_config().map(|config| envelope("current_state", &current_state(&config, bin_dir))),
+        "resolve" => match parse_pattern(&request.params) {
+            Ok(pattern) => config::load_config().map(|config| {
+                let resolved = resolve(&config, bin_dir, &pattern);
+                envelope("resolved_version", &resolved)
+            }),
+            Err(message) => return Response::failure(id, INVALID_PARAMS, message),
+        },
+        "switch" => match parse_pattern(&request.params) {
+            Ok(pattern) => switch(&pattern, bin_dir).map(|result| envelope("switch_result", &result)),
+            Err(message) => return Response::failure(id, INVALID_PARAMS, message),
+        },
+        other => return Response::failure(id, METHOD_NOT_FOUND, format!("Unknown method '{}'", other)),
+    };
+
+    match result {
+        Ok(value) => Response::success(id, value),
+        Err(e) => Response::failure(id, INTERNAL_ERROR, e.to_string()),
+    }
+}
+
+/// Build the version list for the `list` method
+pub fn list(config: &Config, bin_dir: &Path) -> VersionList {
+    let current = switcher::active_entry(config, bin_dir).map(|entry| entry.version.clone());
+    let versions = config
+        .versions
+        .iter()
+        .map(|entry| VersionInfo::from_entry(entry, current.as_deref() == Some(entry.version.as_str())))
+        .collect();
+
+    VersionList {
+        current,
+        default: config.default_entry().map(|entry| entry.version.clone()),
+        versions,
+        runtimes: config.runtimes.clone(),
+        containers: config.containers.clone(),
+    }
+}
+
+/// Resolve a pattern against the cached versions without switching
+///
+/// Besides plain versions ("8.2") this accepts composer constraints
+/// ("^8.1", ">=8.1 <8.3"), resolving to the newest version satisfying them.
+pub fn resolve(config: &Config, bin_dir: &Path, pattern: &str) -> Option<VersionInfo> {
+    let active = switcher::active_entry(config, bin_dir).map(|entry| entry.version.clone());
+    config
+        .find_version(pattern)
+        .or_else(|| composer::newest_satisfying(config, pattern).ok().flatten())
+        .map(|entry| VersionInfo::from_entry(entry, active.as_deref() == Some(entry.version.as_str())))
+}
+
+/// Answer a one-shot request of the form `{"op": "<method>", ...params}`
+///
+/// The ops are exactly the JSON-RPC methods, with the params inlined, so
+/// wrappers written against `php-switcher query` carry over to the daemon.
+pub fn query(request: &str, bin_dir: &Path) -> Response {
+    let mut fields = match serde_json::from_str::<Value>(request) {
+        Ok(Value::Object(fields)) => fields,
+        Ok(_) => return Response::failure(Value::Null, INVALID_PARAMS, "Expected a JSON object"),
+        Err(e) => return Response::failure(Value::Null, PARSE_ERROR, format!("Invalid JSON: {}", e)),
+    };
+
+    let id = fields.remove("id");
+    let Some(Value::String(op)) = fields.remove("op") else {
+        return Response::failure(id.unwrap_or(Value::Null), INVALID_PARAMS, "Expected {\"op\": \"<method>\"}");
+    };
+
+    handle_request(&Request { id, method: op, params: Value::Object(fields) }, bin_dir)
+}
+
+/// Describe the version the switcher bin directory currently points to
+pub fn current_state(config: &Config, bin_dir: &Path) -> CurrentState {
+    CurrentState {
+        version: switcher::active_entry(config, bin_dir).map(|entry| entry.version.clone()),
+        target: std::fs::read_link(bin_dir.join("php")).ok(),
+    }
+}
+
+fn switch(pattern: &str, bin_dir: &Path) -> Result<SwitchResult> {
+    let mut config = config::load_config()?;
+
+    if config.find_version(pattern).is_none() {
+        switcher::rescan(&mut config)?;
+    }
+
+    let entry = config
+        .find_version(pattern)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("PHP {} not found", pattern))?;
+
+    let options = switcher::SwitchOptions {
+        trigger: Some(Trigger::Api),
+        ..Default::default()
+    };
+    let outcome = switcher::activate(&mut config, &entry, bin_dir, &options)?;
+
+    Ok(SwitchResult {
+        version: outcome.version,
+        primary_path: outcome.primary_path,
+        links: outcome.links.into_iter().map(|link| link.name).collect(),
+        shims: outcome.shims.into_iter().map(|shim| shim.name).collect(),
+    })
+}
+
+fn parse_pattern(params: &Value) -> std::result::Result<String, String> {
+    serde_json::from_value::<PatternParams>(params.clone())
+        .map(|p| p.pattern)
+        .map_err(|_| "Expected params {\"pattern\": \"<version>\"}".to_string())
+}
+
+fn envelope<T: Serialize>(kind: &str, data: &T) -> Value {
+    serde_json::to_value(Envelope::new(kind, data)).unwrap_or(Value::Null)
+}
+
+fn state_changed(state: &CurrentState) -> Notification {
+    Notification {
+        jsonrpc: "2.0",
+        method: "state_changed",
+        params: envelope("current_state", state),
+    }
+}
+
+fn write_message<T: Serialize>(stdout: &Mutex<std::io::Stdout>, message: &T) -> Result<()> {
+    let line = serde_json::to_string(message)?;
+    let mut out = stdout.lock().unwrap();
+    writeln!(out, "{}", line)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Poll the bin directory and push a notification when another process switches
+fn spawn_watcher(bin_dir: PathBuf, stdout: Arc<Mutex<std::io::Stdout>>, last_state: Arc<Mutex<CurrentState>>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(WATCH_INTERVAL);
+
+        let Ok(config) = config::load_config() else { continue };
+        let state = current_state(&config, &bin_dir);
+
+        let changed = {
+            let mut last = last_state.lock().unwrap();
+            if *last != state {
+                *last = state.clone();
+                true
+            } else {
+                false
+            }
+        };
+
+        if changed && write_message(&stdout, &state_changed(&state)).is_err() {
+            break;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VersionEntry;
+    use tempfile::TempDir;
+
+    fn sample_config(dir: &Path) -> Config {
+        let mut config = Config::default();
+        for version in ["8.3.1", "8.2.12"] {
+            config.versions.push(VersionEntry {
+                version: version.to_string(),
+                paths: vec![dir.join(format!("php{}", version))],
+                source: "auto".to_string(),
+                ..VersionEntry::default()
+            });
+        }
+        config
+    }
+
+    #[test]
+    fn test_resolve_picks_matching_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = sample_config(temp_dir.path());
+
+        let resolved = resolve(&config, temp_dir.path(), "8.2").unwrap();
+        assert_eq!(resolved.version, "8.2.12");
+        assert!(!resolved.active);
+
+        assert!(resolve(&config, temp_dir.path(), "7.4").is_none());
+        assert_eq!(resolve(&config, temp_dir.path(), "^8.1").unwrap().version, "8.3.1");
+        assert_eq!(resolve(&config, temp_dir.path(), ">=8.1 <8.3").unwrap().version, "8.2.12");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_current_state_follows_symlink() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = sample_config(temp_dir.path());
+        let bin_dir = temp_dir.path().join("bin");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+
+        assert_eq!(current_state(&config, &bin_dir).version, None);
+
+        std::os::unix::fs::symlink(temp_dir.path().join("php8.3.1"), bin_dir.join("php")).unwrap();
+        assert_eq!(current_state(&config, &bin_dir).version.as_deref(), Some("8.3.1"));
+
+        let listed = list(&config, &bin_dir);
+        assert_eq!(listed.current.as_deref(), Some("8.3.1"));
+        assert!(listed.versions[0].active);
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let temp_dir = TempDir::new().unwrap();
+        let request: Request = serde_json::from_str(r#"{"jsonrpc":"2.0","id":7,"method":"bogus"}"#).unwrap();
+
+        let response = handle_request(&request, temp_dir.path());
+        assert_eq!(response.id, Value::from(7));
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_query_inlines_params() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let response = query(r#"{"op":"resolve"}"#, temp_dir.path());
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        let response = query(r#"{"pattern":"8.2"}"#, temp_dir.path());
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        let response = query("not json", temp_dir.path());
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+
+        let response = query(r#"{"op":"bogus","id":"a"}"#, temp_dir.path());
+        assert_eq!(response.id, Value::from("a"));
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_resolve_requires_pattern() {
+        let temp_dir = TempDir::new().unwrap();
+        let request: Request = serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"method":"resolve","params":{}}"#).unwrap();
+
+        let response = handle_request(&request, temp_dir.path());
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+    }
+}
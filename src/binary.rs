@@ -0,0 +1,282 @@
+// Executable header module
+//
+// A PHP build restored from a backup or copied from another machine can be
+// for the wrong CPU or OS (an arm64 Mach-O on an x86_64 Linux server). Running
+// it only fails with "Exec format error", which `php -v` during verification
+// swallows, so the switch would leave a `php` that can't start. Reading the
+// ELF, Mach-O or PE header up front catches that before anything is linked.
+//
+// The version a build reports is compiled into it as well, so `scan --no-exec`
+// can tell versions apart without running files it found in writable places.
+
+use anyhow::{anyhow, Result};
+use std::io::Read;
+use std::path::Path;
+
+/// Operating system family an executable format belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    /// ELF: Linux and the BSDs
+    Unix,
+    MacOs,
+    Windows,
+}
+
+impl Os {
+    fn name(&self) -> &'static str {
+        match self {
+            Os::Unix => "Linux/BSD (ELF)",
+            Os::MacOs => "macOS (Mach-O)",
+            Os::Windows => "Windows (PE)",
+        }
+    }
+
+    /// The family of the OS this program runs on
+    pub fn current() -> Self {
+        match std::env::consts::OS {
+            "macos" | "ios" => Os::MacOs,
+            "windows" => Os::Windows,
+            _ => Os::Unix,
+        }
+    }
+}
+
+/// What an executable header says it was built for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryTarget {
+    pub os: Os,
+    /// Architectures as in `std::env::consts::ARCH`; several for universal binaries,
+    /// empty when the header names one this module doesn't know
+    pub arches: Vec<&'static str>,
+}
+
+impl BinaryTarget {
+    /// Read the target from the start of a file; `None` for scripts and unknown formats
+    pub fn parse(header: &[u8]) -> Option<Self> {
+        let u16_at = |offset: usize, big: bool| {
+            let bytes = [*header.get(offset)?, *header.get(offset + 1)?];
+            Some(if big { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+        };
+        let u32_at = |offset: usize, big: bool| {
+            let bytes: [u8; 4] = header.get(offset..offset + 4)?.try_into().ok()?;
+            Some(if big { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+        };
+
+        match header.get(..4)? {
+            [0x7f, b'E', b'L', b'F'] => {
+                let big = *header.get(5)? == 2;
+                let arches = elf_arch(u16_at(18, big)?).into_iter().collect();
+                Some(BinaryTarget { os: Os::Unix, arches })
+            }
+            [0xcf, 0xfa, 0xed, 0xfe] | [0xce, 0xfa, 0xed, 0xfe] => {
+                let arches = macho_arch(u32_at(4, false)?).into_iter().collect();
+                Some(BinaryTarget { os: Os::MacOs, arches })
+            }
+            [0xca, 0xfe, 0xba, 0xbe] => {
+                // Universal binary: a list of 20-byte entries, each starting with a CPU type
+                let count = u32_at(4, true)? as usize;
+                let arches = (0..count.min(8)).filter_map(|i| macho_arch(u32_at(8 + i * 20, true)?)).collect();
+                Some(BinaryTarget { os: Os::MacOs, arches })
+            }
+            [b'M', b'Z', ..] => Some(BinaryTarget { os: Os::Windows, arches: Vec::new() }),
+            _ => None,
+        }
+    }
+
+    /// Why this binary can't run on `os`/`arch`, or `None` if it can (or it's unclear)
+    pub fn mismatch(&self, os: Os, arch: &str) -> Option<String> {
+        if self.os != os {
+            return Some(format!("it is a {} binary", self.os.name()));
+        }
+        if self.arches.is_empty() || self.arches.iter().any(|a| runs_on(a, os, arch)) {
+            return None;
+        }
+        Some(format!("it is built for {}, this machine is {}", self.arches.join("/"), arch))
+    }
+}
+
+/// Whether code for `binary_arch` runs on an `arch` CPU, counting 32-bit modes and Rosetta
+fn runs_on(binary_arch: &str, os: Os, arch: &str) -> bool {
+    binary_arch == arch
+        || (arch == "x86_64" && binary_arch == "x86")
+        || (arch == "aarch64" && binary_arch == "arm" && os == Os::Unix)
+        || (arch == "aarch64" && binary_arch == "x86_64" && os == Os::MacOs)
+}
+
+fn elf_arch(machine: u16) -> Option<&'static str> {
+    Some(match machine {
+        0x03 => "x86",
+        0x3e => "x86_64",
+        0x28 => "arm",
+        0xb7 => "aarch64",
+        0xf3 => "riscv64",
+        0x15 => "powerpc64",
+        0x16 => "s390x",
+        _ => return None,
+    })
+}
+
+fn macho_arch(cpu_type: u32) -> Option<&'static str> {
+    Some(match cpu_type {
+        0x0000_0007 => "x86",
+        0x0100_0007 => "x86_64",
+        0x0000_000c => "arm",
+        0x0100_000c => "aarch64",
+        _ => return None,
+    })
+}
+
+/// Read the first bytes of a file, enough for any header `BinaryTarget::parse` knows
+fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut header = [0u8; 64];
+    let len = std::fs::File::open(path).and_then(|mut file| file.read(&mut header))?;
+    Ok(header[..len].to_vec())
+}
+
+/// What the binary at `path` was built for, if it's an executable this module knows
+pub fn read_target(path: &Path) -> Option<BinaryTarget> {
+    BinaryTarget::parse(&read_header(path).ok()?)
+}
+
+/// Every PHP build carries its version in this header string (`SAPI_PHP_VERSION_HEADER`)
+const VERSION_MARKER: &[u8] = b"X-Powered-By: PHP/";
+
+/// Longest version string taken after the marker (`8.1.2-1ubuntu2.14` and the like)
+const MAX_VERSION_LEN: usize = 64;
+
+/// How much of a binary is read at a time while looking for the marker
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// The version string following the marker, if `bytes` holds all of it up to its NUL
+pub fn find_php_version(bytes: &[u8]) -> Option<String> {
+    let mut rest = bytes;
+    while let Some(start) = rest.windows(VERSION_MARKER.len()).position(|window| window == VERSION_MARKER) {
+        rest = &rest[start + VERSION_MARKER.len()..];
+        let Some(end) = rest.iter().take(MAX_VERSION_LEN + 1).position(|&b| b == 0) else { continue };
+        let version = &rest[..end];
+        if version.first().is_some_and(u8::is_ascii_digit) && version.iter().all(u8::is_ascii_graphic) {
+            return Some(String::from_utf8_lossy(version).into_owned());
+        }
+    }
+    None
+}
+
+/// Read the PHP version compiled into the binary at `path`, without running it
+///
+/// `Ok(None)` for scripts, unknown formats and executables that aren't PHP.
+pub fn read_php_version(path: &Path) -> std::io::Result<Option<String>> {
+    if BinaryTarget::parse(&read_header(path)?).is_none() {
+        return Ok(None);
+    }
+
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut kept = 0;
+    loop {
+        let read = file.read(&mut buffer[kept..])?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let filled = kept + read;
+        if let Some(version) = find_php_version(&buffer[..filled]) {
+            return Ok(Some(version));
+        }
+        // The marker or the version may straddle the end of this chunk
+        kept = filled.min(VERSION_MARKER.len() + MAX_VERSION_LEN);
+        buffer.copy_within(filled - kept..filled, 0);
+    }
+}
+
+/// Fail if the binary at `path` is for another OS or CPU than this machine
+pub fn check_runnable(path: &Path) -> Result<()> {
+    let header = read_header(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    check_header(path, &header, Os::current(), std::env::consts::ARCH)
+}
+
+fn check_header(path: &Path, header: &[u8], os: Os, arch: &str) -> Result<()> {
+    let Some(target) = BinaryTarget::parse(header) else { return Ok(()) };
+    // WSL's interop runs Windows builds on its mounted drives, as `list` says
+    if target.os == Os::Windows && os == Os::Unix && crate::detector::is_wsl_windows_binary(path) {
+        return Ok(());
+    }
+    match target.mismatch(os, arch) {
+        Some(reason) => Err(anyhow!("{} can't run on this machine: {}", path.display(), reason)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elf(machine: u16) -> Vec<u8> {
+        let mut header = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+        header.resize(18, 0);
+        header.extend(machine.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        assert_eq!(BinaryTarget::parse(&elf(0x3e)), Some(BinaryTarget { os: Os::Unix, arches: vec!["x86_64"] }));
+        assert_eq!(BinaryTarget::parse(&elf(0xb7)).unwrap().arches, ["aarch64"]);
+
+        let thin = [0xcf, 0xfa, 0xed, 0xfe, 0x0c, 0x00, 0x00, 0x01];
+        assert_eq!(BinaryTarget::parse(&thin), Some(BinaryTarget { os: Os::MacOs, arches: vec!["aarch64"] }));
+
+        let mut fat = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2];
+        fat.extend([0x01, 0, 0, 0x07]);
+        fat.resize(28, 0);
+        fat.extend([0x01, 0, 0, 0x0c]);
+        assert_eq!(BinaryTarget::parse(&fat).unwrap().arches, ["x86_64", "aarch64"]);
+
+        assert_eq!(BinaryTarget::parse(b"#!/bin/sh\nexec php8.2 \"$@\"\n"), None);
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let arm_elf = BinaryTarget::parse(&elf(0xb7)).unwrap();
+        assert_eq!(
+            arm_elf.mismatch(Os::Unix, "x86_64").as_deref(),
+            Some("it is built for aarch64, this machine is x86_64")
+        );
+        assert_eq!(arm_elf.mismatch(Os::Unix, "aarch64"), None);
+        assert_eq!(arm_elf.mismatch(Os::MacOs, "aarch64").as_deref(), Some("it is a Linux/BSD (ELF) binary"));
+
+        // Rosetta runs Intel builds on Apple silicon, but not the other way round
+        let intel_mac = BinaryTarget { os: Os::MacOs, arches: vec!["x86_64"] };
+        assert_eq!(intel_mac.mismatch(Os::MacOs, "aarch64"), None);
+        let arm_mac = BinaryTarget { os: Os::MacOs, arches: vec!["aarch64"] };
+        assert!(arm_mac.mismatch(Os::MacOs, "x86_64").is_some());
+
+        // Unknown machine types get the benefit of the doubt
+        assert_eq!(BinaryTarget::parse(&elf(0x1234)).unwrap().mismatch(Os::Unix, "x86_64"), None);
+    }
+
+    #[test]
+    fn test_check_header_allows_windows_builds_under_wsl() {
+        let pe = b"MZ\x90\x00";
+        assert!(check_header(Path::new("/mnt/c/tools/php83/php.exe"), pe, Os::Unix, "x86_64").is_ok());
+        assert!(check_header(Path::new("/opt/php/php.exe"), pe, Os::Unix, "x86_64").is_err());
+        assert!(check_header(Path::new("/mnt/c/tools/php83/php.exe"), pe, Os::MacOs, "aarch64").is_err());
+    }
+
+    #[test]
+    fn test_read_php_version() {
+        assert_eq!(find_php_version(b"..X-Powered-By: PHP/\0..X-Powered-By: PHP/8.1.2-1ubuntu2.14\0.."), Some("8.1.2-1ubuntu2.14".to_string()));
+        assert_eq!(find_php_version(b"X-Powered-By: PHP/8.2.1"), None);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let php = temp_dir.path().join("php");
+        // The marker straddles the first chunk boundary
+        let mut content = elf(0x3e);
+        content.resize(CHUNK_SIZE - 5, b'x');
+        content.extend(b"X-Powered-By: PHP/8.3.4\0");
+        std::fs::write(&php, &content).unwrap();
+        assert_eq!(read_php_version(&php).unwrap().as_deref(), Some("8.3.4"));
+
+        // Scripts aren't searched, even when they mention a version
+        std::fs::write(&php, "#!/bin/sh\necho 'X-Powered-By: PHP/8.3.4\0'\n").unwrap();
+        assert_eq!(read_php_version(&php).unwrap(), None);
+    }
+}
@@ -0,0 +1,149 @@
+// C API module (feature `capi`)
+//
+// A small C ABI over the typed API in api.rs so non-Rust tooling (Alfred
+// workflows, PHP extensions, editors embedding via FFI) can query state
+// without spawning the CLI. Every query returns a JSON document in the same
+// envelope as `--json` output, as a string owned by the caller that must be
+// released with `php_switcher_free`. NULL means failure; the reason is
+// available from `php_switcher_last_error`. The declarations are in
+// `include/php_switcher.h`.
+
+use crate::output::to_json;
+use crate::{api, config, switcher};
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::path::Path;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Hand a result to C: a newly allocated string, or NULL with the error recorded
+fn into_c(result: Result<String>) -> *mut c_char {
+    let result = result.and_then(|json| CString::new(json).map_err(|e| anyhow!("Invalid output: {}", e)));
+
+    match result {
+        Ok(json) => json.into_raw(),
+        Err(e) => {
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(e.to_string()));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+fn with_state<T>(query: impl FnOnce(&config::Config, &Path) -> Result<T>) -> Result<T> {
+    let config = config::load_config()?;
+    query(&config, &switcher::get_bin_dir()?)
+}
+
+/// `version_list` document for the tracked versions
+pub fn list_json(config: &config::Config, bin_dir: &Path) -> Result<String> {
+    to_json("version_list", &api::list(config, bin_dir))
+}
+
+/// `resolved_version` document for the version a pattern resolves to
+pub fn resolve_json(config: &config::Config, bin_dir: &Path, pattern: &str) -> Result<String> {
+    let info = api::resolve(config, bin_dir, pattern)
+        .ok_or_else(|| anyhow!("No PHP installation found matching '{}'", pattern))?;
+    to_json("resolved_version", &info)
+}
+
+/// `current_state` document for the active version
+pub fn current_json(config: &config::Config, bin_dir: &Path) -> Result<String> {
+    to_json("current_state", &api::current_state(config, bin_dir))
+}
+
+/// List tracked versions as JSON
+#[no_mangle]
+pub extern "C" fn php_switcher_list() -> *mut c_char {
+    into_c(with_state(list_json))
+}
+
+/// Resolve a version pattern (e.g. "8.2") as JSON, without switching
+///
+/// # Safety
+///
+/// `pattern` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn php_switcher_resolve(pattern: *const c_char) -> *mut c_char {
+    if pattern.is_null() {
+        return into_c(Err(anyhow!("pattern is NULL")));
+    }
+
+    // SAFETY: non-NULL and NUL-terminated per the contract above
+    let pattern = unsafe { CStr::from_ptr(pattern) }.to_string_lossy();
+    into_c(with_state(|config, bin_dir| resolve_json(config, bin_dir, &pattern)))
+}
+
+/// Describe the active version as JSON
+#[no_mangle]
+pub extern "C" fn php_switcher_current() -> *mut c_char {
+    into_c(with_state(current_json))
+}
+
+/// Get the error of the last failed call on this thread (NULL if none)
+///
+/// The string must be released with `php_switcher_free`.
+#[no_mangle]
+pub extern "C" fn php_switcher_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_deref() {
+        Some(message) => CString::new(message).map(CString::into_raw).unwrap_or(std::ptr::null_mut()),
+        None => std::ptr::null_mut(),
+    })
+}
+
+/// Release a string returned by this library
+///
+/// # Safety
+///
+/// `string` must be NULL or a pointer returned by a `php_switcher_*`
+/// function that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn php_switcher_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: allocated by CString::into_raw in into_c, per the contract above
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, VersionEntry};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_json_queries() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            versions: vec![VersionEntry {
+                version: "8.2.12".to_string(),
+                paths: vec![PathBuf::from("/usr/bin/php8.2")],
+                source: "auto".to_string(),
+                ..VersionEntry::default()
+            }],
+            ..Config::default()
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&resolve_json(&config, temp_dir.path(), "8.2").unwrap()).unwrap();
+        assert_eq!(json["kind"], "resolved_version");
+        assert_eq!(json["data"]["version"], "8.2.12");
+
+        assert!(resolve_json(&config, temp_dir.path(), "7.4").is_err());
+
+        let json: serde_json::Value = serde_json::from_str(&current_json(&config, temp_dir.path()).unwrap()).unwrap();
+        assert_eq!(json["data"]["version"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_errors_cross_the_boundary() {
+        let result = unsafe { php_switcher_resolve(std::ptr::null()) };
+        assert!(result.is_null());
+
+        let error = php_switcher_last_error();
+        assert_eq!(unsafe { CStr::from_ptr(error) }.to_str().unwrap(), "pattern is NULL");
+        unsafe { php_switcher_free(error) };
+    }
+}
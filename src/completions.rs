@@ -0,0 +1,238 @@
+// Shell completion module
+//
+// The scripts printed by `php-switcher completions <shell>` are thin: on
+// every <Tab> they call the hidden `php-switcher __complete <shell> -- <words...>`
+// command. That answers from the clap command tree plus the cached config,
+// so subcommands, flags, installed versions and tool names all complete
+// without the script going stale or the system being re-scanned.
+//
+// `__complete` is an internal but stable interface for shell integrations:
+// one candidate per line, plain for bash and `value:description` for zsh.
+// It only reads the config and never writes anything.
+
+use crate::config::Config;
+use clap::{Arg, Command};
+
+/// Shells completion scripts can be generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+}
+
+const BASH_SCRIPT: &str = r#"_php_switcher() {
+    local IFS=$'\n'
+    COMPREPLY=($(php-switcher __complete bash -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null))
+}
+complete -o default -F _php_switcher php-switcher
+"#;
+
+const ZSH_SCRIPT: &str = r#"#compdef php-switcher
+_php_switcher() {
+    local -a candidates
+    candidates=("${(@f)$(php-switcher __complete zsh -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    if [[ -n "${candidates[1]}" ]]; then
+        _describe 'php-switcher' candidates
+    else
+        _files
+    fi
+}
+compdef _php_switcher php-switcher
+"#;
+
+/// Get the completion script for a shell
+pub fn script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH_SCRIPT,
+        Shell::Zsh => ZSH_SCRIPT,
+    }
+}
+
+/// A completion candidate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub value: String,
+    pub help: Option<String>,
+}
+
+impl Candidate {
+    fn new(value: impl Into<String>, help: Option<String>) -> Self {
+        Self { value: value.into(), help }
+    }
+
+    /// Format as one line of `__complete` output for a shell
+    pub fn render(&self, shell: Shell) -> String {
+        match (shell, &self.help) {
+            (Shell::Zsh, Some(help)) => format!("{}:{}", self.value.replace(':', "\\:"), help),
+            (Shell::Zsh, None) => self.value.replace(':', "\\:"),
+            (Shell::Bash, _) => self.value.clone(),
+        }
+    }
+}
+
+/// Work out the candidates for the last of `words` (the arguments typed so far)
+pub fn complete(command: &Command, config: &Config, words: &[String]) -> Vec<Candidate> {
+    let Some((current, done)) = words.split_last() else { return Vec::new() };
+
+    // Building propagates global flags to the subcommands
+    let mut command = command.clone();
+    command.build();
+
+    let mut cmd = &command;
+    let mut positionals = 0;
+    let mut expects_value: Option<&Arg> = None;
+
+    for word in done {
+        if expects_value.take().is_some() {
+            continue;
+        }
+
+        if let Some(long) = word.strip_prefix("--") {
+            let arg = cmd.get_arguments().find(|a| a.get_long() == Some(long));
+            expects_value = arg.filter(|a| a.get_action().takes_values());
+        } else if word.starts_with('-') && word.len() > 1 {
+            // Short flags never take values here
+        } else if let Some(sub) = cmd.find_subcommand(word) {
+            cmd = sub;
+            positionals = 0;
+        } else {
+            positionals += 1;
+        }
+    }
+
+    let candidates = if let Some(arg) = expects_value {
+        values_for(arg, config)
+    } else if current.starts_with('-') {
+        cmd.get_arguments()
+            .filter(|a| !a.is_hide_set())
+            .filter_map(|a| {
+                let long = a.get_long()?;
+                Some(Candidate::new(format!("--{}", long), a.get_help().map(|h| h.to_string())))
+            })
+            .collect()
+    } else {
+        let mut candidates = Vec::new();
+        if positionals == 0 {
+            candidates.extend(
+                cmd.get_subcommands()
+                    .filter(|s| !s.is_hide_set())
+                    .map(|s| Candidate::new(s.get_name(), s.get_about().map(|h| h.to_string()))),
+            );
+        }
+        if let Some(arg) = cmd.get_positionals().nth(positionals) {
+            candidates.extend(values_for(arg, config));
+        }
+        candidates
+    };
+
+    candidates.into_iter().filter(|c| c.value.starts_with(current.as_str())).collect()
+}
+
+/// Values an argument accepts, from its definition or the cached config
+fn values_for(arg: &Arg, config: &Config) -> Vec<Candidate> {
+    match arg.get_id().as_str() {
+        "version" | "php_version" => version_candidates(config),
+        "tool" => config.tools.managed.iter().map(|t| Candidate::new(&t.name, None)).collect(),
+        "toolchain" => config
+            .toolchains
+            .iter()
+            .map(|(name, toolchain)| Candidate::new(name, Some(format!("PHP {}", toolchain.php))))
+            .collect(),
+        _ => arg
+            .get_possible_values()
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| Candidate::new(v.get_name(), v.get_help().map(|h| h.to_string())))
+            .collect(),
+    }
+}
+
+/// Installed versions, each minor version followed by its patch releases
+fn version_candidates(config: &Config) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for group in config.grouped_by_minor() {
+        if group.entries.iter().all(|e| e.version != group.minor) {
+            candidates.push(Candidate::new(&group.minor, Some(format!("newest PHP {}", group.minor))));
+        }
+        candidates.extend(
+            group
+                .entries
+                .iter()
+                .map(|e| Candidate::new(&e.version, e.primary_path().map(|p| p.display().to_string()))),
+        );
+    }
+    for entry in &config.versions {
+        for label in &entry.labels {
+            candidates.push(Candidate::new(label, Some(format!("PHP {}", entry.version))));
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ToolEntry, VersionEntry};
+    use std::path::PathBuf;
+
+    fn command() -> Command {
+        Command::new("php-switcher")
+            .arg(Arg::new("php_version"))
+            .arg(Arg::new("config").long("config").global(true))
+            .subcommand(
+                Command::new("use")
+                    .arg(Arg::new("version"))
+                    .arg(Arg::new("force").long("force").action(clap::ArgAction::SetTrue)),
+            )
+            .subcommand(Command::new("tools").subcommand(Command::new("pin").arg(Arg::new("tool"))))
+            .subcommand(Command::new("__complete").hide(true))
+    }
+
+    fn config() -> Config {
+        let entry = |version: &str| VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(format!("/usr/bin/php{}", version))],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+
+        let mut config = Config {
+            versions: vec![entry("8.2.12"), entry("8.2.20"), entry("8.3.4")],
+            ..Config::default()
+        };
+        config.tools.managed.push(ToolEntry {
+            name: "composer".to_string(),
+            original_path: PathBuf::from("/usr/bin/composer"),
+            shebang: "#!/usr/bin/php".to_string(),
+            shim_created: false,
+        });
+        config
+    }
+
+    fn complete_words(words: &[&str]) -> Vec<String> {
+        let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        complete(&command(), &config(), &words).into_iter().map(|c| c.value).collect()
+    }
+
+    #[test]
+    fn test_completes_subcommands_and_versions() {
+        assert_eq!(complete_words(&["t"]), vec!["tools"]);
+        assert_eq!(complete_words(&["use", "8.2"]), vec!["8.2", "8.2.20", "8.2.12"]);
+        assert_eq!(complete_words(&["use", "8.2", ""]), Vec::<String>::new());
+        assert_eq!(complete_words(&["--config", "x", "tools", "pin", ""]), vec!["composer"]);
+    }
+
+    #[test]
+    fn test_completes_flags() {
+        assert_eq!(complete_words(&["use", "--f"]), vec!["--force"]);
+        assert_eq!(complete_words(&["use", "--c"]), vec!["--config"]);
+    }
+
+    #[test]
+    fn test_render_per_shell() {
+        let candidate = Candidate::new("8.2.12", Some("/usr/bin/php8.2".to_string()));
+        assert_eq!(candidate.render(Shell::Bash), "8.2.12");
+        assert_eq!(candidate.render(Shell::Zsh), "8.2.12:/usr/bin/php8.2");
+        assert_eq!(Candidate::new("a:b", None).render(Shell::Zsh), "a\\:b");
+    }
+}
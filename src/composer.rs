@@ -0,0 +1,151 @@
+// Composer integration module
+//
+// Teams that live inside composer get php-switcher through composer scripts:
+// `project composer-scripts` adds `php:use`/`php:check` scripts plus a
+// `pre-install-cmd` guard, and `project check` compares the active PHP with
+// the `require.php` constraint from composer.json.
+
+use crate::config::{Config, VersionEntry};
+use crate::version::PhpVersion;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+/// Name of composer's manifest
+pub const COMPOSER_FILE: &str = "composer.json";
+
+/// Command run by the `php:check` script and the install guard
+pub const CHECK_COMMAND: &str = "php-switcher project check";
+
+/// Events the check is hooked into
+const GUARDED_EVENTS: &[&str] = &["pre-install-cmd"];
+
+/// Find the nearest composer.json, starting at `start` and walking up
+pub fn find_composer_json(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(COMPOSER_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Read and parse a composer.json
+pub fn load(path: &Path) -> Result<Value> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&contents).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Write a composer.json back with composer's own formatting (4-space indent)
+pub fn save(path: &Path, manifest: &Value) -> Result<()> {
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    manifest.serialize(&mut serializer)?;
+    out.push(b'\n');
+
+    std::fs::write(path, out).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Get the `require.php` constraint of a manifest
+pub fn php_constraint(manifest: &Value) -> Option<&str> {
+    manifest.get("require")?.get("php")?.as_str()
+}
+
+/// Pick the newest tracked version satisfying a constraint
+pub fn newest_satisfying<'a>(config: &'a Config, constraint: &str) -> Result<Option<&'a VersionEntry>> {
+    for entry in config.grouped_by_minor().into_iter().flat_map(|group| group.entries) {
+        let Ok(version) = PhpVersion::from_php_output(&format!("PHP {}", entry.version)) else { continue };
+        if version.satisfies(constraint)? {
+            return Ok(Some(entry));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Add the php-switcher scripts to a manifest
+///
+/// Existing scripts are kept; returns the names that were added or changed.
+pub fn add_scripts(manifest: &mut Value) -> Result<Vec<String>> {
+    let root = manifest
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("composer.json is not a JSON object"))?;
+    let scripts = root
+        .entry("scripts")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("composer.json \"scripts\" is not an object"))?;
+
+    let mut changed = Vec::new();
+
+    let commands = [
+        ("php:use", "php-switcher use --if-needed"),
+        ("php:check", CHECK_COMMAND),
+    ];
+    for (name, command) in commands {
+        if scripts.get(name).and_then(Value::as_str) != Some(command) {
+            scripts.insert(name.to_string(), Value::from(command));
+            changed.push(name.to_string());
+        }
+    }
+
+    // Events take a string or a list; keep whatever already runs there
+    for event in GUARDED_EVENTS {
+        let mut list = match scripts.remove(*event) {
+            None => Vec::new(),
+            Some(Value::Array(list)) => list,
+            Some(other) => vec![other],
+        };
+
+        if !list.iter().any(|v| v.as_str() == Some(CHECK_COMMAND)) {
+            list.insert(0, Value::from(CHECK_COMMAND));
+            changed.push(event.to_string());
+        }
+        scripts.insert(event.to_string(), Value::Array(list));
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_newest_satisfying() {
+        let entry = |version: &str| VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(format!("/usr/bin/php{}", version))],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+        let config = Config {
+            versions: vec![entry("7.4.33"), entry("8.3.4"), entry("8.2.12")],
+            ..Config::default()
+        };
+
+        assert_eq!(newest_satisfying(&config, "^8.0").unwrap().unwrap().version, "8.3.4");
+        assert_eq!(newest_satisfying(&config, "~8.2.0").unwrap().unwrap().version, "8.2.12");
+        assert!(newest_satisfying(&config, "^9.0").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_add_scripts_keeps_existing() {
+        let mut manifest = json!({
+            "name": "acme/app",
+            "require": { "php": "^8.2" },
+            "scripts": { "test": "phpunit", "pre-install-cmd": "@lint" }
+        });
+
+        let changed = add_scripts(&mut manifest).unwrap();
+        assert_eq!(changed, vec!["php:use", "php:check", "pre-install-cmd"]);
+        assert_eq!(manifest["scripts"]["test"], "phpunit");
+        assert_eq!(manifest["scripts"]["pre-install-cmd"], json!([CHECK_COMMAND, "@lint"]));
+        assert_eq!(php_constraint(&manifest), Some("^8.2"));
+
+        // Running it again changes nothing
+        assert!(add_scripts(&mut manifest).unwrap().is_empty());
+    }
+}
@@ -1,90 +1,509 @@
 // Configuration management module
 
-use crate::detector::PhpInstallation;
+use crate::confirm::ConfirmPolicy;
+use crate::detector::{BuildInfo, InstallLayout, PhpInstallation};
+use crate::project::{PinFile, DEFAULT_PIN_FILES};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Config {
+    #[serde(default)]
     pub settings: Settings,
+    #[serde(default)]
     pub versions: Vec<VersionEntry>,
     #[serde(default)]
     pub tools: ToolsConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    #[serde(default)]
+    pub runtimes: Vec<RuntimeEntry>,
+    /// `php` images of the local Docker daemon, found by the last scan
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub containers: Vec<ContainerImage>,
+    /// PHP binaries the last scan found but couldn't run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub broken: Vec<BrokenInstall>,
+    /// Named toolchains, written as `[toolchain.<name>]` tables
+    #[serde(default, rename = "toolchain")]
+    pub toolchains: BTreeMap<String, Toolchain>,
+    /// Directories pinned with `pin`, consulted before `.php-version` files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pins: Vec<DirectoryPin>,
 }
 
+/// A directory pinned to a version in the config instead of a `.php-version` file
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DirectoryPin {
+    #[serde(with = "crate::paths::path")]
+    pub dir: PathBuf,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Settings {
+    #[serde(default)]
     pub last_scan: Option<String>,
+    /// Version `use` falls back to outside pinned projects (set by `default`)
+    #[serde(default)]
     pub default_version: Option<String>,
+    /// Version selected by the last switch
+    #[serde(default)]
+    pub current_version: Option<String>,
+    /// Version selected before it, which `prev` switches back to
+    #[serde(default)]
+    pub previous_version: Option<String>,
+    /// Collect local usage counters (opt-in, never uploaded)
+    #[serde(default)]
+    pub stats_enabled: bool,
+    /// Patch releases of each minor version kept by `prune` (managed installs only)
+    #[serde(default)]
+    pub keep_patches: Option<usize>,
+    /// Permissions of the switcher bin directory, e.g. `0o700` (default `0o755`)
+    #[serde(default)]
+    pub bin_dir_mode: Option<u32>,
+    /// Permissions of generated tool shims and switch scripts (default `0o755`)
+    #[serde(default)]
+    pub shim_mode: Option<u32>,
+    /// How binaries are exposed (defaults to the platform's preferred strategy)
+    #[serde(default, alias = "switch_mode")]
+    pub link_strategy: Option<LinkStrategyKind>,
+    /// Check opcache and the preload script after every switch
+    #[serde(default)]
+    pub check_opcache: bool,
+    /// Pin files read in each directory, first match wins (default `.php-version`, then `.tool-versions`)
+    #[serde(default)]
+    pub pin_files: Option<Vec<PinFile>>,
+    /// How confirmation prompts are answered: `ask` (default), `yes` or `no`
+    #[serde(default)]
+    pub confirm: Option<ConfirmPolicy>,
+    /// What `use` does when a pinned version isn't installed (default `fail`)
+    #[serde(default)]
+    pub missing_version: Option<MissingVersionStrategy>,
+    /// How loosely a pin may match before `missing_version` applies, tried in order (default `exact`)
+    #[serde(default)]
+    pub pin_match: Option<Vec<PinMatch>>,
+    /// Words instead of symbols and color in all output (like `--accessible`)
+    #[serde(default)]
+    pub accessible: bool,
+    /// Directories scanned for PHP binaries in addition to the built-in ones, e.g. `/srv/php/8.2/bin`
+    #[serde(default, with = "crate::paths::vec")]
+    pub extra_scan_dirs: Vec<PathBuf>,
+    /// How many levels below each of `extra_scan_dirs` to look (default 3)
+    #[serde(default)]
+    pub scan_depth: Option<usize>,
+    /// Binaries in or below these paths are left out of scans (a chroot, a backup)
+    #[serde(default, with = "crate::paths::vec")]
+    pub exclude_paths: Vec<PathBuf>,
+    /// Versions left out of scans, as prefixes or ranges (`7.4`, `<8.0`)
+    #[serde(default)]
+    pub exclude_versions: Vec<String>,
+    /// Read versions out of binaries instead of running them during scans (like `scan --no-exec`)
+    #[serde(default)]
+    pub no_exec_scan: bool,
+    /// Seconds a binary may take to answer when probed before it's killed and skipped (default 5)
+    #[serde(default)]
+    pub probe_timeout: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Permissions used for the bin directory and shims unless configured
+pub const DEFAULT_MODE: u32 = 0o755;
+
+impl Settings {
+    pub fn bin_dir_mode(&self) -> u32 {
+        self.bin_dir_mode.unwrap_or(DEFAULT_MODE)
+    }
+
+    pub fn shim_mode(&self) -> u32 {
+        self.shim_mode.unwrap_or(DEFAULT_MODE)
+    }
+
+    pub fn pin_files(&self) -> &[PinFile] {
+        self.pin_files.as_deref().unwrap_or(DEFAULT_PIN_FILES)
+    }
+
+    pub fn pin_match(&self) -> &[PinMatch] {
+        self.pin_match.as_deref().unwrap_or(&[PinMatch::Exact])
+    }
+
+    /// Extra directories and exclusions for system scans
+    pub fn scan_options(&self) -> crate::detector::ScanOptions {
+        crate::detector::ScanOptions {
+            extra_dirs: self.extra_scan_dirs.clone(),
+            depth: self.scan_depth.unwrap_or(crate::detector::DEFAULT_SCAN_DEPTH),
+            exclude_paths: self.exclude_paths.clone(),
+            exclude_versions: self.exclude_versions.clone(),
+            no_exec: self.no_exec_scan,
+        }
+    }
+
+    /// Remember a switch to `version`; the selection it replaces becomes `previous_version`
+    ///
+    /// `replaced` is what the bin directory pointed at, for configs from before
+    /// selections were recorded.
+    pub fn record_selection(&mut self, version: &str, replaced: Option<&str>) {
+        let replaced = self.current_version.replace(version.to_string()).or_else(|| replaced.map(str::to_string));
+        // Switching to the same version again keeps the one to go back to
+        if let Some(replaced) = replaced.filter(|replaced| replaced != version) {
+            self.previous_version = Some(replaced);
+        }
+    }
+}
+
+/// How an installed version may stand in for a pinned one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PinMatch {
+    /// Only the pinned version itself
+    Exact,
+    /// The newest patch release of the pinned minor version (8.1.27 → 8.1.30)
+    SameMinor,
+    /// The newest version satisfying composer.json's `require.php`, or `^pin` without one
+    SatisfyConstraint,
+}
+
+impl PinMatch {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PinMatch::Exact => "exact",
+            PinMatch::SameMinor => "same-minor",
+            PinMatch::SatisfyConstraint => "satisfy-constraint",
+        }
+    }
+}
+
+/// What to do when a pin asks for a version that isn't installed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingVersionStrategy {
+    /// Stop with installation hints
+    #[default]
+    Fail,
+    /// Use the closest older installed version
+    NearestLower,
+    /// Use the closest newer installed version
+    NearestHigher,
+    /// Offer the closest version (newer first) and fail unless accepted
+    Ask,
+}
+
+impl MissingVersionStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MissingVersionStrategy::Fail => "fail",
+            MissingVersionStrategy::NearestLower => "nearest-lower",
+            MissingVersionStrategy::NearestHigher => "nearest-higher",
+            MissingVersionStrategy::Ask => "ask",
+        }
+    }
+}
+
+/// Which side of a missing version to look for a stand-in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nearest {
+    Lower,
+    Higher,
+}
+
+/// How the switcher exposes the active version's binaries
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkStrategyKind {
+    Symlink,
+    Hardlink,
+    Copy,
+    CmdShim,
+    /// Shell scripts exec'ing the binary, after exporting the version's `env`
+    Shim,
+    UpdateAlternatives,
+    BrewLink,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct VersionEntry {
     pub version: String,
+    #[serde(with = "crate::paths::vec")]
     pub paths: Vec<PathBuf>,
     pub source: String,
+    /// Names this version can be referred to by (e.g. `legacy-billing`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Free-form notes (e.g. `owner = "billing-team"`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Environment set for this version by `shim` links and `exec`/`each`/`shell`/`env` (e.g. `PHPRC`, `PHP_INI_SCAN_DIR`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Install prefix and config directories, recorded at scan time
+    #[serde(default, skip_serializing_if = "InstallLayout::is_empty")]
+    pub layout: InstallLayout,
+    /// Thread safety, debug build and architecture, recorded at scan time
+    #[serde(default, skip_serializing_if = "BuildInfo::is_empty")]
+    pub build: BuildInfo,
+    /// Extensions loaded at scan time (`php -m`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Symlinks and hardlinks found leading to the same binaries as `paths`
+    #[serde(default, with = "crate::paths::vec", skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<PathBuf>,
+}
+
+/// Application servers that run PHP code themselves
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeKind {
+    /// Embeds its own PHP build
+    FrankenPhp,
+    /// Runs workers with whatever PHP its `.rr.yaml` points at
+    RoadRunner,
+}
+
+impl RuntimeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuntimeKind::FrankenPhp => "FrankenPHP",
+            RuntimeKind::RoadRunner => "RoadRunner",
+        }
+    }
+
+    /// Name of the executable
+    pub fn binary_name(&self) -> &'static str {
+        match self {
+            RuntimeKind::FrankenPhp => "frankenphp",
+            RuntimeKind::RoadRunner => "rr",
+        }
+    }
+}
+
+/// A detected FrankenPHP or RoadRunner binary
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuntimeEntry {
+    pub kind: RuntimeKind,
+    /// Version of the runtime itself
+    pub version: String,
+    #[serde(with = "crate::paths::path")]
+    pub path: PathBuf,
+    /// PHP version embedded in the runtime (FrankenPHP only)
+    #[serde(default)]
+    pub php_version: Option<String>,
+}
+
+/// A local Docker image of PHP; listed, but not a native installation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContainerImage {
+    /// Image reference, e.g. `php:8.2-cli`
+    pub image: String,
+    /// PHP version named by the tag
+    pub php_version: String,
+}
+
+/// A PHP binary that doesn't run (missing libraries, another CPU), kept so `list` and `info` can say why
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BrokenInstall {
+    /// The version its name promises, e.g. `8.3` for `php8.3`
+    pub version: String,
+    #[serde(with = "crate::paths::path")]
+    pub path: PathBuf,
+    /// What went wrong when it was run
+    pub reason: String,
 }
 
+impl BrokenInstall {
+    /// Why it can't be used, for errors of commands that asked for it
+    pub fn describe(&self) -> String {
+        format!("PHP {} at {} doesn't run ({}); fix it and run 'php-switcher scan'", self.version, self.path.display(), self.reason)
+    }
+}
+
+/// A PHP version switched together with pinned tools and an ini profile
+///
+/// `legacy = { php = "7.4", composer = "1", ini = "legacy" }`: every key
+/// besides `php` and `ini` pins a tool to a version (or a path).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Toolchain {
+    pub php: String,
+    /// Profile directory under `~/.php-switcher/ini`
+    #[serde(default)]
+    pub ini: Option<String>,
+    #[serde(flatten)]
+    pub tools: BTreeMap<String, String>,
+}
+
+/// Guardrails restricting which versions may be activated
+///
+/// When set in the machine-level config, the `[policy]` table can't be
+/// overridden by users.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PolicyConfig {
+    /// Version patterns that may be switched to (empty = everything allowed)
+    #[serde(default)]
+    pub allowed_versions: Vec<String>,
+}
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    PreSwitch,
+    PostSwitch,
+}
+
+impl HookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreSwitch => "pre-switch",
+            HookEvent::PostSwitch => "post-switch",
+        }
+    }
+}
+
+/// What to do when a hook fails or times out
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailurePolicy {
+    #[default]
+    Warn,
+    Abort,
+}
+
+/// A shell command run around switches
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HookConfig {
+    pub event: HookEvent,
+    pub command: String,
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+fn default_hook_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct ToolsConfig {
+    /// Opt-in by default
     #[serde(default)]
     pub scan_for_tools: bool,
     #[serde(default)]
     pub custom_tool_names: Vec<String>,
-    #[serde(default)]
+    #[serde(default, with = "crate::paths::vec")]
     pub custom_search_paths: Vec<PathBuf>,
     #[serde(default)]
     pub managed: Vec<ToolEntry>,
+    /// Custom shim template (defaults to `shim.template` in the config dir)
+    #[serde(default, with = "crate::paths::option")]
+    pub shim_template: Option<PathBuf>,
+    /// Extra arguments passed to PHP by every shim
+    #[serde(default)]
+    pub shim_php_args: Vec<String>,
+    /// Environment variables exported by every shim
+    #[serde(default)]
+    pub shim_env: std::collections::BTreeMap<String, String>,
+    /// Ini settings passed as `-d` to PHP by one tool's shim, e.g. `[tools.ini.box]`
+    /// with `"phar.readonly" = "0"`
+    #[serde(default)]
+    pub ini: BTreeMap<String, BTreeMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ToolEntry {
     pub name: String,
+    #[serde(with = "crate::paths::path")]
     pub original_path: PathBuf,
     pub shebang: String,
     pub shim_created: bool,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            settings: Settings::default(),
-            versions: Vec::new(),
-            tools: ToolsConfig::default(),
+impl PolicyConfig {
+    /// Check whether a concrete version (e.g. "8.2.12") is allowed by the policy
+    pub fn allows(&self, version: &str) -> bool {
+        use crate::version::PhpVersion;
+
+        if self.allowed_versions.is_empty() {
+            return true;
+        }
+
+        match PhpVersion::from_php_output(&format!("PHP {}", version)) {
+            Ok(parsed) => self.allowed_versions.iter().any(|pattern| parsed.matches(pattern)),
+            Err(_) => false,
         }
     }
 }
 
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            last_scan: None,
-            default_version: None,
-        }
+impl VersionEntry {
+    /// Get the primary PHP binary path (the 'php' executable)
+    pub fn primary_path(&self) -> Option<&PathBuf> {
+        // Prefer the binary named exactly "php"
+        self.paths
+            .iter()
+            .find(|p| p.file_name().and_then(|n| n.to_str()) == Some("php"))
+            .or_else(|| self.paths.first())
+    }
+
+    /// Whether this is a Nix build whose store path is gone, usually after `nix-collect-garbage`
+    pub fn is_garbage_collected(&self) -> bool {
+        self.source == "nix" && self.primary_path().is_some_and(|path| !path.exists())
     }
 }
 
-impl Default for ToolsConfig {
-    fn default() -> Self {
-        Self {
-            scan_for_tools: false, // Opt-in by default
-            custom_tool_names: Vec::new(),
-            custom_search_paths: Vec::new(),
-            managed: Vec::new(),
-        }
+/// Check whether a label can be used; labels must not look like version patterns
+pub fn is_valid_label(label: &str) -> bool {
+    label.starts_with(|c: char| c.is_ascii_alphabetic())
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// How a rescan would change the tracked versions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanDiff {
+    pub added: Vec<VersionEntry>,
+    pub removed: Vec<VersionEntry>,
+    /// Versions whose paths or source would change (new entry)
+    pub updated: Vec<VersionEntry>,
+}
+
+impl ScanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
     }
 }
 
+/// Tracked versions sharing a major.minor, newest first
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionGroup<'a> {
+    pub minor: String,
+    pub entries: Vec<&'a VersionEntry>,
+}
+
 impl Config {
     pub fn update_from_installations(&mut self, installations: &[PhpInstallation]) {
-        self.versions.clear();
+        let previous = std::mem::take(&mut self.versions);
 
         for installation in installations {
+            let version = installation.version.to_string();
+            // Labels, metadata and env belong to the version, so they survive rescans
+            let old = previous.iter().find(|e| e.version == version);
+            let mut metadata = old.map(|e| e.metadata.clone()).unwrap_or_default();
+            if let Some(name) = installation.port_select_name() {
+                metadata.insert("port_select".to_string(), name);
+            }
+
             self.versions.push(VersionEntry {
-                version: installation.version.to_string(),
                 paths: installation.paths.clone(),
-                source: "auto".to_string(),
+                source: installation.source().to_string(),
+                labels: old.map(|e| e.labels.clone()).unwrap_or_default(),
+                metadata,
+                env: old.map(|e| e.env.clone()).unwrap_or_default(),
+                layout: installation.layout.clone(),
+                build: installation.build.clone(),
+                extensions: installation.extensions.clone(),
+                aliases: installation.aliases.clone(),
+                version,
             });
         }
 
@@ -92,19 +511,203 @@ impl Config {
         self.settings.last_scan = Some(chrono::Utc::now().to_rfc3339());
     }
 
-    /// Get all paths for a version matching the pattern
-    pub fn get_installation_by_version(&self, version_pattern: &str) -> Option<Vec<PathBuf>> {
+    /// Preview what `update_from_installations` would change, without modifying the config
+    pub fn diff_scan(&self, installations: &[PhpInstallation]) -> ScanDiff {
+        let mut scanned = self.clone();
+        scanned.update_from_installations(installations);
+
+        let find = |versions: &[VersionEntry], version: &str| versions.iter().find(|e| e.version == version).cloned();
+
+        let mut diff = ScanDiff::default();
+        for entry in &scanned.versions {
+            match find(&self.versions, &entry.version) {
+                None => diff.added.push(entry.clone()),
+                Some(old) if old != *entry => diff.updated.push(entry.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = self
+            .versions
+            .iter()
+            .filter(|entry| find(&scanned.versions, &entry.version).is_none())
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    /// Get the version entry carrying the pattern as a label, else the first one
+    /// matching it (the newest one, for a range)
+    pub fn find_version(&self, version_pattern: &str) -> Option<&VersionEntry> {
+        use crate::version::PhpVersion;
+
+        if let Some(entry) = self.versions.iter().find(|e| e.labels.iter().any(|l| l == version_pattern)) {
+            return Some(entry);
+        }
+
+        let mut matching = self.versions.iter().filter_map(|entry| {
+            let version = PhpVersion::from_php_output(&format!("PHP {}", entry.version)).ok()?;
+            version.matches(version_pattern).then_some((version, entry))
+        });
+
+        // A range like `^8.1` spans minor versions, so it means the newest match
+        if crate::version::is_range(version_pattern) {
+            matching.max_by(|(a, _), (b, _)| a.cmp(b)).map(|(_, entry)| entry)
+        } else {
+            matching.next().map(|(_, entry)| entry)
+        }
+    }
+
+    /// Find a broken binary whose version fits a pattern (`8.3` and `8.3.4` both fit `php8.3`)
+    pub fn find_broken(&self, version_pattern: &str) -> Option<&BrokenInstall> {
+        let fits = |a: &str, b: &str| a == b || a.starts_with(&format!("{}.", b));
+        self.broken.iter().find(|broken| fits(&broken.version, version_pattern) || fits(version_pattern, &broken.version))
+    }
+
+    /// Find the installed version closest to a plain version pattern on one side of it
+    ///
+    /// `8.1` with 8.0.30, 8.2.10 and 8.2.12 installed gives 8.0.30 below and
+    /// 8.2.12 above: the nearest minor version, at its newest patch release.
+    /// Labels and ranges have no "near", so they give nothing.
+    pub fn nearest_installed(&self, version_pattern: &str, side: Nearest) -> Option<&VersionEntry> {
         use crate::version::PhpVersion;
 
-        for entry in &self.versions {
-            if let Ok(version) = PhpVersion::from_php_output(&format!("PHP {}", entry.version)) {
-                if version.matches(version_pattern) {
-                    return Some(entry.paths.clone());
+        let wanted: Vec<u32> = version_pattern.split('.').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        if wanted.len() > 3 {
+            return None;
+        }
+
+        // Compare as many components as the pattern has
+        let candidates = self.versions.iter().filter_map(|entry| {
+            let version = PhpVersion::from_php_output(&format!("PHP {}", entry.version)).ok()?;
+            let prefix = [version.major, version.minor, version.patch][..wanted.len()].to_vec();
+            Some((prefix, version, entry))
+        });
+
+        let (_, _, entry) = match side {
+            Nearest::Lower => candidates.filter(|(prefix, ..)| *prefix < wanted).max_by(|a, b| a.1.cmp(&b.1))?,
+            Nearest::Higher => {
+                let higher: Vec<_> = candidates.filter(|(prefix, ..)| *prefix > wanted).collect();
+                let closest = higher.iter().map(|(prefix, ..)| prefix.clone()).min()?;
+                higher.into_iter().filter(|(prefix, ..)| *prefix == closest).max_by(|a, b| a.1.cmp(&b.1))?
+            }
+        };
+        Some(entry)
+    }
+
+    /// Find the newest installed patch release of a full version's minor version
+    ///
+    /// `8.1.27` with 8.1.29 and 8.1.30 installed gives 8.1.30; anything but a
+    /// full `major.minor.patch` version gives nothing.
+    pub fn same_minor(&self, version_pattern: &str) -> Option<&VersionEntry> {
+        use crate::version::PhpVersion;
+
+        let parts: Vec<u32> = version_pattern.split('.').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        let [major, minor, _] = parts[..] else { return None };
+
+        self.versions
+            .iter()
+            .filter_map(|entry| Some((PhpVersion::from_php_output(&format!("PHP {}", entry.version)).ok()?, entry)))
+            .filter(|(version, _)| version.major == major && version.minor == minor)
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, entry)| entry)
+    }
+
+    /// Pin a directory to a version, replacing any earlier pin of it
+    pub fn set_pin(&mut self, dir: &Path, version: &str) {
+        self.remove_pin(dir);
+        self.pins.push(DirectoryPin { dir: dir.to_path_buf(), version: version.to_string() });
+        self.pins.sort_by(|a, b| a.dir.cmp(&b.dir));
+    }
+
+    /// Remove a directory's pin, returning the version it was pinned to
+    pub fn remove_pin(&mut self, dir: &Path) -> Option<String> {
+        let index = self.pins.iter().position(|p| p.dir == dir)?;
+        Some(self.pins.remove(index).version)
+    }
+
+    /// Get the entry `settings.default_version` refers to, if it's set and installed
+    pub fn default_entry(&self) -> Option<&VersionEntry> {
+        self.find_version(self.settings.default_version.as_deref()?)
+    }
+
+    /// Label the version matching a pattern: `name` adds a label, `key=value` sets metadata
+    ///
+    /// A label names a single version, so it's taken away from any other entry.
+    /// With `remove`, the named labels and metadata keys are dropped instead.
+    /// Returns the labelled version.
+    pub fn label_version(&mut self, version_pattern: &str, items: &[String], remove: bool) -> Result<String> {
+        let index = self
+            .find_version(version_pattern)
+            .and_then(|found| self.versions.iter().position(|e| e.version == found.version))
+            .ok_or_else(|| anyhow!("No PHP installation found matching '{}'", version_pattern))?;
+
+        for item in items {
+            let (name, value) = match item.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value.trim())),
+                None => (item.as_str(), None),
+            };
+
+            if remove {
+                self.versions[index].labels.retain(|l| l != name);
+                self.versions[index].metadata.remove(name);
+                continue;
+            }
+
+            match value {
+                Some(_) if name.is_empty() => return Err(anyhow!("Missing metadata key in '{}'", item)),
+                Some(value) => {
+                    self.versions[index].metadata.insert(name.to_string(), value.to_string());
+                }
+                None if !is_valid_label(name) => {
+                    return Err(anyhow!(
+                        "Invalid label '{}': labels start with a letter and use letters, digits, '-', '_' or '.'",
+                        name
+                    ))
+                }
+                None => {
+                    for entry in &mut self.versions {
+                        entry.labels.retain(|l| l != name);
+                    }
+                    self.versions[index].labels.push(name.to_string());
                 }
             }
         }
 
-        None
+        Ok(self.versions[index].version.clone())
+    }
+
+    /// Group tracked versions by minor version (e.g. "8.2"), newest group first
+    ///
+    /// Entries whose version can't be parsed get a group of their own.
+    pub fn grouped_by_minor(&self) -> Vec<VersionGroup<'_>> {
+        use crate::version::PhpVersion;
+
+        let mut parsed: Vec<(Option<PhpVersion>, &VersionEntry)> = self
+            .versions
+            .iter()
+            .map(|entry| (PhpVersion::from_php_output(&format!("PHP {}", entry.version)).ok(), entry))
+            .collect();
+        parsed.sort_by(|a, b| b.0.cmp(&a.0));
+
+        let mut groups: Vec<VersionGroup> = Vec::new();
+        for (version, entry) in parsed {
+            let minor = version
+                .map(|v| v.short_version())
+                .unwrap_or_else(|| entry.version.clone());
+
+            match groups.last_mut() {
+                Some(group) if group.minor == minor => group.entries.push(entry),
+                _ => groups.push(VersionGroup { minor, entries: vec![entry] }),
+            }
+        }
+
+        groups
+    }
+
+    /// Get all paths for a version matching the pattern
+    pub fn get_installation_by_version(&self, version_pattern: &str) -> Option<Vec<PathBuf>> {
+        self.find_version(version_pattern).map(|entry| entry.paths.clone())
     }
 
     /// Get the primary PHP binary path for a version matching the pattern
@@ -121,17 +724,176 @@ impl Config {
     }
 }
 
-/// Get the path to the config file
+/// Machine-level config shared by all users, layered under the user's config
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/php-switcher/config.toml";
+
+/// Get the path to the machine-level config file
+///
+/// `PHP_SWITCHER_SYSTEM_CONFIG` overrides the default location.
+pub fn get_system_config_path() -> PathBuf {
+    std::env::var_os("PHP_SWITCHER_SYSTEM_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(SYSTEM_CONFIG_PATH))
+}
+
+/// Get the path to the user's config file
+///
+/// `PHP_SWITCHER_CONFIG` (set by `--config`) overrides the default location.
 pub fn get_config_path() -> Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
-    let config_dir = home.join(".php-switcher");
-    Ok(config_dir.join("config.toml"))
+    if let Some(path) = std::env::var_os("PHP_SWITCHER_CONFIG") {
+        return Ok(PathBuf::from(path));
+    }
+    Ok(get_config_dir()?.join("config.toml"))
 }
 
-/// Get the config directory
+/// Get the directory holding the switcher's state (bin dir, history, ...)
+///
+/// `PHP_SWITCHER_HOME` overrides the default `~/.php-switcher`. Without a
+/// usable home directory (common in containers and CI) a per-user directory
+/// under the system temp dir is used instead of failing; an existing
+/// `~/.php-switcher` that can't be written is used read-only.
 pub fn get_config_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
-    Ok(home.join(".php-switcher"))
+    if let Some(dir) = std::env::var_os("PHP_SWITCHER_HOME") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    match default_state_dir() {
+        Some((dir, HomeState::Writable | HomeState::ReadOnly)) => Ok(dir),
+        _ => fallback_state_dir(),
+    }
+}
+
+/// How far `~/.php-switcher` can be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HomeState {
+    Writable,
+    /// It exists but can't be written, so it's used read-only
+    ReadOnly,
+    /// It doesn't exist and can't be created, so the fallback dir is used
+    Unwritable,
+}
+
+/// The default state directory and how far it can be used, probed once
+///
+/// A one-line notice on stderr says when it can't be written.
+fn default_state_dir() -> Option<(PathBuf, HomeState)> {
+    static STATE: std::sync::OnceLock<Option<(PathBuf, HomeState)>> = std::sync::OnceLock::new();
+    STATE
+        .get_or_init(|| {
+            let dir = dirs::home_dir()?.join(".php-switcher");
+            let state = probe_home(&dir);
+            match state {
+                HomeState::Writable => {}
+                HomeState::ReadOnly => eprintln!("{} {} isn't writable; running read-only", crate::style::warn(), dir.display()),
+                HomeState::Unwritable => {
+                    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()).map(PathBuf::from);
+                    eprintln!(
+                        "{} {} can't be created; keeping state in {}",
+                        crate::style::warn(),
+                        dir.display(),
+                        fallback_state_location(runtime_dir).display()
+                    )
+                }
+            }
+            Some((dir, state))
+        })
+        .clone()
+}
+
+fn probe_home(dir: &Path) -> HomeState {
+    if dir.is_dir() {
+        if is_writable(dir) {
+            HomeState::Writable
+        } else {
+            HomeState::ReadOnly
+        }
+    } else if dir.parent().is_some_and(is_writable) {
+        HomeState::Writable
+    } else {
+        HomeState::Unwritable
+    }
+}
+
+/// Check whether entries can be created in an existing directory
+#[cfg(unix)]
+fn is_writable(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else { return false };
+    // SAFETY: access(2) only reads the NUL-terminated path
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(dir: &Path) -> bool {
+    std::fs::metadata(dir).is_ok_and(|metadata| metadata.is_dir() && !metadata.permissions().readonly())
+}
+
+/// Per-user state directory used when there is no home directory
+///
+/// Lives under `$XDG_RUNTIME_DIR` when set, else under the shared temp dir,
+/// where another user could create it first and plant hooks or a `bin/php`.
+/// It is created private, and refused unless it is ours with mode 0700.
+fn fallback_state_dir() -> Result<PathBuf> {
+    let dir = fallback_state_location(std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()).map(PathBuf::from));
+    #[cfg(unix)]
+    ensure_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Where the fallback state directory goes, given `$XDG_RUNTIME_DIR`
+fn fallback_state_location(runtime_dir: Option<PathBuf>) -> PathBuf {
+    if let Some(runtime_dir) = runtime_dir {
+        return runtime_dir.join("php-switcher");
+    }
+
+    // SAFETY: getuid(2) always succeeds and has no side effects
+    #[cfg(unix)]
+    let user = unsafe { libc::getuid() }.to_string();
+    #[cfg(not(unix))]
+    let user = std::env::var("USERNAME").unwrap_or_default();
+
+    std::env::temp_dir().join(format!("php-switcher-{}", user))
+}
+
+/// Create a directory only the current user can use, or check an existing one is
+#[cfg(unix)]
+fn ensure_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        // Exactly 0700, even under an unusual umask that would leave it unusable
+        Ok(()) => {
+            return std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+                .map_err(|e| anyhow!("Failed to create state directory {}: {}", dir.display(), e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(anyhow!("Failed to create state directory {}: {}", dir.display(), e)),
+    }
+
+    // Not following symlinks, which could point anywhere
+    let metadata = std::fs::symlink_metadata(dir).map_err(|e| anyhow!("Failed to read {}: {}", dir.display(), e))?;
+    // SAFETY: getuid(2) always succeeds and has no side effects
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o777 != 0o700 {
+        return Err(anyhow!(
+            "Refusing to keep state in {}: it must be a directory owned by you with mode 0700 (set PHP_SWITCHER_HOME instead)",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Check whether writes to the state directory are disabled
+///
+/// Set by `--read-only` or `PHP_SWITCHER_READ_ONLY=1`; commands that only
+/// read (like `list`) keep working, anything that would write fails or skips.
+pub fn is_read_only() -> bool {
+    if std::env::var_os("PHP_SWITCHER_READ_ONLY").is_some_and(|v| !v.is_empty() && v != "0") {
+        return true;
+    }
+    // An unwritable ~/.php-switcher behaves the same
+    std::env::var_os("PHP_SWITCHER_HOME").is_none() && matches!(default_state_dir(), Some((_, HomeState::ReadOnly)))
 }
 
 /// Save config to a file
@@ -171,16 +933,106 @@ pub fn load_config_from_file<P: AsRef<Path>>(path: P) -> Result<Config> {
     Ok(config)
 }
 
+/// Read a config file as a raw TOML table (empty if the file doesn't exist)
+fn read_toml_table(path: &Path) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+
+    toml::from_str(&contents).map_err(|e| anyhow!("Failed to parse config {}: {}", path.display(), e))
+}
+
+/// Merge `overlay` into `base`, with tables merged recursively and other values replaced
+pub fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Remove every value from `table` that is identical to the one in `base`
+///
+/// Used when saving so inherited machine-level values aren't copied into the
+/// user's file, where they would shadow later changes made by the admin.
+pub fn strip_inherited(table: &mut toml::Table, base: &toml::Table) {
+    table.retain(|key, value| match (value, base.get(key)) {
+        (toml::Value::Table(inner), Some(toml::Value::Table(base_inner))) => {
+            strip_inherited(inner, base_inner);
+            !inner.is_empty()
+        }
+        (value, Some(base_value)) => value != base_value,
+        (_, None) => true,
+    });
+}
+
+/// Load the machine-level config with the user's config layered on top
+pub fn load_layered_config_from_files<P: AsRef<Path>, Q: AsRef<Path>>(system_path: P, user_path: Q) -> Result<Config> {
+    let system = read_toml_table(system_path.as_ref())?;
+    let mut merged = system.clone();
+    merge_tables(&mut merged, read_toml_table(user_path.as_ref())?);
+
+    // Machine-level policy is authoritative: users can't loosen it
+    if let Some(policy) = system.get("policy") {
+        merged.insert("policy".to_string(), policy.clone());
+    }
+
+    toml::Value::Table(merged)
+        .try_into()
+        .map_err(|e| anyhow!("Failed to parse config: {}", e))
+}
+
+/// Save the user layer of a config, leaving out values inherited from the machine-level config
+pub fn save_layered_config_to_files<P: AsRef<Path>, Q: AsRef<Path>>(config: &Config, system_path: P, user_path: Q) -> Result<()> {
+    let user_path = user_path.as_ref();
+    let system = read_toml_table(system_path.as_ref())?;
+
+    let mut table = toml::Table::try_from(config)
+        .map_err(|e| anyhow!("Failed to serialize config: {}", e))?;
+    strip_inherited(&mut table, &system);
+
+    if let Some(parent) = user_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Failed to create config directory: {}", e))?;
+    }
+
+    let toml_str =
+        toml::to_string_pretty(&table).map_err(|e| anyhow!("Failed to serialize config: {}", e))?;
+
+    std::fs::write(user_path, toml_str)
+        .map_err(|e| anyhow!("Failed to write config file: {}", e))?;
+
+    Ok(())
+}
+
 /// Load config from the default location
+///
+/// Settings that shape all output and probing (`accessible`, `probe_timeout`)
+/// take effect here, so commands don't load the config just for them.
 pub fn load_config() -> Result<Config> {
     let path = get_config_path()?;
-    load_config_from_file(path)
+    let config = crate::timing::measure(|| "config load".to_string(), || load_layered_config_from_files(get_system_config_path(), path))?;
+    crate::style::apply_accessible_setting(config.settings.accessible);
+    crate::detector::set_probe_timeout_setting(config.settings.probe_timeout);
+    Ok(config)
 }
 
 /// Save config to the default location
 pub fn save_config(config: &Config) -> Result<()> {
+    if is_read_only() {
+        return Err(anyhow!("Read-only mode: not saving the configuration"));
+    }
+
     let path = get_config_path()?;
-    save_config_to_file(config, path)
+    save_layered_config_to_files(config, get_system_config_path(), path)
 }
 
 #[cfg(test)]
@@ -197,6 +1049,73 @@ mod tests {
         assert!(config.versions.is_empty());
     }
 
+    #[test]
+    fn test_default_entry() {
+        let mut config = Config::default();
+        config.versions.push(VersionEntry {
+            version: "8.2.12".to_string(),
+            paths: vec![PathBuf::from("/usr/bin/php8.2")],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        });
+        assert_eq!(config.default_entry(), None);
+
+        config.settings.default_version = Some("8.2".to_string());
+        assert_eq!(config.default_entry().map(|e| e.version.as_str()), Some("8.2.12"));
+
+        config.settings.default_version = Some("7.4".to_string());
+        assert_eq!(config.default_entry(), None);
+    }
+
+    #[test]
+    fn test_nearest_installed() {
+        let mut config = Config::default();
+        for version in ["8.3.4", "8.2.10", "8.2.12", "8.0.30", "7.4.33"] {
+            config.versions.push(VersionEntry { version: version.to_string(), ..VersionEntry::default() });
+        }
+        let nearest = |pattern, side| config.nearest_installed(pattern, side).map(|e| e.version.as_str());
+
+        assert_eq!(nearest("8.1", Nearest::Lower), Some("8.0.30"));
+        assert_eq!(nearest("8.1", Nearest::Higher), Some("8.2.12"));
+        assert_eq!(nearest("8.2.11", Nearest::Higher), Some("8.2.12"));
+        assert_eq!(nearest("8.2.11", Nearest::Lower), Some("8.2.10"));
+        assert_eq!(nearest("7", Nearest::Higher), Some("8.3.4"));
+        assert_eq!(nearest("7.4", Nearest::Lower), None);
+        assert_eq!(nearest("^8.1", Nearest::Higher), None);
+    }
+
+    #[test]
+    fn test_same_minor() {
+        let mut config = Config::default();
+        for version in ["8.1.29", "8.1.30", "8.2.12"] {
+            config.versions.push(VersionEntry { version: version.to_string(), ..VersionEntry::default() });
+        }
+
+        assert_eq!(config.same_minor("8.1.27").map(|e| e.version.as_str()), Some("8.1.30"));
+        assert_eq!(config.same_minor("8.0.30"), None);
+        assert_eq!(config.same_minor("8.1"), None);
+
+        let settings: Settings = toml::from_str("pin_match = [\"exact\", \"same-minor\"]\n").unwrap();
+        assert_eq!(settings.pin_match(), &[PinMatch::Exact, PinMatch::SameMinor]);
+        assert_eq!(Settings::default().pin_match(), &[PinMatch::Exact]);
+    }
+
+    #[test]
+    fn test_is_garbage_collected() {
+        let temp_dir = TempDir::new().unwrap();
+        let php = temp_dir.path().join("php");
+        std::fs::write(&php, "").unwrap();
+
+        let mut entry = VersionEntry { paths: vec![php.clone()], source: "nix".to_string(), ..Default::default() };
+        assert!(!entry.is_garbage_collected());
+        std::fs::remove_file(&php).unwrap();
+        assert!(entry.is_garbage_collected());
+
+        // Other sources are checked elsewhere
+        entry.source = "auto".to_string();
+        assert!(!entry.is_garbage_collected());
+    }
+
     #[test]
     fn test_config_serialization() {
         let mut config = Config::default();
@@ -205,6 +1124,7 @@ mod tests {
             version: "8.2.12".to_string(),
             paths: vec![PathBuf::from("/usr/bin/php8.2"), PathBuf::from("/usr/bin/php-cgi")],
             source: "auto".to_string(),
+            ..VersionEntry::default()
         });
 
         // Serialize to TOML
@@ -238,6 +1158,7 @@ mod tests {
             version: "8.2.12".to_string(),
             paths: vec![PathBuf::from("/usr/bin/php8.2")],
             source: "auto".to_string(),
+            ..VersionEntry::default()
         });
 
         // Save config
@@ -280,11 +1201,348 @@ mod tests {
             ),
         ];
 
+        let mut installations = installations;
+        installations[0].layout.prefix = Some(PathBuf::from("/usr"));
+        installations[0].layout.extension_dir = Some(PathBuf::from("/usr/lib/php/20220829"));
+
         config.update_from_installations(&installations);
 
         assert_eq!(config.versions.len(), 2);
         assert_eq!(config.versions[0].version, "8.2.12");
         assert_eq!(config.versions[1].version, "7.4.33");
+
+        // The layout round-trips, and an unknown one isn't written at all
+        let toml_str = toml::to_string(&config).unwrap();
+        assert_eq!(toml_str.matches("[versions.layout]").count(), 1);
+        let loaded: Config = toml::from_str(&toml_str).unwrap();
+        assert_eq!(loaded.versions[0].layout, installations[0].layout);
+    }
+
+    #[test]
+    fn test_fallback_state_dir_is_per_user_temp_dir() {
+        let dir = fallback_state_location(None);
+        assert!(dir.starts_with(std::env::temp_dir()));
+        assert!(dir.file_name().unwrap().to_string_lossy().starts_with("php-switcher-"));
+
+        // The runtime dir is private already, so it's preferred
+        let runtime_dir = PathBuf::from("/run/user/1000");
+        assert_eq!(fallback_state_location(Some(runtime_dir.clone())), runtime_dir.join("php-switcher"));
+    }
+
+    #[test]
+    fn test_probe_home() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join(".php-switcher");
+        assert_eq!(probe_home(&dir), HomeState::Writable);
+        std::fs::create_dir(&dir).unwrap();
+        assert_eq!(probe_home(&dir), HomeState::Writable);
+
+        // A home directory that doesn't exist can't hold the state either
+        assert_eq!(probe_home(&temp_dir.path().join("missing/.php-switcher")), HomeState::Unwritable);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_ensure_private_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("state");
+        ensure_private_dir(&dir).unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        ensure_private_dir(&dir).unwrap();
+
+        // Anyone could have planted files in a directory others can write to
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(ensure_private_dir(&dir).is_err());
+
+        let file = temp_dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(ensure_private_dir(&file).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_save_and_load_non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("config.toml");
+
+        let config = Config {
+            versions: vec![VersionEntry {
+                version: "8.2.12".to_string(),
+                paths: vec![PathBuf::from(OsStr::from_bytes(b"/opt/caf\xe9/bin/php"))],
+                source: "auto".to_string(),
+                ..VersionEntry::default()
+            }],
+            ..Config::default()
+        };
+
+        save_config_to_file(&config, &config_file).unwrap();
+        assert_eq!(load_config_from_file(&config_file).unwrap(), config);
+    }
+
+    #[test]
+    fn test_permission_settings() {
+        let config: Config = toml::from_str("[settings]\nbin_dir_mode = 0o700\n").unwrap();
+
+        assert_eq!(config.settings.bin_dir_mode(), 0o700);
+        assert_eq!(config.settings.shim_mode(), DEFAULT_MODE);
+    }
+
+    #[test]
+    fn test_record_selection() {
+        let mut settings = Settings::default();
+        settings.record_selection("8.3.4", Some("8.2.12"));
+        assert_eq!(settings.current_version.as_deref(), Some("8.3.4"));
+        assert_eq!(settings.previous_version.as_deref(), Some("8.2.12"));
+
+        // The recorded selection wins over what the bin directory shows
+        settings.record_selection("8.1.27", Some("7.4.33"));
+        assert_eq!(settings.previous_version.as_deref(), Some("8.3.4"));
+
+        settings.record_selection("8.1.27", None);
+        assert_eq!(settings.previous_version.as_deref(), Some("8.3.4"));
+        settings.record_selection("8.3.4", None);
+        assert_eq!(settings.previous_version.as_deref(), Some("8.1.27"));
+    }
+
+    #[test]
+    fn test_switch_mode_alias() {
+        let config: Config = toml::from_str("[settings]\nswitch_mode = \"shim\"\n").unwrap();
+        assert_eq!(config.settings.link_strategy, Some(LinkStrategyKind::Shim));
+    }
+
+    #[test]
+    fn test_diff_scan() {
+        use crate::version::PhpVersion;
+
+        let entry = |version: &str, path: &str| VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(path)],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+
+        let config = Config {
+            versions: vec![entry("8.1.0", "/usr/bin/php8.1"), entry("8.2.12", "/usr/bin/php8.2"), entry("8.3.4", "/usr/bin/php8.3")],
+            ..Config::default()
+        };
+
+        let installations = vec![
+            PhpInstallation::new(PhpVersion::new(8, 2, 12), PathBuf::from("/usr/bin/php8.2")),
+            PhpInstallation::new(PhpVersion::new(8, 3, 4), PathBuf::from("/opt/php/bin/php")),
+            PhpInstallation::new(PhpVersion::new(8, 4, 1), PathBuf::from("/usr/bin/php8.4")),
+        ];
+
+        let diff = config.diff_scan(&installations);
+        assert_eq!(diff.added, vec![entry("8.4.1", "/usr/bin/php8.4")]);
+        assert_eq!(diff.removed, vec![entry("8.1.0", "/usr/bin/php8.1")]);
+        assert_eq!(diff.updated, vec![entry("8.3.4", "/opt/php/bin/php")]);
+
+        // Previewing doesn't touch the config
+        assert_eq!(config.versions.len(), 3);
+        assert!(config.settings.last_scan.is_none());
+
+        // Extensions are part of what a scan records
+        let mut with_extensions = installations[0].clone();
+        with_extensions.extensions = vec!["Core".to_string(), "intl".to_string()];
+        let diff = config.diff_scan(&[with_extensions]);
+        assert_eq!(diff.updated[0].extensions, ["Core", "intl"]);
+    }
+
+    #[test]
+    fn test_grouped_by_minor() {
+        let entry = |version: &str| VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(format!("/opt/php-{}/bin/php", version))],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+
+        let config = Config {
+            versions: vec![entry("8.2.10"), entry("8.3.4"), entry("8.2.20"), entry("8.2.15")],
+            ..Config::default()
+        };
+
+        let groups: Vec<(String, Vec<&str>)> = config
+            .grouped_by_minor()
+            .into_iter()
+            .map(|g| (g.minor, g.entries.iter().map(|e| e.version.as_str()).collect()))
+            .collect();
+
+        assert_eq!(
+            groups,
+            vec![
+                ("8.3".to_string(), vec!["8.3.4"]),
+                ("8.2".to_string(), vec!["8.2.20", "8.2.15", "8.2.10"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_directory_pins() {
+        let mut config = Config::default();
+        config.set_pin(Path::new("/srv/b"), "8.2");
+        config.set_pin(Path::new("/srv/a"), "7.4");
+        config.set_pin(Path::new("/srv/b"), "8.3");
+
+        let toml_str = toml::to_string(&config).unwrap();
+        let loaded: Config = toml::from_str(&toml_str).unwrap();
+        let pins: Vec<(&str, &str)> = loaded.pins.iter().map(|p| (p.dir.to_str().unwrap(), p.version.as_str())).collect();
+        assert_eq!(pins, vec![("/srv/a", "7.4"), ("/srv/b", "8.3")]);
+
+        assert_eq!(config.remove_pin(Path::new("/srv/a")), Some("7.4".to_string()));
+        assert_eq!(config.remove_pin(Path::new("/srv/a")), None);
+        assert!(!toml::to_string(&Config::default()).unwrap().contains("pins"));
+    }
+
+    #[test]
+    fn test_find_version_with_range() {
+        let entry = |version: &str| VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(format!("/opt/php-{}/bin/php", version))],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+
+        let config = Config {
+            versions: vec![entry("8.1.27"), entry("8.3.4"), entry("7.4.33"), entry("8.2.12")],
+            ..Config::default()
+        };
+
+        let found = |pattern: &str| config.find_version(pattern).map(|e| e.version.as_str());
+        assert_eq!(found("^8.1"), Some("8.3.4"));
+        assert_eq!(found(">=8.0 <8.3"), Some("8.2.12"));
+        assert_eq!(found("~8.1.20"), Some("8.1.27"));
+        assert_eq!(found("7.*"), Some("7.4.33"));
+        assert_eq!(found("^9.0"), None);
+        assert_eq!(found("8"), Some("8.1.27"));
+    }
+
+    #[test]
+    fn test_find_broken() {
+        let config = Config {
+            broken: vec![BrokenInstall {
+                version: "8.3".to_string(),
+                path: PathBuf::from("/opt/arm/bin/php8.3"),
+                reason: "it is built for aarch64, this machine is x86_64".to_string(),
+            }],
+            ..Config::default()
+        };
+
+        let found = |pattern: &str| config.find_broken(pattern).map(|b| b.path.display().to_string());
+        assert_eq!(found("8.3").as_deref(), Some("/opt/arm/bin/php8.3"));
+        assert_eq!(found("8.3.4").as_deref(), Some("/opt/arm/bin/php8.3"));
+        assert_eq!(found("8").as_deref(), Some("/opt/arm/bin/php8.3"));
+        assert_eq!(found("8.30"), None);
+        assert_eq!(found("7"), None);
+        assert!(config.broken[0].describe().starts_with("PHP 8.3 at /opt/arm/bin/php8.3 doesn't run (it is built for aarch64"));
+    }
+
+    #[test]
+    fn test_layered_config_user_overrides_system() {
+        let temp_dir = TempDir::new().unwrap();
+        let system_file = temp_dir.path().join("system.toml");
+        let user_file = temp_dir.path().join("user.toml");
+
+        std::fs::write(
+            &system_file,
+            "[settings]\ndefault_version = \"8.2\"\n\n[tools]\nscan_for_tools = true\n",
+        )
+        .unwrap();
+        std::fs::write(&user_file, "[settings]\ndefault_version = \"8.3\"\n").unwrap();
+
+        let config = load_layered_config_from_files(&system_file, &user_file).unwrap();
+
+        // User value wins, untouched system values are inherited
+        assert_eq!(config.settings.default_version.as_deref(), Some("8.3"));
+        assert!(config.tools.scan_for_tools);
+    }
+
+    #[test]
+    fn test_layered_config_missing_files() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let config = load_layered_config_from_files(
+            temp_dir.path().join("none.toml"),
+            temp_dir.path().join("also-none.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_layered_save_omits_inherited_values() {
+        let temp_dir = TempDir::new().unwrap();
+        let system_file = temp_dir.path().join("system.toml");
+        let user_file = temp_dir.path().join("user.toml");
+
+        std::fs::write(&system_file, "[tools]\nscan_for_tools = true\n").unwrap();
+
+        let mut config = load_layered_config_from_files(&system_file, &user_file).unwrap();
+        config.settings.default_version = Some("8.1".to_string());
+        save_layered_config_to_files(&config, &system_file, &user_file).unwrap();
+
+        let saved = std::fs::read_to_string(&user_file).unwrap();
+        assert!(saved.contains("default_version"));
+        assert!(!saved.contains("scan_for_tools"));
+
+        // Reloading still yields the full merged config
+        let reloaded = load_layered_config_from_files(&system_file, &user_file).unwrap();
+        assert_eq!(reloaded, config);
+    }
+
+    #[test]
+    fn test_policy_allows() {
+        let mut policy = PolicyConfig::default();
+        assert!(policy.allows("7.4.33"));
+
+        policy.allowed_versions = vec!["8.2".to_string(), "8.3".to_string()];
+        assert!(policy.allows("8.2.12"));
+        assert!(policy.allows("8.3.0"));
+        assert!(!policy.allows("7.4.33"));
+        assert!(!policy.allows("garbage"));
+    }
+
+    #[test]
+    fn test_system_policy_cannot_be_overridden() {
+        let temp_dir = TempDir::new().unwrap();
+        let system_file = temp_dir.path().join("system.toml");
+        let user_file = temp_dir.path().join("user.toml");
+
+        std::fs::write(&system_file, "[policy]\nallowed_versions = [\"8.2\"]\n").unwrap();
+        std::fs::write(&user_file, "[policy]\nallowed_versions = []\n").unwrap();
+
+        let config = load_layered_config_from_files(&system_file, &user_file).unwrap();
+        assert_eq!(config.policy.allowed_versions, vec!["8.2".to_string()]);
+    }
+
+    #[test]
+    fn test_hooks_deserialization() {
+        let config: Config = toml::from_str(
+            r#"
+            [[hooks]]
+            event = "post-switch"
+            command = "systemctl --user restart php-fpm"
+
+            [[hooks]]
+            event = "pre-switch"
+            command = "check-deps"
+            timeout_secs = 5
+            on_failure = "abort"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.hooks.len(), 2);
+        assert_eq!(config.hooks[0].timeout_secs, 30);
+        assert_eq!(config.hooks[0].on_failure, FailurePolicy::Warn);
+        assert_eq!(config.hooks[1].event, HookEvent::PreSwitch);
+        assert_eq!(config.hooks[1].on_failure, FailurePolicy::Abort);
     }
 
     #[test]
@@ -358,4 +1616,65 @@ mod tests {
         assert_eq!(config.tools.managed.len(), 1);
         assert_eq!(config.tools.managed[0].name, "composer");
     }
+
+    #[test]
+    fn test_labels_and_metadata() {
+        let entry = |version: &str| VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(format!("/usr/bin/php{}", version))],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+        let mut config = Config {
+            versions: vec![entry("8.3.4"), entry("8.1.27")],
+            ..Config::default()
+        };
+
+        let items = vec!["legacy-billing".to_string(), "owner = billing-team".to_string()];
+        assert_eq!(config.label_version("8.1", &items, false).unwrap(), "8.1.27");
+        assert_eq!(config.find_version("legacy-billing").unwrap().version, "8.1.27");
+        assert_eq!(config.versions[1].metadata.get("owner").map(String::as_str), Some("billing-team"));
+
+        // Labels that look like versions would shadow real patterns
+        assert!(config.label_version("8.3", &["8.1".to_string()], false).is_err());
+
+        // A label moves to the newly labelled version
+        config.label_version("8.3", &["legacy-billing".to_string()], false).unwrap();
+        assert_eq!(config.find_version("legacy-billing").unwrap().version, "8.3.4");
+        assert!(config.versions[1].labels.is_empty());
+
+        config.label_version("legacy-billing", &["legacy-billing".to_string()], true).unwrap();
+        assert!(config.find_version("legacy-billing").is_none());
+
+        // Rescans keep labels, metadata and env
+        config.versions[1].env.insert("PHPRC".to_string(), "/etc/php/billing".to_string());
+        let toml_str = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&toml_str).unwrap(), config);
+        let installations = vec![PhpInstallation::new(
+            crate::version::PhpVersion::new(8, 1, 27),
+            PathBuf::from("/usr/bin/php8.1"),
+        )];
+        config.update_from_installations(&installations);
+        assert_eq!(config.versions[0].metadata.get("owner").map(String::as_str), Some("billing-team"));
+        assert_eq!(config.versions[0].env.get("PHPRC").map(String::as_str), Some("/etc/php/billing"));
+    }
+
+    #[test]
+    fn test_toolchain_deserialization() {
+        let toml_str = r#"
+            [toolchain.legacy]
+            php = "7.4"
+            composer = "1"
+            ini = "legacy"
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let legacy = &config.toolchains["legacy"];
+        assert_eq!(legacy.php, "7.4");
+        assert_eq!(legacy.ini.as_deref(), Some("legacy"));
+        assert_eq!(legacy.tools.get("composer").map(String::as_str), Some("1"));
+
+        let roundtrip: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(roundtrip, config);
+    }
 }
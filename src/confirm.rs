@@ -0,0 +1,108 @@
+// Confirmation module
+//
+// Commands that delete files or change things outside ~/.php-switcher ask
+// first. Automation fixes the answer up front with `--yes`/`--no` (exported
+// as `PHP_SWITCHER_ASSUME` so hooks and nested runs agree), or with
+// `confirm = "yes"` under [settings]. Without a terminal and without a fixed
+// answer, every question falls back to its own default, so unattended runs
+// behave the way the command always did.
+
+use crate::config::Config;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Environment variable fixing the answer to every confirmation (`yes`/`no`)
+pub const ASSUME_ENV: &str = "PHP_SWITCHER_ASSUME";
+
+/// How confirmation questions are answered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmPolicy {
+    /// Prompt on a terminal, use the question's default otherwise
+    #[default]
+    Ask,
+    /// Answer yes without asking
+    Yes,
+    /// Answer no without asking
+    No,
+}
+
+impl ConfirmPolicy {
+    fn from_env_value(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "yes" | "y" | "1" | "true" => Some(ConfirmPolicy::Yes),
+            "no" | "n" | "0" | "false" => Some(ConfirmPolicy::No),
+            "ask" => Some(ConfirmPolicy::Ask),
+            _ => None,
+        }
+    }
+
+    /// The answer, if it's decided without prompting
+    pub fn answer(&self, interactive: bool, default: bool) -> Option<bool> {
+        match self {
+            ConfirmPolicy::Yes => Some(true),
+            ConfirmPolicy::No => Some(false),
+            ConfirmPolicy::Ask if interactive => None,
+            ConfirmPolicy::Ask => Some(default),
+        }
+    }
+}
+
+/// The policy in effect: the environment (`--yes`/`--no`) wins over the config
+pub fn policy(config: &Config) -> ConfirmPolicy {
+    std::env::var(ASSUME_ENV)
+        .ok()
+        .and_then(|value| ConfirmPolicy::from_env_value(&value))
+        .or(config.settings.confirm)
+        .unwrap_or_default()
+}
+
+/// Ask a yes/no question; `default` is the answer for Enter and for unattended runs
+pub fn confirm(config: &Config, question: &str, default: bool) -> Result<bool> {
+    use std::io::Write;
+
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    let policy = policy(config);
+
+    if let Some(answer) = policy.answer(crate::process::is_interactive(), default) {
+        // Leave a trace of fixed answers in logs, on stderr to keep stdout for command output
+        if policy != ConfirmPolicy::Ask {
+            eprintln!("{} {} {}", question, hint, if answer { "yes" } else { "no" });
+        }
+        return Ok(answer);
+    }
+
+    eprint!("{} {} ", question, hint);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(match answer.trim() {
+        "" => default,
+        answer => matches!(answer, "y" | "Y" | "yes"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_answers() {
+        assert_eq!(ConfirmPolicy::Ask.answer(true, false), None);
+        assert_eq!(ConfirmPolicy::Ask.answer(false, true), Some(true));
+        assert_eq!(ConfirmPolicy::Ask.answer(false, false), Some(false));
+        assert_eq!(ConfirmPolicy::Yes.answer(true, false), Some(true));
+        assert_eq!(ConfirmPolicy::No.answer(false, true), Some(false));
+    }
+
+    #[test]
+    fn test_policy_from_env_value() {
+        assert_eq!(ConfirmPolicy::from_env_value("YES"), Some(ConfirmPolicy::Yes));
+        assert_eq!(ConfirmPolicy::from_env_value("0"), Some(ConfirmPolicy::No));
+        assert_eq!(ConfirmPolicy::from_env_value("maybe"), None);
+
+        let config: Config = toml::from_str("[settings]\nconfirm = \"no\"\n").unwrap();
+        assert_eq!(config.settings.confirm, Some(ConfirmPolicy::No));
+    }
+}
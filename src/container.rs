@@ -0,0 +1,93 @@
+// Container module
+//
+// Images of the official `php` repository pulled into the local Docker
+// daemon are PHP versions too, just not native ones: nothing can be linked
+// into the bin directory from them. Scans record them so `list` can show
+// them next to the installed versions, clearly marked as containers.
+
+use crate::config::ContainerImage;
+use regex::Regex;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the daemon; an unreachable socket shouldn't stall scans
+const DOCKER_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Names the official image goes by
+const PHP_REPOSITORIES: [&str; 3] = ["php", "library/php", "docker.io/library/php"];
+
+/// Parse `docker images --format '{{.Repository}}:{{.Tag}}'` output
+///
+/// Only tags starting with a version (`8.2-cli`, `8.3.4-fpm-alpine`) are
+/// kept; `latest` and friends don't say which PHP they hold.
+pub fn parse_images(output: &str) -> Vec<ContainerImage> {
+    let Ok(re) = Regex::new(r"^(\d+(?:\.\d+){0,2})(?:-|$)") else { return Vec::new() };
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let (repository, tag) = line.trim().rsplit_once(':')?;
+            if !PHP_REPOSITORIES.contains(&repository) {
+                return None;
+            }
+            let php_version = re.captures(tag)?[1].to_string();
+            Some(ContainerImage { image: format!("php:{}", tag), php_version })
+        })
+        .collect()
+}
+
+/// List the `php` images of the local Docker daemon
+///
+/// Empty when Docker isn't installed, the daemon isn't running, or it doesn't answer in time.
+pub fn find_images() -> Vec<ContainerImage> {
+    crate::timing::measure(|| "docker images".to_string(), || docker_images().map(|output| parse_images(&output)))
+        .unwrap_or_default()
+}
+
+fn docker_images() -> Option<String> {
+    use std::io::Read;
+
+    let mut child = Command::new("docker")
+        .args(["images", "--format", "{{.Repository}}:{{.Tag}}", "--filter", "reference=php"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let deadline = Instant::now() + DOCKER_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            _ => {
+                child.kill().ok();
+                child.wait().ok();
+                return None;
+            }
+        }
+    };
+    if !status.success() {
+        return None;
+    }
+
+    let mut stdout = String::new();
+    child.stdout.take()?.read_to_string(&mut stdout).ok()?;
+    Some(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_images() {
+        let output = "php:8.3-cli\nphp:8.2.12-fpm-alpine\nphp:latest\nmyorg/php:8.1\ndocker.io/library/php:7.4\n<none>:<none>\nphp:8\n";
+        let images = parse_images(output);
+        let found: Vec<(&str, &str)> = images.iter().map(|i| (i.image.as_str(), i.php_version.as_str())).collect();
+        assert_eq!(
+            found,
+            [("php:8.3-cli", "8.3"), ("php:8.2.12-fpm-alpine", "8.2.12"), ("php:7.4", "7.4"), ("php:8", "8")]
+        );
+    }
+}
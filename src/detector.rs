@@ -1,26 +1,207 @@
 // PHP installation detection module
 
+use crate::progress::{self, ScanEvent, Verdict};
+use crate::{process, timing};
 use crate::version::PhpVersion;
 use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PhpInstallation {
     pub version: PhpVersion,
     pub paths: Vec<PathBuf>,
+    pub layout: InstallLayout,
+    pub build: BuildInfo,
+    /// Extensions `php -m` listed at scan time
+    pub extensions: Vec<String>,
+    /// Other paths leading to the same binaries (symlinks, hardlinks), e.g. `/usr/bin/php`
+    pub aliases: Vec<PathBuf>,
 }
 
-impl PhpInstallation {
-    pub fn new(version: PhpVersion, path: PathBuf) -> Self {
+/// What put an installation on the machine, judged by where it lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstallSource {
+    Homebrew,
+    MacPorts,
+    Phpbrew,
+    Phpenv,
+    Asdf,
+    Mise,
+    Scoop,
+    Chocolatey,
+    Xampp,
+    Mamp,
+    Laragon,
+    /// cPanel's EasyApache packages
+    Cpanel,
+    /// Plesk's PHP handlers
+    Plesk,
+    /// CloudLinux's alt-php packages
+    CloudLinux,
+    /// A Nix profile or the Nix store
+    Nix,
+    /// A snap package (`/snap/bin` launchers or a snap's own files)
+    Snap,
+    /// A CI runner's tool cache (GitHub Actions, setup-php)
+    ToolCache,
+    /// The distribution's package manager (`/usr/bin`, `/usr/lib`)
+    System,
+    /// Anything else, e.g. a source build in `/usr/local` or `/opt`
+    Manual,
+}
+
+impl InstallSource {
+    /// Classify a binary by its (preferably resolved) path
+    pub fn classify(path: &Path) -> Self {
+        let path = path.to_string_lossy().replace('\\', "/").to_ascii_lowercase();
+        let markers = [
+            ("/cellar/", InstallSource::Homebrew),
+            ("/opt/homebrew/", InstallSource::Homebrew),
+            ("/home/linuxbrew/", InstallSource::Homebrew),
+            ("/opt/local/", InstallSource::MacPorts),
+            ("/.phpbrew/", InstallSource::Phpbrew),
+            ("/.phpenv/", InstallSource::Phpenv),
+            ("/.asdf/installs/", InstallSource::Asdf),
+            ("/mise/installs/", InstallSource::Mise),
+            ("/scoop/apps/", InstallSource::Scoop),
+            ("/tools/php", InstallSource::Chocolatey),
+            ("/xampp/", InstallSource::Xampp),
+            ("/opt/lampp/", InstallSource::Xampp),
+            ("/mamp/bin/php/", InstallSource::Mamp),
+            ("/opt/cpanel/ea-php", InstallSource::Cpanel),
+            ("/opt/plesk/php/", InstallSource::Plesk),
+            ("/opt/alt/php", InstallSource::CloudLinux),
+            ("/laragon/", InstallSource::Laragon),
+            ("/nix/store/", InstallSource::Nix),
+            ("/.nix-profile/", InstallSource::Nix),
+            ("/nix/var/nix/profiles/", InstallSource::Nix),
+            ("/run/current-system/sw/", InstallSource::Nix),
+            ("/etc/profiles/per-user/", InstallSource::Nix),
+            ("/hostedtoolcache/", InstallSource::ToolCache),
+        ];
+
+        match markers.iter().find(|(marker, _)| path.contains(marker)) {
+            Some((_, source)) => *source,
+            // Only the mount point; `~/snap` holds per-user snap data
+            None if path.starts_with("/snap/") => InstallSource::Snap,
+            None if ["/usr/bin/", "/bin/", "/usr/lib/"].iter().any(|dir| path.starts_with(dir)) => InstallSource::System,
+            None => InstallSource::Manual,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstallSource::Homebrew => "Homebrew",
+            InstallSource::MacPorts => "MacPorts",
+            InstallSource::Phpbrew => "phpbrew",
+            InstallSource::Phpenv => "phpenv",
+            InstallSource::Asdf => "asdf",
+            InstallSource::Mise => "mise",
+            InstallSource::Scoop => "Scoop",
+            InstallSource::Chocolatey => "Chocolatey",
+            InstallSource::Xampp => "XAMPP",
+            InstallSource::Mamp => "MAMP",
+            InstallSource::Laragon => "Laragon",
+            InstallSource::Cpanel => "cPanel",
+            InstallSource::Plesk => "Plesk",
+            InstallSource::CloudLinux => "CloudLinux alt-php",
+            InstallSource::Nix => "Nix",
+            InstallSource::Snap => "Snap",
+            InstallSource::ToolCache => "CI tool cache",
+            InstallSource::System => "system packages",
+            InstallSource::Manual => "manual builds",
+        }
+    }
+}
+
+/// Where an installation keeps its files, beyond the binaries
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct InstallLayout {
+    /// Install prefix (`--prefix` at build time)
+    #[serde(default, with = "crate::paths::option", skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<PathBuf>,
+    /// Directory searched for php.ini
+    #[serde(default, with = "crate::paths::option", skip_serializing_if = "Option::is_none")]
+    pub ini_dir: Option<PathBuf>,
+    /// The php.ini actually loaded
+    #[serde(default, with = "crate::paths::option", skip_serializing_if = "Option::is_none")]
+    pub ini_file: Option<PathBuf>,
+    /// Directories scanned for additional .ini files
+    #[serde(default, with = "crate::paths::vec", skip_serializing_if = "Vec::is_empty")]
+    pub ini_scan_dirs: Vec<PathBuf>,
+    #[serde(default, with = "crate::paths::option", skip_serializing_if = "Option::is_none")]
+    pub extension_dir: Option<PathBuf>,
+    /// Pool directory of the matching php-fpm, if one is installed
+    #[serde(default, with = "crate::paths::option", skip_serializing_if = "Option::is_none")]
+    pub fpm_config_dir: Option<PathBuf>,
+    /// The matching php-fpm binary, if one is installed
+    #[serde(default, with = "crate::paths::option", skip_serializing_if = "Option::is_none")]
+    pub fpm_binary: Option<PathBuf>,
+    /// Where the default pool listens: a socket path or `host:port`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fpm_listen: Option<String>,
+}
+
+impl InstallLayout {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How a PHP binary was built, telling apart installations of the same version
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BuildInfo {
+    /// Thread-safe (ZTS) rather than NTS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zts: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<bool>,
+    /// CPU architecture, e.g. `aarch64`; `x86_64/aarch64` for universal binaries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+}
+
+impl BuildInfo {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Read the build from `php -i` entries (see `probe::parse_build_info`)
+    pub fn from_php_info(build: &std::collections::BTreeMap<String, String>) -> Self {
+        let flag = |key: &str, yes: &str| build.get(key).map(|value| value.eq_ignore_ascii_case(yes));
         Self {
-            version,
-            paths: vec![path],
+            zts: flag("Thread Safety", "enabled"),
+            debug: flag("Debug Build", "yes"),
+            arch: build.get("Architecture").cloned(),
         }
     }
 
+    /// Short description, e.g. `ZTS, debug, aarch64`
+    pub fn summary(&self) -> String {
+        let threads = self.zts.map(|zts| if zts { "ZTS" } else { "NTS" });
+        let debug = self.debug.filter(|debug| *debug).map(|_| "debug");
+        [threads, debug, self.arch.as_deref()].into_iter().flatten().collect::<Vec<_>>().join(", ")
+    }
+}
+
+impl PhpInstallation {
+    pub fn new(version: PhpVersion, path: PathBuf) -> Self {
+        Self::with_paths(version, vec![path])
+    }
+
     pub fn with_paths(version: PhpVersion, paths: Vec<PathBuf>) -> Self {
-        Self { version, paths }
+        Self {
+            version,
+            paths,
+            layout: InstallLayout::default(),
+            build: BuildInfo::default(),
+            extensions: Vec::new(),
+            aliases: Vec::new(),
+        }
     }
 
     /// Get the primary PHP binary path (the 'php' executable)
@@ -38,14 +219,50 @@ impl PhpInstallation {
             self.paths.push(path);
         }
     }
+
+    /// Record another path leading to one of the binaries
+    pub fn add_alias(&mut self, path: PathBuf) {
+        if !self.paths.contains(&path) && !self.aliases.contains(&path) {
+            self.aliases.push(path);
+        }
+    }
+
+    /// The version manager that installed this, as recorded in the config (`auto` for anything else)
+    ///
+    /// `windows` marks a Windows build seen from WSL, whoever installed it.
+    pub fn source(&self) -> &'static str {
+        if self.primary_path().is_some_and(|path| is_wsl_windows_binary(path)) {
+            return "windows";
+        }
+        match self.primary_path().map(|path| InstallSource::classify(path)) {
+            Some(InstallSource::Asdf) => "asdf",
+            Some(InstallSource::Mise) => "mise",
+            Some(InstallSource::Nix) => "nix",
+            Some(InstallSource::Snap) => "snap",
+            Some(InstallSource::MacPorts) => "macports",
+            Some(InstallSource::CloudLinux) => "cloudlinux",
+            _ => "auto",
+        }
+    }
+
+    /// The `port select` name of a MacPorts installation, e.g. `php82`
+    pub fn port_select_name(&self) -> Option<String> {
+        self.paths.iter().filter(|path| InstallSource::classify(path) == InstallSource::MacPorts).find_map(|path| {
+            // `/opt/local/bin/php` is the link `port select` maintains
+            let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            let name = resolved.file_name()?.to_str()?;
+            let digits = name.strip_prefix("php")?;
+            (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then(|| name.to_string())
+        })
+    }
 }
 
 /// Get the version from a PHP binary by running it with -v
 pub fn get_version_from_binary<P: AsRef<Path>>(binary_path: P) -> Result<PhpVersion> {
-    let output = Command::new(binary_path.as_ref())
-        .arg("-v")
-        .output()
-        .map_err(|e| anyhow!("Failed to execute PHP binary: {}", e))?;
+    let binary_path = binary_path.as_ref();
+    let output = run_php(binary_path, &["-v"])
+        .map_err(|e| anyhow!("Failed to execute PHP binary: {}", e))?
+        .ok_or_else(|| anyhow!("PHP binary didn't answer -v within {}s", probe_timeout().as_secs()))?;
 
     if !output.status.success() {
         return Err(anyhow!("PHP binary returned non-zero exit code"));
@@ -60,6 +277,222 @@ pub fn parse_php_v_output(output: &str) -> Result<PhpVersion> {
     PhpVersion::from_php_output(output)
 }
 
+/// Get the php.ini a binary loads, from 'php --ini'
+pub fn get_ini_file<P: AsRef<Path>>(binary_path: P) -> Option<PathBuf> {
+    get_ini_locations(binary_path).0
+}
+
+/// Get the php.ini and the additional ini directories of a binary, from 'php --ini'
+pub fn get_ini_locations<P: AsRef<Path>>(binary_path: P) -> (Option<PathBuf>, Vec<PathBuf>) {
+    let binary_path = binary_path.as_ref();
+    let Ok(Some(output)) = run_php(binary_path, &["--ini"]) else {
+        return (None, Vec::new());
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    (parse_ini_output(&stdout), parse_ini_scan_dirs(&stdout))
+}
+
+/// Parse the loaded configuration file out of 'php --ini'
+pub fn parse_ini_output(output: &str) -> Option<PathBuf> {
+    ini_output_field(output, "Loaded Configuration File:").map(PathBuf::from)
+}
+
+/// Parse the additional ini directories out of 'php --ini'
+pub fn parse_ini_scan_dirs(output: &str) -> Vec<PathBuf> {
+    ini_output_field(output, "Scan for additional .ini files in:")
+        .map(|dirs| std::env::split_paths(dirs).collect())
+        .unwrap_or_default()
+}
+
+fn ini_output_field<'a>(output: &'a str, label: &str) -> Option<&'a str> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix(label))
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && *value != "(none)")
+}
+
+/// Get the extensions a binary loads, from 'php -m'
+pub fn get_extensions<P: AsRef<Path>>(binary_path: P) -> Vec<String> {
+    let binary_path = binary_path.as_ref();
+    match run_php(binary_path, &["-m"]) {
+        Ok(Some(output)) if output.status.success() => parse_modules_output(&String::from_utf8_lossy(&output.stdout)),
+        _ => Vec::new(),
+    }
+}
+
+/// Work out the install layout of a binary from 'php -i' and a sibling php-config
+pub fn get_install_layout<P: AsRef<Path>>(binary_path: P) -> InstallLayout {
+    get_install_details(binary_path.as_ref()).0
+}
+
+/// Layout and build of an installation, from a single `php -i`
+///
+/// The architecture comes from the executable header when it can be read,
+/// since `php -i` only reports it on Windows.
+pub fn get_install_details(binary_path: &Path) -> (InstallLayout, BuildInfo) {
+    let info = run_php(binary_path, &["-i"])
+        .ok()
+        .flatten()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default();
+    let mut layout = parse_install_layout(&info);
+    let mut build = BuildInfo::from_php_info(&crate::probe::parse_build_info(&info));
+    if let Some(target) = crate::binary::read_target(binary_path).filter(|target| !target.arches.is_empty()) {
+        build.arch = Some(target.arches.join("/"));
+    }
+
+    // php-config (from the dev package) knows the prefix even when the configure line is gone
+    if let Some(php_config) = php_config_for(binary_path) {
+        if let Ok(Some(output)) = run_php(&php_config, &["--prefix"]) {
+            let prefix = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if output.status.success() && !prefix.is_empty() {
+                layout.prefix = Some(PathBuf::from(prefix));
+            }
+        }
+    }
+
+    layout.fpm_config_dir = fpm_config_dir_candidates(&layout).into_iter().find(|dir| dir.is_dir());
+    // `/usr/bin/php` is usually a link; its target's name carries the version suffix, so try it first
+    let binaries: Vec<PathBuf> = binary_path.canonicalize().into_iter().chain([binary_path.to_path_buf()]).collect();
+    layout.fpm_binary = fpm_binary_candidates(&binaries, &layout).into_iter().find(|path| path.is_file());
+    if layout.fpm_binary.is_some() {
+        layout.fpm_listen = layout.fpm_config_dir.as_deref().and_then(fpm_listen);
+    }
+    (layout, build)
+}
+
+/// Parse prefix, php.ini directory and extension directory out of 'php -i'
+pub fn parse_install_layout(output: &str) -> InstallLayout {
+    let field = |label: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(label)?.trim_start().strip_prefix("=> "))
+            .and_then(|value| value.split(" => ").next())
+            .map(str::trim)
+            .filter(|value| !value.is_empty() && *value != "(none)" && *value != "no value")
+    };
+
+    let prefix = field("Configure Command").and_then(|command| {
+        let re = Regex::new(r#"--prefix=([^'"\s]+)"#).ok()?;
+        Some(PathBuf::from(&re.captures(command)?[1]))
+    });
+
+    InstallLayout {
+        prefix,
+        ini_dir: field("Configuration File (php.ini) Path").map(PathBuf::from),
+        // The same as 'php --ini' reports, without running the binary again
+        ini_file: field("Loaded Configuration File").map(PathBuf::from),
+        ini_scan_dirs: field("Scan this dir for additional .ini files")
+            .map(|dirs| std::env::split_paths(dirs).collect())
+            .unwrap_or_default(),
+        extension_dir: field("extension_dir").map(PathBuf::from),
+        fpm_config_dir: None,
+        fpm_binary: None,
+        fpm_listen: None,
+    }
+}
+
+/// The php-config next to a binary (`php8.2` → `php-config8.2`), if installed
+fn php_config_for(binary_path: &Path) -> Option<PathBuf> {
+    let name = binary_path.file_name()?.to_str()?;
+    let suffix = name.strip_prefix("php")?;
+    let php_config = binary_path.with_file_name(format!("php-config{}", suffix));
+    php_config.is_file().then_some(php_config)
+}
+
+/// Where the php-fpm of an installation keeps its config, most specific first
+///
+/// Debian splits `/etc/php/8.2/cli` and `/etc/php/8.2/fpm`; Homebrew keeps
+/// `php-fpm.d` next to php.ini; source builds use `<prefix>/etc/php-fpm.d`.
+pub fn fpm_config_dir_candidates(layout: &InstallLayout) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(ini_dir) = &layout.ini_dir {
+        if ini_dir.file_name().is_some_and(|name| name == "cli") {
+            candidates.extend(ini_dir.parent().map(|dir| dir.join("fpm")));
+        }
+        candidates.push(ini_dir.join("php-fpm.d"));
+    }
+    candidates.extend(layout.prefix.as_ref().map(|prefix| prefix.join("etc/php-fpm.d")));
+    candidates
+}
+
+/// Where the php-fpm of an installation may be, most specific first
+///
+/// Packages put it in the `sbin` next to `bin` with the same suffix as the
+/// CLI (`/usr/bin/php8.2` → `/usr/sbin/php-fpm8.2`, `php82` → `php-fpm82`).
+pub fn fpm_binary_candidates(binaries: &[PathBuf], layout: &InstallLayout) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    for binary in binaries {
+        let (Some(dir), Some(suffix)) = (
+            binary.parent(),
+            binary.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_prefix("php")),
+        ) else {
+            continue;
+        };
+        let name = format!("php-fpm{}", suffix);
+        candidates.extend(dir.parent().map(|parent| parent.join("sbin").join(&name)));
+        candidates.push(dir.join(&name));
+    }
+    candidates.extend(layout.prefix.as_ref().map(|prefix| prefix.join("sbin/php-fpm")));
+
+    let mut seen = std::collections::HashSet::new();
+    candidates.retain(|path| seen.insert(path.clone()));
+    candidates
+}
+
+/// The address the first pool in an fpm config directory listens on
+///
+/// Debian keeps pools in `pool.d`, Homebrew and source builds directly in `php-fpm.d`.
+pub fn fpm_listen(config_dir: &Path) -> Option<String> {
+    [config_dir.join("pool.d"), config_dir.to_path_buf()].iter().find_map(|dir| {
+        let mut pools: Vec<PathBuf> = std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "conf"))
+            .collect();
+        pools.sort();
+        pools.iter().find_map(|pool| parse_fpm_listen(&std::fs::read_to_string(pool).ok()?))
+    })
+}
+
+/// Parse the `listen` directive out of a pool config, filling in `$pool`
+pub fn parse_fpm_listen(content: &str) -> Option<String> {
+    let mut pool = "";
+    for line in content.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            pool = name;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else { continue };
+        if key.trim() == "listen" && !line.starts_with(';') {
+            let value = value.trim().trim_matches('"');
+            return (!value.is_empty()).then(|| value.replace("$pool", pool));
+        }
+    }
+    None
+}
+
+/// Parse the module list printed by 'php -m'
+///
+/// Only lines under a `[... Modules]` header count, so wrappers that print
+/// something else yield no extensions rather than garbage.
+pub fn parse_modules_output(output: &str) -> Vec<String> {
+    let mut in_section = false;
+    let mut modules = Vec::new();
+
+    for line in output.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line.ends_with("Modules]");
+        } else if in_section && !line.is_empty() {
+            modules.push(line.to_string());
+        }
+    }
+
+    modules
+}
+
 /// Check if a binary is a valid PHP executable
 pub fn is_valid_php_binary<P: AsRef<Path>>(binary_path: P) -> Result<()> {
     let path = binary_path.as_ref();
@@ -70,10 +503,9 @@ pub fn is_valid_php_binary<P: AsRef<Path>>(binary_path: P) -> Result<()> {
     }
 
     // Try to run it with -v
-    let output = Command::new(path)
-        .arg("-v")
-        .output()
-        .map_err(|e| anyhow!("Failed to execute binary: {}", e))?;
+    let output = run_php(path, &["-v"])
+        .map_err(|e| anyhow!("Failed to execute binary: {}", e))?
+        .ok_or_else(|| anyhow!("Binary didn't answer -v within {}s", probe_timeout().as_secs()))?;
 
     // Check if output contains "PHP"
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -86,162 +518,947 @@ pub fn is_valid_php_binary<P: AsRef<Path>>(binary_path: P) -> Result<()> {
 
 /// Detect the currently active PHP installation (from PATH)
 pub fn detect_current_php() -> Result<PhpInstallation> {
-    let version = get_version_from_binary("php")?;
+    timing::measure(|| "detect current php".to_string(), detect_php_on_path)
+}
 
-    // Find the actual path to PHP
-    let which_output = Command::new("which")
-        .arg("php")
-        .output()
-        .map_err(|e| anyhow!("Failed to run 'which php': {}", e))?;
+fn detect_php_on_path() -> Result<PhpInstallation> {
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let path = find_in_path("php", &path_var).ok_or_else(|| anyhow!("Could not find PHP in PATH"))?;
 
-    if !which_output.status.success() {
-        return Err(anyhow!("Could not find PHP in PATH"));
+    // The switcher's own link is already described by the config; only run other binaries
+    let version = match switched_version(&path) {
+        Some(version) => version,
+        None => get_version_from_binary(&path)?,
+    };
+
+    Ok(PhpInstallation::new(version, path))
+}
+
+/// Version of the tracked entry the switcher's `php` link points to, if `path` is that link
+fn switched_version(path: &Path) -> Option<PhpVersion> {
+    let bin_dir = crate::switcher::get_bin_dir().ok()?;
+    if path != bin_dir.join("php") {
+        return None;
     }
 
-    let path_str = String::from_utf8_lossy(&which_output.stdout);
-    let path = PathBuf::from(path_str.trim());
+    let config = crate::config::load_config().ok()?;
+    let entry = crate::switcher::active_entry(&config, &bin_dir)?;
+    PhpVersion::from_php_output(&format!("PHP {}", entry.version)).ok()
+}
 
-    Ok(PhpInstallation::new(version, path))
+/// Find the executable a command name resolves to, the way the shell walks PATH
+///
+/// Done in-process so detection works where `which` isn't installed
+/// (minimal containers, some distributions).
+pub fn find_in_path(name: &str, path_var: &std::ffi::OsStr) -> Option<PathBuf> {
+    std::env::split_paths(path_var)
+        // An empty entry means the current directory
+        .map(|dir| if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir })
+        .flat_map(|dir| executable_names(name).into_iter().map(move |name| dir.join(name)))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// File names a command can have: just the name on Unix, with an executable extension on Windows
+fn executable_names(name: &str) -> Vec<String> {
+    if cfg!(windows) {
+        ["exe", "cmd", "bat"].iter().map(|ext| format!("{}.{}", name, ext)).collect()
+    } else {
+        vec![name.to_string()]
+    }
+}
+
+/// Check whether a path is a file anyone may execute
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Check whether a path is a file; Windows decides executability by extension
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// How long a binary may take to answer a probe unless `probe_timeout` says otherwise
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Overrides `settings.probe_timeout` (in seconds) for every probe
+pub const PROBE_TIMEOUT_ENV: &str = "PHP_SWITCHER_PROBE_TIMEOUT";
+
+/// `settings.probe_timeout` from the last loaded config, 0 when unset
+static PROBE_TIMEOUT_SETTING: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Use `settings.probe_timeout`; called when the config is loaded
+pub fn set_probe_timeout_setting(seconds: Option<u64>) {
+    PROBE_TIMEOUT_SETTING.store(seconds.unwrap_or(0), std::sync::atomic::Ordering::Relaxed);
+}
+
+/// How long a binary may take to answer a probe
+pub fn probe_timeout() -> Duration {
+    std::env::var(PROBE_TIMEOUT_ENV)
+        .ok()
+        .and_then(|seconds| seconds.trim().parse().ok())
+        .or_else(|| Some(PROBE_TIMEOUT_SETTING.load(std::sync::atomic::Ordering::Relaxed)))
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_PROBE_TIMEOUT)
+}
+
+/// Run a PHP binary (or its php-config) for its output; `None` when it was killed for taking too long
+///
+/// A hung or hostile binary must not stall a scan, so nothing here waits
+/// longer than `probe_timeout`.
+pub fn run_php(binary: &Path, args: &[&str]) -> std::io::Result<Option<Output>> {
+    timing::measure(
+        || format!("{} {}", binary.display(), args.join(" ")),
+        || process::output_within(Command::new(binary).args(args), probe_timeout()),
+    )
+}
+
+/// Why part of a scan failed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    /// A directory or binary we weren't allowed to read or run
+    PermissionDenied,
+    /// A binary that didn't answer `-v` in time
+    Timeout,
+    /// A php binary that didn't run or didn't print a PHP version
+    InvalidBinary,
+    /// A directory that couldn't be read for another reason
+    Unreadable,
+}
+
+impl ScanErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PermissionDenied => "permission denied",
+            Self::Timeout => "timed out",
+            Self::InvalidBinary => "invalid binary",
+            Self::Unreadable => "unreadable",
+        }
+    }
+}
+
+/// A place the scan couldn't look at, as opposed to one without PHP
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanError {
+    #[serde(with = "crate::paths::path")]
+    pub path: PathBuf,
+    pub kind: ScanErrorKind,
+    pub message: String,
+}
+
+impl ScanError {
+    fn from_io(path: &Path, error: &std::io::Error) -> Self {
+        let kind = match error.kind() {
+            std::io::ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+            _ => ScanErrorKind::Unreadable,
+        };
+        Self { path: path.to_path_buf(), kind, message: error.to_string() }
+    }
+}
+
+/// Installations found by a scan, and the errors it ran into
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanReport {
+    pub installations: Vec<PhpInstallation>,
+    pub errors: Vec<ScanError>,
+    /// Binaries left out because of `exclude_paths` or `exclude_versions`
+    pub excluded: Vec<Exclusion>,
+    /// Binaries named like a PHP version (`php8.3`) that couldn't be run
+    pub broken: Vec<crate::config::BrokenInstall>,
+}
+
+/// A binary the scan found but left out on purpose
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Exclusion {
+    #[serde(with = "crate::paths::path")]
+    pub path: PathBuf,
+    /// The setting entry that matched, e.g. `path /mnt/backup` or `version 7.4`
+    pub rule: String,
+}
+
+/// What a system scan looks at beyond the built-in locations, and what it leaves out
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanOptions {
+    /// Scanned after the built-in locations, down to `depth` levels (see `dirs_below`)
+    pub extra_dirs: Vec<PathBuf>,
+    pub depth: usize,
+    /// Binaries in or below these paths aren't run
+    pub exclude_paths: Vec<PathBuf>,
+    /// Versions matching these patterns (`7.4`, `<8.0`) are dropped
+    pub exclude_versions: Vec<String>,
+    /// Read versions out of the binaries instead of running them (see `read_version`)
+    pub no_exec: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            extra_dirs: Vec::new(),
+            depth: DEFAULT_SCAN_DEPTH,
+            exclude_paths: Vec::new(),
+            exclude_versions: Vec::new(),
+            no_exec: false,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// The exclusion rule a binary falls under, judged by its path
+    pub fn path_exclusion(&self, path: &Path) -> Option<String> {
+        self.exclude_paths.iter().find(|dir| path.starts_with(dir)).map(|dir| format!("path {}", dir.display()))
+    }
+
+    /// The exclusion rule an installation falls under, judged by its version
+    pub fn version_exclusion(&self, version: &PhpVersion) -> Option<String> {
+        self.exclude_versions.iter().find(|pattern| version.matches(pattern)).map(|pattern| format!("version {}", pattern))
+    }
 }
 
 /// Scan a directory for PHP binaries
 pub fn scan_directory_for_php<P: AsRef<Path>>(dir_path: P) -> Result<Vec<PhpInstallation>> {
     let dir = dir_path.as_ref();
-    let mut installations = Vec::new();
+    let report = scan_directory(dir);
 
-    if !dir.exists() || !dir.is_dir() {
-        return Ok(installations);
+    match report.errors.iter().find(|e| e.path == dir) {
+        Some(error) => Err(anyhow!("Failed to read directory {}: {}", dir.display(), error.message)),
+        None => Ok(report.installations),
     }
+}
 
-    // Read directory entries
-    let entries = std::fs::read_dir(dir)
-        .map_err(|e| anyhow!("Failed to read directory {}: {}", dir.display(), e))?;
+/// Scan a directory and those below it (see `dirs_below`) for PHP binaries, reporting what couldn't be read or run
+///
+/// A missing directory is not an error; there's just no PHP there.
+pub fn scan_directory(dir: &Path) -> ScanReport {
+    scan_directory_matching(dir, DEFAULT_SCAN_DEPTH, |filename| filename.as_encoded_bytes().starts_with(b"php"))
+}
 
-    for entry in entries.flatten() {
-        let path = entry.path();
+/// Scan a directory for PHP binaries, only running the files whose name passes `wanted`
+fn scan_directory_matching(dir: &Path, depth: usize, wanted: impl Fn(&std::ffi::OsStr) -> bool) -> ScanReport {
+    let mut errors = Vec::new();
+    let candidates: Vec<PathBuf> =
+        dirs_below(dir, depth, &mut errors).iter().flat_map(|dir| candidates_in(dir, &wanted, &mut errors)).collect();
+    let mut report = probe_all(&candidates);
+    errors.append(&mut report.errors);
+    report.errors = errors;
+    report
+}
 
-        // Only check files (not directories)
-        if !path.is_file() {
-            continue;
+/// Files in a directory worth running `-v` on
+fn candidates_in(dir: &Path, wanted: impl Fn(&std::ffi::OsStr) -> bool, errors: &mut Vec<ScanError>) -> Vec<PathBuf> {
+    read_dir_reporting(dir, errors)
+        .into_iter()
+        .filter(|path| {
+            // Only check files (not directories)
+            path.is_file()
+                && path.file_name().is_some_and(&wanted)
+                // Windows builds keep php8ts.dll and friends next to php.exe
+                && (!cfg!(windows) || path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("exe")))
+        })
+        .collect()
+}
+
+/// Probe binaries concurrently and report them in the order given
+fn probe_all(paths: &[PathBuf]) -> ScanReport {
+    let mut report = ScanReport::default();
+    for (path, result) in paths.iter().zip(probe_concurrently(paths, false)) {
+        match result {
+            Ok(version) => report.installations.push(PhpInstallation::new(version, path.clone())),
+            Err(error) if is_reportable(&error) => report.errors.push(error),
+            Err(_) => {}
         }
+    }
+    report
+}
 
-        // Check if filename starts with "php"
-        if let Some(filename) = path.file_name() {
-            let filename_str = filename.to_string_lossy();
-            if filename_str.starts_with("php") {
-                // Try to get version from this binary
-                if let Ok(version) = get_version_from_binary(&path) {
-                    installations.push(PhpInstallation::new(version, path));
-                }
+/// php-config, phpize and friends aren't PHP; only complain about binaries that should be
+fn is_reportable(error: &ScanError) -> bool {
+    error.kind != ScanErrorKind::InvalidBinary || error.path.file_name().is_some_and(is_php_binary_name)
+}
+
+/// `probe_concurrently`, answering unchanged binaries from the version cache
+///
+/// Snap launchers are always run: they stay the same file when the snap behind them is refreshed.
+fn probe_with_cache(paths: &[PathBuf], no_exec: bool) -> Vec<std::result::Result<PhpVersion, ScanError>> {
+    use crate::probe::{self, Fingerprint};
+
+    let mut cache = probe::load_version_cache();
+    let fingerprints: Vec<Option<Fingerprint>> =
+        paths.iter().map(|path| Fingerprint::of(path).filter(|_| !is_snap_launcher(path))).collect();
+    let mut results: Vec<Option<std::result::Result<PhpVersion, ScanError>>> = paths
+        .iter()
+        .zip(&fingerprints)
+        .map(|(path, fingerprint)| {
+            let fingerprint = fingerprint.filter(|_| !probe::is_cache_disabled())?;
+            cache.get(path, fingerprint).map(Ok)
+        })
+        .collect();
+
+    let pending: Vec<usize> = (0..paths.len()).filter(|&i| results[i].is_none()).collect();
+    let pending_paths: Vec<PathBuf> = pending.iter().map(|&i| paths[i].clone()).collect();
+    for (&i, result) in pending.iter().zip(probe_concurrently(&pending_paths, no_exec)) {
+        if let (Ok(version), Some(fingerprint)) = (&result, fingerprints[i]) {
+            cache.insert(&paths[i], fingerprint, version);
+        }
+        results[i] = Some(result);
+    }
+    if !pending.is_empty() {
+        cache.retain_existing();
+        probe::save_version_cache(&cache).ok();
+    }
+
+    results.into_iter().map(|result| result.expect("every binary is probed or cached")).collect()
+}
+
+/// Upper bound on `-v` probes running at once
+const MAX_SCAN_THREADS: usize = 8;
+
+/// Run `-v` on every binary (or read their versions, with `no_exec`) with a bounded pool of threads
+///
+/// Results come back in the order of `paths`, whichever probe finishes first,
+/// so scans stay deterministic. Probes are recorded for `--profile-startup`
+/// on the calling thread, since spans are thread-local.
+fn probe_concurrently(paths: &[PathBuf], no_exec: bool) -> Vec<std::result::Result<PhpVersion, ScanError>> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get()).clamp(2, MAX_SCAN_THREADS).min(paths.len());
+    progress::report(|| ScanEvent::Probing { total: paths.len() });
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; paths.len()]);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else { break };
+                let started = Instant::now();
+                let result = if no_exec { read_version(path) } else { scan_version(path) };
+                progress::report(|| ScanEvent::Probed);
+                results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some((result, started.elapsed()));
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    paths
+        .iter()
+        .zip(results)
+        .map(|(path, result)| {
+            let (result, elapsed) = result.expect("every binary is probed");
+            if timing::is_enabled() {
+                timing::record(format!("{} -v", path.display()), elapsed);
             }
+            result
+        })
+        .collect()
+}
+
+/// List a directory's entries, recording why it couldn't be read (if it exists)
+fn read_dir_reporting(dir: &Path, errors: &mut Vec<ScanError>) -> Vec<PathBuf> {
+    progress::report(|| ScanEvent::Directory(dir.to_path_buf()));
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound || !dir.exists() => Vec::new(),
+        Err(e) => {
+            errors.push(ScanError::from_io(dir, &e));
+            Vec::new()
         }
     }
+}
+
+/// Directories `depth` levels below `root` that may hold PHP binaries
+///
+/// The first level only counts directories named `php*` (`C:\tools\php83`,
+/// `scoop\apps\php82`); deeper levels take every directory (Scoop's version
+/// directories).
+pub fn php_dirs_below(root: &Path, depth: usize, errors: &mut Vec<ScanError>) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    for level in 0..depth {
+        dirs = dirs
+            .iter()
+            .flat_map(|dir| read_dir_reporting(dir, errors))
+            .filter(|path| path.is_dir())
+            .filter(|path| level > 0 || path.file_name().is_some_and(|n| n.to_string_lossy().to_ascii_lowercase().starts_with("php")))
+            .collect();
+    }
+    dirs.sort();
+    dirs
+}
+
+/// Check whether a file name is the CLI itself (`php`, `php8.2`, `php82`) rather than `php-cgi` and friends
+pub fn is_php_cli_name(name: &std::ffi::OsStr) -> bool {
+    let Some(name) = name.to_str() else { return false };
+    let name = name.strip_suffix(".exe").or_else(|| name.strip_suffix(".EXE")).unwrap_or(name);
+    name.strip_prefix("php")
+        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit() || c == '.'))
+}
+
+/// The version a CLI binary's name promises: `php8.3` and `php83` give `8.3`, plain `php` nothing
+pub fn version_from_file_name(name: &std::ffi::OsStr) -> Option<String> {
+    if !is_php_cli_name(name) {
+        return None;
+    }
+    let name = name.to_str()?;
+    let name = name.strip_suffix(".exe").or_else(|| name.strip_suffix(".EXE")).unwrap_or(name);
+    let digits = &name["php".len()..];
+    if digits.contains('.') {
+        let parts: Vec<&str> = digits.split('.').collect();
+        return (parts.len() <= 3 && parts.iter().all(|part| !part.is_empty())).then(|| digits.to_string());
+    }
+    match digits.len() {
+        1 => Some(digits.to_string()),
+        2 => Some(format!("{}.{}", &digits[..1], &digits[1..])),
+        _ => None,
+    }
+}
 
-    Ok(installations)
+/// Check whether a file name is one of PHP's own binaries (`php`, `php8.2`, `php-cgi`, `php-fpm8.3`, ...)
+pub fn is_php_binary_name(name: &std::ffi::OsStr) -> bool {
+    let Some(name) = name.to_str() else { return false };
+    let name = match name.len().checked_sub(4) {
+        Some(stem) if name.is_char_boundary(stem) && name[stem..].eq_ignore_ascii_case(".exe") => &name[..stem],
+        _ => name,
+    };
+    let rest = ["php-cgi", "php-fpm", "phpdbg", "php"]
+        .iter()
+        .find_map(|base| name.strip_prefix(base))
+        .unwrap_or(name);
+    rest.len() < name.len() && rest.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Run `<binary> -v` with a timeout, classifying failures
+fn scan_version(binary: &Path) -> std::result::Result<PhpVersion, ScanError> {
+    let error = |kind, message: String| ScanError { path: binary.to_path_buf(), kind, message };
+
+    // "Exec format error" and exit code 127 say little; the header tells a wrong CPU or OS apart
+    let mismatch = || {
+        crate::binary::read_target(binary)
+            .and_then(|target| target.mismatch(crate::binary::Os::current(), std::env::consts::ARCH))
+    };
+
+    let output = run_php(binary, &["-v"])
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => error(ScanErrorKind::PermissionDenied, e.to_string()),
+            _ => error(ScanErrorKind::InvalidBinary, mismatch().unwrap_or_else(|| e.to_string())),
+        })?
+        .ok_or_else(|| {
+            error(ScanErrorKind::Timeout, format!("no answer to -v within {}s, killed", probe_timeout().as_secs()))
+        })?;
+
+    if !output.status.success() {
+        // The dynamic loader names the missing library on stderr
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let complaint = stderr.lines().map(str::trim).find(|line| !line.is_empty()).map(str::to_string);
+        let message = mismatch().or(complaint).unwrap_or_else(|| format!("-v exited with {}", output.status));
+        return Err(error(ScanErrorKind::InvalidBinary, message));
+    }
+
+    parse_php_v_output(&String::from_utf8_lossy(&output.stdout)).map_err(|e| error(ScanErrorKind::InvalidBinary, e.to_string()))
+}
+
+/// Read a binary's version without running it
+///
+/// Only real executables carry one; launcher scripts (snap's, wrappers) are
+/// rejected, since finding out what they start would mean running them.
+fn read_version(binary: &Path) -> std::result::Result<PhpVersion, ScanError> {
+    let error = |message: String| ScanError { path: binary.to_path_buf(), kind: ScanErrorKind::InvalidBinary, message };
+
+    match crate::binary::read_php_version(binary) {
+        Ok(Some(version)) => parse_php_v_output(&format!("PHP {}", version)).map_err(|e| error(e.to_string())),
+        Ok(None) => Err(error("no PHP version compiled into it, and no-exec scans don't run it".to_string())),
+        Err(e) => Err(ScanError::from_io(binary, &e)),
+    }
 }
 
 /// Find all PHP installations on the system
-pub fn find_all_php_installations() -> Result<Vec<PhpInstallation>> {
-    use std::collections::{HashMap, HashSet};
+pub fn find_all_php_installations(options: &ScanOptions) -> Result<Vec<PhpInstallation>> {
+    Ok(scan_system(options).installations)
+}
 
-    let mut installations_by_version: HashMap<String, PhpInstallation> = HashMap::new();
-    let mut seen_canonical_paths = HashSet::new();
+/// Parse `update-alternatives --list php`: one registered binary per line
+pub fn parse_alternatives_list(output: &str) -> Vec<PathBuf> {
+    output.lines().map(str::trim).filter(|line| line.starts_with('/')).map(PathBuf::from).collect()
+}
+
+/// The binaries registered for Debian's `php` alternative
+///
+/// They normally live in `/usr/bin`, but packages outside the archive can
+/// register binaries anywhere.
+fn alternatives_candidates() -> Vec<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+    Command::new("update-alternatives")
+        .args(["--list", "php"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| parse_alternatives_list(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// Where MacPorts links its binaries (`php82`, `php83`, and `php` after `port select`)
+pub const MACPORTS_BIN: &str = "/opt/local/bin";
+
+/// Where MAMP keeps one directory per bundled PHP build (`php8.2.0/bin/php`)
+pub const MAMP_PHP_DIR: &str = "/Applications/MAMP/bin/php";
+
+/// XAMPP's bin directories on Linux and macOS
+pub const XAMPP_BIN_DIRS: [&str; 2] = ["/opt/lampp/bin", "/Applications/XAMPP/xamppfiles/bin"];
+
+/// cPanel's EasyApache packages, SCL style: `ea-php82/root/usr/bin/php`
+pub const CPANEL_DIR: &str = "/opt/cpanel";
+
+/// Plesk's PHP handlers: `8.2/bin/php`
+pub const PLESK_PHP_DIR: &str = "/opt/plesk/php";
+
+/// CloudLinux's alt-php packages: `php82/usr/bin/php`
+pub const CLOUDLINUX_ALT_DIR: &str = "/opt/alt";
+
+/// `<dir>/<prefix><version>/<below>` for every versioned directory in `dir`
+///
+/// Stacks and hosting panels keep one directory per PHP version, named like
+/// `php8.2.0` (MAMP), `ea-php82` (cPanel) or `8.2` (Plesk).
+pub fn versioned_dirs(dir: &Path, prefix: &str, below: &str, errors: &mut Vec<ScanError>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = read_dir_reporting(dir, errors)
+        .into_iter()
+        .filter(|dir| {
+            let name = dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            name.strip_prefix(prefix).is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .map(|dir| dir.join(below))
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// GitHub Actions' tool cache, unless `RUNNER_TOOL_CACHE` says otherwise
+pub const HOSTED_TOOLCACHE_DIR: &str = "/opt/hostedtoolcache";
+
+/// Architecture directories of the tool cache, `php/8.3.4/x64/bin/php`
+const TOOLCACHE_ARCHES: [&str; 2] = ["x64", "arm64"];
+
+/// Where setup-php keeps the builds it installs itself: `8.3/bin/php`
+pub const SETUP_PHP_DIR: &str = "/usr/local/php";
+
+/// The bin directories of every PHP in a runner's tool cache (`<tool_cache>/php/<version>/<arch>/bin`)
+pub fn toolcache_bin_dirs(tool_cache: &Path, errors: &mut Vec<ScanError>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = read_dir_reporting(&tool_cache.join("php"), errors)
+        .into_iter()
+        .filter(|dir| dir.file_name().is_some_and(|name| name.to_string_lossy().starts_with(|c: char| c.is_ascii_digit())))
+        .flat_map(|version_dir| TOOLCACHE_ARCHES.map(|arch| version_dir.join(arch).join("bin")))
+        .filter(|dir| dir.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Where WSL mounts the Windows drives (`/mnt/c`)
+pub const WSL_MOUNT_ROOT: &str = "/mnt";
+
+/// The Windows drives mounted below `mount_root`, i.e. its single-letter directories
+pub fn wsl_drives(mount_root: &Path) -> Vec<PathBuf> {
+    let mut drives: Vec<PathBuf> = std::fs::read_dir(mount_root)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    drives.retain(|path| {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        name.len() == 1 && name.chars().all(|c| c.is_ascii_lowercase()) && path.is_dir()
+    });
+    drives.sort();
+    drives
+}
+
+/// Check whether a binary is a Windows build on a drive WSL mounted (`/mnt/c/php/php.exe`)
+///
+/// Such builds only run through WSL's interop, with Windows paths and ini files.
+pub fn is_wsl_windows_binary(path: &Path) -> bool {
+    let Ok(rest) = path.strip_prefix(WSL_MOUNT_ROOT) else { return false };
+    let drive = rest.components().next().map(|c| c.as_os_str().to_string_lossy().into_owned()).unwrap_or_default();
+    drive.len() == 1
+        && drive.chars().all(|c| c.is_ascii_lowercase())
+        && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+}
+
+/// Homebrew prefixes: macOS on Intel, macOS on Apple Silicon, and Linuxbrew
+pub const HOMEBREW_PREFIXES: [&str; 3] = ["/usr/local", "/opt/homebrew", "/home/linuxbrew/.linuxbrew"];
+
+/// The bin directories of a Homebrew prefix: `bin` and every PHP formula in the Cellar
+///
+/// The Cellar structure is `<prefix>/Cellar/php@8.2/8.2.12/bin/php`.
+pub fn homebrew_bin_dirs(prefix: &Path, errors: &mut Vec<ScanError>) -> Vec<PathBuf> {
+    let mut dirs = vec![prefix.join("bin")];
+    for formula in read_dir_reporting(&prefix.join("Cellar"), errors) {
+        if formula.file_name().is_some_and(|name| name.as_encoded_bytes().starts_with(b"php")) {
+            let mut versions = read_dir_reporting(&formula, errors);
+            versions.sort();
+            dirs.extend(versions.into_iter().map(|version_dir| version_dir.join("bin")));
+        }
+    }
+    dirs
+}
+
+/// Directories holding one subdirectory per installed version, for each version manager
+///
+/// asdf and mise can be moved with `ASDF_DATA_DIR`, `MISE_DATA_DIR` or `XDG_DATA_HOME`.
+pub fn version_manager_dirs(home: &Path, env: impl Fn(&str) -> Option<std::ffi::OsString>) -> Vec<PathBuf> {
+    let asdf = env("ASDF_DATA_DIR").map(PathBuf::from).unwrap_or_else(|| home.join(".asdf"));
+    let mise = env("MISE_DATA_DIR").map(PathBuf::from).unwrap_or_else(|| {
+        env("XDG_DATA_HOME").map(PathBuf::from).unwrap_or_else(|| home.join(".local/share")).join("mise")
+    });
+
+    vec![
+        home.join(".phpbrew/php"),
+        home.join(".phpenv/versions"),
+        asdf.join("installs/php"),
+        mise.join("installs/php"),
+    ]
+}
+
+/// Where snapd mounts snaps
+const SNAP_ROOT: &str = "/snap";
+
+/// What identifies a binary when deduplicating a scan
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum BinaryId {
+    Path(PathBuf),
+    /// Device and inode, which also tell hardlinks of one file apart from copies
+    #[cfg(unix)]
+    Inode(u64, u64),
+}
+
+/// The identity of the file a path resolves to
+///
+/// Every snap launcher in `/snap/bin` is a symlink to `/usr/bin/snap`, which
+/// picks the snap by the name it was invoked as; those are identified by
+/// their own path.
+fn dedup_key(path: &Path) -> Option<BinaryId> {
+    let canonical = path.canonicalize().ok()?;
+    if is_snap_launcher(path) {
+        return Some(BinaryId::Path(path.to_path_buf()));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = std::fs::metadata(&canonical) {
+            return Some(BinaryId::Inode(metadata.dev(), metadata.ino()));
+        }
+    }
+    Some(BinaryId::Path(canonical))
+}
+
+/// Longest symlink chain followed before giving up (as the kernel does)
+const MAX_LINK_HOPS: usize = 40;
+
+/// How many symlinks lead from a path to the real file
+///
+/// `/usr/bin/php` → `/etc/alternatives/php` → `/usr/bin/php8.2` is two hops;
+/// the binary itself, or a hardlink to it, is none.
+pub fn link_hops(path: &Path) -> usize {
+    let mut current = path.to_path_buf();
+    let mut hops = 0;
+    while hops < MAX_LINK_HOPS {
+        let Ok(target) = std::fs::read_link(&current) else { break };
+        current = current.parent().map(|dir| dir.join(&target)).unwrap_or(target);
+        hops += 1;
+    }
+    hops
+}
+
+/// Group scan candidates by the binary they resolve to, in order of first appearance
+///
+/// Within a group the candidate the fewest symlinks away from the binary
+/// comes first and stands for it; the rest are its aliases. Candidates that
+/// don't exist are dropped.
+fn group_by_binary(candidates: Vec<(PathBuf, bool)>) -> Vec<Vec<(PathBuf, bool)>> {
+    use std::collections::HashMap;
+
+    let mut index: HashMap<BinaryId, usize> = HashMap::new();
+    let mut groups: Vec<Vec<(PathBuf, bool)>> = Vec::new();
+    for (path, resolve) in candidates {
+        let Some(id) = dedup_key(&path) else {
+            let verdict = Verdict::Rejected("doesn't resolve to a file".to_string());
+            progress::report(|| ScanEvent::Candidate { path, verdict });
+            continue;
+        };
+        match index.get(&id) {
+            Some(&i) => groups[i].push((path, resolve)),
+            None => {
+                index.insert(id, groups.len());
+                groups.push(vec![(path, resolve)]);
+            }
+        }
+    }
+    for group in &mut groups {
+        // Stable, so ties keep scan order
+        group.sort_by_key(|(path, _)| link_hops(path));
+    }
+    groups
+}
+
+/// Check whether a path is a snap launcher, i.e. resolves to the `snap` binary
+pub fn is_snap_launcher(path: &Path) -> bool {
+    path.canonicalize().is_ok_and(|canonical| canonical.file_name().is_some_and(|name| name == "snap"))
+}
+
+/// `bin` directories inside the current revision of PHP snaps that have no launcher in `snap_bin`
+///
+/// `current` is kept in the paths rather than resolved, since it moves to a
+/// new revision on every refresh.
+pub fn snap_revision_dirs(snap_root: &Path, snap_bin: &Path) -> Vec<PathBuf> {
+    let launchers: Vec<String> = std::fs::read_dir(snap_bin)
+        .map(|entries| entries.flatten().map(|entry| entry.file_name().to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    let Ok(snaps) = std::fs::read_dir(snap_root) else { return Vec::new() };
+
+    let mut dirs: Vec<PathBuf> = snaps
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let launched = launchers.iter().any(|l| *l == name || l.starts_with(&format!("{}.", name)));
+            (name.starts_with("php") && !launched).then(|| entry.path().join("current"))
+        })
+        .flat_map(|current| [current.join("usr/bin"), current.join("bin")])
+        .filter(|dir| dir.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// `bin` directories of the Nix profiles: the user's, the per-user and default profiles, and NixOS's system one
+pub fn nix_profile_dirs(home: &Path, env: impl Fn(&str) -> Option<std::ffi::OsString>) -> Vec<PathBuf> {
+    let mut dirs = vec![home.join(".nix-profile/bin")];
+    if let Some(user) = env("USER") {
+        dirs.push(Path::new("/etc/profiles/per-user").join(user).join("bin"));
+    }
+    dirs.push(PathBuf::from("/nix/var/nix/profiles/default/bin"));
+    dirs.push(PathBuf::from("/run/current-system/sw/bin"));
+    dirs
+}
+
+/// Find all PHP installations on the system, with the places that couldn't be scanned
+///
+/// See `Settings::scan_options` for the extra directories and exclusions of the config.
+pub fn scan_system(options: &ScanOptions) -> ScanReport {
+    timing::measure(|| "scan for PHP installations".to_string(), || scan_for_php_installations(options))
+}
+
+/// How many levels below a directory recursive scans look by default (`/opt/php` → `8.2/bin`)
+pub const DEFAULT_SCAN_DEPTH: usize = 3;
+
+/// Directories recursive scans stop at; none of them hold PHP binaries, but some hold a lot
+const SKIPPED_DIRS: [&str; 14] = [
+    "include", "share", "man", "doc", "docs", "lib", "lib64", "etc", "var", "src", "tests", "vendor", "node_modules", "cache",
+];
+
+/// Most directories a recursive scan looks at, so a root like `/srv` can't stall it
+const MAX_SCAN_DIRS: usize = 500;
+
+/// A directory and the directories up to `depth` levels below it, level by level
+///
+/// So both `/srv/php/8.2/bin` and the prefixes `/srv/php/8.2` and `/srv/php`
+/// work. Hidden directories, symlinks to directories (which can loop) and the
+/// likes of `share` and `include` are skipped.
+pub fn dirs_below(root: &Path, depth: usize, errors: &mut Vec<ScanError>) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    let mut level = vec![root.to_path_buf()];
+    for _ in 0..depth {
+        let mut next: Vec<PathBuf> = level
+            .iter()
+            .flat_map(|dir| read_dir_reporting(dir, errors))
+            .filter(|path| std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir()))
+            .filter(|path| {
+                path.file_name().and_then(|name| name.to_str()).is_some_and(|name| !name.starts_with('.') && !SKIPPED_DIRS.contains(&name))
+            })
+            .collect();
+        next.sort();
+        next.truncate(MAX_SCAN_DIRS.saturating_sub(dirs.len()));
+        if next.is_empty() {
+            break;
+        }
+        dirs.extend(next.iter().cloned());
+        level = next;
+    }
+    dirs
+}
+
+fn scan_for_php_installations(options: &ScanOptions) -> ScanReport {
+    use std::collections::HashMap;
+
+    let mut errors = Vec::new();
+    // Binaries to run, in scan order, and whether to record them by their resolved path
+    let mut candidates: Vec<(PathBuf, bool)> = Vec::new();
+    let mut add = |paths: Vec<PathBuf>, resolve: bool| candidates.extend(paths.into_iter().map(|path| (path, resolve)));
+    let starts_with_php = |name: &std::ffi::OsStr| name.as_encoded_bytes().starts_with(b"php");
 
     // Common directories to scan
     let scan_dirs = vec![
         "/usr/bin",
-        "/usr/local/bin",
-        "/opt/homebrew/bin",
         "/usr/lib",
         "/usr/local/lib",
     ];
+    for dir in scan_dirs {
+        add(candidates_in(Path::new(dir), starts_with_php, &mut errors), false);
+    }
+
+    // Debian's alternatives system knows every packaged PHP, wherever it lives
+    add(alternatives_candidates().into_iter().filter(|path| path.is_file()).collect(), false);
+
+    // Homebrew (macOS Intel and Apple Silicon, Linuxbrew): the linked bin dir and the Cellar
+    for prefix in HOMEBREW_PREFIXES {
+        for dir in homebrew_bin_dirs(Path::new(prefix), &mut errors) {
+            add(candidates_in(&dir, starts_with_php, &mut errors), false);
+        }
+    }
+
+    // MacPorts installs versioned binaries side by side; skip php-config82, phpize82 and friends
+    add(candidates_in(Path::new(MACPORTS_BIN), is_php_binary_name, &mut errors), false);
+
+    // All-in-one stacks: XAMPP ships one PHP, MAMP one per bundled version.
+    // Their bin directories also hold mysql, apachectl and the like
+    for dir in XAMPP_BIN_DIRS {
+        add(candidates_in(Path::new(dir), is_php_binary_name, &mut errors), false);
+    }
+    for dir in versioned_dirs(Path::new(MAMP_PHP_DIR), "php", "bin", &mut errors) {
+        add(candidates_in(&dir, is_php_binary_name, &mut errors), false);
+    }
+
+    // Hosting panels on RHEL-family servers keep their PHP builds out of PATH
+    let panel_dirs = [
+        versioned_dirs(Path::new(CPANEL_DIR), "ea-php", "root/usr/bin", &mut errors),
+        versioned_dirs(Path::new(PLESK_PHP_DIR), "", "bin", &mut errors),
+        versioned_dirs(Path::new(CLOUDLINUX_ALT_DIR), "php", "usr/bin", &mut errors),
+    ];
+    for dir in panel_dirs.iter().flatten() {
+        add(candidates_in(dir, is_php_binary_name, &mut errors), false);
+    }
 
-    // Also check for Homebrew Cellar directories
-    let homebrew_dirs = vec![
-        "/usr/local/Cellar",
-        "/opt/homebrew/Cellar",
+    // CI runners: the GitHub Actions tool cache and setup-php's own builds, one per matrix version
+    let tool_cache = std::env::var_os("RUNNER_TOOL_CACHE").map_or_else(|| PathBuf::from(HOSTED_TOOLCACHE_DIR), PathBuf::from);
+    let ci_dirs = [
+        toolcache_bin_dirs(&tool_cache, &mut errors),
+        versioned_dirs(Path::new(SETUP_PHP_DIR), "", "bin", &mut errors),
     ];
+    for dir in ci_dirs.iter().flatten() {
+        add(candidates_in(dir, is_php_binary_name, &mut errors), false);
+    }
 
-    // Helper function to merge found installations
-    let mut merge_installation = |installation: PhpInstallation| {
-        let version_key = installation.version.to_string();
-
-        // For each path in the installation
-        for path in installation.paths {
-            // Check if we've already seen this canonical path
-            if let Ok(canonical) = path.canonicalize() {
-                if seen_canonical_paths.insert(canonical) {
-                    // Add this path to the installation for this version
-                    installations_by_version
-                        .entry(version_key.clone())
-                        .and_modify(|inst| inst.add_path(path.clone()))
-                        .or_insert_with(|| PhpInstallation::new(installation.version.clone(), path));
-                }
+    // Check home directory paths for version managers (phpbrew, phpenv, asdf, mise)
+    if let Some(home) = dirs::home_dir() {
+        for manager_dir in version_manager_dirs(&home, |name| std::env::var_os(name)) {
+            for version_dir in read_dir_reporting(&manager_dir, &mut errors) {
+                add(candidates_in(&version_dir.join("bin"), starts_with_php, &mut errors), false);
             }
         }
-    };
+    }
 
-    // Scan common binary directories
-    for dir in scan_dirs {
-        if let Ok(found) = scan_directory_for_php(dir) {
-            for installation in found {
-                merge_installation(installation);
-            }
+    // Snap packages: the launchers in /snap/bin, or the binaries of snaps without one
+    let snap_bin = Path::new(SNAP_ROOT).join("bin");
+    add(candidates_in(&snap_bin, is_php_binary_name, &mut errors), false);
+    for dir in snap_revision_dirs(Path::new(SNAP_ROOT), &snap_bin) {
+        add(candidates_in(&dir, is_php_binary_name, &mut errors), false);
+    }
+
+    // Nix profiles link into the store; record the store paths, which name one exact build.
+    // A profile link moves to a new build on every `nix profile upgrade`, so a
+    // recorded `~/.nix-profile/bin/php` could silently become another version
+    if let Some(home) = dirs::home_dir() {
+        for dir in nix_profile_dirs(&home, |name| std::env::var_os(name)) {
+            add(candidates_in(&dir, starts_with_php, &mut errors), true);
         }
     }
 
-    // Scan Homebrew Cellar for php@ versioned formulas
-    for homebrew_dir in homebrew_dirs {
-        if let Ok(entries) = std::fs::read_dir(homebrew_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if let Some(name) = path.file_name() {
-                    let name_str = name.to_string_lossy();
-                    if name_str.starts_with("php") {
-                        // Look for bin/php in this Cellar directory
-                        // Structure is usually: /path/to/Cellar/php@8.2/8.2.12/bin/php
-                        if let Ok(version_dirs) = std::fs::read_dir(&path) {
-                            for version_dir in version_dirs.flatten() {
-                                let bin_dir = version_dir.path().join("bin");
-                                if let Ok(found) = scan_directory_for_php(&bin_dir) {
-                                    for installation in found {
-                                        merge_installation(installation);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    // Windows installers each keep PHP in a directory of their own
+    #[cfg(windows)]
+    for root in crate::platform::windows::scan_roots(|name| std::env::var_os(name)) {
+        for dir in php_dirs_below(&root.dir, root.depth, &mut errors) {
+            add(candidates_in(&dir, starts_with_php, &mut errors), false);
         }
     }
 
-    // Check home directory paths for version managers
-    if let Some(home) = dirs::home_dir() {
-        // phpbrew
-        let phpbrew_dir = home.join(".phpbrew/php");
-        if let Ok(entries) = std::fs::read_dir(&phpbrew_dir) {
-            for entry in entries.flatten() {
-                let bin_dir = entry.path().join("bin");
-                if let Ok(found) = scan_directory_for_php(&bin_dir) {
-                    for installation in found {
-                        merge_installation(installation);
-                    }
+    // Under WSL, PHP kept on the Windows side lives on the mounted drives and runs through interop
+    #[cfg(target_os = "linux")]
+    if crate::platform::is_wsl() {
+        for drive in wsl_drives(Path::new(WSL_MOUNT_ROOT)) {
+            let users: Vec<PathBuf> = std::fs::read_dir(drive.join("Users"))
+                .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+                .unwrap_or_default();
+            // Most of a Windows drive isn't ours to read; only report trouble inside real PHP roots
+            let roots = crate::platform::windows::wsl_scan_roots(&drive, &users).into_iter().filter(|root| root.dir.is_dir());
+            for root in roots {
+                for dir in php_dirs_below(&root.dir, root.depth, &mut errors) {
+                    add(candidates_in(&dir, is_php_binary_name, &mut errors), false);
                 }
             }
         }
+    }
 
-        // phpenv
-        let phpenv_dir = home.join(".phpenv/versions");
-        if let Ok(entries) = std::fs::read_dir(&phpenv_dir) {
-            for entry in entries.flatten() {
-                let bin_dir = entry.path().join("bin");
-                if let Ok(found) = scan_directory_for_php(&bin_dir) {
-                    for installation in found {
-                        merge_installation(installation);
-                    }
+    // Custom builds in places only the user knows about
+    for extra_dir in &options.extra_dirs {
+        for dir in dirs_below(extra_dir, options.depth, &mut errors) {
+            add(candidates_in(&dir, is_php_binary_name, &mut errors), false);
+        }
+    }
+
+    // Settings can rule out whole trees (a chroot, a backup) before anything runs
+    let mut excluded = Vec::new();
+    candidates.retain(|(path, _)| match options.path_exclusion(path) {
+        Some(rule) => {
+            progress::report(|| ScanEvent::Candidate { path: path.clone(), verdict: Verdict::Excluded(rule.clone()) });
+            excluded.push(Exclusion { path: path.clone(), rule });
+            false
+        }
+        None => true,
+    });
+
+    // Run each binary once, however many links and hardlinks lead to it
+    let groups = group_by_binary(candidates);
+    let paths: Vec<PathBuf> = groups.iter().map(|group| group[0].0.clone()).collect();
+    let mut installations_by_version: HashMap<String, PhpInstallation> = HashMap::new();
+    let mut broken = Vec::new();
+    for (mut group, result) in groups.into_iter().zip(probe_with_cache(&paths, options.no_exec)) {
+        let (path, resolve) = group.remove(0);
+        let aliases: Vec<PathBuf> = group.into_iter().map(|(alias, _)| alias).collect();
+        let verdict = match &result {
+            Ok(version) => options.version_exclusion(version).map_or_else(|| Verdict::Accepted(version.clone()), Verdict::Excluded),
+            Err(error) => Verdict::Rejected(format!("{} ({})", error.kind.name(), error.message)),
+        };
+        progress::report(|| ScanEvent::Candidate { path: path.clone(), verdict: verdict.clone() });
+        for alias in &aliases {
+            progress::report(|| ScanEvent::Candidate { path: alias.clone(), verdict: Verdict::Alias(path.clone()) });
+        }
+
+        let version = match (result, verdict) {
+            (Ok(version), Verdict::Accepted(_)) => version,
+            (_, Verdict::Excluded(rule)) => {
+                excluded.extend(std::iter::once(&path).chain(&aliases).map(|path| Exclusion { path: path.clone(), rule: rule.clone() }));
+                continue;
+            }
+            (Err(error), _) if is_reportable(&error) => {
+                // A `php8.3` that fails to run is still PHP 8.3 to its user; keep it with the reason.
+                // Not so for no-exec scans, which can't tell it from a launcher script, or hung binaries
+                let name_version = || std::iter::once(&path).chain(&aliases).find_map(|path| version_from_file_name(path.file_name()?));
+                match name_version().filter(|_| error.kind != ScanErrorKind::Timeout && !options.no_exec) {
+                    Some(version) => broken.push(crate::config::BrokenInstall { version, path, reason: error.message }),
+                    None => errors.push(error),
                 }
+                continue;
             }
+            _ => continue,
+        };
+        let path = if resolve { path.canonicalize().unwrap_or(path) } else { path };
+        let installation = installations_by_version
+            .entry(version.to_string())
+            .and_modify(|inst| inst.add_path(path.clone()))
+            .or_insert_with(|| PhpInstallation::new(version, path));
+        for alias in aliases {
+            installation.add_alias(alias);
         }
     }
 
@@ -251,7 +1468,39 @@ pub fn find_all_php_installations() -> Result<Vec<PhpInstallation>> {
     // Sort by version (newest first)
     installations.sort_by(|a, b| b.version.cmp(&a.version));
 
-    Ok(installations)
+    for installation in &mut installations {
+        // Without a binary named `php` (now usually an alias) the first path is
+        // the primary one, so put the CLI ahead of php-cgi and friends
+        installation.paths.sort_by_key(|path| !path.file_name().is_some_and(is_php_cli_name));
+        let Some(primary) = installation.primary_path().cloned() else { continue };
+        if options.no_exec {
+            // Layout, build and extensions all come from running it; the header is all there is
+            installation.build.arch = crate::binary::read_target(&primary)
+                .filter(|target| !target.arches.is_empty())
+                .map(|target| target.arches.join("/"));
+        } else {
+            (installation.layout, installation.build) = get_install_details(&primary);
+            installation.extensions = get_extensions(&primary);
+        }
+    }
+
+    ScanReport { installations, errors, excluded, broken }
+}
+
+/// Find all PHP tools on the system based on tools configuration
+pub fn find_all_php_tools(tools_config: &crate::config::ToolsConfig) -> Result<Vec<crate::tools::PhpTool>> {
+    use crate::tools;
+
+    // If scanning is disabled, return empty list
+    if !tools_config.scan_for_tools {
+        return Ok(Vec::new());
+    }
+
+    // Use the tools module to scan for PHP tools
+    tools::scan_for_php_tools(
+        &tools_config.custom_tool_names,
+        &tools_config.custom_search_paths,
+    )
 }
 
 #[cfg(test)]
@@ -290,6 +1539,66 @@ mod tests {
         assert_eq!(version.patch, 12);
     }
 
+    #[test]
+    fn test_parse_ini_and_modules_output() {
+        let ini = "Configuration File (php.ini) Path: /etc/php/8.2/cli\nLoaded Configuration File:         /etc/php/8.2/cli/php.ini\n";
+        assert_eq!(parse_ini_output(ini), Some(PathBuf::from("/etc/php/8.2/cli/php.ini")));
+        assert_eq!(parse_ini_output("Loaded Configuration File:         (none)\n"), None);
+
+        let scan = "Scan for additional .ini files in: /etc/php/8.2/cli/conf.d:/opt/extra\n";
+        assert_eq!(parse_ini_scan_dirs(scan), vec![PathBuf::from("/etc/php/8.2/cli/conf.d"), PathBuf::from("/opt/extra")]);
+
+        let modules = "[PHP Modules]\nCore\nctype\n\n[Zend Modules]\nZend OPcache\n";
+        assert_eq!(parse_modules_output(modules), vec!["Core", "ctype", "Zend OPcache"]);
+        assert!(parse_modules_output("PHP 8.2.12 (cli) (NTS)\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_install_layout() {
+        let output = "phpinfo()\nConfigure Command =>  './configure'  '--build=x86_64-linux-gnu' '--prefix=/usr' '--with-config-file-path=/etc/php/8.2/cli'\nConfiguration File (php.ini) Path => /etc/php/8.2/cli\nLoaded Configuration File => /etc/php/8.2/cli/php.ini\nScan this dir for additional .ini files => /etc/php/8.2/cli/conf.d\nsqlite3.extension_dir => no value => no value\nextension_dir => /usr/lib/php/20220829 => /usr/lib/php/20220829\n";
+        let layout = parse_install_layout(output);
+
+        assert_eq!(layout.prefix, Some(PathBuf::from("/usr")));
+        assert_eq!(layout.ini_dir, Some(PathBuf::from("/etc/php/8.2/cli")));
+        assert_eq!(layout.ini_file, Some(PathBuf::from("/etc/php/8.2/cli/php.ini")));
+        assert_eq!(layout.ini_scan_dirs, [PathBuf::from("/etc/php/8.2/cli/conf.d")]);
+        assert_eq!(layout.extension_dir, Some(PathBuf::from("/usr/lib/php/20220829")));
+        assert_eq!(
+            fpm_config_dir_candidates(&layout),
+            vec![
+                PathBuf::from("/etc/php/8.2/fpm"),
+                PathBuf::from("/etc/php/8.2/cli/php-fpm.d"),
+                PathBuf::from("/usr/etc/php-fpm.d"),
+            ]
+        );
+
+        assert!(parse_install_layout("PHP 8.2.12 (cli)\n").is_empty());
+    }
+
+    #[test]
+    fn test_fpm_binary_candidates() {
+        let layout = InstallLayout { prefix: Some(PathBuf::from("/usr")), ..Default::default() };
+        let binaries = [PathBuf::from("/usr/bin/php8.2"), PathBuf::from("/usr/bin/php")];
+        assert_eq!(
+            fpm_binary_candidates(&binaries, &layout),
+            [
+                PathBuf::from("/usr/sbin/php-fpm8.2"),
+                PathBuf::from("/usr/bin/php-fpm8.2"),
+                PathBuf::from("/usr/sbin/php-fpm"),
+                PathBuf::from("/usr/bin/php-fpm"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fpm_listen() {
+        let debian = "; Start a new pool named 'www'.\n[www]\nuser = www-data\nlisten = /run/php/php8.2-fpm.sock\nlisten.owner = www-data\n";
+        assert_eq!(parse_fpm_listen(debian).as_deref(), Some("/run/php/php8.2-fpm.sock"));
+        let templated = "[app]\n;listen = 127.0.0.1:9000\nlisten.mode = 0660\nlisten = /run/$pool.sock\n";
+        assert_eq!(parse_fpm_listen(templated).as_deref(), Some("/run/app.sock"));
+        assert_eq!(parse_fpm_listen("[global]\npid = /run/php-fpm.pid\n"), None);
+    }
+
     #[test]
     fn test_installation_from_path() {
         // Test creating a PhpInstallation
@@ -336,10 +1645,456 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_scan_directory_reports_errors() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = |name: &str, body: &str, mode: u32| {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        script("php8.9", "echo 'PHP 8.9.1 (cli)'", 0o755);
+        script("php7.9", "exit 1", 0o755);
+        script("php-config8.9", "echo usage; exit 1", 0o755);
+        script("php8.1", "echo 'PHP 8.1.0 (cli)'", 0o644);
+
+        let report = scan_directory(temp_dir.path());
+        assert_eq!(report.installations.len(), 1);
+        assert_eq!(report.installations[0].version.to_string(), "8.9.1");
+
+        let mut errors: Vec<(String, ScanErrorKind)> = report
+            .errors
+            .iter()
+            .map(|e| (e.path.file_name().unwrap().to_string_lossy().to_string(), e.kind))
+            .collect();
+        errors.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            errors,
+            vec![
+                ("php7.9".to_string(), ScanErrorKind::InvalidBinary),
+                ("php8.1".to_string(), ScanErrorKind::PermissionDenied),
+            ]
+        );
+
+        // A missing directory simply has no PHP
+        assert_eq!(scan_directory(&temp_dir.path().join("missing")), ScanReport::default());
+
+        // Versions kept one directory each are found below the root
+        std::fs::create_dir_all(temp_dir.path().join("8.4/bin")).unwrap();
+        std::fs::rename(temp_dir.path().join("php8.9"), temp_dir.path().join("8.4/bin/php")).unwrap();
+        let report = scan_directory(temp_dir.path());
+        assert_eq!(report.installations[0].paths, [temp_dir.path().join("8.4/bin/php")]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_probe_concurrently_keeps_order() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // The slowest binary comes first, so finishing order differs from input order
+        let paths: Vec<PathBuf> = [("php8.1", "0.3"), ("php8.2", "0"), ("php8.3", "0.1")]
+            .iter()
+            .map(|(name, delay)| {
+                let path = temp_dir.path().join(name);
+                let version = name.trim_start_matches("php");
+                std::fs::write(&path, format!("#!/bin/sh\nsleep {}\necho 'PHP {}.0 (cli)'\n", delay, version)).unwrap();
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+                path
+            })
+            .collect();
+
+        let versions: Vec<String> = probe_concurrently(&paths, false).into_iter().map(|r| r.unwrap().to_string()).collect();
+        assert_eq!(versions, ["8.1.0", "8.2.0", "8.3.0"]);
+        assert!(probe_concurrently(&[], false).is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_probe_without_running() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let ran = temp_dir.path().join("ran");
+        let script = temp_dir.path().join("php8.1");
+        std::fs::write(&script, format!("#!/bin/sh\ntouch {}\necho 'PHP 8.1.0 (cli)'\n", ran.display())).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let build = temp_dir.path().join("php8.3");
+        let mut content = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+        content.resize(18, 0);
+        content.extend(0x3eu16.to_le_bytes());
+        content.extend(b"\0PHP %s (%s)\0X-Powered-By: PHP/8.3.4\0");
+        std::fs::write(&build, content).unwrap();
+
+        let results = probe_concurrently(&[script, build], true);
+        assert_eq!(results[0].as_ref().unwrap_err().kind, ScanErrorKind::InvalidBinary);
+        assert_eq!(results[1].as_ref().unwrap().to_string(), "8.3.4");
+        assert!(!ran.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_in_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (first, second) = (temp_dir.path().join("first"), temp_dir.path().join("second"));
+        std::fs::create_dir_all(first.join("php")).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        std::fs::write(second.join("php"), "#!/bin/sh\n").unwrap();
+
+        let path_var = std::env::join_paths([&first, &second]).unwrap();
+
+        // Directories and non-executable files are skipped, like the shell does
+        std::fs::set_permissions(second.join("php"), std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(find_in_path("php", &path_var), None);
+
+        std::fs::set_permissions(second.join("php"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(find_in_path("php", &path_var), Some(second.join("php")));
+        assert_eq!(find_in_path("php", std::ffi::OsStr::new("")), None);
+    }
+
+    #[test]
+    fn test_is_php_binary_name() {
+        use std::ffi::OsStr;
+
+        for name in ["php", "php8.2", "php-cgi", "php-fpm8.3", "phpdbg8.1", "php.exe", "php-cgi.EXE"] {
+            assert!(is_php_binary_name(OsStr::new(name)), "{}", name);
+        }
+        for name in ["php-config8.2", "phpize", "php-cs-fixer", "phpunit", "php8ts.dll"] {
+            assert!(!is_php_binary_name(OsStr::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_install_source() {
+        let classify = |path: &str| InstallSource::classify(Path::new(path));
+
+        assert_eq!(classify("/opt/homebrew/Cellar/php@8.2/8.2.12/bin/php"), InstallSource::Homebrew);
+        assert_eq!(classify("/home/dev/.phpbrew/php/php-8.1.27/bin/php"), InstallSource::Phpbrew);
+        assert_eq!(classify(r"C:\Users\dev\scoop\apps\php82\8.2.12\php.exe"), InstallSource::Scoop);
+        assert_eq!(classify(r"C:\tools\php83\php.exe"), InstallSource::Chocolatey);
+        assert_eq!(classify("/usr/bin/php8.2"), InstallSource::System);
+        assert_eq!(classify("/usr/local/bin/php"), InstallSource::Manual);
+        assert_eq!(classify("/opt/php-7.4/bin/php"), InstallSource::Manual);
+        assert_eq!(classify("/nix/store/2c4f9v-php-8.2.12/bin/php"), InstallSource::Nix);
+        assert_eq!(classify("/snap/bin/php82"), InstallSource::Snap);
+        assert_eq!(classify("/home/dev/snap/php82/common/php"), InstallSource::Manual);
+    }
+
+    #[test]
+    fn test_version_manager_dirs() {
+        let home = Path::new("/home/dev");
+        assert_eq!(
+            version_manager_dirs(home, |_| None),
+            [
+                home.join(".phpbrew/php"),
+                home.join(".phpenv/versions"),
+                home.join(".asdf/installs/php"),
+                home.join(".local/share/mise/installs/php"),
+            ]
+        );
+
+        let env = |name: &str| match name {
+            "ASDF_DATA_DIR" => Some("/data/asdf".into()),
+            "XDG_DATA_HOME" => Some("/data/xdg".into()),
+            _ => None,
+        };
+        let dirs = version_manager_dirs(home, env);
+        assert_eq!(dirs[2], Path::new("/data/asdf/installs/php"));
+        assert_eq!(dirs[3], Path::new("/data/xdg/mise/installs/php"));
+
+        let mise = PhpInstallation::new(
+            PhpVersion::from_php_output("PHP 8.3.4").unwrap(),
+            dirs[3].join("8.3.4/bin/php"),
+        );
+        assert_eq!(mise.source(), "mise");
+    }
+
+    #[test]
+    fn test_homebrew_bin_dirs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let prefix = temp_dir.path().join(".linuxbrew");
+        for formula in ["php@8.2/8.2.12", "php/8.3.4", "node/20.1.0"] {
+            std::fs::create_dir_all(prefix.join("Cellar").join(formula)).unwrap();
+        }
+
+        let mut errors = Vec::new();
+        let mut dirs = homebrew_bin_dirs(&prefix, &mut errors);
+        dirs.sort();
+        assert_eq!(
+            dirs,
+            [prefix.join("Cellar/php/8.3.4/bin"), prefix.join("Cellar/php@8.2/8.2.12/bin"), prefix.join("bin")]
+        );
+        assert!(errors.is_empty());
+        assert_eq!(homebrew_bin_dirs(&temp_dir.path().join("missing"), &mut errors), [temp_dir.path().join("missing/bin")]);
+
+        let macports = PhpInstallation::new(
+            PhpVersion::from_php_output("PHP 8.2.12").unwrap(),
+            Path::new(MACPORTS_BIN).join("php82"),
+        );
+        assert_eq!(macports.source(), "macports");
+        assert_eq!(macports.port_select_name().as_deref(), Some("php82"));
+        let homebrew = PhpInstallation::new(macports.version.clone(), PathBuf::from("/opt/homebrew/bin/php82"));
+        assert_eq!(homebrew.port_select_name(), None);
+
+        let linuxbrew = Path::new("/home/linuxbrew/.linuxbrew/Cellar/php@8.2/8.2.12/bin/php");
+        assert_eq!(InstallSource::classify(linuxbrew), InstallSource::Homebrew);
+    }
+
+    #[test]
+    fn test_parse_alternatives_list() {
+        let output = "/usr/bin/php7.4\n/usr/bin/php8.2\n/opt/php-custom/bin/php\n\n";
+        assert_eq!(
+            parse_alternatives_list(output),
+            [PathBuf::from("/usr/bin/php7.4"), PathBuf::from("/usr/bin/php8.2"), PathBuf::from("/opt/php-custom/bin/php")]
+        );
+        assert!(parse_alternatives_list("update-alternatives: error: no alternatives for php\n").is_empty());
+    }
+
+    #[test]
+    fn test_build_info() {
+        let info = "Architecture => x64\nThread Safety => enabled\nDebug Build => yes\n";
+        let build = BuildInfo::from_php_info(&crate::probe::parse_build_info(info));
+        assert_eq!(build, BuildInfo { zts: Some(true), debug: Some(true), arch: Some("x64".to_string()) });
+        assert_eq!(build.summary(), "ZTS, debug, x64");
+
+        let nts = BuildInfo::from_php_info(&crate::probe::parse_build_info("Thread Safety => disabled\nDebug Build => no\n"));
+        assert_eq!(nts.summary(), "NTS");
+        assert!(BuildInfo::from_php_info(&Default::default()).is_empty());
+    }
+
+    #[test]
+    fn test_versioned_dirs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for dir in ["php8.2.0/bin", "php7.4.33/bin", "phpMyAdmin5", "ea-php82/root/usr/bin", "ea-phpcli", "8.3/bin"] {
+            std::fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+        }
+        std::fs::write(temp_dir.path().join("README"), "").unwrap();
+
+        let mut errors = Vec::new();
+        let root = temp_dir.path();
+        assert_eq!(versioned_dirs(root, "php", "bin", &mut errors), [root.join("php7.4.33/bin"), root.join("php8.2.0/bin")]);
+        assert_eq!(versioned_dirs(root, "ea-php", "root/usr/bin", &mut errors), [root.join("ea-php82/root/usr/bin")]);
+        assert_eq!(versioned_dirs(root, "", "bin", &mut errors), [root.join("8.3/bin")]);
+        assert!(errors.is_empty());
+
+        let classify = |path: &str| InstallSource::classify(Path::new(path));
+        assert_eq!(classify("/Applications/MAMP/bin/php/php8.2.0/bin/php"), InstallSource::Mamp);
+        assert_eq!(classify("/opt/lampp/bin/php"), InstallSource::Xampp);
+        assert_eq!(classify("/Applications/XAMPP/xamppfiles/bin/php"), InstallSource::Xampp);
+        assert_eq!(classify("/opt/cpanel/ea-php82/root/usr/bin/php"), InstallSource::Cpanel);
+        assert_eq!(classify("/opt/plesk/php/8.2/bin/php"), InstallSource::Plesk);
+        assert_eq!(classify("/opt/alt/php82/usr/bin/php"), InstallSource::CloudLinux);
+
+        let alt = PhpInstallation::new(
+            PhpVersion::from_php_output("PHP 8.2.12").unwrap(),
+            Path::new(CLOUDLINUX_ALT_DIR).join("php82/usr/bin/php"),
+        );
+        assert_eq!(alt.source(), "cloudlinux");
+    }
+
+    #[test]
+    fn test_wsl_drives() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for dir in ["c", "d", "wsl", "wslg"] {
+            std::fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+        }
+        std::fs::write(temp_dir.path().join("e"), "").unwrap();
+        assert_eq!(wsl_drives(temp_dir.path()), [temp_dir.path().join("c"), temp_dir.path().join("d")]);
+
+        assert!(is_wsl_windows_binary(Path::new("/mnt/c/tools/php83/php.exe")));
+        assert!(is_wsl_windows_binary(Path::new("/mnt/d/php/PHP.EXE")));
+        assert!(!is_wsl_windows_binary(Path::new("/mnt/data/php/php.exe")));
+        assert!(!is_wsl_windows_binary(Path::new("/mnt/c/php/php")));
+
+        let windows = PhpInstallation::new(PhpVersion::new(8, 3, 4), PathBuf::from("/mnt/c/tools/php83/php.exe"));
+        assert_eq!(windows.source(), "windows");
+    }
+
+    #[test]
+    fn test_toolcache_bin_dirs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["php/8.3.4/x64/bin", "php/8.1.27/x64/bin", "php/8.1.27/arm64/bin", "php/8.2.0/x64", "node/20.11.0/x64/bin"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("php/8.3.4/x64.complete"), "").unwrap();
+
+        let mut errors = Vec::new();
+        assert_eq!(
+            toolcache_bin_dirs(root, &mut errors),
+            [root.join("php/8.1.27/arm64/bin"), root.join("php/8.1.27/x64/bin"), root.join("php/8.3.4/x64/bin")]
+        );
+        assert!(errors.is_empty());
+        assert_eq!(InstallSource::classify(Path::new("/opt/hostedtoolcache/php/8.3.4/x64/bin/php")), InstallSource::ToolCache);
+    }
+
+    #[test]
+    fn test_nix_profile_dirs() {
+        let home = Path::new("/home/dev");
+        let env = |name: &str| (name == "USER").then(|| "dev".into());
+        assert_eq!(
+            nix_profile_dirs(home, env),
+            [
+                home.join(".nix-profile/bin"),
+                PathBuf::from("/etc/profiles/per-user/dev/bin"),
+                PathBuf::from("/nix/var/nix/profiles/default/bin"),
+                PathBuf::from("/run/current-system/sw/bin"),
+            ]
+        );
+
+        let nix = PhpInstallation::new(
+            PhpVersion::from_php_output("PHP 8.2.12").unwrap(),
+            PathBuf::from("/nix/store/2c4f9v-php-8.2.12/bin/php"),
+        );
+        assert_eq!(nix.source(), "nix");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_snap_layout() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let snap_bin = root.join("bin");
+        std::fs::create_dir_all(&snap_bin).unwrap();
+        for dir in ["php74/current/usr/bin", "php82/current/usr/bin", "core22/current/usr/bin"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        // Launchers all point at the snap binary, so each counts on its own
+        let launcher = root.join("snap");
+        std::fs::write(&launcher, "").unwrap();
+        std::os::unix::fs::symlink(&launcher, snap_bin.join("php82")).unwrap();
+        std::os::unix::fs::symlink(&launcher, snap_bin.join("php82.composer")).unwrap();
+        assert_eq!(dedup_key(&snap_bin.join("php82")), Some(BinaryId::Path(snap_bin.join("php82"))));
+
+        // Only the snap without a launcher is looked into
+        assert_eq!(snap_revision_dirs(root, &snap_bin), [root.join("php74/current/usr/bin")]);
+    }
+
+    #[test]
+    fn test_php_dirs_below() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let apps = temp_dir.path().join("scoop/apps");
+        for dir in ["php82/8.2.12", "php82/current", "php/8.3.4", "git/2.44.0"] {
+            std::fs::create_dir_all(apps.join(dir)).unwrap();
+        }
+
+        let mut errors = Vec::new();
+        assert_eq!(
+            php_dirs_below(&apps, 2, &mut errors),
+            [apps.join("php/8.3.4"), apps.join("php82/8.2.12"), apps.join("php82/current")]
+        );
+        assert_eq!(php_dirs_below(&apps, 1, &mut errors), [apps.join("php"), apps.join("php82")]);
+        assert_eq!(php_dirs_below(&temp_dir.path().join("missing"), 1, &mut errors), Vec::<PathBuf>::new());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_dirs_below() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut errors = Vec::new();
+        let root = temp_dir.path().join("srv/php");
+        let prefix = root.join("8.2");
+        for dir in ["8.2/bin", "8.2/share/man", "8.2/.git", "8.3/sbin"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        assert_eq!(dirs_below(&prefix, DEFAULT_SCAN_DEPTH, &mut errors), [prefix.clone(), prefix.join("bin")]);
+        assert_eq!(dirs_below(&prefix.join("bin"), DEFAULT_SCAN_DEPTH, &mut errors), [prefix.join("bin")]);
+        assert_eq!(
+            dirs_below(&root, 2, &mut errors),
+            [root.clone(), root.join("8.2"), root.join("8.3"), root.join("8.2/bin"), root.join("8.3/sbin")]
+        );
+        assert_eq!(dirs_below(&root, 0, &mut errors), [root]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_group_by_binary() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("alternatives")).unwrap();
+        std::fs::write(dir.join("php8.2"), "").unwrap();
+        std::fs::write(dir.join("php8.3"), "").unwrap();
+        std::fs::hard_link(dir.join("php8.2"), dir.join("php82")).unwrap();
+        std::os::unix::fs::symlink(dir.join("php8.2"), dir.join("alternatives/php")).unwrap();
+        std::os::unix::fs::symlink("alternatives/php", dir.join("php")).unwrap();
+
+        let candidates = ["php", "php8.3", "php82", "php8.2", "missing"].map(|name| (dir.join(name), false));
+        let groups: Vec<Vec<PathBuf>> =
+            group_by_binary(candidates.to_vec()).into_iter().map(|group| group.into_iter().map(|(path, _)| path).collect()).collect();
+
+        // The real files stand for the group, hardlinks in scan order; the link chain comes last
+        assert_eq!(groups, [vec![dir.join("php82"), dir.join("php8.2"), dir.join("php")], vec![dir.join("php8.3")]]);
+        assert_eq!(link_hops(&dir.join("php")), 2);
+        assert_eq!(link_hops(&dir.join("php8.2")), 0);
+    }
+
+    #[test]
+    fn test_is_php_cli_name() {
+        for name in ["php", "php8.2", "php82", "php.exe"] {
+            assert!(is_php_cli_name(std::ffi::OsStr::new(name)), "{}", name);
+        }
+        for name in ["php-cgi8.2", "phpdbg", "php-fpm", "phpize82"] {
+            assert!(!is_php_cli_name(std::ffi::OsStr::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_version_from_file_name() {
+        let version = |name: &str| version_from_file_name(std::ffi::OsStr::new(name));
+        assert_eq!(version("php8.3").as_deref(), Some("8.3"));
+        assert_eq!(version("php83").as_deref(), Some("8.3"));
+        assert_eq!(version("php8.1.27").as_deref(), Some("8.1.27"));
+        assert_eq!(version("php7.exe").as_deref(), Some("7"));
+        assert_eq!(version("php"), None);
+        assert_eq!(version("php8."), None);
+        assert_eq!(version("php-cgi8.3"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_scan_version_reports_why_it_failed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let php = temp_dir.path().join("php8.3");
+        let complaint = "php8.3: error while loading shared libraries: libonig.so.5: cannot open shared object file";
+        std::fs::write(&php, format!("#!/bin/sh\necho '{}' >&2\nexit 127\n", complaint)).unwrap();
+        std::fs::set_permissions(&php, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let error = scan_version(&php).unwrap_err();
+        assert_eq!(error.kind, ScanErrorKind::InvalidBinary);
+        assert_eq!(error.message, complaint);
+    }
+
+    #[test]
+    fn test_scan_options_exclusions() {
+        let options = ScanOptions {
+            exclude_paths: vec![PathBuf::from("/mnt/chroot"), PathBuf::from("/opt/old/bin/php")],
+            exclude_versions: vec!["7.4".to_string(), "<7.0".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(options.path_exclusion(Path::new("/mnt/chroot/usr/bin/php")).as_deref(), Some("path /mnt/chroot"));
+        assert_eq!(options.path_exclusion(Path::new("/opt/old/bin/php")).as_deref(), Some("path /opt/old/bin/php"));
+        assert_eq!(options.path_exclusion(Path::new("/mnt/chroot2/php")), None);
+
+        assert_eq!(options.version_exclusion(&PhpVersion::new(7, 4, 33)).as_deref(), Some("version 7.4"));
+        assert_eq!(options.version_exclusion(&PhpVersion::new(5, 6, 40)).as_deref(), Some("version <7.0"));
+        assert_eq!(options.version_exclusion(&PhpVersion::new(8, 2, 12)), None);
+    }
+
     #[test]
     fn test_find_all_php_installations() {
         // Test finding all PHP installations on the system
-        let result = find_all_php_installations();
+        let result = find_all_php_installations(&ScanOptions::default());
 
         // This should always return Ok, even if empty
         assert!(result.is_ok());
@@ -423,6 +2178,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(unix)]
     fn test_find_all_php_tools_enabled() {
         use crate::config::ToolsConfig;
         use tempfile::TempDir;
@@ -438,9 +2194,11 @@ mod tests {
         fs::write(&composer_path, "#!/usr/bin/php\n<?php\necho 'composer';").unwrap();
         fs::set_permissions(&composer_path, fs::Permissions::from_mode(0o755)).unwrap();
 
-        let mut tools_config = ToolsConfig::default();
-        tools_config.scan_for_tools = true;
-        tools_config.custom_search_paths = vec![bin_dir];
+        let tools_config = ToolsConfig {
+            scan_for_tools: true,
+            custom_search_paths: vec![bin_dir],
+            ..Default::default()
+        };
 
         let tools = find_all_php_tools(&tools_config);
 
@@ -452,6 +2210,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(unix)]
     fn test_find_all_php_tools_custom_paths() {
         use crate::config::ToolsConfig;
         use tempfile::TempDir;
@@ -467,10 +2226,12 @@ mod tests {
         fs::write(&my_tool, "#!/usr/bin/php\n<?php\necho 'test';").unwrap();
         fs::set_permissions(&my_tool, fs::Permissions::from_mode(0o755)).unwrap();
 
-        let mut tools_config = ToolsConfig::default();
-        tools_config.scan_for_tools = true;
-        tools_config.custom_tool_names = vec!["my-php-tool".to_string()];
-        tools_config.custom_search_paths = vec![custom_bin];
+        let tools_config = ToolsConfig {
+            scan_for_tools: true,
+            custom_tool_names: vec!["my-php-tool".to_string()],
+            custom_search_paths: vec![custom_bin],
+            ..Default::default()
+        };
 
         let tools = find_all_php_tools(&tools_config);
 
@@ -481,19 +2242,3 @@ mod tests {
         assert!(tools.iter().any(|t| t.name == "my-php-tool"));
     }
 }
-
-/// Find all PHP tools on the system based on tools configuration
-pub fn find_all_php_tools(tools_config: &crate::config::ToolsConfig) -> Result<Vec<crate::tools::PhpTool>> {
-    use crate::tools;
-
-    // If scanning is disabled, return empty list
-    if !tools_config.scan_for_tools {
-        return Ok(Vec::new());
-    }
-
-    // Use the tools module to scan for PHP tools
-    tools::scan_for_php_tools(
-        &tools_config.custom_tool_names,
-        &tools_config.custom_search_paths,
-    )
-}
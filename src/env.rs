@@ -0,0 +1,75 @@
+// Shell environment module
+//
+// `php-switcher env 8.2` prints variable assignments that activate a version
+// in the current shell when evaluated (`eval "$(php-switcher env 8.2)"`),
+// for Makefiles and dotfiles that can't rely on the global symlinks.
+// Evaluating it again for another version replaces the earlier PATH entry
+// instead of stacking them.
+
+use crate::platform::ShellKind;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// PATH with `bin_dir` first and any switcher bin directories (`managed`) removed
+///
+/// `managed` are the global bin directory and the per-version directories
+/// (`~/.php-switcher/envs`), whose entries would otherwise shadow `bin_dir`
+/// or pile up on repeated evaluation.
+pub fn path_with(bin_dir: &Path, path_var: &OsStr, global_bin_dir: &Path, envs_dir: &Path) -> Vec<PathBuf> {
+    std::iter::once(bin_dir.to_path_buf())
+        .chain(std::env::split_paths(path_var).filter(|dir| dir != global_bin_dir && !dir.starts_with(envs_dir)))
+        .collect()
+}
+
+/// Render variable assignments for a shell
+pub fn render(vars: &[(&str, String)], shell: ShellKind) -> String {
+    vars.iter()
+        .map(|(name, value)| match shell {
+            ShellKind::Posix => format!("export {}={}", name, quote(value)),
+            ShellKind::Csh => format!("setenv {} {};", name, quote(value)),
+            // fish splits PATH-like variables on colons itself
+            ShellKind::Fish => format!("set -gx {} {};", name, quote(value)),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Single-quote a value; all three shell families accept `'\''` for a quote
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_with_replaces_switcher_dirs() {
+        let home = Path::new("/home/dev/.php-switcher");
+        let path_var = std::env::join_paths([
+            home.join("envs/8.2.12"),
+            home.join("bin"),
+            PathBuf::from("/usr/local/bin"),
+            PathBuf::from("/usr/bin"),
+        ])
+        .unwrap();
+
+        let path = path_with(&home.join("envs/8.3.4"), &path_var, &home.join("bin"), &home.join("envs"));
+        assert_eq!(
+            path,
+            vec![home.join("envs/8.3.4"), PathBuf::from("/usr/local/bin"), PathBuf::from("/usr/bin")]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let vars = [("PHP_SWITCHER_VERSION", "8.2.12".to_string()), ("PATH", "/opt/it's:/usr/bin".to_string())];
+
+        assert_eq!(
+            render(&vars, ShellKind::Posix),
+            "export PHP_SWITCHER_VERSION='8.2.12'\nexport PATH='/opt/it'\\''s:/usr/bin'"
+        );
+        assert_eq!(render(&vars[..1], ShellKind::Fish), "set -gx PHP_SWITCHER_VERSION '8.2.12';");
+        assert_eq!(render(&vars[..1], ShellKind::Csh), "setenv PHP_SWITCHER_VERSION '8.2.12';");
+    }
+}
@@ -0,0 +1,205 @@
+// State change notification module
+//
+// Every subscriber (a tray widget, `php-switcher events`, an editor plugin)
+// binds its own Unix datagram socket inside `~/.php-switcher/events/`. Emitting
+// an event sends one JSON datagram to each socket found there, so no broker
+// process has to be running. Sockets whose owner has gone away are removed on
+// the next emit.
+
+use crate::config;
+use crate::output::Envelope;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+
+/// Largest event payload accepted by subscribers
+#[cfg(unix)]
+const MAX_EVENT_SIZE: usize = 64 * 1024;
+
+/// A state change broadcast to subscribers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The active PHP version changed
+    Switched {
+        version: String,
+        #[serde(with = "crate::paths::path")]
+        primary_path: PathBuf,
+    },
+    /// A scan for installations completed
+    ScanFinished { installations: usize },
+    /// The `php` found on PATH is not the one php-switcher manages
+    DriftDetected {
+        #[serde(default, with = "crate::paths::option")]
+        expected: Option<PathBuf>,
+        #[serde(with = "crate::paths::path")]
+        actual: PathBuf,
+    },
+}
+
+/// Get the directory holding subscriber sockets
+pub fn get_events_dir() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join("events"))
+}
+
+/// Send an event to every subscriber in the default events directory
+///
+/// Failures are ignored: notifications must never break the command emitting them.
+pub fn emit(event: &Event) {
+    if let Ok(dir) = get_events_dir() {
+        emit_to_dir(event, &dir).ok();
+    }
+}
+
+/// Send an event to every subscriber socket in a directory, returning how many received it
+#[cfg(unix)]
+pub fn emit_to_dir(event: &Event, dir: &Path) -> Result<usize> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(0),
+    };
+
+    let payload = serde_json::to_vec(&Envelope::new("event", event))?;
+    let sender = UnixDatagram::unbound()?;
+    let mut delivered = 0;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("sock") {
+            continue;
+        }
+
+        match sender.send_to(&payload, &path) {
+            Ok(_) => delivered += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                // Nobody is listening anymore
+                std::fs::remove_file(&path).ok();
+            }
+            Err(_) => {}
+        }
+    }
+
+    Ok(delivered)
+}
+
+/// Nobody can subscribe without Unix sockets, so there is no one to send to
+#[cfg(not(unix))]
+pub fn emit_to_dir(_event: &Event, _dir: &Path) -> Result<usize> {
+    Ok(0)
+}
+
+/// A bound subscriber socket, removed again when dropped
+pub struct Subscription {
+    #[cfg(unix)]
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl Subscription {
+    /// Bind a new subscriber socket in the default events directory
+    pub fn new() -> Result<Self> {
+        Self::in_dir(&get_events_dir()?)
+    }
+
+    /// Bind a new subscriber socket in the given directory
+    #[cfg(unix)]
+    pub fn in_dir(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("Failed to create events directory: {}", e))?;
+
+        let path = dir.join(format!("{}.sock", std::process::id()));
+        std::fs::remove_file(&path).ok();
+
+        let socket = UnixDatagram::bind(&path)
+            .map_err(|e| anyhow!("Failed to bind event socket {}: {}", path.display(), e))?;
+
+        Ok(Self { socket, path })
+    }
+
+    #[cfg(not(unix))]
+    pub fn in_dir(_dir: &Path) -> Result<Self> {
+        Err(anyhow!("Event subscriptions need Unix sockets, which this platform doesn't have"))
+    }
+
+    /// Block until the next event arrives
+    #[cfg(unix)]
+    pub fn recv(&self) -> Result<Envelope<Event>> {
+        let mut buf = vec![0u8; MAX_EVENT_SIZE];
+        let len = self.socket.recv(&mut buf)?;
+        serde_json::from_slice(&buf[..len]).map_err(|e| anyhow!("Invalid event payload: {}", e))
+    }
+
+    #[cfg(not(unix))]
+    pub fn recv(&self) -> Result<Envelope<Event>> {
+        Err(anyhow!("Event subscriptions need Unix sockets, which this platform doesn't have"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_emit_without_subscribers() {
+        let temp_dir = TempDir::new().unwrap();
+        let event = Event::ScanFinished { installations: 3 };
+
+        assert_eq!(emit_to_dir(&event, temp_dir.path()).unwrap(), 0);
+        assert_eq!(emit_to_dir(&event, &temp_dir.path().join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_subscriber_receives_event() {
+        let temp_dir = TempDir::new().unwrap();
+        let subscription = Subscription::in_dir(temp_dir.path()).unwrap();
+
+        let event = Event::Switched {
+            version: "8.2.12".to_string(),
+            primary_path: PathBuf::from("/usr/bin/php8.2"),
+        };
+        assert_eq!(emit_to_dir(&event, temp_dir.path()).unwrap(), 1);
+
+        let received = subscription.recv().unwrap();
+        assert_eq!(received.schema_version, crate::output::SCHEMA_VERSION);
+        assert_eq!(received.data, event);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stale_socket_is_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("12345.sock");
+
+        // Simulate a crashed subscriber that left its socket file behind
+        drop(UnixDatagram::bind(&path).unwrap());
+        assert!(path.exists());
+
+        emit_to_dir(&Event::ScanFinished { installations: 0 }, temp_dir.path()).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let json = serde_json::to_string(&Event::DriftDetected {
+            expected: None,
+            actual: PathBuf::from("/usr/bin/php"),
+        })
+        .unwrap();
+
+        assert!(json.contains("\"event\":\"drift_detected\""));
+    }
+}
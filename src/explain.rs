@@ -0,0 +1,226 @@
+// Resolution explanation module
+//
+// `php-switcher resolve [dir]` answers "why did I get 7.4?". It walks every
+// source `use` consults, in the order `use` consults them, and reports what
+// each one says, where it says it (file and line) and which one wins:
+//
+//   1. a version given on the command line
+//   2. a config pin (`php-switcher pin`) on the directory or a parent
+//   3. the nearest pin file (`.php-version`, `.tool-versions`)
+//   4. the nearest composer.json's `require.php`
+//   5. the default version (`php-switcher default`)
+//   6. whatever `php` is on PATH
+
+use crate::config::Config;
+use crate::project::{self, PinFile};
+use crate::{composer, detector, switcher};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// A place a version can come from, in precedence order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Argument,
+    ConfigPin,
+    PinFile,
+    Composer,
+    Default,
+    System,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Argument => "command line",
+            Source::ConfigPin => "config pin",
+            Source::PinFile => "pin file",
+            Source::Composer => "composer.json",
+            Source::Default => "default version",
+            Source::System => "PATH",
+        }
+    }
+}
+
+/// What one source says
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Step {
+    pub source: Source,
+    /// The version (or constraint) asked for; `None` when the source has nothing to say
+    pub requested: Option<String>,
+    /// Where it was found: `file:line`, a pinned directory or a binary
+    pub location: Option<String>,
+    /// The tracked version it resolves to, if any is installed
+    pub resolved: Option<String>,
+}
+
+impl Step {
+    fn absent(source: Source) -> Self {
+        Step { source, requested: None, location: None, resolved: None }
+    }
+}
+
+/// The outcome: every source in order, and the index of the one that wins
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Explanation {
+    #[serde(with = "crate::paths::path")]
+    pub dir: PathBuf,
+    pub steps: Vec<Step>,
+    pub winner: Option<usize>,
+}
+
+/// Explain which version `use` would pick in `dir`, given an optional command line version
+pub fn explain(config: &Config, dir: &Path, argument: Option<&str>) -> Explanation {
+    let resolve = |version: &str| config.find_version(version).map(|e| e.version.clone());
+
+    let mut steps = vec![Step {
+        resolved: argument.and_then(resolve),
+        requested: argument.map(str::to_string),
+        location: argument.map(|_| "argument".to_string()),
+        source: Source::Argument,
+    }];
+
+    steps.push(match project::central_pin(config, dir) {
+        Some(pin) => Step {
+            source: Source::ConfigPin,
+            resolved: resolve(&pin.version),
+            requested: Some(pin.version),
+            location: Some(format!("{} (config)", pin.dir.display())),
+        },
+        None => Step::absent(Source::ConfigPin),
+    });
+
+    steps.push(match project::find_pin(dir, config.settings.pin_files()) {
+        Some(pin) => Step {
+            source: Source::PinFile,
+            resolved: resolve(&pin.version),
+            location: pin_file_location(&pin, config.settings.pin_files()),
+            requested: Some(pin.version),
+        },
+        None => Step::absent(Source::PinFile),
+    });
+
+    steps.push(composer_step(config, dir));
+
+    steps.push(match &config.settings.default_version {
+        Some(version) => Step {
+            source: Source::Default,
+            requested: Some(version.clone()),
+            location: Some("settings.default_version".to_string()),
+            resolved: resolve(version),
+        },
+        None => Step::absent(Source::Default),
+    });
+
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    steps.push(match detector::find_in_path("php", &path_var) {
+        Some(php) => Step {
+            source: Source::System,
+            requested: None,
+            resolved: switched_version(config, &php),
+            location: Some(php.display().to_string()),
+        },
+        None => Step::absent(Source::System),
+    });
+
+    let winner = steps.iter().position(|step| step.requested.is_some() || step.location.is_some());
+    Explanation { dir: dir.to_path_buf(), steps, winner }
+}
+
+/// `file:line` of the pin file holding a pin
+fn pin_file_location(pin: &project::Pin, files: &[PinFile]) -> Option<String> {
+    files.iter().find_map(|file| {
+        let path = pin.dir.join(file.file_name());
+        let contents = std::fs::read_to_string(&path).ok()?;
+        let line = line_of(&contents, |line| file.parse(line).as_ref() == Some(&pin.version))?;
+        Some(format!("{}:{}", path.display(), line))
+    })
+}
+
+fn composer_step(config: &Config, dir: &Path) -> Step {
+    let Some(manifest) = composer::find_composer_json(dir) else { return Step::absent(Source::Composer) };
+    let Ok(contents) = composer::load(&manifest) else { return Step::absent(Source::Composer) };
+    let Some(constraint) = composer::php_constraint(&contents) else { return Step::absent(Source::Composer) };
+
+    let line = std::fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|text| line_of(&text, |line| line.trim_start().starts_with("\"php\"")));
+    Step {
+        source: Source::Composer,
+        requested: Some(constraint.to_string()),
+        location: Some(match line {
+            Some(line) => format!("{}:{}", manifest.display(), line),
+            None => manifest.display().to_string(),
+        }),
+        resolved: composer::newest_satisfying(config, constraint).ok().flatten().map(|e| e.version.clone()),
+    }
+}
+
+/// 1-based number of the first line matching
+fn line_of(contents: &str, matches: impl Fn(&str) -> bool) -> Option<usize> {
+    contents.lines().position(matches).map(|i| i + 1)
+}
+
+/// The tracked version behind `php` if it's the switcher's own link; other binaries aren't run
+fn switched_version(config: &Config, php: &Path) -> Option<String> {
+    let bin_dir = switcher::get_bin_dir().ok()?;
+    if php != bin_dir.join("php") {
+        return None;
+    }
+    switcher::active_entry(config, &bin_dir).map(|e| e.version.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DirectoryPin, VersionEntry};
+
+    #[test]
+    fn test_explain_precedence() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let app = root.join("app");
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::write(root.join(".git"), "").unwrap();
+        std::fs::write(app.join(".php-version"), "# legacy\n7.4\n").unwrap();
+        std::fs::write(app.join("composer.json"), "{\n    \"require\": {\n        \"php\": \"^8.1\"\n    }\n}\n").unwrap();
+
+        let mut config = Config::default();
+        for version in ["8.2.12", "7.4.33"] {
+            config.versions.push(VersionEntry { version: version.to_string(), ..VersionEntry::default() });
+        }
+        config.settings.default_version = Some("8.2".to_string());
+
+        let explanation = explain(&config, &app, None);
+        let sources: Vec<_> = explanation.steps.iter().map(|s| s.source).collect();
+        assert_eq!(
+            sources,
+            [Source::Argument, Source::ConfigPin, Source::PinFile, Source::Composer, Source::Default, Source::System]
+        );
+        assert_eq!(explanation.winner, Some(2));
+
+        let pin_file = &explanation.steps[2];
+        assert_eq!(pin_file.location, Some(format!("{}:2", app.join(".php-version").display())));
+        assert_eq!(pin_file.resolved.as_deref(), Some("7.4.33"));
+
+        let composer = &explanation.steps[3];
+        assert_eq!(composer.requested.as_deref(), Some("^8.1"));
+        assert_eq!(composer.location, Some(format!("{}:3", app.join("composer.json").display())));
+        assert_eq!(composer.resolved.as_deref(), Some("8.2.12"));
+
+        // Config pins beat pin files, and the command line beats everything
+        config.pins.push(DirectoryPin { dir: root.to_path_buf(), version: "8.2".to_string() });
+        assert_eq!(explain(&config, &app, None).winner, Some(1));
+        assert_eq!(explain(&config, &app, Some("7.4")).winner, Some(0));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_explanation_keeps_non_utf8_dirs() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = PathBuf::from(std::ffi::OsStr::from_bytes(b"/srv/caf\xe9/app"));
+        let json = serde_json::to_value(explain(&Config::default(), &dir, None)).unwrap();
+        assert_eq!(crate::paths::from_bytes(&serde_json::from_value::<Vec<u8>>(json["dir"]["bytes"].clone()).unwrap()), dir);
+    }
+}
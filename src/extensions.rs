@@ -0,0 +1,192 @@
+// Extension module
+//
+// Projects declare the extensions they need as `ext-*` requirements in
+// composer.json. After a project-triggered switch these are compared with
+// what the new installation loads: extensions that are installed but
+// commented out in its ini files can be enabled on the spot, genuinely
+// missing ones get install hints.
+
+use crate::probe::{self, Probe};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// An extension whose directive is commented out in an ini file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisabledExtension {
+    pub name: String,
+    pub file: PathBuf,
+    /// 1-based line of the commented directive
+    pub line: usize,
+}
+
+/// Required extensions that aren't loaded by an installation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtensionReport {
+    pub disabled: Vec<DisabledExtension>,
+    pub missing: Vec<String>,
+}
+
+impl ExtensionReport {
+    pub fn is_empty(&self) -> bool {
+        self.disabled.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Get the `ext-*` requirements (require and require-dev) of a composer manifest
+pub fn required_extensions(manifest: &Value) -> Vec<String> {
+    let mut names: Vec<String> = ["require", "require-dev"]
+        .iter()
+        .filter_map(|section| manifest.get(section)?.as_object())
+        .flat_map(|requirements| requirements.keys())
+        .filter_map(|key| key.strip_prefix("ext-"))
+        .map(normalize)
+        .collect();
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Normalize an extension name as printed by `php -m` or used in ini files
+///
+/// `Zend OPcache` becomes `opcache`, `php_intl.dll` becomes `intl`.
+pub fn normalize(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_prefix("zend ").unwrap_or(&name);
+    let name = name.strip_prefix("php_").unwrap_or(name);
+    let name = name.strip_suffix(".so").or_else(|| name.strip_suffix(".dll")).unwrap_or(name);
+    name.to_string()
+}
+
+/// Parse an `extension=` / `zend_extension=` line into (commented out, name)
+pub fn parse_directive(line: &str) -> Option<(bool, String)> {
+    let trimmed = line.trim();
+    let uncommented = trimmed.trim_start_matches(';').trim_start();
+    let commented = uncommented.len() != trimmed.len();
+
+    let (key, value) = uncommented.split_once('=')?;
+    if !matches!(key.trim(), "extension" | "zend_extension") {
+        return None;
+    }
+
+    let value = value.trim().trim_matches('"').trim_matches('\'');
+    let file = Path::new(value).file_name()?.to_str()?;
+    Some((commented, normalize(file)))
+}
+
+/// Compare required extensions with what a PHP binary loads
+pub fn check(binary: &Path, required: &[String]) -> ExtensionReport {
+    let probe = probe::probe(binary);
+    let loaded: Vec<String> = probe.extensions.iter().map(|m| normalize(m)).collect();
+    let ini_files = ini_files(&probe);
+
+    let mut report = ExtensionReport::default();
+    for name in required.iter().filter(|name| !loaded.contains(name)) {
+        match find_disabled(&ini_files, name) {
+            Some(disabled) => report.disabled.push(disabled),
+            None => report.missing.push(name.clone()),
+        }
+    }
+
+    report
+}
+
+/// The php.ini and every file in the additional ini directories of a binary
+fn ini_files(probe: &Probe) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = probe.ini_file.iter().cloned().collect();
+    for dir in &probe.ini_scan_dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else { continue };
+        let mut inis: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "ini"))
+            .collect();
+        inis.sort();
+        files.extend(inis);
+    }
+
+    files
+}
+
+/// Find a commented-out directive for an extension
+pub fn find_disabled(files: &[PathBuf], name: &str) -> Option<DisabledExtension> {
+    files.iter().find_map(|file| {
+        let contents = std::fs::read_to_string(file).ok()?;
+        contents.lines().enumerate().find_map(|(i, line)| match parse_directive(line) {
+            Some((true, found)) if found == name => Some(DisabledExtension {
+                name: found,
+                file: file.clone(),
+                line: i + 1,
+            }),
+            _ => None,
+        })
+    })
+}
+
+/// Enable an extension by uncommenting its directive
+pub fn enable(disabled: &DisabledExtension) -> Result<()> {
+    let contents = std::fs::read_to_string(&disabled.file)
+        .map_err(|e| anyhow!("Failed to read {}: {}", disabled.file.display(), e))?;
+
+    let enabled: Vec<String> = contents
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i + 1 == disabled.line {
+                line.trim_start().trim_start_matches(';').trim_start().to_string()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+
+    std::fs::write(&disabled.file, enabled.concat())
+        .map_err(|e| anyhow!("Failed to write {}: {}", disabled.file.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_required_extensions() {
+        let manifest = json!({
+            "require": { "php": "^8.2", "ext-intl": "*", "ext-PDO_mysql": "*" },
+            "require-dev": { "ext-xdebug": "*", "ext-intl": "*" }
+        });
+
+        assert_eq!(required_extensions(&manifest), vec!["intl", "pdo_mysql", "xdebug"]);
+    }
+
+    #[test]
+    fn test_parse_directive() {
+        assert_eq!(parse_directive("extension=intl"), Some((false, "intl".to_string())));
+        assert_eq!(parse_directive(";extension=pdo_mysql.so"), Some((true, "pdo_mysql".to_string())));
+        assert_eq!(
+            parse_directive("; zend_extension = \"/usr/lib/php/20220829/opcache.so\""),
+            Some((true, "opcache".to_string()))
+        );
+        assert_eq!(parse_directive(";extension=php_curl.dll"), Some((true, "curl".to_string())));
+        assert_eq!(parse_directive("memory_limit=128M"), None);
+        assert_eq!(normalize("Zend OPcache"), "opcache");
+    }
+
+    #[test]
+    fn test_find_and_enable_disabled() {
+        let temp_dir = TempDir::new().unwrap();
+        let ini = temp_dir.path().join("20-intl.ini");
+        std::fs::write(&ini, "; priority=20\n;extension=intl.so\n").unwrap();
+        let files = vec![ini.clone()];
+
+        let disabled = find_disabled(&files, "intl").unwrap();
+        assert_eq!(disabled.line, 2);
+        assert_eq!(find_disabled(&files, "xdebug"), None);
+
+        enable(&disabled).unwrap();
+        assert_eq!(std::fs::read_to_string(&ini).unwrap(), "; priority=20\nextension=intl.so\n");
+        assert_eq!(find_disabled(&files, "intl"), None);
+    }
+}
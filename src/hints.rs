@@ -1,21 +1,271 @@
 // Installation hints module
 //
 // Provides helpful suggestions for installing PHP versions that aren't found on the system.
-// Keeps hints deliberately generic to minimize maintenance burden.
+// What each distribution calls its packages and which repository ships which
+// PHP series lives in a table (hints.toml), not in code: the copy embedded at
+// build time can be replaced by a newer one with `hints update`, so the
+// guidance keeps up with PHP releases between php-switcher releases.
 
+use crate::{config, style};
 use crate::platform::Platform;
+use crate::version::PhpVersion;
+use anyhow::{anyhow, Result};
 use colored::Colorize;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Table shipped with php-switcher
+const EMBEDDED_TABLE: &str = include_str!("hints.toml");
+
+/// Table schema this build understands
+pub const SCHEMA: u32 = 1;
+
+/// Where `hints update` fetches the latest table from unless given a URL
+pub const DEFAULT_UPDATE_URL: &str = "https://raw.githubusercontent.com/nschmoyer/php-switcher/main/src/hints.toml";
+
+/// File name of the downloaded table in the config directory
+const OVERRIDE_FILE: &str = "hints.toml";
+
+/// Per-distribution installation knowledge
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct HintTable {
+    pub schema: u32,
+    #[serde(default, rename = "distro")]
+    pub distros: Vec<Distro>,
+}
+
+/// How one distribution (or package manager) packages PHP
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Distro {
+    pub name: String,
+    /// `linux`, `macos`, `bsd`, `windows` or `other`
+    pub platform: String,
+    /// `/etc/os-release` IDs this entry applies to (Linux only)
+    #[serde(default)]
+    pub ids: Vec<String>,
+    pub package: String,
+    #[serde(default)]
+    pub extension_package: Option<String>,
+    pub search: String,
+    pub install: String,
+    /// Series the default repositories ship
+    #[serde(default)]
+    pub native: Vec<String>,
+    #[serde(default, rename = "repo")]
+    pub repos: Vec<Repo>,
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+/// A third-party repository adding PHP series to a distribution
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Repo {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub setup: Vec<String>,
+    /// Install command, when it differs from the distribution's
+    #[serde(default)]
+    pub install: Option<String>,
+    pub versions: Vec<String>,
+}
+
+impl HintTable {
+    /// Parse a table, rejecting schemas this build doesn't understand
+    pub fn parse(contents: &str) -> Result<Self> {
+        let table: HintTable = toml::from_str(contents).map_err(|e| anyhow!("Failed to parse hints table: {}", e))?;
+        if table.schema != SCHEMA {
+            return Err(anyhow!("Unsupported hints table schema {} (expected {})", table.schema, SCHEMA));
+        }
+        Ok(table)
+    }
+
+    /// The table shipped with php-switcher
+    pub fn embedded() -> Self {
+        Self::parse(EMBEDDED_TABLE).expect("embedded hints table is valid")
+    }
+
+    /// Entries for a platform; on Linux only the running distribution's, if it's known
+    pub fn for_platform(&self, platform: Platform, os_ids: &[String]) -> Vec<&Distro> {
+        let key = platform_key(platform);
+        let candidates: Vec<&Distro> = self.distros.iter().filter(|d| d.platform == key).collect();
+
+        let detected: Vec<&Distro> =
+            candidates.iter().copied().filter(|d| d.ids.iter().any(|id| os_ids.contains(id))).collect();
+        if detected.is_empty() {
+            candidates
+        } else {
+            detected
+        }
+    }
+}
+
+fn platform_key(platform: Platform) -> &'static str {
+    match platform {
+        Platform::Linux => "linux",
+        Platform::MacOS => "macos",
+        Platform::BSD => "bsd",
+        Platform::Windows => "windows",
+        Platform::Other => "other",
+    }
+}
+
+impl Distro {
+    /// Every series this entry knows a package source for
+    fn known_series(&self) -> impl Iterator<Item = &String> {
+        self.native.iter().chain(self.repos.iter().flat_map(|r| r.versions.iter()))
+    }
+
+    /// The series a requested version refers to
+    ///
+    /// `8.2.12` and `8.2` mean 8.2; a bare major version or a range means
+    /// the newest known series it allows.
+    pub fn series_for(&self, version: &str) -> String {
+        let parts: Vec<&str> = version.split('.').collect();
+        if parts.len() >= 2 && parts[..2].iter().all(|p| p.parse::<u32>().is_ok()) {
+            return format!("{}.{}", parts[0], parts[1]);
+        }
+
+        self.known_series()
+            .filter_map(|series| {
+                let parsed = PhpVersion::from_php_output(&format!("PHP {}.0", series)).ok()?;
+                let newest_patch = PhpVersion::new(parsed.major, parsed.minor, 999);
+                (parsed.matches(version) || newest_patch.matches(version)).then_some(parsed)
+            })
+            .max()
+            .map(|v| v.short_version())
+            .unwrap_or_else(|| version.to_string())
+    }
+
+    /// Lines describing how to install a PHP series
+    pub fn install_lines(&self, series: &str) -> Vec<String> {
+        let package = fill(&self.package, series, "");
+        let render = |template: &str| fill(template, series, &package);
+        let mut lines = Vec::new();
+
+        if self.native.iter().any(|v| v == series) {
+            lines.push(render(&self.install));
+        } else if let Some(repo) = self.repos.iter().find(|r| r.versions.iter().any(|v| v == series)) {
+            lines.push(format!("PHP {} comes from {} ({}):", series, repo.name, repo.url));
+            lines.extend(repo.setup.iter().map(|step| render(step)));
+            lines.push(render(repo.install.as_deref().unwrap_or(&self.install)));
+        } else {
+            lines.push(render(&self.search));
+            if self.known_series().next().is_some() {
+                lines.push(format!("No known package source ships PHP {} yet", series));
+            }
+        }
+
+        lines.extend(self.notes.iter().cloned());
+        lines
+    }
+
+    /// Command installing extensions for a series, if the distribution packages them
+    pub fn extension_line(&self, series: &str, extensions: &[String]) -> Option<String> {
+        let template = self.extension_package.as_deref()?;
+        let packages: Vec<String> =
+            extensions.iter().map(|ext| fill(&template.replace("{extension}", ext), series, "")).collect();
+        Some(fill(&self.install, series, &packages.join(" ")))
+    }
+}
+
+/// Fill a command template's placeholders for a series
+fn fill(template: &str, series: &str, packages: &str) -> String {
+    let major = series.split('.').next().unwrap_or(series);
+    template
+        .replace("{version}", series)
+        .replace("{nodot}", &series.replace('.', ""))
+        .replace("{major}", major)
+        .replace("{packages}", packages)
+}
+
+/// IDs of the running Linux distribution (`ID` and `ID_LIKE` of `/etc/os-release`)
+pub fn os_release_ids(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.strip_prefix("ID=").or_else(|| line.strip_prefix("ID_LIKE=")))
+        .flat_map(|value| value.trim_matches('"').split_whitespace().map(str::to_string).collect::<Vec<_>>())
+        .collect()
+}
+
+fn detect_os_ids() -> Vec<String> {
+    std::fs::read_to_string("/etc/os-release").map(|c| os_release_ids(&c)).unwrap_or_default()
+}
+
+/// Location of a table downloaded by `hints update`
+pub fn override_path() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join(OVERRIDE_FILE))
+}
+
+/// Load the downloaded table if there is a usable one, otherwise the embedded one
+pub fn load_table() -> HintTable {
+    let Ok(path) = override_path() else { return HintTable::embedded() };
+    load_table_from(&path)
+}
+
+fn load_table_from(path: &Path) -> HintTable {
+    let Ok(contents) = std::fs::read_to_string(path) else { return HintTable::embedded() };
+
+    match HintTable::parse(&contents) {
+        Ok(table) => table,
+        Err(e) => {
+            eprintln!("{} Ignoring {}: {}", style::warn().yellow(), path.display(), e);
+            HintTable::embedded()
+        }
+    }
+}
+
+/// Download a newer table and store it in the config directory
+///
+/// The download is validated first, so a bad file never replaces a working one.
+pub fn update(url: &str) -> Result<HintTable> {
+    let output = std::process::Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", url])
+        .output()
+        .map_err(|e| anyhow!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!("Failed to download {}: {}", url, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let contents = String::from_utf8(output.stdout).map_err(|e| anyhow!("Failed to read {}: {}", url, e))?;
+    let table = HintTable::parse(&contents)?;
+
+    let path = override_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| anyhow!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    std::fs::write(&path, contents).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(table)
+}
+
+/// Remove a downloaded table, going back to the embedded one
+pub fn reset() -> Result<bool> {
+    let path = override_path()?;
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(anyhow!("Failed to remove {}: {}", path.display(), e)),
+    }
+}
 
 /// Show installation hints for a missing PHP version
 pub fn show_installation_hints(version: &str, platform: Platform) {
     println!("\n{}", format!("PHP {} not found on your system.", version).red().bold());
     println!("\n{}", "To install PHP:".bold());
 
-    match platform {
-        Platform::Linux => show_linux_hints(version),
-        Platform::MacOS => show_macos_hints(version),
-        Platform::BSD => show_bsd_hints(version),
-        Platform::Other => show_generic_hints(version),
+    let table = load_table();
+    let distros = table.for_platform(platform, &detect_os_ids());
+
+    if distros.is_empty() {
+        println!("  {} Check your system's package manager for PHP {}", style::bullet().green(), version);
+        println!("  {} Or download from PHP.net", style::bullet().green());
+    }
+
+    for distro in distros {
+        println!("  {} {}:", style::bullet().green(), distro.name);
+        for line in distro.install_lines(&distro.series_for(version)) {
+            println!("    {}", line);
+        }
     }
 
     // Always show the generic PHP.net link
@@ -23,43 +273,21 @@ pub fn show_installation_hints(version: &str, platform: Platform) {
     println!("  {}", "https://www.php.net/manual/en/install.php".cyan());
 }
 
-fn show_linux_hints(version: &str) {
-    println!("  {} Search your package manager:", "•".green());
-    println!("    dnf search php{} php{}", version, version.replace('.', ""));
-    println!("    apt search php{}", version);
-    println!("    zypper search php{}", version);
-    println!();
-    println!("  {} Popular third-party repositories:", "•".green());
-    println!("    {} {}",
-        "Remi (RHEL/Fedora/CentOS):".bold(),
-        "https://rpms.remirepo.net/".cyan()
-    );
-    println!("    {} {}",
-        "Ondrej PPA (Ubuntu/Debian):".bold(),
-        "https://launchpad.net/~ondrej/+archive/ubuntu/php".cyan()
-    );
-}
-
-fn show_macos_hints(version: &str) {
-    println!("  {} Using Homebrew:", "•".green());
-    println!("    brew install php@{}", version);
-    println!();
-    println!("  {} If formula not found, try:", "•".green());
-    println!("    brew tap shivammathur/php");
-    println!("    brew install shivammathur/php/php@{}", version);
-}
+/// Show installation hints for extensions a project needs but PHP lacks
+pub fn show_extension_hints(extensions: &[String], version: &str, platform: Platform) {
+    println!("\n{}", format!("Missing extensions for PHP {}: {}", version, extensions.join(", ")).red().bold());
+    println!("\n{}", "To install them:".bold());
 
-fn show_bsd_hints(version: &str) {
-    println!("  {} Using pkg:", "•".green());
-    println!("    pkg search php{}", version.replace('.', ""));
-    println!("    pkg install php{}", version.replace('.', ""));
-    println!();
-    println!("  {} Or check your BSD's ports collection", "•".green());
-}
+    let table = load_table();
+    for distro in table.for_platform(platform, &detect_os_ids()) {
+        if let Some(line) = distro.extension_line(&distro.series_for(version), extensions) {
+            println!("  {} {}:", style::bullet().green(), distro.name);
+            println!("    {}", line);
+        }
+    }
 
-fn show_generic_hints(version: &str) {
-    println!("  {} Check your system's package manager for PHP {}", "•".green(), version);
-    println!("  {} Or download from PHP.net", "•".green());
+    println!("  {} Or build them with PECL:", style::bullet().green());
+    println!("    pecl install {}", extensions.join(" "));
 }
 
 #[cfg(test)]
@@ -88,6 +316,14 @@ mod tests {
         show_installation_hints("7.4", Platform::Other);
     }
 
+    #[test]
+    fn test_show_extension_hints() {
+        let extensions = vec!["intl".to_string(), "redis".to_string()];
+        for platform in [Platform::Linux, Platform::MacOS, Platform::BSD, Platform::Windows, Platform::Other] {
+            show_extension_hints(&extensions, "8.2", platform);
+        }
+    }
+
     #[test]
     fn test_hints_with_various_version_formats() {
         // Test with different version string formats
@@ -95,4 +331,65 @@ mod tests {
         show_installation_hints("8.1.0", Platform::Linux);
         show_installation_hints("8", Platform::MacOS);
     }
+
+    #[test]
+    fn test_table_drives_install_lines() {
+        let table = HintTable::embedded();
+        let ubuntu = table.for_platform(Platform::Linux, &["ubuntu".to_string(), "debian".to_string()]);
+        assert_eq!(ubuntu.len(), 1);
+
+        assert_eq!(
+            ubuntu[0].install_lines("7.4"),
+            vec![
+                "PHP 7.4 comes from Ondrej PPA (https://launchpad.net/~ondrej/+archive/ubuntu/php):",
+                "add-apt-repository ppa:ondrej/php",
+                "apt update",
+                "apt install php7.4",
+            ]
+        );
+        assert_eq!(
+            ubuntu[0].extension_line("8.2", &["intl".to_string(), "redis".to_string()]).as_deref(),
+            Some("apt install php8.2-intl php8.2-redis")
+        );
+
+        let brew = &table.for_platform(Platform::MacOS, &[])[0];
+        assert_eq!(brew.install_lines("8.3"), vec!["brew install php@8.3"]);
+        assert_eq!(brew.install_lines("9.0")[1], "No known package source ships PHP 9.0 yet");
+
+        // Unknown distributions get every Linux entry
+        assert!(table.for_platform(Platform::Linux, &["gentoo".to_string()]).len() > 1);
+    }
+
+    #[test]
+    fn test_series_for() {
+        let brew = HintTable::embedded().for_platform(Platform::MacOS, &[])[0].clone();
+
+        assert_eq!(brew.series_for("8.2.12"), "8.2");
+        assert_eq!(brew.series_for("7"), "7.4");
+        assert_eq!(brew.series_for("^8.1 <8.4"), "8.3");
+        assert_eq!(brew.series_for("~8.2.3"), "8.2");
+    }
+
+    #[test]
+    fn test_table_schema_and_override() {
+        assert!(HintTable::parse("schema = 2").is_err());
+        assert_eq!(HintTable::parse("schema = 1").unwrap().distros, Vec::new());
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(OVERRIDE_FILE);
+        assert_eq!(load_table_from(&path), HintTable::embedded());
+
+        std::fs::write(&path, "schema = 1\n").unwrap();
+        assert!(load_table_from(&path).distros.is_empty());
+
+        // A broken download never hides the embedded table
+        std::fs::write(&path, "not toml [").unwrap();
+        assert_eq!(load_table_from(&path), HintTable::embedded());
+    }
+
+    #[test]
+    fn test_os_release_ids() {
+        let contents = "NAME=\"Rocky Linux\"\nID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\nVERSION_ID=\"9.3\"\n";
+        assert_eq!(os_release_ids(contents), vec!["rocky", "rhel", "centos", "fedora"]);
+    }
 }
@@ -0,0 +1,250 @@
+// Switch history module
+//
+// Every switch is appended as one JSON line to `~/.php-switcher/history.jsonl`,
+// recording what triggered it and where it was run from so switches on shared
+// machines and in automation can be audited later.
+
+use crate::config;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// What caused a switch
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    /// Someone ran the CLI by hand
+    Manual,
+    /// A shell hook reacting to a directory change
+    CdHook,
+    /// A git hook (post-checkout, post-merge, ...)
+    GitHook,
+    /// A CI pipeline
+    Ci,
+    /// A command run over SSH
+    Remote,
+    /// The JSON-RPC api mode
+    Api,
+    /// Links repaired after their targets disappeared
+    Repair,
+}
+
+impl Trigger {
+    /// Work out the trigger from the environment
+    ///
+    /// Hooks set `PHP_SWITCHER_TRIGGER` explicitly; otherwise CI and SSH
+    /// sessions are recognized from their standard variables.
+    pub fn detect() -> Self {
+        Self::from_env(|name| std::env::var(name).ok())
+    }
+
+    fn from_env<F: Fn(&str) -> Option<String>>(get: F) -> Self {
+        if let Some(trigger) = get("PHP_SWITCHER_TRIGGER").and_then(|v| Self::parse(&v)) {
+            return trigger;
+        }
+
+        if get("CI").is_some_and(|v| !v.is_empty() && v != "false") {
+            return Trigger::Ci;
+        }
+
+        if get("SSH_CONNECTION").is_some() || get("SSH_CLIENT").is_some() {
+            return Trigger::Remote;
+        }
+
+        Trigger::Manual
+    }
+
+    /// Parse a trigger name like "cd-hook"
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "manual" => Some(Trigger::Manual),
+            "cd-hook" => Some(Trigger::CdHook),
+            "git-hook" => Some(Trigger::GitHook),
+            "ci" => Some(Trigger::Ci),
+            "remote" => Some(Trigger::Remote),
+            "api" => Some(Trigger::Api),
+            "repair" => Some(Trigger::Repair),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Trigger::Manual => "manual",
+            Trigger::CdHook => "cd-hook",
+            Trigger::GitHook => "git-hook",
+            Trigger::Ci => "ci",
+            Trigger::Remote => "remote",
+            Trigger::Api => "api",
+            Trigger::Repair => "repair",
+        }
+    }
+}
+
+/// A single recorded switch
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub from: Option<String>,
+    pub to: String,
+    pub trigger: Trigger,
+    #[serde(default, with = "crate::paths::option")]
+    pub cwd: Option<PathBuf>,
+}
+
+impl HistoryEntry {
+    /// Create an entry for a switch happening now in the current directory
+    pub fn now(from: Option<String>, to: &str, trigger: Trigger) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            from,
+            to: to.to_string(),
+            trigger,
+            cwd: std::env::current_dir().ok(),
+        }
+    }
+}
+
+/// Get the path to the history log
+pub fn get_history_path() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join("history.jsonl"))
+}
+
+/// Append an entry to a history file
+pub fn append_to_file<P: AsRef<Path>>(entry: &HistoryEntry, path: P) -> Result<()> {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Failed to create history directory: {}", e))?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow!("Failed to open history file: {}", e))?;
+
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+        .map_err(|e| anyhow!("Failed to write history file: {}", e))?;
+
+    Ok(())
+}
+
+/// Read all entries from a history file, oldest first
+///
+/// Lines that can't be parsed (e.g. from a newer version) are skipped.
+pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<HistoryEntry>> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow!("Failed to open history file: {}", e))?;
+
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Append an entry to the default history log (skipped in read-only mode)
+pub fn record(entry: &HistoryEntry) -> Result<()> {
+    if config::is_read_only() {
+        return Ok(());
+    }
+    append_to_file(entry, get_history_path()?)
+}
+
+/// Read the default history log, oldest first
+pub fn read() -> Result<Vec<HistoryEntry>> {
+    read_from_file(get_history_path()?)
+}
+
+/// The version that was active before the `n`th most recent switch (1 = the last one)
+pub fn version_before(entries: &[HistoryEntry], n: usize) -> Result<String> {
+    let entry = n
+        .checked_sub(1)
+        .and_then(|back| entries.iter().rev().nth(back))
+        .ok_or_else(|| anyhow!("No switch #{} in the history ({} recorded)", n, entries.len()))?;
+    entry
+        .from
+        .clone()
+        .ok_or_else(|| anyhow!("No version was active before switch #{} (to {})", n, entry.to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn detect_with(vars: &[(&str, &str)]) -> Trigger {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Trigger::from_env(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_trigger_detection() {
+        assert_eq!(detect_with(&[]), Trigger::Manual);
+        assert_eq!(detect_with(&[("CI", "true")]), Trigger::Ci);
+        assert_eq!(detect_with(&[("CI", "false")]), Trigger::Manual);
+        assert_eq!(detect_with(&[("SSH_CONNECTION", "10.0.0.1 22 10.0.0.2 22")]), Trigger::Remote);
+
+        // Explicit trigger wins over everything else
+        assert_eq!(detect_with(&[("PHP_SWITCHER_TRIGGER", "cd-hook"), ("CI", "1")]), Trigger::CdHook);
+    }
+
+    #[test]
+    fn test_trigger_parse_roundtrip() {
+        for trigger in [Trigger::Manual, Trigger::CdHook, Trigger::GitHook, Trigger::Ci, Trigger::Remote, Trigger::Api] {
+            assert_eq!(Trigger::parse(trigger.name()), Some(trigger));
+        }
+        assert_eq!(Trigger::parse("cron"), None);
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let temp_dir = TempDir::new().unwrap();
+        let history_file = temp_dir.path().join("history.jsonl");
+
+        let first = HistoryEntry::now(None, "8.2.12", Trigger::Manual);
+        let second = HistoryEntry::now(Some("8.2.12".to_string()), "8.3.4", Trigger::GitHook);
+
+        append_to_file(&first, &history_file).unwrap();
+        append_to_file(&second, &history_file).unwrap();
+
+        let entries = read_from_file(&history_file).unwrap();
+        assert_eq!(entries, vec![first, second]);
+        assert!(entries[1].cwd.is_some());
+    }
+
+    #[test]
+    fn test_version_before() {
+        let entries = vec![
+            HistoryEntry::now(None, "8.2.12", Trigger::Manual),
+            HistoryEntry::now(Some("8.2.12".to_string()), "8.3.4", Trigger::CdHook),
+        ];
+
+        assert_eq!(version_before(&entries, 1).unwrap(), "8.2.12");
+        assert!(version_before(&entries, 2).is_err());
+        assert!(version_before(&entries, 3).is_err());
+        assert!(version_before(&entries, 0).is_err());
+    }
+
+    #[test]
+    fn test_read_skips_garbage_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let history_file = temp_dir.path().join("history.jsonl");
+
+        std::fs::write(&history_file, "not json\n").unwrap();
+        append_to_file(&HistoryEntry::now(None, "8.1.0", Trigger::Ci), &history_file).unwrap();
+
+        assert_eq!(read_from_file(&history_file).unwrap().len(), 1);
+        assert!(read_from_file(temp_dir.path().join("missing")).unwrap().is_empty());
+    }
+}
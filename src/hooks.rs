@@ -0,0 +1,193 @@
+// Switch hook module
+//
+// Hooks are shell commands configured as `[[hooks]]` in config.toml and run
+// before or after a switch. Each receives a JSON payload on stdin plus
+// `PHP_SWITCHER_*` environment variables, is killed (with anything it
+// started) after its timeout, and either warns or aborts on failure
+// depending on its `on_failure` policy.
+// Aborting only prevents the switch for `pre-switch` hooks; a failing
+// `post-switch` hook makes the command fail but the switch has already happened.
+
+use crate::config::{FailurePolicy, HookConfig, HookEvent};
+use crate::history::Trigger;
+use crate::output::Envelope;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How often a running hook is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Data passed to hooks on stdin
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HookPayload {
+    pub event: HookEvent,
+    pub old_version: Option<String>,
+    pub new_version: String,
+    #[serde(with = "crate::paths::path")]
+    pub primary_path: PathBuf,
+    #[serde(with = "crate::paths::vec")]
+    pub paths: Vec<PathBuf>,
+    pub trigger: Trigger,
+}
+
+/// Run every hook registered for an event, in config order
+///
+/// Returns warnings for failed `warn` hooks, or an error as soon as an
+/// `abort` hook fails.
+pub fn run_hooks(hooks: &[HookConfig], event: HookEvent, payload: &HookPayload) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+
+    for hook in hooks.iter().filter(|h| h.event == event) {
+        if let Err(e) = run_hook(hook, payload) {
+            let message = format!("{} hook '{}' failed: {}", event.name(), hook.command, e);
+            match hook.on_failure {
+                FailurePolicy::Warn => warnings.push(message),
+                FailurePolicy::Abort => return Err(anyhow!(message)),
+            }
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Run a single hook, enforcing its timeout
+pub fn run_hook(hook: &HookConfig, payload: &HookPayload) -> Result<()> {
+    let body = serde_json::to_vec(&Envelope::new("hook", payload))?;
+
+    // Hook output goes to stderr so it can't corrupt machine-readable stdout
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(&hook.command)
+        .env("PHP_SWITCHER_HOOK_EVENT", payload.event.name())
+        .env("PHP_SWITCHER_OLD_VERSION", payload.old_version.as_deref().unwrap_or(""))
+        .env("PHP_SWITCHER_NEW_VERSION", &payload.new_version)
+        .env("PHP_SWITCHER_PHP", &payload.primary_path)
+        .env("PHP_SWITCHER_TRIGGER", payload.trigger.name())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::from(std::io::stderr()));
+    // In a group of its own, so a timeout also kills whatever the hook started
+    #[cfg(unix)]
+    crate::process::signals::own_process_group(&mut command, false);
+    let mut child = command.spawn().map_err(|e| anyhow!("could not start: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores its payload may close stdin early; that's fine
+        stdin.write_all(&body).ok();
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(hook.timeout_secs);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if status.success() {
+                return Ok(());
+            }
+            return Err(anyhow!("exited with {}", status));
+        }
+
+        if Instant::now() >= deadline {
+            #[cfg(unix)]
+            crate::process::signals::kill_group(child.id());
+            child.kill().ok();
+            child.wait().ok();
+            return Err(anyhow!("timed out after {}s", hook.timeout_secs));
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn payload() -> HookPayload {
+        HookPayload {
+            event: HookEvent::PostSwitch,
+            old_version: Some("8.2.12".to_string()),
+            new_version: "8.3.4".to_string(),
+            primary_path: PathBuf::from("/usr/bin/php8.3"),
+            paths: vec![PathBuf::from("/usr/bin/php8.3")],
+            trigger: Trigger::Manual,
+        }
+    }
+
+    fn hook(command: &str, on_failure: FailurePolicy) -> HookConfig {
+        HookConfig {
+            event: HookEvent::PostSwitch,
+            command: command.to_string(),
+            timeout_secs: 5,
+            on_failure,
+        }
+    }
+
+    #[test]
+    fn test_hook_receives_payload_and_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let out = temp_dir.path().join("out");
+        let command = format!(
+            "cat > {0}.json; echo \"$PHP_SWITCHER_OLD_VERSION -> $PHP_SWITCHER_NEW_VERSION\" > {0}.env",
+            out.display()
+        );
+
+        run_hook(&hook(&command, FailurePolicy::Abort), &payload()).unwrap();
+
+        let json = std::fs::read_to_string(out.with_extension("json")).unwrap();
+        let received: Envelope<HookPayload> = serde_json::from_str(&json).unwrap();
+        assert_eq!(received.data, payload());
+
+        let env = std::fs::read_to_string(out.with_extension("env")).unwrap();
+        assert_eq!(env.trim(), "8.2.12 -> 8.3.4");
+    }
+
+    #[test]
+    fn test_hook_timeout() {
+        let mut slow = hook("sleep 5", FailurePolicy::Abort);
+        slow.timeout_secs = 0;
+
+        let started = Instant::now();
+        let result = run_hook(&slow, &payload());
+
+        assert!(result.unwrap_err().to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_hook_timeout_kills_what_it_started() {
+        let temp_dir = TempDir::new().unwrap();
+        let pid_file = temp_dir.path().join("pid");
+        let mut slow = hook(&format!("sleep 30 & echo $! > {}; wait", pid_file.display()), FailurePolicy::Abort);
+        slow.timeout_secs = 1;
+
+        assert!(run_hook(&slow, &payload()).is_err());
+
+        // Gone, or a zombie waiting for init to reap it
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = PathBuf::from("/proc").join(pid.trim()).join("stat");
+        let alive = || std::fs::read_to_string(&stat).is_ok_and(|stat| !stat.contains(") Z "));
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while alive() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!alive());
+    }
+
+    #[test]
+    fn test_failure_policies() {
+        let hooks = vec![hook("exit 1", FailurePolicy::Warn), hook("true", FailurePolicy::Abort)];
+        let warnings = run_hooks(&hooks, HookEvent::PostSwitch, &payload()).unwrap();
+        assert_eq!(warnings.len(), 1);
+
+        let hooks = vec![hook("exit 3", FailurePolicy::Abort)];
+        assert!(run_hooks(&hooks, HookEvent::PostSwitch, &payload()).is_err());
+
+        // Hooks for other events are not run
+        assert!(run_hooks(&hooks, HookEvent::PreSwitch, &payload()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+// Offline inspection module
+//
+// Support engineers get a copy of a user's `~/.php-switcher` directory and
+// need the conclusions `list` and friends would draw on that machine. All of
+// it comes from files: the config, the links in `bin/`, the switch history
+// and the probe cache. Nothing in the copy is executed, and nothing on the
+// inspecting machine is consulted, since paths refer to the user's system.
+
+use crate::config::{self, Config, VersionEntry};
+use crate::history::{self, HistoryEntry};
+use crate::probe;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Marker line written into every generated shim
+const SHIM_MARKER: &str = "Auto-generated shim";
+
+/// What an entry of the copied bin directory points at
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "state", content = "version")]
+pub enum LinkState {
+    /// A binary of the active version
+    Active,
+    /// A binary of another tracked version
+    Foreign(String),
+    /// A target no tracked version contains
+    Untracked,
+    /// A tool shim script
+    Shim,
+    /// A regular file (copy or hard link strategy)
+    File,
+}
+
+/// One entry of the copied bin directory
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkReport {
+    pub name: String,
+    #[serde(with = "crate::paths::option")]
+    pub target: Option<PathBuf>,
+    #[serde(flatten)]
+    pub state: LinkState,
+}
+
+/// Conclusions about a copied state directory
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Inspection {
+    #[serde(with = "crate::paths::path")]
+    pub dir: PathBuf,
+    pub versions: Vec<String>,
+    /// Version the `php` link belongs to
+    pub active: Option<String>,
+    pub links: Vec<LinkReport>,
+    pub last_switch: Option<HistoryEntry>,
+    /// Extensions of the active version, as last probed on the user's machine
+    pub extensions: Option<Vec<String>>,
+    /// Problems worth looking into, most important first
+    pub findings: Vec<String>,
+}
+
+/// Inspect a copied `~/.php-switcher` directory without running anything in it
+pub fn inspect(dir: &Path) -> Result<Inspection> {
+    if dir.is_file() {
+        return Err(anyhow!(
+            "{} is a file; report bundles aren't supported, pass a copied ~/.php-switcher directory",
+            dir.display()
+        ));
+    }
+
+    let config_file = dir.join("config.toml");
+    if !config_file.is_file() {
+        return Err(anyhow!("No config.toml in {}", dir.display()));
+    }
+    let config = config::load_config_from_file(&config_file)?;
+
+    let links = inspect_bin_dir(&dir.join("bin"), &config);
+    let active = links
+        .iter()
+        .find(|l| l.name == "php")
+        .and_then(|l| owner(&config, l.target.as_deref()?))
+        .map(|e| e.version.clone());
+
+    let history = history::read_from_file(dir.join("history.jsonl")).unwrap_or_default();
+    let last_switch = history.last().cloned();
+
+    let extensions = active
+        .as_deref()
+        .and_then(|version| config.versions.iter().find(|e| e.version == version))
+        .and_then(|entry| {
+            let cache = probe::load_cache_from_file(dir.join("probes.json")).ok()?;
+            entry.paths.iter().find_map(|path| cache.last_known(path)).map(|p| p.extensions.clone())
+        });
+
+    let mut inspection = Inspection {
+        dir: dir.to_path_buf(),
+        versions: config.versions.iter().map(|e| e.version.clone()).collect(),
+        active,
+        links,
+        last_switch,
+        extensions,
+        findings: Vec::new(),
+    };
+    inspection.findings = findings(&config, &inspection);
+
+    Ok(inspection)
+}
+
+/// The tracked version a binary path belongs to
+fn owner<'a>(config: &'a Config, target: &Path) -> Option<&'a VersionEntry> {
+    config.versions.iter().find(|e| e.paths.iter().any(|p| p == target))
+}
+
+/// Classify every entry of a copied bin directory from file contents and link targets
+pub fn inspect_bin_dir(bin_dir: &Path, config: &Config) -> Vec<LinkReport> {
+    let Ok(entries) = std::fs::read_dir(bin_dir) else { return Vec::new() };
+
+    let active = std::fs::read_link(bin_dir.join("php"))
+        .ok()
+        .and_then(|target| owner(config, &target).map(|e| e.version.clone()));
+
+    let mut links: Vec<LinkReport> = entries
+        .flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let target = std::fs::read_link(entry.path()).ok();
+
+            let state = match &target {
+                Some(target) => match owner(config, target) {
+                    Some(e) if Some(&e.version) == active.as_ref() => LinkState::Active,
+                    Some(e) => LinkState::Foreign(e.version.clone()),
+                    None => LinkState::Untracked,
+                },
+                None if std::fs::read_to_string(entry.path()).is_ok_and(|s| s.contains(SHIM_MARKER)) => LinkState::Shim,
+                None => LinkState::File,
+            };
+
+            LinkReport { name, target, state }
+        })
+        .collect();
+
+    links.sort_by(|a, b| a.name.cmp(&b.name));
+    links
+}
+
+fn findings(config: &Config, inspection: &Inspection) -> Vec<String> {
+    let mut findings = Vec::new();
+
+    if config.versions.is_empty() {
+        findings.push("No versions tracked: 'scan' never found an installation".to_string());
+    }
+
+    match inspection.links.iter().find(|l| l.name == "php") {
+        None => findings.push("bin/php is missing: no version was ever activated".to_string()),
+        Some(LinkReport { state: LinkState::Untracked, target, .. }) => findings.push(format!(
+            "bin/php points to {}, which no tracked version contains (upgraded or removed since the last scan?)",
+            target.as_deref().map(|t| t.display().to_string()).unwrap_or_default()
+        )),
+        Some(LinkReport { state: LinkState::File, .. }) => {
+            findings.push("bin/php is a regular file (copy or hard link strategy): the active version can't be told offline".to_string())
+        }
+        Some(_) => {}
+    }
+
+    for link in &inspection.links {
+        match &link.state {
+            LinkState::Foreign(version) if link.name != "php" => findings.push(format!(
+                "bin/{} still belongs to PHP {} while php is {}",
+                link.name,
+                version,
+                inspection.active.as_deref().unwrap_or("unknown")
+            )),
+            LinkState::Untracked if link.name != "php" => {
+                findings.push(format!("bin/{} points to a binary no tracked version contains", link.name))
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(active) = &inspection.active {
+        if !config.policy.allows(active) {
+            findings.push(format!("Active PHP {} is not allowed by policy (switched with --force?)", active));
+        }
+
+        if let Some(last) = &inspection.last_switch {
+            if config.find_version(&last.to).is_some_and(|e| &e.version != active) {
+                findings.push(format!(
+                    "The last recorded switch was to {} but php links to {}: the bin directory changed outside php-switcher",
+                    last.to, active
+                ));
+            }
+        }
+    }
+
+    if config.tools.scan_for_tools {
+        for tool in config.tools.managed.iter().filter(|t| crate::tools::needs_shim(&t.shebang)) {
+            if !inspection.links.iter().any(|l| l.name == tool.name && l.state == LinkState::Shim) {
+                findings.push(format!("Tool scanning is enabled but {} has no shim in bin/", tool.name));
+            }
+        }
+    }
+
+    findings
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_state(dir: &Path) {
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        std::fs::write(
+            dir.join("config.toml"),
+            r#"
+[[versions]]
+version = "8.3.4"
+paths = ["/usr/bin/php8.3", "/usr/bin/php-cgi8.3"]
+source = "auto"
+
+[[versions]]
+version = "8.2.12"
+paths = ["/usr/bin/php8.2", "/usr/bin/php-cgi8.2"]
+source = "auto"
+
+[policy]
+allowed_versions = ["8.3"]
+"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_inspect_never_follows_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        write_state(dir);
+
+        // The targets only exist on the user's machine
+        std::os::unix::fs::symlink("/usr/bin/php8.2", dir.join("bin/php")).unwrap();
+        std::os::unix::fs::symlink("/usr/bin/php-cgi8.3", dir.join("bin/php-cgi")).unwrap();
+        std::fs::write(dir.join("bin/composer"), "#!/bin/bash\n# Auto-generated shim for composer by php-switcher\n").unwrap();
+
+        let inspection = inspect(dir).unwrap();
+        assert_eq!(inspection.active.as_deref(), Some("8.2.12"));
+        assert_eq!(inspection.links[0].state, LinkState::Shim);
+        assert_eq!(inspection.links[1].state, LinkState::Active);
+        assert_eq!(inspection.links[2].state, LinkState::Foreign("8.3.4".to_string()));
+        assert_eq!(
+            inspection.findings,
+            vec![
+                "bin/php-cgi still belongs to PHP 8.3.4 while php is 8.2.12",
+                "Active PHP 8.2.12 is not allowed by policy (switched with --force?)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inspect_reports_untracked_php() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        write_state(dir);
+        std::os::unix::fs::symlink("/opt/php-8.1/bin/php", dir.join("bin/php")).unwrap();
+
+        let inspection = inspect(dir).unwrap();
+        assert_eq!(inspection.active, None);
+        assert!(inspection.findings[0].starts_with("bin/php points to /opt/php-8.1/bin/php"));
+
+        assert!(inspect(&dir.join("config.toml")).is_err());
+        assert!(inspect(&dir.join("bin")).is_err());
+    }
+}
@@ -5,3 +5,43 @@ pub mod switcher;
 pub mod platform;
 pub mod hints;
 pub mod tools;
+pub mod output;
+pub mod api;
+pub mod events;
+#[cfg(feature = "tray")]
+pub mod tray;
+pub mod history;
+pub mod stats;
+pub mod hooks;
+pub mod link;
+pub mod prune;
+pub mod paths;
+pub mod process;
+pub mod project;
+pub mod project_config;
+pub mod completions;
+pub mod composer;
+pub mod extensions;
+pub mod probe;
+pub mod opcache;
+pub mod runtime;
+pub mod container;
+pub mod toolchain;
+pub mod inspect;
+pub mod timing;
+pub mod progress;
+pub mod confirm;
+pub mod report;
+pub mod env;
+pub mod managers;
+pub mod watch;
+pub mod crash;
+pub mod binary;
+pub mod explain;
+pub mod tree;
+pub mod which;
+pub mod style;
+pub mod repair;
+pub mod migrate;
+#[cfg(feature = "capi")]
+pub mod capi;
@@ -0,0 +1,584 @@
+// Link strategy module
+//
+// A `LinkStrategy` decides how the planned binaries (see
+// `switcher::plan_links`) are materialized. The switch orchestration in
+// switcher.rs is the same for every strategy; only this last step differs.
+// The strategy comes from `settings.link_strategy`, falling back to the
+// platform default.
+
+use crate::config::{Config, LinkStrategyKind};
+use crate::platform::Platform;
+use crate::switcher::LinkedBinary;
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How binaries are exposed in the switcher bin directory
+pub trait LinkStrategy {
+    /// Name as used in `settings.link_strategy`
+    fn name(&self) -> &'static str;
+
+    /// Expose a single binary under `bin_dir`
+    fn link(&self, link: &LinkedBinary, bin_dir: &Path) -> Result<()>;
+
+    /// Check whether a binary is already exposed with the right target
+    fn is_linked(&self, link: &LinkedBinary, bin_dir: &Path) -> bool;
+
+    /// Expose all binaries of an installation
+    fn apply(&self, links: &[LinkedBinary], bin_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(bin_dir)?;
+        for link in links {
+            self.link(link, bin_dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the strategy configured for this machine
+pub fn strategy_for_config(config: &Config) -> Box<dyn LinkStrategy> {
+    let kind = config
+        .settings
+        .link_strategy
+        .unwrap_or_else(|| Platform::detect().default_link_strategy());
+    strategy_for(kind, config.settings.shim_mode())
+}
+
+/// Get the implementation of a strategy; scripts it writes get `script_mode` (less the umask)
+pub fn strategy_for(kind: LinkStrategyKind, script_mode: u32) -> Box<dyn LinkStrategy> {
+    match kind {
+        LinkStrategyKind::Symlink => Box::new(Symlink { script_mode }),
+        LinkStrategyKind::Hardlink => Box::new(Hardlink),
+        LinkStrategyKind::Copy => Box::new(FileCopy),
+        LinkStrategyKind::CmdShim => Box::new(CmdShim),
+        LinkStrategyKind::Shim => Box::new(Shim { mode: script_mode }),
+        LinkStrategyKind::UpdateAlternatives => Box::new(UpdateAlternatives),
+        LinkStrategyKind::BrewLink => Box::new(BrewLink),
+    }
+}
+
+/// Remove whatever currently occupies a path in the bin directory
+fn clear(path: &Path) {
+    if path.symlink_metadata().is_ok() {
+        std::fs::remove_file(path).ok();
+    }
+}
+
+/// Symbolic links into the installation (default on Unix)
+pub struct Symlink {
+    /// Mode of the wrappers written instead of links to snap launchers
+    pub script_mode: u32,
+}
+
+impl Default for Symlink {
+    fn default() -> Self {
+        Self { script_mode: crate::config::DEFAULT_MODE }
+    }
+}
+
+impl LinkStrategy for Symlink {
+    fn name(&self) -> &'static str {
+        "symlink"
+    }
+
+    fn link(&self, link: &LinkedBinary, bin_dir: &Path) -> Result<()> {
+        let path = bin_dir.join(&link.name);
+        clear(&path);
+
+        #[cfg(unix)]
+        {
+            if crate::detector::is_snap_launcher(&link.target) {
+                return write_snap_wrapper(&link.target, &path, self.script_mode);
+            }
+            std::os::unix::fs::symlink(&link.target, &path)?;
+            Ok(())
+        }
+
+        #[cfg(not(unix))]
+        {
+            Err(anyhow!("Symlinks are not supported on this platform"))
+        }
+    }
+
+    fn is_linked(&self, link: &LinkedBinary, bin_dir: &Path) -> bool {
+        link_target(&bin_dir.join(&link.name)).as_ref() == Some(&link.target)
+    }
+}
+
+/// Start of the wrapper scripts standing in for symlinks to snap launchers
+const SNAP_WRAPPER_HEADER: &str = "#!/bin/sh\n# php-switcher: snap launcher\n";
+
+/// Write a script running a snap launcher
+///
+/// A launcher picks the snap by the name it's invoked as, so a symlink named
+/// `php` pointing at `/snap/bin/php82` would run the `php` snap (or nothing).
+#[cfg(unix)]
+fn write_snap_wrapper(target: &Path, path: &Path, mode: u32) -> Result<()> {
+    let mut script = SNAP_WRAPPER_HEADER.as_bytes().to_vec();
+    script.extend_from_slice(b"exec ");
+    script.extend(crate::tools::shell_quote_bytes(target.as_os_str().as_encoded_bytes()));
+    script.extend_from_slice(b" \"$@\"\n");
+    write_script(path, &script, mode)
+}
+
+/// Write an executable script with a configured mode, honoring the umask
+#[cfg(unix)]
+fn write_script(path: &Path, script: &[u8], mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::write(path, script).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(crate::platform::apply_umask(mode)))
+        .map_err(|e| anyhow!("Failed to make {} executable: {}", path.display(), e))
+}
+
+/// What a bin directory entry points at: a symlink's target, or the binary a snap wrapper or shim runs
+pub fn link_target(path: &Path) -> Option<PathBuf> {
+    if let Ok(target) = std::fs::read_link(path) {
+        return Some(target);
+    }
+
+    // Read as bytes: the scripts keep non-UTF-8 targets as they are
+    let contents = std::fs::read(path).ok()?;
+    if let Some(quoted) = contents.strip_prefix(CmdShim::HEADER.as_bytes()) {
+        let quoted = std::str::from_utf8(quoted).ok()?;
+        return quoted.strip_prefix('"')?.strip_suffix("\" %*\r\n").map(PathBuf::from);
+    }
+    let mut body = contents
+        .strip_prefix(SNAP_WRAPPER_HEADER.as_bytes())
+        .or_else(|| contents.strip_prefix(SHIM_HEADER.as_bytes()))?;
+    while body.starts_with(b"export ") {
+        body = &body[body.iter().position(|&b| b == b'\n')? + 1..];
+    }
+    let quoted = body.strip_prefix(b"exec ")?.strip_suffix(b" \"$@\"\n")?;
+    let target = crate::tools::replace_bytes(quoted.strip_prefix(b"'")?.strip_suffix(b"'")?, b"'\\''", b"'");
+    Some(crate::paths::from_bytes(&target))
+}
+
+/// Start of the scripts written by the `shim` strategy
+const SHIM_HEADER: &str = "#!/bin/sh\n# php-switcher: shim\n";
+
+/// Small `sh` scripts exec'ing the binary
+///
+/// For filesystems where symlinks are restricted, and to give each version
+/// its own environment: the version's `env` (`PHPRC`, `PHP_INI_SCAN_DIR`)
+/// is exported before the binary runs.
+pub struct Shim {
+    /// Mode of the scripts (`settings.shim_mode`)
+    pub mode: u32,
+}
+
+impl Default for Shim {
+    fn default() -> Self {
+        Self { mode: crate::config::DEFAULT_MODE }
+    }
+}
+
+impl Shim {
+    /// The script as bytes, so a non-UTF-8 target is kept as it is
+    fn render(link: &LinkedBinary) -> Vec<u8> {
+        let mut script = SHIM_HEADER.as_bytes().to_vec();
+        for (name, value) in &link.env {
+            script.extend_from_slice(format!("export {}={}\n", name, crate::tools::shell_quote(value)).as_bytes());
+        }
+        script.extend_from_slice(b"exec ");
+        script.extend(crate::tools::shell_quote_bytes(link.target.as_os_str().as_encoded_bytes()));
+        script.extend_from_slice(b" \"$@\"\n");
+        script
+    }
+}
+
+/// Check that a name can be exported from `sh`
+pub(crate) fn is_env_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl LinkStrategy for Shim {
+    fn name(&self) -> &'static str {
+        "shim"
+    }
+
+    fn link(&self, link: &LinkedBinary, bin_dir: &Path) -> Result<()> {
+        if let Some((name, _)) = link.env.iter().find(|(name, _)| !is_env_name(name)) {
+            return Err(anyhow!("Invalid environment variable name '{}' in the version's env", name));
+        }
+        let path = bin_dir.join(&link.name);
+        clear(&path);
+
+        #[cfg(unix)]
+        {
+            write_script(&path, &Self::render(link), self.mode)
+        }
+
+        #[cfg(not(unix))]
+        {
+            Err(anyhow!("Shell shims are not supported on this platform; use cmd-shim"))
+        }
+    }
+
+    fn is_linked(&self, link: &LinkedBinary, bin_dir: &Path) -> bool {
+        std::fs::read(bin_dir.join(&link.name)).is_ok_and(|contents| contents == Self::render(link))
+    }
+}
+
+/// Which of `binaries` a hard link or copy in the bin directory is
+///
+/// Those strategies leave no target to read back, so the file is compared
+/// with each binary: by inode, else by size and contents.
+pub fn identify_copy(path: &Path, binaries: &[PathBuf]) -> Option<PathBuf> {
+    let size = std::fs::metadata(path).ok().filter(|metadata| metadata.is_file())?.len();
+    if let Some(binary) = binaries.iter().find(|binary| same_file(path, binary)) {
+        return Some(binary.clone());
+    }
+
+    let candidates: Vec<&PathBuf> = binaries.iter().filter(|binary| std::fs::metadata(binary).is_ok_and(|m| m.len() == size)).collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    let contents = std::fs::read(path).ok()?;
+    candidates.into_iter().find(|binary| std::fs::read(binary).is_ok_and(|other| other == contents)).cloned()
+}
+
+/// Hard links, for filesystems or tools that don't follow symlinks
+///
+/// Only works when the bin directory is on the same filesystem as the installation.
+pub struct Hardlink;
+
+impl LinkStrategy for Hardlink {
+    fn name(&self) -> &'static str {
+        "hardlink"
+    }
+
+    fn link(&self, link: &LinkedBinary, bin_dir: &Path) -> Result<()> {
+        let path = bin_dir.join(&link.name);
+        clear(&path);
+        std::fs::hard_link(&link.target, &path)
+            .map_err(|e| anyhow!("Failed to hard link {}: {}", link.target.display(), e))
+    }
+
+    fn is_linked(&self, link: &LinkedBinary, bin_dir: &Path) -> bool {
+        same_file(&bin_dir.join(&link.name), &link.target)
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+/// Plain copies of the binaries
+///
+/// Last resort for sandboxes that forbid links; copies go stale when the
+/// installation is upgraded, so `is_linked` compares contents.
+pub struct FileCopy;
+
+impl LinkStrategy for FileCopy {
+    fn name(&self) -> &'static str {
+        "copy"
+    }
+
+    fn link(&self, link: &LinkedBinary, bin_dir: &Path) -> Result<()> {
+        let path = bin_dir.join(&link.name);
+        clear(&path);
+        std::fs::copy(&link.target, &path)
+            .map_err(|e| anyhow!("Failed to copy {}: {}", link.target.display(), e))?;
+        Ok(())
+    }
+
+    fn is_linked(&self, link: &LinkedBinary, bin_dir: &Path) -> bool {
+        match (std::fs::read(bin_dir.join(&link.name)), std::fs::read(&link.target)) {
+            (Ok(copy), Ok(original)) => copy == original,
+            _ => false,
+        }
+    }
+}
+
+/// `.cmd` wrapper scripts, for Windows where symlinks need elevation
+pub struct CmdShim;
+
+impl CmdShim {
+    const HEADER: &'static str = "@echo off\r\n";
+
+    fn render(target: &Path) -> String {
+        format!("{}\"{}\" %*\r\n", Self::HEADER, target.display())
+    }
+}
+
+impl LinkStrategy for CmdShim {
+    fn name(&self) -> &'static str {
+        "cmd-shim"
+    }
+
+    fn link(&self, link: &LinkedBinary, bin_dir: &Path) -> Result<()> {
+        let path = bin_dir.join(format!("{}.cmd", link.name));
+        clear(&path);
+        std::fs::write(&path, Self::render(&link.target))
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn is_linked(&self, link: &LinkedBinary, bin_dir: &Path) -> bool {
+        std::fs::read_to_string(bin_dir.join(format!("{}.cmd", link.name)))
+            .is_ok_and(|contents| contents == Self::render(&link.target))
+    }
+}
+
+/// Debian's `update-alternatives`, switching the system-wide links
+///
+/// The alternatives (`php`, `php-cgi`, `phpize`, ...) must already be
+/// registered, which the distribution packages do. Needs root.
+pub struct UpdateAlternatives;
+
+impl LinkStrategy for UpdateAlternatives {
+    fn name(&self) -> &'static str {
+        "update-alternatives"
+    }
+
+    fn link(&self, link: &LinkedBinary, _bin_dir: &Path) -> Result<()> {
+        run(privileged("update-alternatives").arg("--set").arg(&link.name).arg(&link.target))
+    }
+
+    fn is_linked(&self, link: &LinkedBinary, _bin_dir: &Path) -> bool {
+        Command::new("update-alternatives")
+            .args(["--query", &link.name])
+            .output()
+            .is_ok_and(|output| {
+                let value = format!("Value: {}", link.target.display());
+                // Nothing to link without a registered alternative
+                !output.status.success() || String::from_utf8_lossy(&output.stdout).lines().any(|line| line == value)
+            })
+    }
+
+    // Links live outside bin_dir, so don't create it. Binaries without a
+    // registered alternative (php-fpm runs as a service) are left alone
+    fn apply(&self, links: &[LinkedBinary], bin_dir: &Path) -> Result<()> {
+        for link in links.iter().filter(|link| UpdateAlternatives::is_registered(&link.name)) {
+            self.link(link, bin_dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl UpdateAlternatives {
+    fn is_registered(name: &str) -> bool {
+        Command::new("update-alternatives")
+            .args(["--list", name])
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+}
+
+/// Homebrew's `brew link`, relinking the formula that owns the binaries
+pub struct BrewLink;
+
+impl BrewLink {
+    /// Work out the formula (e.g. `php@8.2`) from a path inside the brew prefix
+    pub fn formula(path: &Path) -> Option<String> {
+        let components: Vec<&str> = path.iter().filter_map(|c| c.to_str()).collect();
+        components
+            .windows(2)
+            .filter(|pair| pair[0] == "Cellar" || pair[0] == "opt")
+            .map(|pair| pair[1])
+            .find(|formula| *formula == "php" || formula.starts_with("php@"))
+            .map(str::to_string)
+    }
+
+    fn formula_for(links: &[LinkedBinary]) -> Result<String> {
+        links
+            .first()
+            .and_then(|link| Self::formula(&link.target))
+            .ok_or_else(|| anyhow!("Not a Homebrew PHP installation"))
+    }
+}
+
+impl LinkStrategy for BrewLink {
+    fn name(&self) -> &'static str {
+        "brew-link"
+    }
+
+    fn link(&self, link: &LinkedBinary, bin_dir: &Path) -> Result<()> {
+        self.apply(std::slice::from_ref(link), bin_dir)
+    }
+
+    fn is_linked(&self, link: &LinkedBinary, _bin_dir: &Path) -> bool {
+        // brew links into its own prefix, which holds the formula's bin dir
+        let Some(prefix) = brew_prefix() else { return false };
+        let linked = prefix.join("bin").join(&link.name);
+        match (linked.canonicalize(), link.target.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn apply(&self, links: &[LinkedBinary], _bin_dir: &Path) -> Result<()> {
+        let formula = Self::formula_for(links)?;
+        run(Command::new("brew").args(["link", "--overwrite", "--force", &formula]))
+    }
+}
+
+fn brew_prefix() -> Option<PathBuf> {
+    let output = Command::new("brew").arg("--prefix").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// A command that changes system-wide links, run through sudo unless already root
+fn privileged(program: &str) -> Command {
+    #[cfg(unix)]
+    if unsafe { libc::geteuid() } != 0 {
+        let mut command = Command::new("sudo");
+        command.arg(program);
+        return command;
+    }
+    Command::new(program)
+}
+
+/// Run an external linking command, surfacing its stderr on failure
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .map_err(|e| anyhow!("Failed to run {}: {}", program, e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, LinkedBinary) {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("php8.3");
+        std::fs::write(&target, "#!/bin/sh\necho 8.3\n").unwrap();
+        let link = LinkedBinary { name: "php".to_string(), target, env: Vec::new() };
+        (temp_dir, link)
+    }
+
+    #[test]
+    fn test_local_strategies_link_and_detect() {
+        for kind in [LinkStrategyKind::Symlink, LinkStrategyKind::Hardlink, LinkStrategyKind::Copy, LinkStrategyKind::CmdShim] {
+            let (temp_dir, link) = setup();
+            let bin_dir = temp_dir.path().join("bin");
+            let strategy = strategy_for(kind, crate::config::DEFAULT_MODE);
+
+            assert!(!strategy.is_linked(&link, &bin_dir), "{}", strategy.name());
+            strategy.apply(std::slice::from_ref(&link), &bin_dir).unwrap();
+            assert!(strategy.is_linked(&link, &bin_dir), "{}", strategy.name());
+
+            // Relinking over an existing entry works
+            strategy.apply(std::slice::from_ref(&link), &bin_dir).unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_snap_wrapper_target() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let wrapper = temp_dir.path().join("php");
+        let launcher = PathBuf::from("/snap/bin/php'82");
+
+        write_snap_wrapper(&launcher, &wrapper, 0o750).unwrap();
+        assert_eq!(link_target(&wrapper), Some(launcher));
+        let mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&wrapper).unwrap().permissions()) & 0o777;
+        assert_eq!(mode, crate::platform::apply_umask(0o750));
+
+        // Launchers whose paths aren't UTF-8 are kept as they are
+        let launcher = PathBuf::from(std::ffi::OsStr::from_bytes(b"/snap/bin/php\xe982"));
+        write_snap_wrapper(&launcher, &wrapper, 0o750).unwrap();
+        assert_eq!(link_target(&wrapper), Some(launcher));
+
+        std::fs::write(&wrapper, "#!/bin/sh\nexec /usr/bin/php8.2 \"$@\"\n").unwrap();
+        assert_eq!(link_target(&wrapper), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_shim_exports_env() {
+        let (temp_dir, mut link) = setup();
+        std::fs::write(&link.target, "#!/bin/sh\necho \"$PHPRC|$1\"\n").unwrap();
+        std::fs::set_permissions(&link.target, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        link.env = vec![("PHPRC".to_string(), "/etc/php's".to_string())];
+        let bin_dir = temp_dir.path().join("bin");
+
+        Shim::default().apply(std::slice::from_ref(&link), &bin_dir).unwrap();
+        assert!(Shim::default().is_linked(&link, &bin_dir));
+        assert_eq!(link_target(&bin_dir.join("php")), Some(link.target.clone()));
+        let output = Command::new(bin_dir.join("php")).arg("-v").output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "/etc/php's|-v\n");
+
+        Shim { mode: 0o700 }.apply(std::slice::from_ref(&link), &bin_dir).unwrap();
+        let mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(bin_dir.join("php")).unwrap().permissions()) & 0o777;
+        assert_eq!(mode, crate::platform::apply_umask(0o700));
+
+        // A changed env is a stale shim
+        link.env.clear();
+        assert!(!Shim::default().is_linked(&link, &bin_dir));
+
+        link.env = vec![("NOT-A-NAME".to_string(), String::new())];
+        assert!(Shim::default().apply(std::slice::from_ref(&link), &bin_dir).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_shim_keeps_non_utf8_target() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let (temp_dir, mut link) = setup();
+        link.target = temp_dir.path().join(std::ffi::OsStr::from_bytes(b"php\xff 8.3"));
+        let bin_dir = temp_dir.path().join("bin");
+
+        Shim::default().apply(std::slice::from_ref(&link), &bin_dir).unwrap();
+        assert!(Shim::default().is_linked(&link, &bin_dir));
+        assert_eq!(link_target(&bin_dir.join("php")), Some(link.target.clone()));
+    }
+
+    #[test]
+    fn test_link_target_of_scripts_and_copies() {
+        let (temp_dir, link) = setup();
+        let bin_dir = temp_dir.path().join("bin");
+
+        CmdShim.apply(std::slice::from_ref(&link), &bin_dir).unwrap();
+        assert_eq!(link_target(&bin_dir.join("php.cmd")), Some(link.target.clone()));
+
+        FileCopy.apply(std::slice::from_ref(&link), &bin_dir).unwrap();
+        assert_eq!(link_target(&bin_dir.join("php")), None);
+        let other = temp_dir.path().join("php8.2");
+        std::fs::write(&other, "#!/bin/sh\necho 8.2\n").unwrap();
+        assert_eq!(identify_copy(&bin_dir.join("php"), &[other.clone(), link.target.clone()]), Some(link.target.clone()));
+        assert_eq!(identify_copy(&bin_dir.join("php"), &[other]), None);
+    }
+
+    #[test]
+    fn test_brew_formula() {
+        assert_eq!(
+            BrewLink::formula(Path::new("/opt/homebrew/opt/php@8.2/bin/php")),
+            Some("php@8.2".to_string())
+        );
+        assert_eq!(
+            BrewLink::formula(Path::new("/usr/local/Cellar/php/8.3.4/bin/php")),
+            Some("php".to_string())
+        );
+        assert_eq!(BrewLink::formula(Path::new("/usr/bin/php8.3")), None);
+    }
+}
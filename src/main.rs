@@ -1,7 +1,8 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use colored::Colorize;
-use php_switcher::{config, detector, switcher};
+use php_switcher::events::{self, Event};
+use php_switcher::{api, completions, composer, config, confirm, container, detector, env, explain, extensions, hints, history, inspect, migrate, output, platform, probe, process, progress, project, prune, report, runtime, stats, switcher, timing, style, toolchain, tools, tree};
 
 #[derive(Parser)]
 #[command(name = "php-switcher")]
@@ -14,27 +15,381 @@ struct Cli {
     /// Version to switch to (shorthand for 'use')
     #[arg(value_name = "VERSION")]
     php_version: Option<String>,
+
+    /// Print machine-readable JSON instead of text (list, scan, info)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Use this config file instead of ~/.php-switcher/config.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<std::path::PathBuf>,
+
+    /// Never write to disk (for read-only homes in containers and CI)
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    /// Answer yes to every confirmation (for scripts and CI)
+    #[arg(long, short = 'y', global = true, conflicts_with = "no")]
+    yes: bool,
+
+    /// Answer no to every confirmation
+    #[arg(long, global = true)]
+    no: bool,
+
+    /// Print a timing breakdown of the run (config load, scans, PHP calls) to stderr
+    #[arg(long, global = true)]
+    profile_startup: bool,
+
+    /// Use words instead of symbols and color, for screen readers and dumb terminals
+    #[arg(long, global = true)]
+    accessible: bool,
+
+    /// Run PHP binaries again instead of trusting cached results (scan, list, info)
+    #[arg(long, global = true)]
+    no_cache: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// List all available PHP versions
-    List,
+    List {
+        /// Show every version on its own line instead of grouping by minor version
+        #[arg(long)]
+        flat: bool,
+
+        /// Show versions grouped by what installed them and their install prefix
+        #[arg(long, conflicts_with = "flat")]
+        tree: bool,
+    },
 
     /// Switch to a specific PHP version
-    Use { version: String },
+    Use {
+        /// Version to switch to, or `-` to read it from stdin (default: the nearest .php-version, else composer.json's require.php)
+        version: Option<String>,
+
+        /// Read the version from a file (e.g. .php-version, or the php line of .tool-versions)
+        #[arg(long, value_name = "PATH", conflicts_with = "version")]
+        from_file: Option<std::path::PathBuf>,
+
+        /// Switch even if the version is not allowed by policy
+        #[arg(long)]
+        force: bool,
+
+        /// Exit immediately if the version is already fully active
+        #[arg(long)]
+        if_needed: bool,
+
+        /// Check opcache and start PHP once with the configured preload script
+        #[arg(long)]
+        check_opcache: bool,
+
+        /// What to do when the pinned version isn't installed (overrides settings.missing_version)
+        #[arg(long, value_enum, value_name = "STRATEGY")]
+        fallback: Option<config::MissingVersionStrategy>,
+
+        /// How loosely a pinned version may match, tried in order (overrides settings.pin_match)
+        #[arg(long, value_enum, value_delimiter = ',', value_name = "MATCH")]
+        pin_match: Vec<config::PinMatch>,
+
+        /// Switch the system-wide php through update-alternatives (Debian/Ubuntu, runs sudo)
+        #[arg(long)]
+        system: bool,
+    },
+
+    /// Switch to a named toolchain (PHP version, pinned tools and ini profile) from the config
+    UseToolchain {
+        /// Toolchain to switch to (lists the configured toolchains if omitted)
+        toolchain: Option<String>,
+
+        /// Switch even if the version is not allowed by policy
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Switch back to the version selected before the current one
+    Prev {
+        /// Switch even if the version is not allowed by policy
+        #[arg(long)]
+        force: bool,
+    },
 
     /// Scan for PHP installations
-    Scan,
+    Scan {
+        /// Show which versions would be added, removed or updated without saving
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also scan this directory (or prefix) and remember it in `settings.extra_scan_dirs`; repeatable
+        #[arg(long = "path", value_name = "DIR")]
+        paths: Vec<std::path::PathBuf>,
+
+        /// Print every candidate binary and why it was kept or left out
+        #[arg(long)]
+        verbose: bool,
+
+        /// Read versions out of the binaries instead of running them (like `settings.no_exec_scan`)
+        #[arg(long)]
+        no_exec: bool,
+    },
+
+    /// Show information about the active PHP installation (or a given version)
+    Info {
+        version: Option<String>,
+
+        /// Describe php-switcher itself (config files, tracked versions)
+        #[arg(long = "self", conflicts_with = "version")]
+        self_info: bool,
+    },
+
+    /// Label a version (e.g. `label 8.1 legacy-billing owner=billing-team`) so it can be used by name
+    Label {
+        version: String,
+
+        /// Labels to add, or `key=value` metadata to set
+        items: Vec<String>,
+
+        /// Remove the given labels and metadata keys instead
+        #[arg(long)]
+        remove: bool,
+    },
 
-    /// Show information about PHP installations
-    Info { version: Option<String> },
+    /// Import build names (as labels) and the selected version (as the default) from phpbrew or phpenv
+    Import {
+        #[arg(value_enum)]
+        from: migrate::Source,
+    },
+
+    /// Set the version 'use' falls back to outside pinned projects
+    Default {
+        /// Version to make the default (shows the current default if omitted)
+        version: Option<String>,
+
+        /// Clear the default instead
+        #[arg(long, conflicts_with = "version")]
+        unset: bool,
+    },
+
+    /// Pin a directory to a version in the config, for projects without a .php-version
+    Pin {
+        /// Directory to pin (lists all config pins if omitted)
+        dir: Option<std::path::PathBuf>,
+
+        /// Version to pin it to (shows the directory's pin if omitted)
+        version: Option<String>,
+
+        /// Remove the directory's pin instead
+        #[arg(long, requires = "dir", conflicts_with = "version")]
+        unset: bool,
+    },
+
+    /// Pin the current directory to a version by writing its .php-version
+    Local {
+        /// Version to pin (e.g. 8.2 to follow its patch releases)
+        #[arg(required_unless_present = "unset")]
+        version: Option<String>,
+
+        /// Remove the current directory's .php-version instead
+        #[arg(long, conflicts_with = "version")]
+        unset: bool,
+    },
+
+    /// Analyze a copy of someone's ~/.php-switcher directory offline, without running anything in it
+    Inspect {
+        /// Copied state directory
+        dir: std::path::PathBuf,
+    },
+
+    /// Serve JSON-RPC requests on stdin/stdout (for editors and GUIs)
+    Api,
+
+    /// Answer one JSON request (e.g. '{"op":"resolve","pattern":"^8.1"}') with a JSON response
+    Query {
+        /// Request object with an "op" (list, current, resolve, switch), or `-` to read it from stdin
+        request: String,
+    },
+
+    /// Print state change events as JSON lines until interrupted
+    Events,
+
+    /// Re-switch whenever a project's .php-version or composer.json asks for another version
+    Watch {
+        /// Project directory (default: the current directory)
+        dir: Option<std::path::PathBuf>,
+    },
+
+    /// Render a menu-bar plugin (xbar/SwiftBar/Argos) for point-and-click switching
+    #[cfg(feature = "tray")]
+    Tray {
+        /// Keep running and re-render on every state change
+        #[arg(long)]
+        stream: bool,
+    },
 
     /// Manage PHP tools (composer, phpunit, etc.)
     Tools {
         #[command(subcommand)]
         tools_command: ToolsCommands,
     },
+
+    /// Remove superseded patch releases of php-switcher managed installs
+    Prune {
+        /// Patch releases to keep per minor version (overrides settings.keep_patches)
+        #[arg(long)]
+        keep: Option<usize>,
+
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Inspect per-project .php-version pins
+    Project {
+        #[command(subcommand)]
+        project_command: ProjectCommands,
+    },
+
+    /// Show or update the installation hints for missing versions
+    Hints {
+        #[command(subcommand)]
+        hints_command: HintsCommands,
+    },
+
+    /// Print shell commands activating a version, for `eval "$(php-switcher env 8.2)"`
+    Env {
+        /// Version (or label); defaults to the directory's pin, then the default, then the active version
+        version: Option<String>,
+
+        /// Shell syntax to print (default: from $SHELL)
+        #[arg(long, value_enum)]
+        shell: Option<platform::ShellKind>,
+    },
+
+    /// Start a subshell with a PHP version active, leaving the global version alone
+    Shell {
+        /// Version (or label) to activate in the subshell
+        version: String,
+    },
+
+    /// Run a command under a specific PHP version without switching
+    Exec {
+        /// Version (or label) to run under
+        version: String,
+
+        /// Command to run (after `--`)
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Run a command once per tracked PHP version
+    Each {
+        /// Run in every pinned project of the workspace with its pinned version instead
+        #[arg(long)]
+        projects: bool,
+
+        /// Write a summary of the runs to a file (JUnit XML for .xml, Markdown for .md)
+        #[arg(long, value_name = "FILE")]
+        report: Option<std::path::PathBuf>,
+
+        /// Report format, when the file extension doesn't tell
+        #[arg(long, value_enum, requires = "report")]
+        report_format: Option<report::ReportFormat>,
+
+        /// Command to run
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Print a shell completion script (e.g. `source <(php-switcher completions bash)`)
+    Completions { shell: completions::Shell },
+
+    /// Print completion candidates for the words typed so far (used by the completion scripts)
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        shell: completions::Shell,
+
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        words: Vec<String>,
+    },
+
+    /// Show which version `use` would pick for a directory, and why
+    #[command(name = "resolve")]
+    Explain {
+        /// Directory to resolve for (default: the current directory)
+        dir: Option<std::path::PathBuf>,
+
+        /// Version as if given to `use`, which beats every other source
+        #[arg(long, value_name = "VERSION")]
+        arg: Option<String>,
+    },
+
+    /// Print the version pinned for a directory (used by shell hooks)
+    #[command(name = "__resolve", hide = true)]
+    Resolve { cwd: std::path::PathBuf },
+
+    /// Show recent switches, newest first, or undo one
+    History {
+        /// Number of switches to show
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+
+        /// Switch back to the version active before switch N (as numbered in the list)
+        #[arg(long, value_name = "N")]
+        undo: Option<usize>,
+
+        /// With --undo, switch even if the version is not allowed by policy
+        #[arg(long, requires = "undo")]
+        force: bool,
+    },
+
+    /// Show local usage statistics (opt-in, never uploaded)
+    Stats {
+        #[command(subcommand)]
+        stats_command: Option<StatsCommands>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatsCommands {
+    /// Start collecting local usage statistics
+    Enable,
+
+    /// Stop collecting usage statistics
+    Disable,
+
+    /// Delete all collected statistics
+    Reset,
+}
+
+#[derive(Subcommand)]
+enum HintsCommands {
+    /// Show how to install a PHP version on this system
+    Show { version: String },
+
+    /// Download the latest hints table (new PHP releases, repositories)
+    Update {
+        /// Where to download the table from
+        #[arg(long, default_value = hints::DEFAULT_UPDATE_URL)]
+        url: String,
+    },
+
+    /// Go back to the hints table shipped with php-switcher
+    Reset,
+}
+
+#[derive(Subcommand)]
+enum ProjectCommands {
+    /// Show every .php-version pin in the workspace
+    ListPins,
+
+    /// Add php:use/php:check scripts and a pre-install-cmd guard to composer.json
+    ComposerScripts {
+        /// Only show which scripts would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Check that the active PHP satisfies composer.json's require.php
+    Check,
 }
 
 #[derive(Subcommand)]
@@ -50,42 +405,313 @@ enum ToolsCommands {
 
     /// Disable automatic tool scanning
     Disable,
+
+    /// Print the shim template currently in effect
+    Template,
+
+    /// Shim a project's vendor/bin tools into its .php-switcher/bin, running on its pinned PHP
+    Project {
+        /// Project directory (default: the nearest composer.json from the current directory)
+        dir: Option<std::path::PathBuf>,
+    },
 }
 
 fn main() -> Result<()> {
+    let started = std::time::Instant::now();
+    php_switcher::crash::install();
+    // Read the umask while this is the only thread; it's cached from then on
+    #[cfg(unix)]
+    platform::umask();
     let cli = Cli::parse();
+    if cli.profile_startup {
+        timing::enable(started);
+        timing::record("parse arguments", started.elapsed());
+    }
+
+    let result = run(cli);
+    print_startup_profile();
+    result
+}
+
+/// Repair links and shims whose targets a package upgrade removed, reporting on stderr
+///
+/// Asks first on a terminal; skipped when confirmations are answered with no (`--no`, `confirm = "no"`).
+fn repair_broken_links() {
+    let Ok(mut config) = config::load_config() else { return };
+    let Ok(bin_dir) = switcher::get_bin_dir() else { return };
+    let damage = php_switcher::repair::inspect(&config, &bin_dir);
+    if damage.is_empty() {
+        return;
+    }
+
+    let names: Vec<&str> = damage.broken_links.iter().chain(&damage.missing_tools).map(String::as_str).collect();
+    eprintln!(
+        "{}",
+        format!("{} {} point(s) at files that are gone (package upgrade?)", style::warn(), names.join(", ")).yellow()
+    );
+    // Unattended runs relink, as the question's default
+    if !confirm::confirm(&config, "Relink them now?", true).unwrap_or(false) {
+        eprintln!("  Run 'php-switcher use <version>' to relink");
+        return;
+    }
+
+    match php_switcher::repair::repair(&mut config, &bin_dir, &damage) {
+        Ok(outcome) => eprintln!("{} Repaired: PHP {} is linked again", style::ok().green(), outcome.version.bold()),
+        Err(e) => eprintln!("{} Couldn't repair: {}", style::fail().red(), e),
+    }
+}
+
+/// Print the `--profile-startup` breakdown, if it was requested
+fn print_startup_profile() {
+    if let Some(report) = timing::finish() {
+        eprintln!("{}\n{}", "Startup profile:".bold(), report);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+
+    // Passed on through the environment so hooks and shims see the same locations
+    if let Some(path) = &cli.config {
+        std::env::set_var("PHP_SWITCHER_CONFIG", path);
+    }
+    // The internal endpoints run on every keystroke or prompt and must never write
+    if cli.read_only || matches!(cli.command, Some(Commands::Complete { .. } | Commands::Resolve { .. })) {
+        std::env::set_var("PHP_SWITCHER_READ_ONLY", "1");
+    }
+    if cli.yes || cli.no {
+        std::env::set_var(confirm::ASSUME_ENV, if cli.yes { "yes" } else { "no" });
+    }
+    if cli.no_cache {
+        std::env::set_var(probe::NO_CACHE_ENV, "1");
+    }
+    // `settings.accessible` and `settings.probe_timeout` apply once a command loads the config
+    if cli.accessible || style::is_accessible() {
+        style::enable_accessible();
+    }
+
+    // Links broken by a package upgrade are fixed before commands that run PHP
+    // through them or write shims next to them. Switching commands relink anyway,
+    // and read-only ones (`list`, `info`, `inspect`, `history`, ...) must never
+    // rewrite the bin directory
+    let uses_links = matches!(
+        cli.command,
+        Some(
+            Commands::Exec { .. }
+                | Commands::Each { .. }
+                | Commands::Shell { .. }
+                | Commands::Watch { .. }
+                | Commands::Tools { tools_command: ToolsCommands::Scan | ToolsCommands::Enable | ToolsCommands::Project { .. } }
+        )
+    );
+    if uses_links && !cli.json && !config::is_read_only() {
+        repair_broken_links();
+    }
 
     // Handle shorthand: php-switcher 8.2 -> php-switcher use 8.2
     if let Some(version) = cli.php_version {
-        return switcher::switch_version(&version);
+        return switcher::switch_version(&version, &switcher::SwitchOptions::default());
     }
 
     match cli.command {
-        Some(Commands::List) | None => list_versions()?,
-        Some(Commands::Use { version }) => switcher::switch_version(&version)?,
-        Some(Commands::Scan) => scan_installations()?,
-        Some(Commands::Info { version }) => show_info(version.as_deref())?,
+        Some(Commands::List { flat, tree }) => list_versions(cli.json, flat, tree)?,
+        None => list_versions(cli.json, false, false)?,
+        Some(Commands::Use { version, from_file, force, if_needed, check_opcache, fallback, pin_match, system }) => {
+            let options = switcher::SwitchOptions {
+                force,
+                if_needed,
+                check_opcache,
+                strategy: system.then_some(config::LinkStrategyKind::UpdateAlternatives),
+                ..Default::default()
+            };
+            // Without an explicit version the switch is for the project in the current directory
+            let project_triggered = version.is_none() && from_file.is_none();
+            let pinned = project_triggered || from_file.is_some();
+            let version = resolve_use_argument(version.as_deref(), from_file.as_deref())?;
+            let version = if pinned { fall_back_if_missing(version, fallback, &pin_match)? } else { version };
+            switcher::switch_version(&version, &options)?;
+            if project_triggered {
+                check_project_extensions(&version)?;
+                refresh_project_shims(&version)?;
+            }
+            let config = config::load_config()?;
+            if let Some(entry) = config.find_version(&version) {
+                warn_runtime_divergence(&entry.version, &config)?;
+            }
+        }
+        Some(Commands::UseToolchain { toolchain: Some(name), force }) => use_toolchain(&name, force)?,
+        Some(Commands::UseToolchain { toolchain: None, .. }) => list_toolchains()?,
+        Some(Commands::Prev { force }) => switch_to_previous(force)?,
+        Some(Commands::Scan { dry_run: true, paths, no_exec, .. }) => scan_preview(&paths, no_exec, cli.json)?,
+        Some(Commands::Scan { dry_run: false, paths, verbose, no_exec }) => {
+            scan_installations(&paths, verbose, no_exec, cli.json)?
+        }
+        Some(Commands::Info { self_info: true, .. }) => show_self_info(cli.json)?,
+        Some(Commands::Info { version, .. }) => show_info(version.as_deref(), cli.json)?,
+        Some(Commands::Label { version, items, remove }) => label_version(&version, &items, remove)?,
+        Some(Commands::Import { from }) => import_from(from)?,
+        Some(Commands::Default { version, unset }) => set_default(version.as_deref(), unset)?,
+        Some(Commands::Pin { dir, version, unset }) => pin_directory(dir.as_deref(), version.as_deref(), unset)?,
+        Some(Commands::Local { version, unset }) => pin_local(version.as_deref(), unset)?,
+        Some(Commands::Inspect { dir }) => inspect_state(&dir, cli.json)?,
+        Some(Commands::Api) => api::run()?,
+        Some(Commands::Query { request }) => run_query(&request)?,
+        Some(Commands::Events) => watch_events()?,
+        Some(Commands::Watch { dir }) => watch_project(dir.as_deref())?,
+        #[cfg(feature = "tray")]
+        Some(Commands::Tray { stream }) => php_switcher::tray::run(stream)?,
         Some(Commands::Tools { tools_command }) => match tools_command {
             ToolsCommands::List => tools_list()?,
             ToolsCommands::Scan => tools_scan()?,
             ToolsCommands::Enable => tools_enable()?,
             ToolsCommands::Disable => tools_disable()?,
+            ToolsCommands::Template => tools_template()?,
+            ToolsCommands::Project { dir } => tools_project(dir.as_deref())?,
+        },
+        Some(Commands::Prune { keep, dry_run }) => prune(keep, dry_run)?,
+        Some(Commands::Project { project_command }) => match project_command {
+            ProjectCommands::ListPins => list_pins(cli.json)?,
+            ProjectCommands::ComposerScripts { dry_run } => composer_scripts(dry_run)?,
+            ProjectCommands::Check => project_check()?,
+        },
+        Some(Commands::Hints { hints_command }) => match hints_command {
+            HintsCommands::Show { version } => hints::show_installation_hints(&version, platform::Platform::detect()),
+            HintsCommands::Update { url } => update_hints(&url)?,
+            HintsCommands::Reset => reset_hints()?,
+        },
+        Some(Commands::Env { version, shell }) => print_env(version.as_deref(), shell)?,
+        Some(Commands::Shell { version }) => run_shell(&version)?,
+        Some(Commands::Exec { version, command }) => run_exec(&version, &command)?,
+        Some(Commands::Each { projects, report, report_format, command }) => {
+            run_each(projects, report.as_deref(), report_format, &command)?
+        }
+        Some(Commands::Completions { shell }) => print!("{}", completions::script(shell)),
+        Some(Commands::Complete { shell, words }) => {
+            // Completion must never fail loudly, so fall back to an empty config
+            let config = config::load_config().unwrap_or_default();
+            for candidate in completions::complete(&Cli::command(), &config, &words) {
+                println!("{}", candidate.render(shell));
+            }
+        }
+        Some(Commands::Explain { dir, arg }) => explain_resolution(dir.as_deref(), arg.as_deref(), cli.json)?,
+        Some(Commands::Resolve { cwd }) => {
+            let config = config::load_config().unwrap_or_default();
+            // Relative paths have no ancestors to search, and config pins are canonical
+            let cwd = cwd.canonicalize().unwrap_or(cwd);
+            match project::resolve(&config, &cwd) {
+                Some(resolution) => println!("{}", resolution.to_line()),
+                None => {
+                    print_startup_profile();
+                    std::process::exit(1)
+                }
+            }
+        }
+        Some(Commands::History { undo: Some(n), force, .. }) => undo_switch(n, force)?,
+        Some(Commands::History { limit, undo: None, .. }) => show_history(limit, cli.json)?,
+        Some(Commands::Stats { stats_command }) => match stats_command {
+            None => stats_show()?,
+            Some(StatsCommands::Enable) => stats_toggle(true)?,
+            Some(StatsCommands::Disable) => stats_toggle(false)?,
+            Some(StatsCommands::Reset) => {
+                stats::reset()?;
+                println!("{}", format!("{} Statistics reset", style::ok()).green());
+            }
         },
     }
 
     Ok(())
 }
 
-fn list_versions() -> Result<()> {
+fn explain_resolution(dir: Option<&std::path::Path>, argument: Option<&str>, json: bool) -> Result<()> {
+    let dir = match dir {
+        Some(dir) => dir.canonicalize().map_err(|e| anyhow::anyhow!("Failed to open {}: {}", dir.display(), e))?,
+        None => std::env::current_dir()?,
+    };
+    let config = config::load_config()?;
+    let explanation = explain::explain(&config, &dir, argument);
+
+    if json {
+        return output::print_json("resolution", &explanation);
+    }
+
+    println!("Resolving PHP for {}\n", dir.display().to_string().bold());
+    for (i, step) in explanation.steps.iter().enumerate() {
+        let marker = if Some(i) == explanation.winner { style::symbol("→", "wins:").green().bold() } else { " ".normal() };
+        let label = format!("{}. {:<16}", i + 1, step.source.name());
+
+        let Some(location) = &step.location else {
+            println!("{} {} {}", marker, label.dimmed(), "not set".dimmed());
+            continue;
+        };
+        let what = match (&step.requested, &step.resolved) {
+            (Some(requested), Some(resolved)) => format!("{} {} PHP {}", requested, style::arrow(), resolved),
+            (Some(requested), None) => format!("{} ({})", requested, "not installed".red()),
+            (None, Some(resolved)) => format!("PHP {}", resolved),
+            (None, None) => "unmanaged php".to_string(),
+        };
+        let line = format!("{} {}  {}", label, what, location.dimmed());
+        match explanation.winner {
+            Some(winner) if i > winner => println!("{} {} {}", marker, line.dimmed(), "(overridden)".dimmed()),
+            _ => println!("{} {}", marker, line),
+        }
+    }
+
+    let Some(step) = explanation.winner.map(|i| &explanation.steps[i]) else {
+        println!("\n{}", "Nothing applies: no pin, composer.json, default version or php on PATH".yellow());
+        return Ok(());
+    };
+    let version = match (&step.resolved, &step.requested) {
+        (Some(resolved), _) => format!("PHP {}", resolved),
+        (None, Some(requested)) => format!("PHP {} (not installed)", requested),
+        (None, None) => "the php on PATH".to_string(),
+    };
+    println!(
+        "\nResult: {} from the {} ({})",
+        version.bold(),
+        step.source.name(),
+        step.location.as_deref().unwrap_or("-")
+    );
+    Ok(())
+}
+
+fn list_versions(json: bool, flat: bool, tree: bool) -> Result<()> {
     // Try to detect current PHP
     let current = detector::detect_current_php().ok();
 
+    if json {
+        let mut config = config::load_config()?;
+        if config.versions.is_empty() {
+            rescan_for_listing(&mut config);
+        }
+
+        let current_version = current.as_ref().map(|c| c.version.to_string());
+        let versions = config
+            .versions
+            .iter()
+            .map(|entry| {
+                output::VersionInfo::from_entry(entry, current_version.as_deref() == Some(entry.version.as_str()))
+            })
+            .collect();
+
+        return output::print_json(
+            "version_list",
+            &output::VersionList {
+                current: current_version,
+                default: config.default_entry().map(|entry| entry.version.clone()),
+                versions,
+                runtimes: config.runtimes.clone(),
+                containers: config.containers.clone(),
+            },
+        );
+    }
+
     if let Some(ref current_php) = current {
         println!(
             "{} {}\n",
             "Current PHP version:".bold(),
             current_php.version.to_string().green()
         );
+
+        report_drift(current_php);
     }
 
     // Load config to get cached installations
@@ -94,9 +720,7 @@ fn list_versions() -> Result<()> {
     // If config is empty, scan for installations
     if config.versions.is_empty() {
         println!("{}", "Scanning for PHP installations...".yellow());
-        let installations = detector::find_all_php_installations()?;
-        config.update_from_installations(&installations);
-        config::save_config(&config)?;
+        rescan_for_listing(&mut config);
     }
 
     if config.versions.is_empty() {
@@ -109,162 +733,1241 @@ fn list_versions() -> Result<()> {
 
     println!("{}", "Available PHP versions:".bold());
 
-    for entry in &config.versions {
-        let is_current = current
-            .as_ref()
-            .map(|c| c.version.to_string() == entry.version)
-            .unwrap_or(false);
+    let current_version = current.as_ref().map(|c| c.version.to_string());
+    let is_current = |entry: &config::VersionEntry| current_version.as_deref() == Some(entry.version.as_str());
+
+    if tree {
+        for line in tree::render(&tree::build(&config, current_version.as_deref()), style::is_accessible()) {
+            println!("  {}", line);
+        }
+    } else if flat {
+        for entry in &config.versions {
+            print_version_entry(&config, entry, is_current(entry), "  ");
+        }
+    } else {
+        for group in config.grouped_by_minor() {
+            if group.entries.len() == 1 {
+                print_version_entry(&config, group.entries[0], is_current(group.entries[0]), "  ");
+                continue;
+            }
+
+            println!("  {} {}", group.minor.bold(), format!("({} versions)", group.entries.len()).dimmed());
+
+            // Expand the newest patch release and the active one, collapse the rest
+            let mut collapsed = Vec::new();
+            for (i, entry) in group.entries.iter().enumerate() {
+                if i == 0 || is_current(entry) {
+                    print_version_entry(&config, entry, is_current(entry), "    ");
+                } else {
+                    collapsed.push(entry.version.as_str());
+                }
+            }
+
+            if !collapsed.is_empty() {
+                println!("      {} {}", "also:".dimmed(), collapsed.join(", ").dimmed());
+            }
+        }
+    }
+
+    if !config.runtimes.is_empty() {
+        println!("\n{}", "Runtimes:".bold());
+        for runtime in &config.runtimes {
+            print_runtime(runtime, "  ");
+        }
+    }
+
+    if !config.containers.is_empty() {
+        println!("\n{} {}", "Containers:".bold(), "(Docker images, not native; can't be switched to)".dimmed());
+        for image in &config.containers {
+            print_container(image, "  ");
+        }
+    }
+
+    if !config.broken.is_empty() {
+        println!("\n{} {}", "Broken:".bold(), "(found but can't run; fix them and run 'php-switcher scan')".dimmed());
+        for broken in &config.broken {
+            print_broken(broken, "  ");
+        }
+    }
+
+    println!("\n{}", "Use 'php-switcher use <version>' to switch versions".dimmed());
+
+    Ok(())
+}
+
+/// Replace a pinned version that isn't installed, per the pin match chain and fallback strategy
+///
+/// The looser pin matches are tried in order first; the fallback strategy
+/// only applies when none of them finds a version. Any replacement is
+/// announced on stderr, so CI logs show it; `--fallback fail` keeps a
+/// pipeline strict whatever the config says.
+fn fall_back_if_missing(
+    version: String,
+    strategy: Option<config::MissingVersionStrategy>,
+    pin_match: &[config::PinMatch],
+) -> Result<String> {
+    use config::{MissingVersionStrategy, Nearest, PinMatch};
+
+    let mut config = config::load_config()?;
+    let strict = strategy == Some(MissingVersionStrategy::Fail);
+    let strategy = strategy.or(config.settings.missing_version).unwrap_or_default();
+    let pin_match = if pin_match.is_empty() { config.settings.pin_match().to_vec() } else { pin_match.to_vec() };
+    let loose = pin_match.iter().any(|m| *m != PinMatch::Exact);
+    if (strategy == MissingVersionStrategy::Fail && !loose) || strict || config.find_version(&version).is_some() {
+        return Ok(version);
+    }
+
+    // The version may have been installed since the last scan
+    switcher::rescan(&mut config)?;
+    if config.find_version(&version).is_some() {
+        return Ok(version);
+    }
+
+    for rule in &pin_match {
+        let matched = match rule {
+            PinMatch::Exact => None,
+            PinMatch::SameMinor => config.same_minor(&version),
+            PinMatch::SatisfyConstraint => {
+                let constraint = std::env::current_dir()
+                    .ok()
+                    .and_then(|cwd| composer::find_composer_json(&cwd))
+                    .and_then(|path| composer::load(&path).ok())
+                    .and_then(|manifest| composer::php_constraint(&manifest).map(str::to_string))
+                    .unwrap_or_else(|| format!("^{}", version));
+                composer::newest_satisfying(&config, &constraint)?
+            }
+        };
+        if let Some(entry) = matched {
+            eprintln!(
+                "{}",
+                format!("{} PHP {} is pinned but not installed; using PHP {} ({} match)", style::warn(), version, entry.version, rule.name())
+                    .yellow()
+                    .bold()
+            );
+            eprintln!("  To install PHP {} itself, see: php-switcher hints show {}", version, version);
+            return Ok(entry.version.clone());
+        }
+    }
+
+    let fallback = match strategy {
+        MissingVersionStrategy::Fail => None,
+        MissingVersionStrategy::NearestLower => config.nearest_installed(&version, Nearest::Lower),
+        MissingVersionStrategy::NearestHigher => config.nearest_installed(&version, Nearest::Higher),
+        MissingVersionStrategy::Ask => {
+            let nearest = config
+                .nearest_installed(&version, Nearest::Higher)
+                .or_else(|| config.nearest_installed(&version, Nearest::Lower));
+            match nearest {
+                Some(entry) => {
+                    let question = format!("PHP {} is pinned but not installed. Use PHP {} instead?", version, entry.version);
+                    confirm::confirm(&config, &question, false)?.then_some(entry)
+                }
+                None => None,
+            }
+        }
+    };
+
+    // Without a stand-in, switching fails with the usual installation hints
+    let Some(entry) = fallback else { return Ok(version) };
+    eprintln!(
+        "{}",
+        format!(
+            "{} PHP {} is pinned but not installed; using PHP {} instead ({} fallback)",
+            style::warn(),
+            version,
+            entry.version,
+            strategy.name()
+        )
+        .yellow()
+        .bold()
+    );
+    Ok(entry.version.clone())
+}
+
+/// Work out the version for `use` from its argument, stdin (`-`) or `--from-file`
+fn resolve_use_argument(version: Option<&str>, from_file: Option<&std::path::Path>) -> Result<String> {
+    use std::io::Read;
+
+    if let Some(path) = from_file {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let file = if path.file_name().is_some_and(|name| name == project::TOOL_VERSIONS_FILE) {
+            project::PinFile::ToolVersions
+        } else {
+            project::PinFile::PhpVersion
+        };
+        return file.parse(&contents).ok_or_else(|| anyhow::anyhow!("No version found in {}", path.display()));
+    }
+
+    match version {
+        Some("-") => {
+            let mut contents = String::new();
+            std::io::stdin()
+                .read_to_string(&mut contents)
+                .map_err(|e| anyhow::anyhow!("Failed to read stdin: {}", e))?;
+            switcher::parse_version_spec(&contents).ok_or_else(|| anyhow::anyhow!("No version found on stdin"))
+        }
+        Some(version) => Ok(version.to_string()),
+        None => {
+            let cwd = std::env::current_dir()?;
+            let config = config::load_config()?;
+            if let Some(pin) = project::nearest_pin(&config, &cwd) {
+                return Ok(pin.version);
+            }
+
+            // Unpinned composer projects get the newest version their require.php allows
+            if let Some(found) = switcher::resolve_composer_constraint(&config, &cwd)? {
+                println!(
+                    "Using PHP {}, the newest installed version satisfying require.php {} ({})",
+                    found.version.bold(),
+                    found.constraint.bold(),
+                    found.manifest.display()
+                );
+                return Ok(found.version);
+            }
+
+            // Outside of projects, fall back to the configured default
+            if let Some(default) = config.settings.default_version {
+                println!("No {} found, using the default version {}", project::PIN_FILE, default.bold());
+                return Ok(default);
+            }
+
+            Err(anyhow::anyhow!(
+                "No version given, no {} found and no default set (see 'php-switcher default')",
+                project::PIN_FILE
+            ))
+        }
+    }
+}
+
+fn use_toolchain(name: &str, force: bool) -> Result<()> {
+    let mut config = config::load_config()?;
+    let options = switcher::SwitchOptions { force, ..Default::default() };
+
+    let outcome = toolchain::apply(&mut config, name, &switcher::get_bin_dir()?, &options)?;
+
+    println!("{} Switched to toolchain {}", style::ok().green(), name.bold());
+    println!("  PHP: {}", outcome.switch.version);
+    for tool in &outcome.tools {
+        println!("  {}: {}", tool.name, tool.original_path.display());
+    }
+
+    match &outcome.ini_profile {
+        Some(profile) => {
+            println!("  ini profile: {}", profile.display());
+            let active = toolchain::get_profiles_dir()?.join(toolchain::ACTIVE_PROFILE);
+            let scan_dir = std::env::var_os("PHP_INI_SCAN_DIR").unwrap_or_default();
+            if !std::env::split_paths(&scan_dir).any(|dir| dir == active) {
+                println!("\n{}", "PHP only reads the profile once PHP_INI_SCAN_DIR includes it; add to your shell rc:".yellow());
+                println!("  export PHP_INI_SCAN_DIR=\":{}\"", active.display());
+            }
+        }
+        None => println!("  ini profile: (none)"),
+    }
+
+    if outcome.switch.links.iter().any(|l| l.name == "php" && l.change == switcher::Change::Added) || !outcome.tools.is_empty() {
+        switcher::show_rehash_hint();
+    }
+
+    Ok(())
+}
+
+fn list_toolchains() -> Result<()> {
+    let config = config::load_config()?;
+
+    if config.toolchains.is_empty() {
+        println!("No toolchains configured.");
+        println!("{}", "Add one to the config, e.g. [toolchain.legacy] with php = \"7.4\", composer = \"1\", ini = \"legacy\"".dimmed());
+        return Ok(());
+    }
+
+    println!("{}", "Toolchains:".bold());
+    for (name, toolchain) in &config.toolchains {
+        let mut parts = vec![format!("php {}", toolchain.php)];
+        parts.extend(toolchain.tools.iter().map(|(tool, pin)| format!("{} {}", tool, pin)));
+        if let Some(ini) = &toolchain.ini {
+            parts.push(format!("ini {}", ini));
+        }
+        println!("  {}  {}", name.bold(), parts.join(", ").dimmed());
+    }
+
+    Ok(())
+}
+
+/// Warn when the CLI PHP differs from the one the project's FrankenPHP/RoadRunner setup runs
+fn warn_runtime_divergence(cli_version: &str, config: &config::Config) -> Result<()> {
+    let Some(runtime) = runtime::detect_project_runtime(&std::env::current_dir()?, config) else { return Ok(()) };
+    let Some(runtime_version) = runtime.php_version else { return Ok(()) };
+
+    if runtime::diverges(cli_version, &runtime_version) {
+        println!(
+            "\n{} CLI PHP {} differs from PHP {} used by {} (from {})",
+            style::warn().yellow(),
+            cli_version.bold(),
+            runtime_version.bold(),
+            runtime.kind.name(),
+            runtime.source.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Make sure a project's composer `ext-*` requirements are met after switching to it
+///
+/// Disabled extensions are enabled after confirmation; missing ones get install hints.
+fn check_project_extensions(version_pattern: &str) -> Result<()> {
+    let Some(path) = composer::find_composer_json(&std::env::current_dir()?) else { return Ok(()) };
+    let required = extensions::required_extensions(&composer::load(&path)?);
+    if required.is_empty() {
+        return Ok(());
+    }
+
+    let config = config::load_config()?;
+    let Some(entry) = config.find_version(version_pattern) else { return Ok(()) };
+    let Some(binary) = entry.primary_path() else { return Ok(()) };
+
+    let report = extensions::check(binary, &required);
+    if report.is_empty() {
+        println!("{} All {} required extension(s) are loaded", style::ok().green(), required.len());
+        return Ok(());
+    }
+
+    if !report.disabled.is_empty() {
+        println!("\n{}", "Required extensions installed but disabled:".yellow());
+        for disabled in &report.disabled {
+            println!("  {} {}", disabled.name.bold(), format!("({}:{})", disabled.file.display(), disabled.line).dimmed());
+        }
+
+        if confirm::confirm(&config, "Enable them now?", false)? {
+            for disabled in &report.disabled {
+                match extensions::enable(disabled) {
+                    Ok(()) => println!("  {} Enabled {}", style::ok().green(), disabled.name),
+                    Err(e) => println!("  {} {} (try again with sudo)", style::fail().red(), e),
+                }
+            }
+        } else {
+            println!("{}", "  Uncomment the lines above to enable them".dimmed());
+        }
+    }
+
+    if !report.missing.is_empty() {
+        let short_version = entry.version.split('.').take(2).collect::<Vec<_>>().join(".");
+        hints::show_extension_hints(&report.missing, &short_version, platform::Platform::detect());
+    }
+
+    Ok(())
+}
+
+/// Rescan for `list`, which must keep working when the results can't be saved
+fn rescan_for_listing(config: &mut config::Config) {
+    if let Err(e) = switcher::rescan(config) {
+        eprintln!("{}", format!("{} Scan results not cached: {}", style::warn(), e).yellow());
+    }
+}
+
+/// Print a FrankenPHP/RoadRunner binary with the PHP it embeds
+fn print_runtime(runtime: &config::RuntimeEntry, indent: &str) {
+    let php = runtime
+        .php_version
+        .as_ref()
+        .map(|v| format!(" (PHP {})", v))
+        .unwrap_or_default();
+    println!(
+        "{}{} {} {}{}  {}",
+        indent,
+        "◆".cyan(),
+        runtime.kind.name(),
+        runtime.version,
+        php,
+        runtime.path.display().to_string().dimmed()
+    );
+}
+
+fn print_container(image: &config::ContainerImage, indent: &str) {
+    println!(
+        "{}{} PHP {}  {}",
+        indent,
+        style::symbol("▣", "container:").cyan(),
+        image.php_version,
+        image.image.dimmed()
+    );
+}
+
+/// Print a PHP binary that doesn't run, with what went wrong
+fn print_broken(broken: &config::BrokenInstall, indent: &str) {
+    println!("{}{} PHP {}  {}", indent, style::fail().red(), broken.version, broken.path.display().to_string().dimmed());
+    println!("{}    {}", indent, broken.reason.yellow());
+}
+
+/// Print one pin as aligned columns, or as a sentence in accessible mode
+fn print_pin_row(dir: &str, width: usize, version: &str, status: colored::ColoredString) {
+    if style::is_accessible() {
+        println!("  {}: PHP {}, {}", dir, version, status);
+    } else {
+        println!("  {:<width$} {:<8} {}", dir, version, status, width = width);
+    }
+}
+
+/// Print one tracked version with its path, status and related binaries
+fn print_version_entry(config: &config::Config, entry: &config::VersionEntry, is_current: bool, indent: &str) {
+    // Get the primary path (prefer 'php' binary)
+    let primary_path = entry.primary_path();
+    let labels = if entry.labels.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", entry.labels.join(", ")).cyan().to_string()
+    };
+    let default = if config.default_entry().is_some_and(|d| d.version == entry.version) {
+        format!("  {}", "[DEFAULT]".blue())
+    } else {
+        String::new()
+    };
+    let build = if entry.build.is_empty() {
+        String::new()
+    } else {
+        format!("  {}", format!("({})", entry.build.summary()).dimmed())
+    };
+
+    if is_current {
+        println!(
+            "{}{} {}{}  {}{}  {}{}",
+            indent,
+            style::active().green(),
+            entry.version.green().bold(),
+            labels,
+            primary_path
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+                .dimmed(),
+            build,
+            // Accessible mode already starts the line with the word
+            style::symbol("[ACTIVE]", "").green().bold(),
+            default
+        );
+    } else {
+        let blocked = if config.policy.allows(&entry.version) {
+            String::new()
+        } else {
+            format!("  {}", "[NOT ALLOWED]".red())
+        };
+
+        println!(
+            "{}{} {}{}  {}{}{}{}",
+            indent,
+            style::inactive().dimmed(),
+            entry.version,
+            labels,
+            primary_path
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+                .dimmed(),
+            build,
+            default,
+            blocked
+        );
+    }
+
+    if entry.is_garbage_collected() {
+        println!(
+            "{}  {} {}",
+            indent,
+            style::warn().yellow(),
+            "Nix store path is gone (garbage-collected?); reinstall it or run 'php-switcher scan'".yellow()
+        );
+    }
+    if entry.source == "windows" {
+        println!("{}  {}", indent, "Windows build on a mounted drive; runs only through WSL interop".dimmed());
+    }
+
+    // Show related binaries if more than just 'php'
+    if entry.paths.len() > 1 {
+        let related: Vec<String> = entry
+            .paths
+            .iter()
+            .filter(|p| p.file_name().and_then(|n| n.to_str()) != Some("php"))
+            .filter_map(|p| p.file_name()?.to_str().map(String::from))
+            .collect();
+
+        if !related.is_empty() {
+            println!(
+                "{}    {} {}",
+                indent,
+                "Related:".dimmed(),
+                related.join(", ").dimmed()
+            );
+        }
+    }
+
+    if !entry.metadata.is_empty() {
+        let metadata: Vec<String> = entry.metadata.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        println!("{}    {} {}", indent, "Metadata:".dimmed(), metadata.join(", ").dimmed());
+    }
+}
+
+/// Notify subscribers when the `php` on PATH bypasses the switcher bin directory
+fn report_drift(current_php: &detector::PhpInstallation) {
+    let Ok(bin_dir) = switcher::get_bin_dir() else { return };
+    let Some(actual) = current_php.primary_path() else { return };
+
+    let expected = bin_dir.join("php");
+    if std::fs::symlink_metadata(&expected).is_err() {
+        return;
+    }
+
+    if actual.canonicalize().ok() != expected.canonicalize().ok() {
+        events::emit(&Event::DriftDetected {
+            expected: std::fs::read_link(&expected).ok(),
+            actual: actual.clone(),
+        });
+    }
+}
+
+/// Answer a single `query` request; failures are reported in the response and the exit code
+fn run_query(request: &str) -> Result<()> {
+    use std::io::Read;
+
+    let mut request = request.to_string();
+    if request == "-" {
+        request.clear();
+        std::io::stdin()
+            .read_to_string(&mut request)
+            .map_err(|e| anyhow::anyhow!("Failed to read stdin: {}", e))?;
+    }
+
+    let response = api::query(&request, &switcher::get_bin_dir()?);
+    println!("{}", serde_json::to_string(&response)?);
+
+    if response.error.is_some() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn watch_events() -> Result<()> {
+    let subscription = events::Subscription::new()?;
+
+    loop {
+        let event = subscription.recv()?;
+        println!("{}", serde_json::to_string(&event)?);
+    }
+}
+
+fn watch_project(dir: Option<&std::path::Path>) -> Result<()> {
+    let dir = match dir {
+        Some(dir) => dir.canonicalize().map_err(|e| anyhow::anyhow!("Failed to open {}: {}", dir.display(), e))?,
+        None => std::env::current_dir()?,
+    };
+
+    let config = config::load_config()?;
+    match php_switcher::watch::wanted_version(&config, &dir)? {
+        Some(version) => {
+            switcher::switch_version(&version, &switcher::SwitchOptions { if_needed: true, ..Default::default() })?
+        }
+        None => println!("{} isn't pinned yet; waiting for a {} or composer.json", dir.display(), project::PIN_FILE),
+    }
+    println!("\n{} {} for pin changes (Ctrl-C to stop)", "Watching".bold(), dir.display());
+
+    php_switcher::watch::watch(
+        &dir,
+        |version| {
+            println!("\n{} The project now wants PHP {}", "↻".cyan(), version.bold());
+            switcher::switch_version(version, &switcher::SwitchOptions { if_needed: true, ..Default::default() })
+        },
+        |e| eprintln!("{} {}", style::fail().red(), e),
+    )
+}
+
+/// Load the config with the directories given to `scan --path` added to `extra_scan_dirs`
+fn config_with_scan_paths(paths: &[std::path::PathBuf]) -> Result<config::Config> {
+    let mut config = config::load_config()?;
+    for path in paths {
+        if !path.is_dir() {
+            return Err(anyhow::anyhow!("Not a directory: {}", path.display()));
+        }
+        let dir = std::path::absolute(path).map_err(|e| anyhow::anyhow!("Failed to resolve {}: {}", path.display(), e))?;
+        if !config.settings.extra_scan_dirs.contains(&dir) {
+            config.settings.extra_scan_dirs.push(dir);
+        }
+    }
+    Ok(config)
+}
+
+/// Scan options from the settings, with `--no-exec` on top
+fn scan_options(config: &config::Config, no_exec: bool) -> detector::ScanOptions {
+    let mut options = config.settings.scan_options();
+    options.no_exec |= no_exec;
+    options
+}
+
+/// Runtimes are identified by running them, which a no-exec scan mustn't do; keep the known ones
+fn scan_runtimes(config: &config::Config, options: &detector::ScanOptions) -> Vec<config::RuntimeEntry> {
+    if options.no_exec {
+        return config.runtimes.clone();
+    }
+    runtime::find_all_runtimes()
+}
+
+fn scan_installations(paths: &[std::path::PathBuf], verbose: bool, no_exec: bool, json: bool) -> Result<()> {
+    let mut config = config_with_scan_paths(paths)?;
+    let options = scan_options(&config, no_exec);
+
+    if json {
+        let started = std::time::Instant::now();
+        let detector::ScanReport { installations, errors, excluded, broken } = detector::scan_system(&options);
+
+        stats::update(&config, |s| s.record_scan(started.elapsed()));
+        config.update_from_installations(&installations);
+        config.broken = broken.clone();
+        config.runtimes = scan_runtimes(&config, &options);
+        config.containers = container::find_images();
+        config::save_config(&config)?;
+        events::emit(&Event::ScanFinished { installations: installations.len() });
+
+        return output::print_json(
+            "scan_result",
+            &output::ScanResult {
+                installations: installations.iter().map(output::InstallationInfo::from).collect(),
+                errors,
+                excluded,
+                broken,
+            },
+        );
+    }
+
+    println!("{}", "Scanning for PHP installations...".yellow());
+
+    let spinner = if verbose {
+        progress::listen(print_scan_candidate);
+        None
+    } else {
+        use std::io::IsTerminal;
+        (std::io::stderr().is_terminal() && !style::is_accessible()).then(show_scan_progress)
+    };
+
+    let started = std::time::Instant::now();
+    let detector::ScanReport { installations, errors, excluded, broken } = detector::scan_system(&options);
+    let scan_duration = started.elapsed();
+    progress::stop();
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+    if verbose {
+        println!();
+    }
+
+    if installations.is_empty() {
+        println!("{}", "No PHP installations found.".red());
+        // The versions found before are kept, but the broken binaries are news
+        if !broken.is_empty() {
+            config.broken = broken;
+            config::save_config(&config)?;
+        }
+        print_scan_broken(&config.broken);
+        print_scan_errors(&errors);
+        print_scan_exclusions(&excluded, verbose);
+        return Ok(());
+    }
+
+    println!(
+        "{} Found {} PHP installation(s)\n",
+        style::ok().green(),
+        installations.len()
+    );
+
+    for installation in &installations {
+        // Get the primary path
+        let primary_path = installation.primary_path();
+
+        println!(
+            "  {} at {}",
+            installation.version.to_string().bold(),
+            primary_path.map(|p| p.display().to_string()).unwrap_or_default()
+        );
+
+        // Show related binaries
+        if installation.paths.len() > 1 {
+            let related: Vec<String> = installation
+                .paths
+                .iter()
+                .filter(|p| Some(*p) != primary_path)
+                .filter_map(|p| p.file_name()?.to_str().map(String::from))
+                .collect();
+
+            if !related.is_empty() {
+                println!(
+                    "      {} {}",
+                    "Related:".dimmed(),
+                    related.join(", ").dimmed()
+                );
+            }
+        }
+    }
+
+    let runtimes = scan_runtimes(&config, &options);
+    if !runtimes.is_empty() {
+        println!("\n{} Found {} runtime(s)", style::ok().green(), runtimes.len());
+        for runtime in &runtimes {
+            print_runtime(runtime, "  ");
+        }
+    }
+
+    let containers = container::find_images();
+    if !containers.is_empty() {
+        println!("\n{} Found {} Docker image(s) of PHP (not native)", style::ok().green(), containers.len());
+        for image in &containers {
+            print_container(image, "  ");
+        }
+    }
+
+    // Save to config
+    config.update_from_installations(&installations);
+    config.runtimes = runtimes;
+    config.containers = containers;
+    config.broken = broken;
+    config::save_config(&config)?;
+    events::emit(&Event::ScanFinished { installations: installations.len() });
+    stats::update(&config, |s| s.record_scan(scan_duration));
+
+    println!("\n{}", "Configuration updated.".green());
+    print_scan_broken(&config.broken);
+    print_scan_errors(&errors);
+    print_scan_exclusions(&excluded, verbose);
+
+    Ok(())
+}
+
+/// List the PHP binaries that were found but don't run, so they can be fixed
+fn print_scan_broken(broken: &[config::BrokenInstall]) {
+    if broken.is_empty() {
+        return;
+    }
+
+    println!("\n{} Found {} PHP binary(ies) that don't run:", style::warn().yellow(), broken.len());
+    for broken in broken {
+        print_broken(broken, "  ");
+    }
+}
+
+/// List the places a scan couldn't look at, so they aren't mistaken for "no PHP there"
+fn print_scan_errors(errors: &[detector::ScanError]) {
+    if errors.is_empty() {
+        return;
+    }
+
+    println!("\n{} Couldn't check {} location(s):", style::warn().yellow(), errors.len());
+    for error in errors {
+        println!("  {}  {} {}", error.path.display(), error.kind.name().yellow(), format!("({})", error.message).dimmed());
+    }
+}
+
+/// A spinner counting listed directories and run binaries while a scan runs
+fn show_scan_progress() -> indicatif::ProgressBar {
+    use progress::ScanEvent;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    let spinner = indicatif::ProgressBar::new_spinner();
+    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+    let (dirs, total, probed) = (AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0));
+    let bar = spinner.clone();
+    progress::listen(move |event| {
+        match event {
+            ScanEvent::Directory(_) => dirs.fetch_add(1, Relaxed),
+            ScanEvent::Probing { total: count } => total.fetch_add(*count, Relaxed),
+            ScanEvent::Probed => probed.fetch_add(1, Relaxed),
+            ScanEvent::Candidate { .. } => return,
+        };
+        bar.set_message(format!(
+            "{} directories listed, {}/{} binaries run",
+            dirs.load(Relaxed),
+            probed.load(Relaxed),
+            total.load(Relaxed)
+        ));
+    });
+    spinner
+}
+
+/// `scan --verbose`: one line per candidate binary with what became of it
+fn print_scan_candidate(event: &progress::ScanEvent) {
+    if let progress::ScanEvent::Candidate { path, verdict } = event {
+        let mark = if verdict.is_accepted() { style::ok().green() } else { style::bullet().dimmed() };
+        println!("  {} {}  {}", mark, path.display(), verdict.describe().dimmed());
+    }
+}
+
+/// Mention what the settings left out; `--verbose` lists it
+fn print_scan_exclusions(excluded: &[detector::Exclusion], verbose: bool) {
+    if excluded.is_empty() {
+        return;
+    }
+    if !verbose {
+        println!("\n{}", format!("{} binary(ies) excluded by settings (scan --verbose lists them)", excluded.len()).dimmed());
+        return;
+    }
+
+    println!("\nExcluded {} binary(ies):", excluded.len());
+    for exclusion in excluded {
+        println!("  {}  {}", exclusion.path.display(), format!("({})", exclusion.rule).dimmed());
+    }
+}
+
+fn scan_preview(paths: &[std::path::PathBuf], no_exec: bool, json: bool) -> Result<()> {
+    let config = config_with_scan_paths(paths)?;
+    let detector::ScanReport { installations, errors, .. } = detector::scan_system(&scan_options(&config, no_exec));
+    let diff = config.diff_scan(&installations);
+
+    if json {
+        return output::print_json("scan_preview", &output::ScanPreview::from(&diff));
+    }
+
+    if diff.is_empty() {
+        println!("{}", format!("{} A scan would not change the configuration.", style::ok()).green());
+        print_scan_errors(&errors);
+        return Ok(());
+    }
+
+    println!("{}", "A scan would make these changes (dry run, nothing saved):".bold());
+    let describe = |entry: &config::VersionEntry| {
+        entry.primary_path().map(|p| p.display().to_string()).unwrap_or_default()
+    };
+    for entry in &diff.added {
+        println!("  {} {}  {}", "+".green(), entry.version.bold(), describe(entry).dimmed());
+    }
+    for entry in &diff.updated {
+        println!("  {} {}  {}", "~".yellow(), entry.version.bold(), describe(entry).dimmed());
+    }
+    for entry in &diff.removed {
+        println!("  {} {}  {}", "-".red(), entry.version.bold(), describe(entry).dimmed());
+    }
+    print_scan_errors(&errors);
+
+    Ok(())
+}
+
+fn show_info(version: Option<&str>, json: bool) -> Result<()> {
+    let config = config::load_config()?;
+    let active = switcher::active_entry(&config, &switcher::get_bin_dir()?);
+
+    let entry = match version {
+        Some(version_pattern) => config.find_version(version_pattern).ok_or_else(|| match config.find_broken(version_pattern) {
+            Some(broken) => anyhow::anyhow!("{}", broken.describe()),
+            None => anyhow::anyhow!("No PHP installation found matching '{}'", version_pattern),
+        })?,
+        // Without a switch yet, describe whatever php is on PATH if it's tracked
+        None => active
+            .or_else(|| {
+                let current = detector::detect_current_php().ok()?;
+                config.find_version(&current.version.to_string())
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!("No active PHP version (run 'php-switcher use <version>', or 'php-switcher info --self')")
+            })?,
+    };
+
+    let is_active = active.is_some_and(|a| a.version == entry.version);
+    let primary_path = entry
+        .primary_path()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("No primary PHP binary found"))?;
+    let version = detector::get_version_from_binary(&primary_path)?;
+    // The scan recorded extensions, build and ini files; the probe covers configs from before that
+    let recorded = !entry.extensions.is_empty();
+    let probe = if recorded { probe::Probe::default() } else { probe::probe(&primary_path) };
+    let extensions = if recorded { entry.extensions.clone() } else { probe.extensions };
+    let ini_file = if recorded { entry.layout.ini_file.clone() } else { probe.ini_file };
+
+    if json {
+        return output::print_json(
+            "installation_details",
+            &output::InstallationDetails {
+                version: version.to_string(),
+                short_version: version.short_version(),
+                primary_path,
+                paths: entry.paths.clone(),
+                source: Some(entry.source.clone()),
+                active: is_active,
+                ini_file,
+                extensions,
+                labels: entry.labels.clone(),
+                metadata: entry.metadata.clone(),
+                layout: entry.layout.clone(),
+                build: entry.build.clone(),
+                aliases: entry.aliases.clone(),
+            },
+        );
+    }
+
+    println!("{}", "PHP Installation Info".bold());
+    println!(
+        "  Version: {}{}",
+        version.to_string().bold(),
+        if is_active { format!("  {}", "[ACTIVE]".green().bold()) } else { String::new() }
+    );
+    println!("  Short version: {}", version.short_version());
+    println!("  Source: {}", entry.source);
+    if !entry.labels.is_empty() {
+        println!("  Labels: {}", entry.labels.join(", ").cyan());
+    }
+    for (key, value) in &entry.metadata {
+        println!("  {}: {}", key, value);
+    }
+    println!("  Primary path: {}", primary_path.display());
+    println!(
+        "  php.ini: {}",
+        ini_file.map(|p| p.display().to_string()).unwrap_or_else(|| "(none)".to_string())
+    );
+    for dir in &entry.layout.ini_scan_dirs {
+        println!("  Scan dir: {}", dir.display());
+    }
+    let layout = [
+        ("Prefix", &entry.layout.prefix),
+        ("Extension dir", &entry.layout.extension_dir),
+        ("FPM binary", &entry.layout.fpm_binary),
+        ("FPM config", &entry.layout.fpm_config_dir),
+    ];
+    for (name, path) in layout {
+        if let Some(path) = path {
+            println!("  {}: {}", name, path.display());
+        }
+    }
+    if let Some(listen) = &entry.layout.fpm_listen {
+        println!("  FPM listens on: {}", listen);
+    }
+
+    // Show all binaries
+    println!("\n  {} binaries:", entry.paths.len());
+    for path in &entry.paths {
+        if let Some(filename) = path.file_name() {
+            println!("    - {} ({})", filename.to_string_lossy(), path.display());
+        }
+    }
+    if !entry.aliases.is_empty() {
+        let aliases: Vec<String> = entry.aliases.iter().map(|path| path.display().to_string()).collect();
+        println!("  Also reachable as: {}", aliases.join(", ").dimmed());
+    }
+
+    // Recorded at scan time; older configs only have what the probe saw
+    let build = if entry.build.is_empty() { detector::BuildInfo::from_php_info(&probe.build) } else { entry.build.clone() };
+    if !build.is_empty() {
+        println!("  Build: {}", build.summary());
+    }
+
+    if !extensions.is_empty() {
+        println!("\n  {} extensions:", extensions.len());
+        println!("    {}", extensions.join(", "));
+    }
+
+    Ok(())
+}
+
+fn label_version(version_pattern: &str, items: &[String], remove: bool) -> Result<()> {
+    if items.is_empty() {
+        return show_info(Some(version_pattern), false);
+    }
+
+    let mut config = config::load_config()?;
+    let version = config.label_version(version_pattern, items, remove)?;
+    config::save_config(&config)?;
+
+    let verb = if remove { "Removed from" } else { "Labelled" };
+    println!("{} {} {}: {}", style::ok().green(), verb, version.bold(), items.join(", "));
+
+    Ok(())
+}
+
+fn import_from(source: migrate::Source) -> Result<()> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
+    let state = migrate::read(source, &home, |name| std::env::var_os(name));
+    if state.versions.is_empty() {
+        println!("No {} builds found in {}", source.name(), source.versions_dir(&home).display());
+        return Ok(());
+    }
+
+    let mut config = config::load_config()?;
+    let summary = migrate::import(&mut config, &state);
+    config::save_config(&config)?;
+
+    for (label, version) in &summary.labels {
+        println!("{} Labelled {}: {}", style::ok().green(), version.bold(), label);
+    }
+    if let Some(version) = &summary.default {
+        println!("{} Default version: {} (selected in {})", style::ok().green(), version.bold(), source.name());
+    }
+    for (name, reason) in &summary.skipped {
+        println!("  {} {}: {}", style::bullet(), name, reason.dimmed());
+    }
+    if summary.labels.is_empty() && summary.default.is_none() {
+        println!("Nothing to import from {}.", source.name());
+    }
+
+    Ok(())
+}
+
+/// Switch to `settings.previous_version`, so running `prev` twice toggles between two versions
+fn switch_to_previous(force: bool) -> Result<()> {
+    let config = config::load_config()?;
+    let previous = config
+        .settings
+        .previous_version
+        .ok_or_else(|| anyhow::anyhow!("No previous version to switch back to; switch with 'php-switcher use <version>' first"))?;
+    switcher::switch_version(&previous, &switcher::SwitchOptions { force, ..Default::default() })
+}
+
+fn set_default(version: Option<&str>, unset: bool) -> Result<()> {
+    let mut config = config::load_config()?;
+
+    if unset {
+        config.settings.default_version = None;
+        config::save_config(&config)?;
+        println!("{} Default version cleared", style::ok().green());
+        return Ok(());
+    }
+
+    let Some(version) = version else {
+        match (&config.settings.default_version, config.default_entry()) {
+            (Some(default), Some(entry)) => println!("Default version: {} ({})", default.bold(), entry.version),
+            (Some(default), None) => println!("Default version: {} {}", default.bold(), "(not installed)".red()),
+            (None, _) => println!("No default version set. Use 'php-switcher default <version>' to set one."),
+        }
+        return Ok(());
+    };
+
+    let entry = config
+        .find_version(version)
+        .ok_or_else(|| anyhow::anyhow!("No PHP installation found matching '{}'. Run 'php-switcher scan' first.", version))?;
+    let resolved = entry.version.clone();
+
+    config.settings.default_version = Some(version.to_string());
+    config::save_config(&config)?;
+
+    println!("{} Default version set to {} ({})", style::ok().green(), version.bold(), resolved);
+    println!("  {} 'php-switcher use' without a version switches to it where no {} applies", style::note().dimmed(), project::PIN_FILE);
+    Ok(())
+}
+
+fn pin_directory(dir: Option<&std::path::Path>, version: Option<&str>, unset: bool) -> Result<()> {
+    let mut config = config::load_config()?;
+
+    let Some(dir) = dir else {
+        if config.pins.is_empty() {
+            println!("No directories pinned in the config.");
+            return Ok(());
+        }
+        println!("{}", "Pinned directories:".bold());
+        for pin in &config.pins {
+            let status = match config.find_version(&pin.version) {
+                Some(entry) => format!("{} {}", style::arrow(), entry.version).dimmed(),
+                None => "not installed".red(),
+            };
+            print_pin_row(&pin.dir.display().to_string(), 40, &pin.version, status);
+        }
+        return Ok(());
+    };
+
+    // Pins are matched against the working directory, which has symlinks resolved
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Failed to resolve {}: {}", dir.display(), e))?;
+
+    if unset {
+        match config.remove_pin(&dir) {
+            Some(version) => {
+                config::save_config(&config)?;
+                println!("{} Removed the pin of {} (was {})", style::ok().green(), dir.display(), version);
+            }
+            None => println!("{} is not pinned in the config", dir.display()),
+        }
+        return Ok(());
+    }
+
+    let Some(version) = version else {
+        match project::central_pin(&config, &dir) {
+            Some(pin) => println!("{} is pinned to {} (by {})", dir.display(), pin.version.bold(), pin.dir.display()),
+            None => println!("{} is not pinned in the config", dir.display()),
+        }
+        return Ok(());
+    };
+
+    let entry = config
+        .find_version(version)
+        .ok_or_else(|| anyhow::anyhow!("No PHP installation found matching '{}'. Run 'php-switcher scan' first.", version))?;
+    let resolved = entry.version.clone();
+
+    config.set_pin(&dir, version);
+    config::save_config(&config)?;
+
+    println!("{} Pinned {} to PHP {} ({})", style::ok().green(), dir.display(), version.bold(), resolved);
+    println!(
+        "  {} 'php-switcher use' and cd hooks use it there and below, ahead of any {}",
+        style::note().dimmed(),
+        project::PIN_FILE
+    );
+    Ok(())
+}
 
-        // Get the primary path (prefer 'php' binary)
-        let primary_path = entry
-            .paths
-            .iter()
-            .find(|p| p.file_name().and_then(|n| n.to_str()) == Some("php"))
-            .or_else(|| entry.paths.first());
+fn pin_local(version: Option<&str>, unset: bool) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    if config::is_read_only() {
+        return Err(anyhow::anyhow!("Read-only mode: not writing {}", project::PIN_FILE));
+    }
 
-        if is_current {
-            println!(
-                "  {} {}  {}  {}",
-                "●".green(),
-                entry.version.green().bold(),
-                primary_path
-                    .map(|p| p.display().to_string())
-                    .unwrap_or_default()
-                    .dimmed(),
-                "[ACTIVE]".green().bold()
-            );
-        } else {
-            println!(
-                "  {} {}  {}",
-                "○".dimmed(),
-                entry.version,
-                primary_path
-                    .map(|p| p.display().to_string())
-                    .unwrap_or_default()
-                    .dimmed()
-            );
+    if unset {
+        match project::remove_pin(&cwd)? {
+            Some(pin) => println!("{} Removed {} (was {})", style::ok().green(), project::PIN_FILE, pin.version),
+            None => println!("No {} in {}", project::PIN_FILE, cwd.display()),
         }
-
-        // Show related binaries if more than just 'php'
-        if entry.paths.len() > 1 {
-            let related: Vec<String> = entry
-                .paths
-                .iter()
-                .filter(|p| p.file_name().and_then(|n| n.to_str()) != Some("php"))
-                .filter_map(|p| p.file_name()?.to_str().map(String::from))
-                .collect();
-
-            if !related.is_empty() {
-                println!(
-                    "      {} {}",
-                    "Related:".dimmed(),
-                    related.join(", ").dimmed()
-                );
-            }
+        if let Some(pin) = project::nearest_pin(&config::load_config()?, &cwd) {
+            println!("  {} now applies here ({})", pin.version.bold(), pin.dir.display());
         }
+        return Ok(());
     }
 
-    println!("\n{}", "Use 'php-switcher use <version>' to switch versions".dimmed());
+    let config = config::load_config()?;
+    let pin = project::write_pin(&config, &cwd, version.unwrap_or_default())?;
+    let resolved = config.find_version(&pin.version).map(|e| e.version.clone()).unwrap_or_default();
+
+    println!("{} Pinned {} to PHP {} ({})", style::ok().green(), cwd.display(), pin.version.bold(), resolved);
+    println!("  {} 'php-switcher use' here (and in subdirectories) switches to it,", style::note().dimmed());
+    println!("    as do cd hooks built on 'php-switcher __resolve'");
+    if let Some(central) = project::central_pin(&config, &cwd) {
+        println!(
+            "  {} The config pins {} to {}, which takes precedence (see 'php-switcher pin')",
+            style::warn().yellow(),
+            central.dir.display(),
+            central.version
+        );
+    }
+    if !config.policy.allows(&resolved) {
+        println!("  {} PHP {} is not allowed by policy; switching will need --force", style::warn().yellow(), resolved);
+    }
 
     Ok(())
 }
 
-fn scan_installations() -> Result<()> {
-    println!("{}", "Scanning for PHP installations...".yellow());
+fn show_history(limit: usize, json: bool) -> Result<()> {
+    let entries = history::read()?;
+    let recent: Vec<&history::HistoryEntry> = entries.iter().rev().take(limit).collect();
 
-    let installations = detector::find_all_php_installations()?;
+    if json {
+        return output::print_json("history", &recent);
+    }
 
-    if installations.is_empty() {
-        println!("{}", "No PHP installations found.".red());
+    if recent.is_empty() {
+        println!("No switches recorded yet.");
         return Ok(());
     }
 
-    println!(
-        "{} Found {} PHP installation(s)\n",
-        "✓".green(),
-        installations.len()
-    );
+    for (index, entry) in recent.iter().enumerate() {
+        let time = chrono::DateTime::parse_from_rfc3339(&entry.timestamp)
+            .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| entry.timestamp.clone());
+        let place = entry.cwd.as_ref().map(|cwd| format!(", {}", cwd.display())).unwrap_or_default();
+        println!(
+            "  {:>3}  {}  {} {} {}  {}",
+            index + 1,
+            time.dimmed(),
+            entry.from.as_deref().unwrap_or("-"),
+            style::arrow(),
+            entry.to.bold(),
+            format!("({}{})", entry.trigger.name(), place).dimmed()
+        );
+    }
+    if entries.len() > recent.len() {
+        println!("  {} {} older switch(es) not shown; use --limit", style::note().dimmed(), entries.len() - recent.len());
+    }
+    println!("\nUse 'php-switcher history --undo <N>' to go back to the version active before a switch");
+    Ok(())
+}
 
-    for installation in &installations {
-        // Get the primary path
-        let primary_path = installation.primary_path();
+/// Switch to what was active before the `n`th most recent switch; the undo is recorded as a switch too
+fn undo_switch(n: usize, force: bool) -> Result<()> {
+    let version = history::version_before(&history::read()?, n)?;
+    switcher::switch_version(&version, &switcher::SwitchOptions { force, ..Default::default() })
+}
+
+fn inspect_state(dir: &std::path::Path, json: bool) -> Result<()> {
+    let inspection = inspect::inspect(dir)?;
 
+    if json {
+        return output::print_json("inspection", &inspection);
+    }
+
+    println!("{} {}", "Inspecting".bold(), inspection.dir.display());
+    println!("  Tracked versions: {}", if inspection.versions.is_empty() { "(none)".to_string() } else { inspection.versions.join(", ") });
+    println!("  Active: {}", inspection.active.as_deref().unwrap_or("(unknown)").bold());
+    if let Some(last) = &inspection.last_switch {
         println!(
-            "  {} at {}",
-            installation.version.to_string().bold(),
-            primary_path.map(|p| p.display().to_string()).unwrap_or_default()
+            "  Last switch: {} {} {} ({}, {})",
+            last.from.as_deref().unwrap_or("-"),
+            style::arrow(),
+            last.to,
+            last.trigger.name(),
+            last.timestamp
         );
+    }
 
-        // Show related binaries
-        if installation.paths.len() > 1 {
-            let related: Vec<String> = installation
-                .paths
-                .iter()
-                .filter(|p| Some(*p) != primary_path)
-                .filter_map(|p| p.file_name()?.to_str().map(String::from))
-                .collect();
-
-            if !related.is_empty() {
-                println!(
-                    "      {} {}",
-                    "Related:".dimmed(),
-                    related.join(", ").dimmed()
-                );
-            }
+    if !inspection.links.is_empty() {
+        println!("\n  {}", "bin/:".dimmed());
+        for link in &inspection.links {
+            let target = link.target.as_ref().map(|t| format!(" {} {}", style::arrow(), t.display())).unwrap_or_default();
+            let state = match &link.state {
+                inspect::LinkState::Active => "active".green().to_string(),
+                inspect::LinkState::Foreign(version) => format!("PHP {}", version).yellow().to_string(),
+                inspect::LinkState::Untracked => "untracked".red().to_string(),
+                inspect::LinkState::Shim => "shim".dimmed().to_string(),
+                inspect::LinkState::File => "file".dimmed().to_string(),
+            };
+            println!("    {}{}  [{}]", link.name, target, state);
         }
     }
 
-    // Save to config
-    let mut config = config::load_config()?;
-    config.update_from_installations(&installations);
-    config::save_config(&config)?;
+    if let Some(extensions) = &inspection.extensions {
+        println!("\n  Extensions (last probed): {}", extensions.join(", "));
+    }
 
-    println!("\n{}", "Configuration updated.".green());
+    println!();
+    if inspection.findings.is_empty() {
+        println!("{} No problems found", style::ok().green());
+    }
+    for finding in &inspection.findings {
+        println!("{} {}", style::warn().yellow(), finding);
+    }
 
     Ok(())
 }
 
-fn show_info(version: Option<&str>) -> Result<()> {
-    if let Some(version_pattern) = version {
-        // Show info for specific version
-        let config = config::load_config()?;
-        let paths = config
-            .get_installation_by_version(version_pattern)
-            .ok_or_else(|| anyhow::anyhow!("No PHP installation found matching '{}'", version_pattern))?;
+fn show_self_info(json: bool) -> Result<()> {
+    let config = config::load_config()?;
 
-        let primary_path = config
-            .get_primary_path_by_version(version_pattern)
-            .ok_or_else(|| anyhow::anyhow!("No primary PHP binary found"))?;
+    if json {
+        return output::print_json(
+            "self_info",
+            &output::SelfInfo {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                config_file: config::get_config_path()?,
+                system_config_file: Some(config::get_system_config_path()).filter(|p| p.exists()),
+                tracked_versions: config.versions.len(),
+                last_scan: config.settings.last_scan,
+            },
+        );
+    }
 
-        if let Ok(version) = detector::get_version_from_binary(&primary_path) {
-            println!("{}", "PHP Installation Info".bold());
-            println!("  Version: {}", version.to_string().bold());
-            println!("  Short version: {}", version.short_version());
-            println!("  Primary path: {}", primary_path.display());
+    println!("{}", "php-switcher".bold());
+    println!("Version: {}", env!("CARGO_PKG_VERSION"));
 
-            // Show all binaries
-            println!("\n  {} binaries:", paths.len());
-            for path in &paths {
-                if let Some(filename) = path.file_name() {
-                    println!("    - {} ({})", filename.to_string_lossy(), path.display());
-                }
-            }
-        }
-    } else {
-        // Show general info
-        println!("{}", "php-switcher".bold());
-        println!("Version: {}", env!("CARGO_PKG_VERSION"));
+    let config_path = config::get_config_path()?;
+    println!("\nConfiguration:");
+    println!("  Config file: {}", config_path.display());
 
-        let config_path = config::get_config_path()?;
-        println!("\nConfiguration:");
-        println!("  Config file: {}", config_path.display());
+    let system_config_path = config::get_system_config_path();
+    if system_config_path.exists() {
+        println!("  System config: {}", system_config_path.display());
+    }
 
-        let config = config::load_config()?;
-        println!("  Tracked versions: {}", config.versions.len());
+    println!("  Tracked versions: {}", config.versions.len());
 
-        if let Some(last_scan) = config.settings.last_scan {
-            println!("  Last scan: {}", last_scan);
-        }
+    if let Some(last_scan) = config.settings.last_scan {
+        println!("  Last scan: {}", last_scan);
     }
 
     Ok(())
@@ -286,7 +1989,7 @@ fn tools_list() -> Result<()> {
 
     println!("Detected tools:");
     for tool in &config.tools.managed {
-        let shim_status = if tool.shim_created { "✓".green() } else { "○".dimmed() };
+        let shim_status = if tool.shim_created { style::ok().green() } else { style::symbol("○", "no shim:").dimmed() };
         let needs_shim = if tool.shebang.contains("/env") { "(uses env)".dimmed().to_string() } else { "".to_string() };
 
         println!("  {} {} - {} {}",
@@ -331,7 +2034,7 @@ fn tools_scan() -> Result<()> {
             shim_created: false, // Will be created during next switch
         });
 
-        println!("  {} {}", "✓".green(), tool.name.bold());
+        println!("  {} {}", style::ok().green(), tool.name.bold());
         println!("      Path: {}", tool.original_path.display().to_string().dimmed());
         println!("      Shebang: {}", tool.shebang.dimmed());
     }
@@ -350,7 +2053,7 @@ fn tools_enable() -> Result<()> {
     config.tools.scan_for_tools = true;
     config::save_config(&config)?;
 
-    println!("{}", "✓ Tool scanning enabled".green());
+    println!("{}", format!("{} Tool scanning enabled", style::ok()).green());
     println!("\nNext steps:");
     println!("  1. Run: php-switcher tools scan");
     println!("  2. Switch PHP version to create shims");
@@ -364,8 +2067,474 @@ fn tools_disable() -> Result<()> {
     config.tools.scan_for_tools = false;
     config::save_config(&config)?;
 
-    println!("{}", "✓ Tool scanning disabled".green());
+    println!("{}", format!("{} Tool scanning disabled", style::ok()).green());
+
+    Ok(())
+}
+
+
+fn tools_project(dir: Option<&std::path::Path>) -> Result<()> {
+    let start = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir()?,
+    };
+    let root = composer::find_composer_json(&start)
+        .and_then(|manifest| manifest.parent().map(std::path::Path::to_path_buf))
+        .ok_or_else(|| anyhow::anyhow!("No composer.json found in {} or above", start.display()))?;
+
+    let config = config::load_config()?;
+    let version = php_switcher::watch::wanted_version(&config, &root)?.ok_or_else(|| {
+        anyhow::anyhow!("{} has no pinned version (see 'php-switcher local')", root.display())
+    })?;
+
+    let shims = create_project_shims(&config, &root, &version)?;
+    if shims.is_empty() {
+        println!("{}", format!("No PHP scripts in {}", root.join("vendor/bin").display()).yellow());
+        return Ok(());
+    }
+
+    println!("{} {} shim(s) running on PHP {}:", style::ok().green(), shims.len(), version.bold());
+    for shim in &shims {
+        println!("  {}", shim.display());
+    }
+    println!(
+        "\nPut {} first on PATH inside the project, e.g. with direnv: PATH_add {}",
+        root.join(tools::PROJECT_BIN_DIR).display(),
+        tools::PROJECT_BIN_DIR
+    );
+
+    Ok(())
+}
+
+/// Regenerate the project's vendor/bin shims after a project switch, if it has any
+fn refresh_project_shims(version: &str) -> Result<()> {
+    let Some(manifest) = composer::find_composer_json(&std::env::current_dir()?) else { return Ok(()) };
+    let Some(root) = manifest.parent() else { return Ok(()) };
+    if !root.join(tools::PROJECT_BIN_DIR).is_dir() {
+        return Ok(());
+    }
+
+    let shims = create_project_shims(&config::load_config()?, root, version)?;
+    println!("  Project tool shims: {} refreshed", shims.len());
+    Ok(())
+}
+
+fn create_project_shims(config: &config::Config, root: &std::path::Path, version: &str) -> Result<Vec<std::path::PathBuf>> {
+    let php = config
+        .find_version(version)
+        .and_then(|entry| entry.primary_path())
+        .ok_or_else(|| anyhow::anyhow!("PHP {} is pinned but not installed", version))?;
+
+    let mut settings = tools::ShimSettings::from_config(&config.tools, &config::get_config_dir()?)?;
+    settings.mode = config.settings.shim_mode();
+    tools::create_project_shims(root, php, &settings)
+}
+
+fn tools_template() -> Result<()> {
+    let config = config::load_config()?;
+    let settings = php_switcher::tools::ShimSettings::from_config(&config.tools, &config::get_config_dir()?)?;
+
+    print!("{}", settings.template);
+
+    Ok(())
+}
+
+fn prune(keep: Option<usize>, dry_run: bool) -> Result<()> {
+    let mut config = config::load_config()?;
+
+    let keep = keep
+        .or(config.settings.keep_patches)
+        .unwrap_or(prune::DEFAULT_KEEP_PATCHES);
+    let active = switcher::active_entry(&config, &switcher::get_bin_dir()?).map(|e| e.version.clone());
+    let candidates = prune::plan_prune(&config, keep, active.as_deref(), &prune::get_installs_dir()?);
+
+    if candidates.is_empty() {
+        println!("{}", format!("{} Nothing to prune (keeping {} patch releases per minor version)", style::ok(), keep).green());
+        return Ok(());
+    }
+
+    let verb = if dry_run { "Would remove" } else { "Removing" };
+    for candidate in &candidates {
+        println!("  {} PHP {}  {}", verb, candidate.version.bold(), candidate.root.display().to_string().dimmed());
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+    if !confirm::confirm(&config, &format!("Remove {} installation(s)?", candidates.len()), true)? {
+        return Ok(());
+    }
+
+    prune::prune(&mut config, &candidates)?;
+    println!("{}", format!("{} Pruned {} installation(s)", style::ok(), candidates.len()).green());
+
+    Ok(())
+}
+
+fn list_pins(json: bool) -> Result<()> {
+    let config = config::load_config()?;
+    let root = project::workspace_root(&std::env::current_dir()?);
+    let pins = project::list_pins(&root, config.settings.pin_files());
+
+    let resolved = |pin: &project::Pin| config.find_version(&pin.version).map(|e| e.version.clone());
+
+    if json {
+        let pins = pins
+            .iter()
+            .map(|pin| output::PinInfo { dir: pin.dir.clone(), version: pin.version.clone(), resolved: resolved(pin) })
+            .collect();
+        return output::print_json("pin_list", &output::PinList { root, pins });
+    }
+
+    if pins.is_empty() {
+        println!("No {} or {} files found in {}", project::PIN_FILE, project::TOOL_VERSIONS_FILE, root.display());
+        return Ok(());
+    }
+
+    println!("{} {}", "Pins in".bold(), root.display().to_string().bold());
+    for pin in &pins {
+        let status = match resolved(pin) {
+            Some(version) => format!("{} {}", style::arrow(), version).dimmed(),
+            None => "not installed".red(),
+        };
+        print_pin_row(&relative_dir(&root, &pin.dir), 30, &pin.version, status);
+    }
+
+    Ok(())
+}
+
+fn composer_scripts(dry_run: bool) -> Result<()> {
+    let path = composer::find_composer_json(&std::env::current_dir()?)
+        .ok_or_else(|| anyhow::anyhow!("No {} found", composer::COMPOSER_FILE))?;
+    let mut manifest = composer::load(&path)?;
+    let changed = composer::add_scripts(&mut manifest)?;
+
+    if changed.is_empty() {
+        println!("{}", format!("{} {} already has the php-switcher scripts", style::ok(), path.display()).green());
+        return Ok(());
+    }
+
+    let verb = if dry_run { "Would update" } else { "Updating" };
+    for name in &changed {
+        println!("  {} script {}", verb, name.bold());
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+    if !confirm::confirm(&config::load_config()?, &format!("Write the scripts to {}?", path.display()), true)? {
+        return Ok(());
+    }
+
+    composer::save(&path, &manifest)?;
+    println!("{}", format!("{} Updated {}", style::ok(), path.display()).green());
+
+    if composer::php_constraint(&manifest).is_none() {
+        println!("{}", "  Note: no require.php constraint, so 'php:check' always passes".dimmed());
+    }
+
+    Ok(())
+}
+
+/// Fail unless the active PHP satisfies the project's require.php
+fn project_check() -> Result<()> {
+    let current = detector::detect_current_php()?;
+    warn_runtime_divergence(&current.version.to_string(), &config::load_config()?)?;
+
+    let path = composer::find_composer_json(&std::env::current_dir()?)
+        .ok_or_else(|| anyhow::anyhow!("No {} found", composer::COMPOSER_FILE))?;
+    let manifest = composer::load(&path)?;
+
+    let Some(constraint) = composer::php_constraint(&manifest) else {
+        println!("{}", format!("{} {} has no require.php constraint", style::ok(), path.display()).green());
+        return Ok(());
+    };
+
+    if current.version.satisfies(constraint)? {
+        println!("{} PHP {} satisfies {}", style::ok().green(), current.version.to_string().bold(), constraint);
+        return Ok(());
+    }
+
+    eprintln!("{} PHP {} does not satisfy {} (from {})", style::fail().red(), current.version.to_string().bold(), constraint, path.display());
+    eprintln!("  Run 'php-switcher use <version>' (or 'composer php:use' with a .php-version) first");
+    Err(anyhow::anyhow!("Active PHP does not satisfy the project's constraint"))
+}
+
+/// Show a project directory relative to the workspace root
+fn relative_dir(root: &std::path::Path, dir: &std::path::Path) -> String {
+    match dir.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => dir.display().to_string(),
+    }
+}
+
+/// Run a command per tracked version, or per pinned project with `--projects`
+fn update_hints(url: &str) -> Result<()> {
+    if config::is_read_only() {
+        return Err(anyhow::anyhow!("Read-only mode: not updating the hints table"));
+    }
+
+    let table = hints::update(url)?;
+    println!(
+        "{} Updated installation hints ({} package sources) in {}",
+        style::ok().green(),
+        table.distros.len(),
+        hints::override_path()?.display()
+    );
+    Ok(())
+}
+
+fn reset_hints() -> Result<()> {
+    if hints::reset()? {
+        println!("{} Using the installation hints shipped with php-switcher", style::ok().green());
+    } else {
+        println!("No downloaded hints table, already using the built-in one");
+    }
+    Ok(())
+}
+
+/// Set in shells started by `shell` to the version they run (for prompts)
+const SHELL_ENV: &str = "PHP_SWITCHER_SHELL";
+
+/// Build a command running under one version: its bin directory goes first on PATH
+fn version_command(
+    config: &config::Config,
+    entry: &config::VersionEntry,
+    path_var: &std::ffi::OsStr,
+    command: &[String],
+) -> Result<std::process::Command> {
+    let bin_dir = switcher::version_bin_dir(config, entry)?;
+    let php = bin_dir.join(if cfg!(windows) { "php.cmd" } else { "php" });
+    let path = std::env::join_paths(std::iter::once(bin_dir).chain(std::env::split_paths(path_var)))?;
+
+    // Tools with ini settings run through PHP, like their shims do
+    let mut child = match tools::ini_invocation(&config.tools, &command[0]) {
+        Some(args) => {
+            let mut child = std::process::Command::new(&php);
+            child.args(args);
+            child
+        }
+        None => std::process::Command::new(&command[0]),
+    };
+    // The version's own env, as its shims would export it
+    child.envs(switcher::version_env(entry)?);
+    child.args(&command[1..]).env("PATH", path).env("PHP_SWITCHER_VERSION", &entry.version);
+    Ok(child)
+}
+
+/// Run one command under a version and exit the way it did, leaving the global links alone
+fn run_exec(version: &str, command: &[String]) -> Result<()> {
+    let config = config_with_version(version)?;
+    let entry = config
+        .find_version(version)
+        .ok_or_else(|| anyhow::anyhow!("No PHP installation found matching '{}'", version))?;
+
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let mut child = version_command(&config, entry, &path_var, command)?;
+    let status = process::run_inherited(&mut child)?;
+
+    print_startup_profile();
+    process::exit_like(status)
+}
+
+/// Start `$SHELL` with a version first on PATH; exiting it returns to the previous version
+fn run_shell(version: &str) -> Result<()> {
+    let config = config_with_version(version)?;
+    let entry = config
+        .find_version(version)
+        .ok_or_else(|| anyhow::anyhow!("No PHP installation found matching '{}'", version))?;
+
+    if let Ok(outer) = std::env::var(SHELL_ENV) {
+        eprintln!("{}", format!("{} Already in a php-switcher shell for PHP {}; nesting another", style::warn(), outer).yellow());
+    }
+
+    let shell = std::env::var("SHELL").ok().filter(|s| !s.is_empty()).unwrap_or_else(|| "/bin/sh".to_string());
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let mut child = version_command(&config, entry, &path_var, std::slice::from_ref(&shell))?;
+    child.env(SHELL_ENV, &entry.version);
+
+    eprintln!("{} PHP {} is active in this shell only; 'exit' to go back", style::note().dimmed(), entry.version.bold());
+    let status = process::run_inherited(&mut child)?;
+    eprintln!("{} Left the PHP {} shell", style::note().dimmed(), entry.version);
+
+    print_startup_profile();
+    process::exit_like(status)
+}
+
+/// Print assignments putting a version first on PATH, without touching the global links
+///
+/// Only the assignments go to stdout, since it's meant to be evaluated.
+fn print_env(version: Option<&str>, shell: Option<platform::ShellKind>) -> Result<()> {
+    let config = config::load_config()?;
+    let bin_dir = switcher::get_bin_dir()?;
+
+    let entry = match version {
+        Some(version) => config.find_version(version),
+        None => project::nearest_pin(&config, &std::env::current_dir()?)
+            .and_then(|pin| config.find_version(&pin.version))
+            .or_else(|| config.default_entry())
+            .or_else(|| switcher::active_entry(&config, &bin_dir)),
+    }
+    .ok_or_else(|| anyhow::anyhow!("No PHP installation found matching '{}'", version.unwrap_or("the pin, default or active version")))?;
+
+    let version_dir = switcher::version_bin_dir(&config, entry)?;
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let envs_dir = version_dir.parent().unwrap_or(&version_dir).to_path_buf();
+    let path = std::env::join_paths(env::path_with(&version_dir, &path_var, &bin_dir, &envs_dir))?;
+
+    let version_env = switcher::version_env(entry)?;
+    let mut vars = vec![
+        ("PATH", path.to_string_lossy().to_string()),
+        ("PHP_SWITCHER_VERSION", entry.version.clone()),
+        ("PHP_SWITCHER_PHP", version_dir.join(if cfg!(windows) { "php.cmd" } else { "php" }).display().to_string()),
+    ];
+    vars.extend(version_env.iter().map(|(name, value)| (name.as_str(), value.clone())));
+    println!("{}", env::render(&vars, shell.unwrap_or_else(platform::ShellKind::detect)));
+    Ok(())
+}
+
+/// Load the config, rescanning when no tracked version matches `version`
+fn config_with_version(version: &str) -> Result<config::Config> {
+    let mut config = config::load_config()?;
+    if config.find_version(version).is_none() {
+        switcher::rescan(&mut config)?;
+    }
+    Ok(config)
+}
+
+fn run_each(
+    projects: bool,
+    report_path: Option<&std::path::Path>,
+    report_format: Option<report::ReportFormat>,
+    command: &[String],
+) -> Result<()> {
+    // Fail before running anything rather than after a long matrix
+    let report_format = match report_path {
+        Some(path) => Some(report_format.or_else(|| report::ReportFormat::from_path(path)).ok_or_else(|| {
+            anyhow::anyhow!("Can't tell the report format of {}; pass --report-format", path.display())
+        })?),
+        None => None,
+    };
+
+    let mut config = config::load_config()?;
+    if config.versions.is_empty() {
+        rescan_for_listing(&mut config);
+    }
+
+    let cwd = std::env::current_dir()?;
+
+    // (label, working directory, version pattern)
+    let runs: Vec<(String, std::path::PathBuf, String)> = if projects {
+        let root = project::workspace_root(&cwd);
+        project::list_pins(&root, config.settings.pin_files())
+            .into_iter()
+            .map(|pin| (format!("{} {}", relative_dir(&root, &pin.dir), pin.version), pin.dir, pin.version))
+            .collect()
+    } else {
+        config.versions.iter().map(|e| (e.version.clone(), cwd.clone(), e.version.clone())).collect()
+    };
+
+    if runs.is_empty() {
+        return Err(anyhow::anyhow!(if projects { "No .php-version pins found" } else { "No PHP installations found" }));
+    }
+
+    // Every run gets the same stdin, so read a piped one up front
+    let input = process::buffer_piped_stdin()?;
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let mut results = Vec::new();
+
+    for (label, dir, pattern) in &runs {
+        let prefix = format!("[{}] ", label);
+
+        let Some(entry) = config.find_version(pattern) else {
+            eprintln!("{}{}", prefix, format!("PHP {} is not installed, skipping", pattern).red());
+            results.push(report::RunResult {
+                label: label.clone(),
+                outcome: report::Outcome::NotInstalled,
+                duration: std::time::Duration::ZERO,
+                output: Vec::new(),
+            });
+            continue;
+        };
+
+        let mut child = version_command(&config, entry, &path_var, command)?;
+        child.current_dir(dir);
+
+        let tail = process::Tail::default();
+        let started = std::time::Instant::now();
+        let status = process::run_prefixed(&mut child, &input, &prefix, &tail)?;
+        let outcome = if status.success() {
+            report::Outcome::Passed
+        } else {
+            eprintln!("{}{}", prefix, format!("exited with status {}", process::exit_code(status)).red());
+            report::Outcome::Failed { exit_code: process::exit_code(status) }
+        };
+
+        results.push(report::RunResult {
+            label: label.clone(),
+            outcome,
+            duration: started.elapsed(),
+            output: tail.lines(report::TAIL_LINES),
+        });
+    }
+
+    if let (Some(path), Some(format)) = (report_path, report_format) {
+        report::write(path, format, &command.join(" "), &results)?;
+        println!("{} Wrote the report to {}", style::note().dimmed(), path.display());
+    }
+
+    let failed = results.iter().filter(|r| r.outcome != report::Outcome::Passed).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} of {} runs failed", failed, runs.len()));
+    }
+
+    println!("{}", format!("{} {} runs succeeded", style::ok(), runs.len()).green());
+    Ok(())
+}
+
+fn stats_show() -> Result<()> {
+    let config = config::load_config()?;
+
+    println!("{}", "Usage Statistics".bold());
+    println!("Collection: {}\n", if config.settings.stats_enabled { "enabled".green() } else { "disabled".red() });
+
+    let stats = stats::load_stats()?;
+
+    if stats.switches.is_empty() && stats.scans.count == 0 {
+        println!("{}", "No statistics recorded yet.".yellow());
+        if !config.settings.stats_enabled {
+            println!("Enable collection with: php-switcher stats enable");
+        }
+        return Ok(());
+    }
+
+    println!("Switches per version:");
+    for (version, count) in stats.most_used() {
+        println!("  {}  {}", version.bold(), count);
+    }
+
+    println!("\nScans: {}", stats.scans.count);
+    if let Some(average) = stats.average_scan_ms() {
+        println!("  Average duration: {} ms", average);
+        println!("  Last duration: {} ms", stats.scans.last_ms);
+    }
 
     Ok(())
 }
 
+fn stats_toggle(enabled: bool) -> Result<()> {
+    let mut config = config::load_config()?;
+
+    config.settings.stats_enabled = enabled;
+    config::save_config(&config)?;
+
+    if enabled {
+        println!("{}", format!("{} Usage statistics enabled", style::ok()).green());
+        println!("Counters are stored locally in {} and never uploaded.", stats::get_stats_path()?.display());
+    } else {
+        println!("{}", format!("{} Usage statistics disabled", style::ok()).green());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+// Competing version managers module
+//
+// A `php` earlier on PATH than the switcher bin directory makes every switch
+// look like it did nothing. Usually that `php` belongs to another mechanism:
+// Debian's alternatives, a Homebrew link, or the shims of asdf, mise or
+// phpenv. Knowing which one lets `use` explain the conflict and offer the
+// right way out: adopting the mechanism as the link strategy where
+// php-switcher can drive it, or the steps that take it out of the way.
+
+use crate::config::LinkStrategyKind;
+use crate::link::BrewLink;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Something other than php-switcher deciding which `php` runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Manager {
+    /// `update-alternatives` (`/usr/bin/php` → `/etc/alternatives/php`)
+    Alternatives,
+    /// `brew link` into the Homebrew prefix
+    Homebrew,
+    Asdf,
+    Mise,
+    Phpenv,
+    /// A plain binary, e.g. from a distribution package
+    Unmanaged,
+}
+
+impl Manager {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Manager::Alternatives => "update-alternatives",
+            Manager::Homebrew => "Homebrew (brew link)",
+            Manager::Asdf => "asdf",
+            Manager::Mise => "mise",
+            Manager::Phpenv => "phpenv",
+            Manager::Unmanaged => "a system package",
+        }
+    }
+
+    /// The link strategy that switches through this mechanism, if there is one
+    pub fn adoptable_strategy(&self) -> Option<LinkStrategyKind> {
+        match self {
+            Manager::Alternatives => Some(LinkStrategyKind::UpdateAlternatives),
+            Manager::Homebrew => Some(LinkStrategyKind::BrewLink),
+            _ => None,
+        }
+    }
+
+    /// Steps that stop this mechanism from shadowing the switcher bin directory
+    pub fn neutralize_steps(&self, bin_dir: &Path) -> Vec<String> {
+        let path_first = format!("export PATH=\"{}:$PATH\"  # last line of ~/.bashrc or ~/.zshrc", bin_dir.display());
+        match self {
+            Manager::Asdf => vec![
+                "Remove php from .tool-versions files you don't want asdf to handle, or:".to_string(),
+                path_first,
+            ],
+            Manager::Mise => vec!["mise unuse --global php".to_string(), path_first],
+            Manager::Phpenv => vec!["phpenv global system".to_string(), path_first],
+            Manager::Alternatives | Manager::Homebrew | Manager::Unmanaged => vec![path_first],
+        }
+    }
+}
+
+/// A `php` that runs instead of the switcher's
+#[derive(Debug, Clone, PartialEq)]
+pub struct Competitor {
+    pub manager: Manager,
+    /// The `php` found on PATH ahead of the bin directory
+    pub path: PathBuf,
+}
+
+/// Find the `php` shadowing the bin directory on PATH, if any
+pub fn detect(path_var: &OsStr, bin_dir: &Path) -> Option<Competitor> {
+    for dir in std::env::split_paths(path_var) {
+        if dir == bin_dir {
+            return None;
+        }
+
+        let php = dir.join("php");
+        if php.is_file() {
+            let manager = classify(&php, std::fs::read_link(&php).ok().as_deref(), php.canonicalize().ok().as_deref());
+            return Some(Competitor { manager, path: php });
+        }
+    }
+    None
+}
+
+/// Work out who manages a `php`, from its path, its link target and where it finally resolves
+pub fn classify(php: &Path, link_target: Option<&Path>, resolved: Option<&Path>) -> Manager {
+    let has = |path: &Path, needle: &str| path.to_string_lossy().contains(needle);
+
+    if has(php, ".asdf/shims") {
+        Manager::Asdf
+    } else if has(php, "mise/shims") || has(php, "mise/installs") {
+        Manager::Mise
+    } else if has(php, ".phpenv/shims") {
+        Manager::Phpenv
+    } else if link_target.is_some_and(|target| target.starts_with("/etc/alternatives")) {
+        Manager::Alternatives
+    } else if resolved.and_then(BrewLink::formula).is_some() {
+        Manager::Homebrew
+    } else {
+        Manager::Unmanaged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let classify_path = |php: &str, target: Option<&str>, resolved: Option<&str>| {
+            classify(Path::new(php), target.map(Path::new), resolved.map(Path::new))
+        };
+
+        assert_eq!(classify_path("/home/dev/.asdf/shims/php", None, None), Manager::Asdf);
+        assert_eq!(classify_path("/home/dev/.local/share/mise/shims/php", None, None), Manager::Mise);
+        assert_eq!(classify_path("/home/dev/.phpenv/shims/php", None, None), Manager::Phpenv);
+        assert_eq!(
+            classify_path("/usr/bin/php", Some("/etc/alternatives/php"), Some("/usr/bin/php8.2")),
+            Manager::Alternatives
+        );
+        assert_eq!(
+            classify_path("/opt/homebrew/bin/php", None, Some("/opt/homebrew/Cellar/php@8.2/8.2.12/bin/php")),
+            Manager::Homebrew
+        );
+        assert_eq!(classify_path("/usr/bin/php", None, Some("/usr/bin/php")), Manager::Unmanaged);
+    }
+
+    #[test]
+    fn test_detect_stops_at_bin_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (shims, bin_dir) = (temp_dir.path().join(".asdf/shims"), temp_dir.path().join("bin"));
+        std::fs::create_dir_all(&shims).unwrap();
+        std::fs::write(shims.join("php"), "#!/bin/sh\n").unwrap();
+
+        let shadowed = std::env::join_paths([&shims, &bin_dir]).unwrap();
+        let competitor = detect(&shadowed, &bin_dir).unwrap();
+        assert_eq!(competitor.manager, Manager::Asdf);
+        assert_eq!(competitor.path, shims.join("php"));
+
+        let first = std::env::join_paths([&bin_dir, &shims]).unwrap();
+        assert_eq!(detect(&first, &bin_dir), None);
+        assert_eq!(Manager::Asdf.adoptable_strategy(), None);
+        assert_eq!(Manager::Alternatives.adoptable_strategy(), Some(LinkStrategyKind::UpdateAlternatives));
+    }
+}
@@ -0,0 +1,218 @@
+// Migration module
+//
+// People moving over from phpbrew or phpenv have their own names for builds
+// (`phpbrew install 8.2 --name=8.2-debug`, phpenv-aliases links such as
+// `versions/legacy -> 7.4.33`) and a version they had selected. Scans already
+// find the builds themselves; `import` carries the names over as labels and
+// the selected version over as the default.
+
+use crate::config::{self, Config};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Version managers whose names and selection can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Source {
+    Phpbrew,
+    Phpenv,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Phpbrew => "phpbrew",
+            Source::Phpenv => "phpenv",
+        }
+    }
+
+    /// The directory holding one subdirectory (or link) per build
+    pub fn versions_dir(&self, home: &Path) -> PathBuf {
+        match self {
+            Source::Phpbrew => home.join(".phpbrew/php"),
+            Source::Phpenv => home.join(".phpenv/versions"),
+        }
+    }
+
+    /// The build selected in the manager: its environment variable, else the file `use`/`global` writes
+    fn active(&self, home: &Path, env: impl Fn(&str) -> Option<OsString>) -> Option<String> {
+        let (var, file) = match self {
+            Source::Phpbrew => ("PHPBREW_PHP", home.join(".phpbrew/init")),
+            Source::Phpenv => ("PHPENV_VERSION", home.join(".phpenv/version")),
+        };
+        env(var)
+            .and_then(|value| value.into_string().ok())
+            .filter(|value| !value.is_empty())
+            .or_else(|| {
+                let content = std::fs::read_to_string(file).ok()?;
+                match self {
+                    Source::Phpbrew => parse_phpbrew_init(&content),
+                    Source::Phpenv => crate::switcher::parse_version_spec(&content),
+                }
+            })
+            .filter(|name| name != "system")
+    }
+}
+
+/// Read the selected build out of `~/.phpbrew/init` (`export PHPBREW_PHP=php-8.2.12`)
+pub fn parse_phpbrew_init(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let value = line.trim().trim_start_matches("export ").strip_prefix("PHPBREW_PHP=")?;
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+/// A build as the other manager knows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignVersion {
+    /// The name it goes by there, e.g. `8.2-debug`
+    pub name: String,
+    pub binary: PathBuf,
+}
+
+/// What a manager knows about its builds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignState {
+    pub source: Source,
+    pub versions: Vec<ForeignVersion>,
+    /// Name of the selected build
+    pub active: Option<String>,
+}
+
+/// Read a manager's builds and selection from the home directory
+pub fn read(source: Source, home: &Path, env: impl Fn(&str) -> Option<OsString>) -> ForeignState {
+    let mut versions: Vec<ForeignVersion> = std::fs::read_dir(source.versions_dir(home))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let binary = entry.path().join("bin/php");
+                    binary.is_file().then(|| ForeignVersion { name: entry.file_name().to_string_lossy().into_owned(), binary })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    ForeignState { source, versions, active: source.active(home, env) }
+}
+
+/// What `import` did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    /// Labels added, with the version they were added to
+    pub labels: Vec<(String, String)>,
+    /// Names not imported, with the reason
+    pub skipped: Vec<(String, String)>,
+    /// The version made the default
+    pub default: Option<String>,
+}
+
+/// Add the manager's names as labels and make its selection the default (unless one is set)
+///
+/// Names that are just a version (`8.2`, `php-8.2.12`) are left out,
+/// and labels already given to another version aren't moved.
+pub fn import(config: &mut Config, state: &ForeignState) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    let tracked = |config: &Config, binary: &Path| {
+        let binary = binary.canonicalize().ok()?;
+        config.versions.iter().position(|entry| {
+            entry.paths.iter().chain(&entry.aliases).any(|path| path.canonicalize().is_ok_and(|path| path == binary))
+        })
+    };
+
+    for foreign in &state.versions {
+        let Some(index) = tracked(config, &foreign.binary) else {
+            summary.skipped.push((foreign.name.clone(), "not tracked; run 'php-switcher scan' first".to_string()));
+            continue;
+        };
+        let version = config.versions[index].version.clone();
+        let name = foreign.name.as_str();
+        // Version numbers already work with `use`, as they are
+        let bare = name.strip_prefix("php-").unwrap_or(name);
+        if bare == version || bare.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            continue;
+        }
+        if !config::is_valid_label(name) {
+            summary.skipped.push((name.to_string(), "not a valid label".to_string()));
+            continue;
+        }
+        match config.versions.iter().find(|entry| entry.labels.iter().any(|label| label == name)) {
+            Some(entry) if entry.version == version => {}
+            Some(entry) => summary.skipped.push((name.to_string(), format!("already labels {}", entry.version))),
+            None => {
+                config.versions[index].labels.push(name.to_string());
+                summary.labels.push((name.to_string(), version));
+            }
+        }
+    }
+
+    let active = state.active.as_ref().and_then(|name| state.versions.iter().find(|foreign| &foreign.name == name));
+    if let Some(foreign) = active {
+        match (&config.settings.default_version, tracked(config, &foreign.binary)) {
+            (Some(default), _) => summary.skipped.push((foreign.name.clone(), format!("the default is already {}", default))),
+            (None, Some(index)) => {
+                let version = config.versions[index].version.clone();
+                config.settings.default_version = Some(version.clone());
+                summary.default = Some(version);
+            }
+            (None, None) => {}
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_phpbrew_init() {
+        assert_eq!(parse_phpbrew_init("export PHPBREW_ROOT=/home/me/.phpbrew\nexport PHPBREW_PHP=php-8.2.12\n").as_deref(), Some("php-8.2.12"));
+        assert_eq!(parse_phpbrew_init("PHPBREW_PHP=\"8.2-debug\"").as_deref(), Some("8.2-debug"));
+        assert_eq!(parse_phpbrew_init("export PHPBREW_PATH=/x\n"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_read_and_import_phpenv() {
+        use crate::config::VersionEntry;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path();
+        let versions = home.join(".phpenv/versions");
+        for version in ["7.4.33", "8.2.12"] {
+            std::fs::create_dir_all(versions.join(version).join("bin")).unwrap();
+            std::fs::write(versions.join(version).join("bin/php"), "").unwrap();
+        }
+        std::os::unix::fs::symlink(versions.join("7.4.33"), versions.join("legacy")).unwrap();
+        std::os::unix::fs::symlink(versions.join("8.2.12"), versions.join("8.2")).unwrap();
+        std::fs::write(home.join(".phpenv/version"), "legacy\n").unwrap();
+
+        let state = read(Source::Phpenv, home, |_| None);
+        let names: Vec<&str> = state.versions.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["7.4.33", "8.2", "8.2.12", "legacy"]);
+        assert_eq!(state.active.as_deref(), Some("legacy"));
+        assert_eq!(read(Source::Phpenv, home, |_| Some("system".into())).active, None);
+
+        let mut config = Config::default();
+        for version in ["7.4.33", "8.2.12"] {
+            let binary = versions.join(version).join("bin/php");
+            config.versions.push(VersionEntry { version: version.to_string(), paths: vec![binary], ..Default::default() });
+        }
+
+        let summary = import(&mut config, &state);
+        assert_eq!(summary.labels, [("legacy".to_string(), "7.4.33".to_string())]);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(summary.default.as_deref(), Some("7.4.33"));
+        assert_eq!(config.find_version("legacy").map(|e| e.version.as_str()), Some("7.4.33"));
+
+        // Importing again changes nothing, and keeps the default chosen since
+        config.settings.default_version = Some("8.2".to_string());
+        let again = import(&mut config, &state);
+        assert!(again.labels.is_empty());
+        assert_eq!(again.default, None);
+    }
+}
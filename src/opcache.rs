@@ -0,0 +1,135 @@
+// OPcache sanity check module
+//
+// Framework setups often configure `opcache.preload` with a script whose
+// includes point into version-specific paths (vendor dirs built for another
+// PHP, extension stubs, ...). Such a script only fails when PHP starts, so
+// after a switch `use --check-opcache` inspects `php --ri opcache` and starts
+// the new PHP once with preloading enabled to surface those failures early.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What `--ri opcache` and a preloading start-up revealed about an installation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpcacheReport {
+    /// Whether the opcache extension is loaded
+    pub loaded: bool,
+    /// Configured preload script, if any
+    pub preload: Option<PathBuf>,
+    pub problems: Vec<String>,
+}
+
+/// Parse the `Directive => Local Value => Master Value` lines of `php --ri`
+pub fn parse_directives(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(" => ")?;
+            let value = value.split(" => ").next()?.trim();
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Check the opcache and preload configuration of a PHP binary
+pub fn check(binary: &Path) -> OpcacheReport {
+    let mut report = OpcacheReport::default();
+
+    let output = match crate::detector::run_php(binary, &["--ri", "opcache"]) {
+        Ok(Some(output)) => output,
+        Ok(None) => {
+            report.problems.push(format!("{} --ri opcache didn't answer in time", binary.display()));
+            return report;
+        }
+        Err(e) => {
+            report.problems.push(format!("Failed to run {} --ri opcache: {}", binary.display(), e));
+            return report;
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || stdout.contains("not present") {
+        report.problems.push("opcache extension is not loaded".to_string());
+        return report;
+    }
+    report.loaded = true;
+
+    let directives = parse_directives(&stdout);
+    report.preload = directives
+        .get("opcache.preload")
+        .filter(|p| !p.is_empty() && p.as_str() != "no value")
+        .map(PathBuf::from);
+
+    let Some(preload) = &report.preload else { return report };
+    if !preload.is_file() {
+        report.problems.push(format!("opcache.preload script {} does not exist", preload.display()));
+        return report;
+    }
+
+    if let Some(problem) = warm_preload(binary) {
+        report.problems.push(problem);
+    }
+
+    report
+}
+
+/// Start PHP once with preloading enabled; returns the first error it printed
+fn warm_preload(binary: &Path) -> Option<String> {
+    let output = Command::new(binary)
+        .args(["-d", "opcache.enable_cli=1", "-r", "echo 'ok';"])
+        .output()
+        .ok()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if output.status.success() && stdout.trim() == "ok" {
+        return None;
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let first_error = stderr
+        .lines()
+        .chain(stdout.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty() && *line != "ok")
+        .unwrap_or("PHP exited with an error");
+    Some(format!("Preloading failed: {}", first_error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_directives() {
+        let output = "\nZend OPcache\n\nOpcode Caching => Up and Running\n\nDirective => Local Value => Master Value\nopcache.enable => On => On\nopcache.preload => /srv/app/config/preload.php => /srv/app/config/preload.php\nopcache.preload_user => no value => no value\n";
+        let directives = parse_directives(output);
+
+        assert_eq!(directives.get("opcache.enable").map(String::as_str), Some("On"));
+        assert_eq!(
+            directives.get("opcache.preload").map(String::as_str),
+            Some("/srv/app/config/preload.php")
+        );
+        assert_eq!(directives.get("opcache.preload_user").map(String::as_str), Some("no value"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_reports_missing_preload_script() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let php = temp_dir.path().join("php");
+        std::fs::write(
+            &php,
+            "#!/bin/sh\necho 'opcache.preload => /nonexistent/preload.php => /nonexistent/preload.php'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&php, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let report = check(&php);
+        assert!(report.loaded);
+        assert_eq!(report.preload, Some(PathBuf::from("/nonexistent/preload.php")));
+        assert_eq!(report.problems, vec!["opcache.preload script /nonexistent/preload.php does not exist"]);
+    }
+}
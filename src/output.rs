@@ -0,0 +1,270 @@
+// Machine-readable output module
+//
+// Every `--json` output is wrapped in an `Envelope` carrying `schema_version`.
+// The structs below are the stable contract for downstream tooling: adding an
+// optional field is fine, but removing/renaming a field or changing its meaning
+// requires bumping SCHEMA_VERSION.
+
+use crate::config::{ContainerImage, RuntimeEntry, ScanDiff, VersionEntry};
+use crate::detector::{BuildInfo, Exclusion, InstallLayout, PhpInstallation, ScanError};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Current version of the JSON output schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Top-level wrapper for all JSON output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Envelope<T> {
+    pub schema_version: u32,
+    pub kind: String,
+    pub data: T,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(kind: &str, data: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            kind: kind.to_string(),
+            data,
+        }
+    }
+}
+
+/// A single PHP version as reported by `list`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VersionInfo {
+    pub version: String,
+    #[serde(default, with = "crate::paths::option")]
+    pub primary_path: Option<PathBuf>,
+    #[serde(with = "crate::paths::vec")]
+    pub paths: Vec<PathBuf>,
+    pub source: String,
+    pub active: bool,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub layout: InstallLayout,
+    #[serde(default)]
+    pub build: BuildInfo,
+}
+
+impl VersionInfo {
+    pub fn from_entry(entry: &VersionEntry, active: bool) -> Self {
+        Self {
+            version: entry.version.clone(),
+            primary_path: entry.primary_path().cloned(),
+            paths: entry.paths.clone(),
+            source: entry.source.clone(),
+            active,
+            labels: entry.labels.clone(),
+            metadata: entry.metadata.clone(),
+            layout: entry.layout.clone(),
+            build: entry.build.clone(),
+        }
+    }
+}
+
+/// Output of `list --json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VersionList {
+    pub current: Option<String>,
+    /// Installed version `settings.default_version` resolves to
+    #[serde(default)]
+    pub default: Option<String>,
+    pub versions: Vec<VersionInfo>,
+    /// FrankenPHP/RoadRunner binaries found by the last scan
+    #[serde(default)]
+    pub runtimes: Vec<RuntimeEntry>,
+    /// Docker images of PHP; not native, so they can't be switched to
+    #[serde(default)]
+    pub containers: Vec<ContainerImage>,
+}
+
+/// A detected installation as reported by `scan`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstallationInfo {
+    pub version: String,
+    #[serde(default, with = "crate::paths::option")]
+    pub primary_path: Option<PathBuf>,
+    #[serde(with = "crate::paths::vec")]
+    pub paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub layout: InstallLayout,
+}
+
+impl From<&PhpInstallation> for InstallationInfo {
+    fn from(installation: &PhpInstallation) -> Self {
+        Self {
+            version: installation.version.to_string(),
+            primary_path: installation.primary_path().cloned(),
+            paths: installation.paths.clone(),
+            layout: installation.layout.clone(),
+        }
+    }
+}
+
+impl From<&VersionEntry> for InstallationInfo {
+    fn from(entry: &VersionEntry) -> Self {
+        Self {
+            version: entry.version.clone(),
+            primary_path: entry.primary_path().cloned(),
+            paths: entry.paths.clone(),
+            layout: entry.layout.clone(),
+        }
+    }
+}
+
+/// Output of `scan --dry-run --json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanPreview {
+    pub added: Vec<InstallationInfo>,
+    pub removed: Vec<InstallationInfo>,
+    pub updated: Vec<InstallationInfo>,
+}
+
+impl From<&ScanDiff> for ScanPreview {
+    fn from(diff: &ScanDiff) -> Self {
+        let infos = |entries: &[VersionEntry]| entries.iter().map(InstallationInfo::from).collect();
+        Self {
+            added: infos(&diff.added),
+            removed: infos(&diff.removed),
+            updated: infos(&diff.updated),
+        }
+    }
+}
+
+/// Output of `scan --json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanResult {
+    pub installations: Vec<InstallationInfo>,
+    /// Directories and binaries that couldn't be read or run
+    #[serde(default)]
+    pub errors: Vec<ScanError>,
+    /// Binaries left out by `exclude_paths` and `exclude_versions`
+    #[serde(default)]
+    pub excluded: Vec<Exclusion>,
+    /// Binaries named like a PHP version that couldn't be run
+    #[serde(default)]
+    pub broken: Vec<crate::config::BrokenInstall>,
+}
+
+/// Output of `info [version] --json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstallationDetails {
+    pub version: String,
+    pub short_version: String,
+    #[serde(with = "crate::paths::path")]
+    pub primary_path: PathBuf,
+    #[serde(with = "crate::paths::vec")]
+    pub paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default, with = "crate::paths::option")]
+    pub ini_file: Option<PathBuf>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub layout: InstallLayout,
+    #[serde(default)]
+    pub build: BuildInfo,
+    /// Other paths leading to the same binaries
+    #[serde(default, with = "crate::paths::vec")]
+    pub aliases: Vec<PathBuf>,
+}
+
+/// Output of `info --self --json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelfInfo {
+    pub version: String,
+    #[serde(with = "crate::paths::path")]
+    pub config_file: PathBuf,
+    #[serde(default, with = "crate::paths::option")]
+    pub system_config_file: Option<PathBuf>,
+    pub tracked_versions: usize,
+    pub last_scan: Option<String>,
+}
+
+/// A `.php-version` pin as reported by `project list-pins`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinInfo {
+    #[serde(with = "crate::paths::path")]
+    pub dir: PathBuf,
+    pub version: String,
+    /// Tracked version the pin resolves to, if any
+    pub resolved: Option<String>,
+}
+
+/// Output of `project list-pins --json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinList {
+    #[serde(with = "crate::paths::path")]
+    pub root: PathBuf,
+    pub pins: Vec<PinInfo>,
+}
+
+/// Serialize a value wrapped in an envelope
+pub fn to_json<T: Serialize>(kind: &str, data: &T) -> Result<String> {
+    serde_json::to_string_pretty(&Envelope::new(kind, data))
+        .map_err(|e| anyhow!("Failed to serialize JSON output: {}", e))
+}
+
+/// Print a value wrapped in an envelope to stdout
+pub fn print_json<T: Serialize>(kind: &str, data: &T) -> Result<()> {
+    println!("{}", to_json(kind, data)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::PhpVersion;
+
+    #[test]
+    fn test_envelope_has_schema_version() {
+        let json = to_json("version_list", &VersionList { current: None, default: None, versions: Vec::new(), runtimes: Vec::new(), containers: Vec::new() }).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["kind"], "version_list");
+        assert!(value["data"]["versions"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_version_info_from_entry() {
+        let entry = VersionEntry {
+            version: "8.2.12".to_string(),
+            paths: vec![PathBuf::from("/usr/bin/php-cgi"), PathBuf::from("/usr/bin/php")],
+            source: "auto".to_string(),
+            labels: vec!["legacy-billing".to_string()],
+            ..VersionEntry::default()
+        };
+
+        let info = VersionInfo::from_entry(&entry, true);
+        assert_eq!(info.primary_path, Some(PathBuf::from("/usr/bin/php")));
+        assert!(info.active);
+        assert_eq!(info.labels, vec!["legacy-billing"]);
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let installation = PhpInstallation::new(PhpVersion::new(8, 3, 1), PathBuf::from("/usr/bin/php8.3"));
+        let result = ScanResult { installations: vec![InstallationInfo::from(&installation)], errors: Vec::new(), excluded: Vec::new(), broken: Vec::new() };
+
+        let json = to_json("scan_result", &result).unwrap();
+        let parsed: Envelope<ScanResult> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.schema_version, SCHEMA_VERSION);
+        assert_eq!(parsed.data, result);
+    }
+}
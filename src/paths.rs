@@ -0,0 +1,145 @@
+// Path serialization module
+//
+// serde refuses to serialize a `PathBuf` that isn't valid UTF-8, and neither
+// TOML nor JSON strings can hold one. These helpers (used via
+// `#[serde(with = "...")]`) write UTF-8 paths as plain strings, so existing
+// files are unchanged, and anything else as `{ bytes = [...] }` with the raw
+// OS bytes so it round-trips without being corrupted or dropped.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Text(String),
+    Bytes { bytes: Vec<u8> },
+}
+
+impl Repr {
+    fn from_path(path: &Path) -> Self {
+        match path.to_str() {
+            Some(text) => Repr::Text(text.to_string()),
+            None => Repr::Bytes { bytes: os_bytes(path) },
+        }
+    }
+
+    fn into_path(self) -> PathBuf {
+        match self {
+            Repr::Text(text) => PathBuf::from(text),
+            Repr::Bytes { bytes } => path_from_bytes(bytes),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn os_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Convert raw bytes (e.g. command output) into a path without lossy decoding
+pub fn from_bytes(bytes: &[u8]) -> PathBuf {
+    path_from_bytes(bytes.to_vec())
+}
+
+/// For `PathBuf` fields
+pub mod path {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        Repr::from_path(path).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Ok(Repr::deserialize(deserializer)?.into_path())
+    }
+}
+
+/// For `Vec<PathBuf>` fields
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|p| Repr::from_path(p)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+        Ok(Vec::<Repr>::deserialize(deserializer)?.into_iter().map(Repr::into_path).collect())
+    }
+}
+
+/// For `Option<PathBuf>` fields (combine with `#[serde(default)]`)
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+        path.as_deref().map(Repr::from_path).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<Repr>::deserialize(deserializer)?.map(Repr::into_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::ffi::OsStr;
+    #[cfg(unix)]
+    use std::os::unix::ffi::OsStrExt;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Entry {
+        #[serde(with = "vec")]
+        paths: Vec<PathBuf>,
+        #[serde(default, with = "option")]
+        template: Option<PathBuf>,
+    }
+
+    #[cfg(unix)]
+    fn non_utf8() -> PathBuf {
+        PathBuf::from(OsStr::from_bytes(b"/opt/caf\xe9/bin/php"))
+    }
+
+    #[test]
+    fn test_utf8_paths_stay_plain_strings() {
+        let entry = Entry { paths: vec![PathBuf::from("/usr/bin/php")], template: None };
+        let toml_str = toml::to_string(&entry).unwrap();
+
+        assert_eq!(toml_str.trim(), "paths = [\"/usr/bin/php\"]");
+        assert_eq!(toml::from_str::<Entry>(&toml_str).unwrap(), entry);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_non_utf8_paths_roundtrip() {
+        let entry = Entry {
+            paths: vec![PathBuf::from("/usr/bin/php"), non_utf8()],
+            template: Some(non_utf8()),
+        };
+
+        let toml_str = toml::to_string(&entry).unwrap();
+        assert_eq!(toml::from_str::<Entry>(&toml_str).unwrap(), entry);
+
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), entry);
+    }
+}
@@ -1,5 +1,7 @@
 // Linux-specific implementation
 
+use std::path::Path;
+
 pub fn get_common_php_paths() -> Vec<&'static str> {
     vec![
         "/usr/bin/php",
@@ -14,5 +16,14 @@ pub fn get_scan_patterns() -> Vec<&'static str> {
         "/usr/local/bin/php*",
         "/usr/lib/php*",
         "/opt/php*",
+        "/home/linuxbrew/.linuxbrew/bin/php*",
+        "/home/linuxbrew/.linuxbrew/Cellar/php*",
     ]
 }
+
+/// Check whether this is WSL, where Windows drives are mounted under `/mnt` and `.exe` files run through interop
+pub fn is_wsl() -> bool {
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists()
+        || std::fs::read_to_string("/proc/sys/kernel/osrelease").is_ok_and(|release| release.to_ascii_lowercase().contains("microsoft"))
+}
@@ -5,6 +5,7 @@ pub fn get_common_php_paths() -> Vec<&'static str> {
         "/usr/bin/php",
         "/usr/local/bin/php",
         "/opt/homebrew/bin/php",
+        "/opt/local/bin/php",
     ]
 }
 
@@ -14,5 +15,6 @@ pub fn get_scan_patterns() -> Vec<&'static str> {
         "/opt/homebrew/Cellar/php*",
         "/usr/local/bin/php*",
         "/opt/homebrew/bin/php*",
+        "/opt/local/bin/php*",
     ]
 }
@@ -1,23 +1,31 @@
 // Platform abstraction module
 
+use crate::config::LinkStrategyKind;
+
 #[cfg(target_os = "linux")]
 mod linux;
 
 #[cfg(target_os = "macos")]
 mod macos;
 
+pub mod windows;
+
 #[cfg(target_os = "linux")]
 pub use linux::*;
 
 #[cfg(target_os = "macos")]
 pub use macos::*;
 
+#[cfg(windows)]
+pub use windows::*;
+
 /// Detected operating system platform
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Platform {
     Linux,
     MacOS,
     BSD,
+    Windows,
     Other,
 }
 
@@ -44,7 +52,13 @@ impl Platform {
             Platform::BSD
         }
 
+        #[cfg(windows)]
+        {
+            Platform::Windows
+        }
+
         #[cfg(not(any(
+            windows,
             target_os = "linux",
             target_os = "macos",
             target_os = "freebsd",
@@ -57,21 +71,179 @@ impl Platform {
         }
     }
 
+    /// How binaries are exposed when `settings.link_strategy` isn't set
+    pub fn default_link_strategy(&self) -> LinkStrategyKind {
+        match self {
+            Platform::Linux | Platform::MacOS | Platform::BSD => LinkStrategyKind::Symlink,
+            Platform::Windows | Platform::Other => LinkStrategyKind::CmdShim,
+        }
+    }
+
     /// Get a human-readable name for the platform
     pub fn name(&self) -> &'static str {
         match self {
             Platform::Linux => "Linux",
             Platform::MacOS => "macOS",
             Platform::BSD => "BSD",
+            Platform::Windows => "Windows",
             Platform::Other => "Unknown",
         }
     }
 }
 
+/// Get the process umask
+///
+/// Read once and cached: where `/proc/self/status` doesn't report it, the only
+/// way to read it is to set it, and swapping it while other threads create
+/// files would give those files the wrong mode. Call this early, before
+/// spawning threads.
+#[cfg(unix)]
+pub fn umask() -> u32 {
+    static UMASK: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    *UMASK.get_or_init(|| read_proc_umask().unwrap_or_else(swap_umask))
+}
+
+/// Read the umask from `/proc/self/status` (Linux 4.7+), without changing it
+#[cfg(unix)]
+fn read_proc_umask() -> Option<u32> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("Umask:"))?;
+    u32::from_str_radix(line.trim(), 8).ok()
+}
+
+/// Read the umask by briefly swapping it and restoring it
+#[cfg(unix)]
+fn swap_umask() -> u32 {
+    // SAFETY: umask(2) cannot fail; the original mask is restored immediately
+    unsafe {
+        let mask = libc::umask(0o022);
+        libc::umask(mask);
+        mask as u32
+    }
+}
+
+/// Restrict a configured mode by the umask, so a restrictive umask always wins
+#[cfg(unix)]
+pub fn apply_umask(mode: u32) -> u32 {
+    mode & !umask()
+}
+
+/// Login shell, as far as command hashing and syntax are concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ShellKind {
+    /// bash, zsh, sh and other POSIX shells
+    Posix,
+    /// csh and tcsh
+    Csh,
+    /// fish, which doesn't cache command locations
+    Fish,
+}
+
+impl ShellKind {
+    /// Detect the user's shell from `$SHELL`
+    pub fn detect() -> Self {
+        Self::from_path(&std::env::var("SHELL").unwrap_or_default())
+    }
+
+    fn from_path(shell: &str) -> Self {
+        match shell.rsplit('/').next().unwrap_or_default() {
+            "fish" => ShellKind::Fish,
+            "csh" | "tcsh" => ShellKind::Csh,
+            _ => ShellKind::Posix,
+        }
+    }
+
+    /// Command that makes a running shell forget cached command locations
+    pub fn rehash_command(&self) -> Option<&'static str> {
+        match self {
+            ShellKind::Posix => Some("hash -r"),
+            ShellKind::Csh => Some("rehash"),
+            ShellKind::Fish => None,
+        }
+    }
+}
+
+/// Terminal multiplexer the switch is running in
+///
+/// Every pane or window runs its own long-lived shell, each with its own
+/// command hash, so a switch in one pane leaves the others stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    /// Detect tmux (`$TMUX`) or GNU screen (`$STY`)
+    pub fn detect() -> Option<Self> {
+        Self::from_env(|name| std::env::var_os(name).filter(|v| !v.is_empty()).is_some())
+    }
+
+    fn from_env(is_set: impl Fn(&str) -> bool) -> Option<Self> {
+        if is_set("TMUX") {
+            Some(Multiplexer::Tmux)
+        } else if is_set("STY") {
+            Some(Multiplexer::Screen)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Multiplexer::Tmux => "tmux",
+            Multiplexer::Screen => "screen",
+        }
+    }
+
+    /// One-liner running the rehash command in every pane sitting at a prompt of that kind of shell
+    ///
+    /// Only tmux can tell which program a pane is running; screen would type
+    /// into editors and REPLs as well, so it gets no broadcast.
+    pub fn broadcast_command(&self, shell: ShellKind) -> Option<String> {
+        let rehash = shell.rehash_command()?;
+        let shells = match shell {
+            ShellKind::Posix => "(ba|z|k|da)?sh",
+            ShellKind::Csh => "t?csh",
+            ShellKind::Fish => return None,
+        };
+
+        match self {
+            Multiplexer::Tmux => Some(format!(
+                "tmux list-panes -a -F '#{{pane_id}} #{{pane_current_command}}' | awk '$2 ~ /^-?{}$/ {{print $1}}' | xargs -I{{}} tmux send-keys -t {{}} '{}' Enter",
+                shells, rehash
+            )),
+            Multiplexer::Screen => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_shell_rehash_command() {
+        assert_eq!(ShellKind::from_path("/bin/bash").rehash_command(), Some("hash -r"));
+        assert_eq!(ShellKind::from_path("/usr/bin/zsh").rehash_command(), Some("hash -r"));
+        assert_eq!(ShellKind::from_path("/bin/tcsh").rehash_command(), Some("rehash"));
+        assert_eq!(ShellKind::from_path("/usr/local/bin/fish").rehash_command(), None);
+        assert_eq!(ShellKind::from_path("").rehash_command(), Some("hash -r"));
+    }
+
+    #[test]
+    fn test_multiplexer_detection() {
+        assert_eq!(Multiplexer::from_env(|name| name == "TMUX"), Some(Multiplexer::Tmux));
+        assert_eq!(Multiplexer::from_env(|name| name == "STY"), Some(Multiplexer::Screen));
+        assert_eq!(Multiplexer::from_env(|_| false), None);
+
+        let broadcast = Multiplexer::Tmux.broadcast_command(ShellKind::Posix).unwrap();
+        assert!(broadcast.contains("send-keys -t {} 'hash -r' Enter"));
+        let broadcast = Multiplexer::Tmux.broadcast_command(ShellKind::Csh).unwrap();
+        assert!(broadcast.contains("/^-?t?csh$/") && broadcast.contains("'rehash' Enter"));
+        assert_eq!(Multiplexer::Screen.broadcast_command(ShellKind::Posix), None);
+    }
+
     #[test]
     fn test_platform_detect() {
         let platform = Platform::detect();
@@ -80,7 +252,7 @@ mod tests {
         // We just verify it returns a valid Platform variant
         assert!(matches!(
             platform,
-            Platform::Linux | Platform::MacOS | Platform::BSD | Platform::Other
+            Platform::Linux | Platform::MacOS | Platform::BSD | Platform::Windows | Platform::Other
         ));
     }
 
@@ -89,9 +261,25 @@ mod tests {
         assert_eq!(Platform::Linux.name(), "Linux");
         assert_eq!(Platform::MacOS.name(), "macOS");
         assert_eq!(Platform::BSD.name(), "BSD");
+        assert_eq!(Platform::Windows.name(), "Windows");
         assert_eq!(Platform::Other.name(), "Unknown");
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_apply_umask() {
+        // A mode can only lose bits to the umask, never gain them
+        assert_eq!(apply_umask(0o755) & !0o755, 0);
+        assert_eq!(apply_umask(0o777), 0o777 & !umask());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_umask_is_read_from_proc() {
+        // Swapping it here would race the other tests' file creation
+        assert_eq!(read_proc_umask(), Some(umask()));
+    }
+
     #[test]
     fn test_current_platform_name() {
         let platform = Platform::detect();
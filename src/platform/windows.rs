@@ -0,0 +1,147 @@
+// Windows-specific implementation
+//
+// Windows PHP builds are plain directories holding `php.exe`, placed by
+// whichever installer was used. The layouts below are compiled on every
+// platform so they can be tested anywhere; only `platform::detect` and the
+// detector decide to use them. Under WSL the same layouts are scanned on the
+// Windows drives mounted at `/mnt/c` and friends.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+pub fn get_common_php_paths() -> Vec<&'static str> {
+    vec![
+        r"C:\php\php.exe",
+        r"C:\xampp\php\php.exe",
+        r"C:\tools\php\php.exe",
+    ]
+}
+
+pub fn get_scan_patterns() -> Vec<&'static str> {
+    vec![
+        r"C:\php*",
+        r"C:\tools\php*",
+        r"C:\xampp\php",
+        r"C:\laragon\bin\php\php*",
+        r"%USERPROFILE%\scoop\apps\php*",
+    ]
+}
+
+/// A directory to look for `php.exe` in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanRoot {
+    pub dir: PathBuf,
+    /// Directory levels between `dir` and `php.exe`; the first level only counts `php*` directories
+    pub depth: usize,
+}
+
+impl ScanRoot {
+    fn new(dir: PathBuf, depth: usize) -> Self {
+        Self { dir, depth }
+    }
+}
+
+/// Where Windows installers put PHP, given a way to read environment variables
+///
+/// - `C:\php` (the windows.php.net zip, by convention) and XAMPP's `C:\xampp\php`
+/// - Chocolatey: `C:\tools\php83` (or under `ChocolateyToolsLocation`)
+/// - Scoop: `~\scoop\apps\php82\8.2.12` (or under `SCOOP`)
+/// - Laragon: `C:\laragon\bin\php\php-8.2.12-Win32-vs16-x64`
+pub fn scan_roots(env: impl Fn(&str) -> Option<OsString>) -> Vec<ScanRoot> {
+    let drive = PathBuf::from(format!("{}\\", env("SystemDrive").unwrap_or_else(|| "C:".into()).to_string_lossy()));
+    let chocolatey_tools = env("ChocolateyToolsLocation").map(PathBuf::from).unwrap_or_else(|| drive.join("tools"));
+    let scoop = env("SCOOP")
+        .map(PathBuf::from)
+        .or_else(|| env("USERPROFILE").map(|home| Path::new(&home).join("scoop")));
+    roots_on(&drive, chocolatey_tools, scoop.into_iter().collect())
+}
+
+/// The same places on a drive WSL mounted (`/mnt/c`), with every user's Scoop
+pub fn wsl_scan_roots(drive: &Path, user_dirs: &[PathBuf]) -> Vec<ScanRoot> {
+    roots_on(drive, drive.join("tools"), user_dirs.iter().map(|home| home.join("scoop")).collect())
+}
+
+fn roots_on(drive: &Path, chocolatey_tools: PathBuf, scoops: Vec<PathBuf>) -> Vec<ScanRoot> {
+    let mut roots = vec![
+        ScanRoot::new(drive.join("php"), 0),
+        ScanRoot::new(drive.join("xampp").join("php"), 0),
+        ScanRoot::new(chocolatey_tools, 1),
+        ScanRoot::new(drive.join("laragon").join("bin").join("php"), 1),
+    ];
+    roots.extend(scoops.into_iter().map(|scoop| ScanRoot::new(scoop.join("apps"), 2)));
+    roots
+}
+
+/// PowerShell commands putting the bin directory first on PATH, for this session and for good
+pub fn powershell_path_instructions(bin_dir: &Path) -> Vec<String> {
+    let dir = bin_dir.display().to_string().replace('\'', "''");
+    vec![
+        format!("$env:Path = '{};' + $env:Path", dir),
+        format!(
+            "[Environment]::SetEnvironmentVariable('Path', '{};' + [Environment]::GetEnvironmentVariable('Path', 'User'), 'User')",
+            dir
+        ),
+    ]
+}
+
+/// Quote a value for a `cmd.exe` batch file
+///
+/// Inside double quotes only `"` and `%` need care: quotes are doubled and
+/// percent signs escaped so they aren't expanded as variables.
+pub fn cmd_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\"").replace('%', "%%"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_roots() {
+        let env = |name: &str| match name {
+            "SystemDrive" => Some(OsString::from("D:")),
+            "USERPROFILE" => Some(OsString::from(r"D:\Users\dev")),
+            _ => None,
+        };
+
+        // Built with `join`, so the expected paths work with either separator
+        let drive = Path::new("D:\\");
+        assert_eq!(
+            scan_roots(env),
+            [
+                ScanRoot::new(drive.join("php"), 0),
+                ScanRoot::new(drive.join("xampp").join("php"), 0),
+                ScanRoot::new(drive.join("tools"), 1),
+                ScanRoot::new(drive.join("laragon").join("bin").join("php"), 1),
+                ScanRoot::new(Path::new(r"D:\Users\dev").join("scoop").join("apps"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wsl_scan_roots() {
+        let drive = Path::new("/mnt/c");
+        let users = [drive.join("Users").join("dev"), drive.join("Users").join("ops")];
+        assert_eq!(
+            wsl_scan_roots(drive, &users),
+            [
+                ScanRoot::new(drive.join("php"), 0),
+                ScanRoot::new(drive.join("xampp").join("php"), 0),
+                ScanRoot::new(drive.join("tools"), 1),
+                ScanRoot::new(drive.join("laragon").join("bin").join("php"), 1),
+                ScanRoot::new(users[0].join("scoop").join("apps"), 2),
+                ScanRoot::new(users[1].join("scoop").join("apps"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_powershell_and_cmd_quoting() {
+        let lines = powershell_path_instructions(Path::new(r"C:\Users\o'neil\.php-switcher\bin"));
+        assert_eq!(lines[0], r"$env:Path = 'C:\Users\o''neil\.php-switcher\bin;' + $env:Path");
+        assert!(lines[1].ends_with("GetEnvironmentVariable('Path', 'User'), 'User')"));
+
+        assert_eq!(cmd_quote(r"C:\php 8\php.exe"), r#""C:\php 8\php.exe""#);
+        assert_eq!(cmd_quote("100%\"done\""), r#""100%%""done""""#);
+    }
+}
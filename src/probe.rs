@@ -0,0 +1,376 @@
+// Installation probe cache module
+//
+// Extensions (`php -m`), ini locations (`php --ini`) and build information
+// (`php -i`) are needed by info, extension checks and prompt-time features.
+// Spawning PHP three times per query is too slow for that, so the parsed
+// results are cached in `~/.php-switcher/probes.json`, per binary and keyed
+// on its modification time and size so upgrades invalidate them. Loaded
+// extensions also depend on the ini files, so editing those (e.g. enabling
+// an extension) invalidates the entry as well.
+//
+// Scans run `php -v` on every candidate binary; the versions are cached the
+// same way in `~/.php-switcher/version-probes.json`, so repeated scans only
+// run binaries that changed. `--no-cache` ignores both caches for a run.
+
+use crate::version::PhpVersion;
+use crate::{config, detector};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Set (to anything but `0`) by `--no-cache` to run binaries instead of trusting cached probes
+pub const NO_CACHE_ENV: &str = "PHP_SWITCHER_NO_CACHE";
+
+/// Check whether cached probes must be ignored for this run
+pub fn is_cache_disabled() -> bool {
+    std::env::var_os(NO_CACHE_ENV).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// `php -i` entries kept as build information
+const BUILD_KEYS: &[&str] = &[
+    "Architecture",
+    "Debug Build",
+    "Thread Safety",
+    "Server API",
+    "Zend Extension Build",
+    "PHP Extension Build",
+    "extension_dir",
+];
+
+/// Parsed facts about one PHP binary
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Probe {
+    pub extensions: Vec<String>,
+    #[serde(default, with = "crate::paths::option")]
+    pub ini_file: Option<PathBuf>,
+    #[serde(default, with = "crate::paths::vec")]
+    pub ini_scan_dirs: Vec<PathBuf>,
+    #[serde(default)]
+    pub build: BTreeMap<String, String>,
+}
+
+/// Identity of a binary on disk; a change means the cached probe is stale
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub size: u64,
+    /// Catches a file replaced by another of the same size within the mtime resolution
+    #[serde(default)]
+    pub inode: u64,
+}
+
+impl Fingerprint {
+    /// Fingerprint the file a binary (or a symlink to it) resolves to
+    pub fn of(binary: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(binary).ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&metadata);
+        #[cfg(not(unix))]
+        let inode = 0;
+        Some(Self {
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            size: metadata.len(),
+            inode,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct CacheEntry {
+    #[serde(with = "crate::paths::path")]
+    binary: PathBuf,
+    fingerprint: Fingerprint,
+    /// Newest modification of the ini files the probe saw
+    ini_stamp: u64,
+    probe: Probe,
+}
+
+/// Newest modification time (in nanoseconds) of a probe's ini files and directories
+fn ini_stamp(probe: &Probe) -> u64 {
+    let mut paths: Vec<PathBuf> = probe.ini_file.iter().cloned().collect();
+    for dir in &probe.ini_scan_dirs {
+        paths.push(dir.clone());
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            paths.extend(entries.flatten().map(|e| e.path()));
+        }
+    }
+
+    paths
+        .iter()
+        .filter_map(|p| std::fs::metadata(p).ok()?.modified().ok()?.duration_since(UNIX_EPOCH).ok())
+        .map(|mtime| mtime.as_nanos() as u64)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Cached probes of all binaries
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProbeCache {
+    entries: Vec<CacheEntry>,
+}
+
+impl ProbeCache {
+    /// Get the cached probe of a binary, if neither it nor its ini files changed since
+    pub fn get(&self, binary: &Path, fingerprint: Fingerprint) -> Option<&Probe> {
+        self.entries
+            .iter()
+            .find(|e| e.binary == binary && e.fingerprint == fingerprint && e.ini_stamp == ini_stamp(&e.probe))
+            .map(|e| &e.probe)
+    }
+
+    /// Get the last probe recorded for a binary, however old
+    ///
+    /// For offline inspection of a copied state directory, where the
+    /// binaries can't be fingerprinted.
+    pub fn last_known(&self, binary: &Path) -> Option<&Probe> {
+        self.entries.iter().find(|e| e.binary == binary).map(|e| &e.probe)
+    }
+
+    /// Store the probe of a binary, replacing any older one
+    pub fn insert(&mut self, binary: &Path, fingerprint: Fingerprint, probe: Probe) {
+        self.entries.retain(|e| e.binary != binary);
+        self.entries.push(CacheEntry {
+            binary: binary.to_path_buf(),
+            fingerprint,
+            ini_stamp: ini_stamp(&probe),
+            probe,
+        });
+    }
+}
+
+/// Get the path of the probe cache
+pub fn get_cache_path() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join("probes.json"))
+}
+
+/// Load the probe cache from a file
+pub fn load_cache_from_file<P: AsRef<Path>>(path: P) -> Result<ProbeCache> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Ok(ProbeCache::default());
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read probe cache: {}", e))?;
+
+    serde_json::from_str(&contents).map_err(|e| anyhow!("Failed to parse probe cache: {}", e))
+}
+
+/// Save the probe cache to a file
+pub fn save_cache_to_file<P: AsRef<Path>>(cache: &ProbeCache, path: P) -> Result<()> {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Failed to create cache directory: {}", e))?;
+    }
+
+    let json = serde_json::to_string(cache).map_err(|e| anyhow!("Failed to serialize probe cache: {}", e))?;
+    std::fs::write(path, json).map_err(|e| anyhow!("Failed to write probe cache: {}", e))
+}
+
+/// Probe a binary, answering from the cache when it hasn't changed
+///
+/// A cache that can't be read or written (read-only mode, no home) only
+/// costs speed, never correctness.
+pub fn probe(binary: &Path) -> Probe {
+    let binary = binary.canonicalize().unwrap_or_else(|_| binary.to_path_buf());
+    let Some(fingerprint) = Fingerprint::of(&binary) else { return run_probe(&binary) };
+
+    let path = get_cache_path().ok();
+    let mut cache = path
+        .as_ref()
+        .and_then(|p| load_cache_from_file(p).ok())
+        .unwrap_or_default();
+
+    if let Some(probe) = cache.get(&binary, fingerprint).filter(|_| !is_cache_disabled()) {
+        return probe.clone();
+    }
+
+    let probe = run_probe(&binary);
+    cache.insert(&binary, fingerprint, probe.clone());
+    if let (Some(path), false) = (path, config::is_read_only()) {
+        save_cache_to_file(&cache, path).ok();
+    }
+
+    probe
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct VersionCacheEntry {
+    #[serde(with = "crate::paths::path")]
+    binary: PathBuf,
+    fingerprint: Fingerprint,
+    version: String,
+}
+
+/// Cached `-v` results of scanned binaries, by the path they were found at
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct VersionCache {
+    entries: Vec<VersionCacheEntry>,
+}
+
+impl VersionCache {
+    /// Get the cached version of a binary, if it hasn't changed since
+    pub fn get(&self, binary: &Path, fingerprint: Fingerprint) -> Option<PhpVersion> {
+        let entry = self.entries.iter().find(|e| e.binary == binary && e.fingerprint == fingerprint)?;
+        PhpVersion::from_php_output(&format!("PHP {}", entry.version)).ok()
+    }
+
+    /// Store the version of a binary, replacing any older one
+    pub fn insert(&mut self, binary: &Path, fingerprint: Fingerprint, version: &PhpVersion) {
+        self.entries.retain(|e| e.binary != binary);
+        self.entries.push(VersionCacheEntry { binary: binary.to_path_buf(), fingerprint, version: version.to_string() });
+    }
+
+    /// Forget binaries that are gone
+    pub fn retain_existing(&mut self) {
+        self.entries.retain(|e| e.binary.exists());
+    }
+}
+
+/// Get the path of the version cache
+pub fn get_version_cache_path() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join("version-probes.json"))
+}
+
+/// Load the version cache, starting over when it's missing or unreadable
+pub fn load_version_cache() -> VersionCache {
+    get_version_cache_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Save the version cache (skipped in read-only mode)
+pub fn save_version_cache(cache: &VersionCache) -> Result<()> {
+    if config::is_read_only() {
+        return Ok(());
+    }
+    let path = get_version_cache_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| anyhow!("Failed to create cache directory: {}", e))?;
+    }
+    let json = serde_json::to_string(cache).map_err(|e| anyhow!("Failed to serialize version cache: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| anyhow!("Failed to write version cache: {}", e))
+}
+
+/// Query a binary directly, bypassing the cache
+pub fn run_probe(binary: &Path) -> Probe {
+    let (ini_file, ini_scan_dirs) = detector::get_ini_locations(binary);
+    let build = detector::run_php(binary, &["-i"])
+        .ok()
+        .flatten()
+        .map(|output| parse_build_info(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default();
+
+    Probe {
+        extensions: detector::get_extensions(binary),
+        ini_file,
+        ini_scan_dirs,
+        build,
+    }
+}
+
+/// Pick the build information out of `php -i` (`Key => value` lines)
+pub fn parse_build_info(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(" => ")?;
+            let key = key.trim();
+            // Directives print "local => master"; the local value is what applies
+            let value = value.split(" => ").next()?.trim();
+            BUILD_KEYS.contains(&key).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_build_info() {
+        let output = "phpinfo()\nPHP Version => 8.2.12\n\nArchitecture => x86_64\nThread Safety => disabled\nextension_dir => /usr/lib/php/20220829 => /usr/lib/php/20220829\nmemory_limit => 128M => 128M\n";
+        let build = parse_build_info(output);
+
+        assert_eq!(build.get("Architecture").map(String::as_str), Some("x86_64"));
+        assert_eq!(build.get("Thread Safety").map(String::as_str), Some("disabled"));
+        assert_eq!(build.get("extension_dir").map(String::as_str), Some("/usr/lib/php/20220829"));
+        assert!(!build.contains_key("memory_limit"));
+    }
+
+    #[test]
+    fn test_cache_invalidated_by_fingerprint() {
+        let temp_dir = TempDir::new().unwrap();
+        let binary = temp_dir.path().join("php");
+        std::fs::write(&binary, "old").unwrap();
+        let fingerprint = Fingerprint::of(&binary).unwrap();
+
+        let probe = Probe { extensions: vec!["Core".to_string()], ..Probe::default() };
+        let mut cache = ProbeCache::default();
+        cache.insert(&binary, fingerprint, probe.clone());
+
+        let cache_file = temp_dir.path().join("probes.json");
+        save_cache_to_file(&cache, &cache_file).unwrap();
+        let cache = load_cache_from_file(&cache_file).unwrap();
+        assert_eq!(cache.get(&binary, fingerprint), Some(&probe));
+
+        // An upgraded binary has a different size (and usually mtime)
+        std::fs::write(&binary, "upgraded").unwrap();
+        assert_eq!(cache.get(&binary, Fingerprint::of(&binary).unwrap()), None);
+    }
+
+    #[test]
+    fn test_version_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let binary = temp_dir.path().join("php8.2");
+        std::fs::write(&binary, "8.2.12").unwrap();
+        let fingerprint = Fingerprint::of(&binary).unwrap();
+
+        let mut cache = VersionCache::default();
+        cache.insert(&binary, fingerprint, &PhpVersion::new(8, 2, 12));
+        assert_eq!(cache.get(&binary, fingerprint), Some(PhpVersion::new(8, 2, 12)));
+        assert_eq!(cache.get(&temp_dir.path().join("php"), fingerprint), None);
+
+        // Replaced by an upgrade: another file, even if size and mtime happen to match
+        std::fs::remove_file(&binary).unwrap();
+        std::fs::write(&binary, "8.2.13 upgraded").unwrap();
+        let upgraded = Fingerprint::of(&binary).unwrap();
+        assert_ne!(upgraded, fingerprint);
+        assert_eq!(cache.get(&binary, upgraded), None);
+
+        std::fs::remove_file(&binary).unwrap();
+        cache.retain_existing();
+        assert_eq!(cache, VersionCache::default());
+    }
+
+    #[test]
+    fn test_cache_invalidated_by_ini_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let binary = temp_dir.path().join("php");
+        let conf_d = temp_dir.path().join("conf.d");
+        std::fs::write(&binary, "php").unwrap();
+        std::fs::create_dir(&conf_d).unwrap();
+        let fingerprint = Fingerprint::of(&binary).unwrap();
+
+        let probe = Probe { ini_scan_dirs: vec![conf_d.clone()], ..Probe::default() };
+        let mut cache = ProbeCache::default();
+        cache.insert(&binary, fingerprint, probe);
+        assert!(cache.get(&binary, fingerprint).is_some());
+
+        // Enabling an extension touches conf.d
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::fs::write(conf_d.join("20-intl.ini"), "extension=intl\n").unwrap();
+        assert!(cache.get(&binary, fingerprint).is_none());
+    }
+}
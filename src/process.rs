@@ -0,0 +1,721 @@
+// Process execution module
+//
+// Shared layer for commands that run programs under a chosen PHP version
+// (`exec`, `each`, ...). Output passed straight through inherits the
+// terminal, so interactive programs (`php -a`, composer prompts, tinker) work
+// unchanged. When output has to be rewritten (e.g. prefixed with the version)
+// and the parent is interactive, the child gets a pseudo-terminal so it still
+// sees a TTY; otherwise plain pipes are used and stdin is fed from `Input`.
+//
+// Children run in their own process group. SIGINT/SIGTERM/SIGHUP/SIGQUIT
+// sent to php-switcher are forwarded to that group, and `exit_like`
+// reproduces the child's exit status, including death by signal.
+
+use anyhow::{anyhow, Result};
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::time::{Duration, Instant};
+
+/// Where a child's stdin comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// Share the parent's stdin (the terminal, or a pipe for a single run)
+    Inherit,
+    /// Replay bytes read up front, so several runs can get the same input
+    Bytes(Vec<u8>),
+    /// No input
+    Null,
+}
+
+/// Check whether both stdin and stdout are attached to a terminal
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Read stdin up front when it is piped, so it can be replayed to several runs
+///
+/// A terminal is left alone and shared instead.
+pub fn buffer_piped_stdin() -> Result<Input> {
+    if std::io::stdin().is_terminal() {
+        return Ok(Input::Inherit);
+    }
+
+    let mut bytes = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut bytes)
+        .map_err(|e| anyhow!("Failed to read stdin: {}", e))?;
+    Ok(Input::Bytes(bytes))
+}
+
+/// Run a command with the parent's stdin, stdout and stderr
+///
+/// On a terminal the child's process group becomes the foreground group, so
+/// Ctrl-C and job control reach it directly, and is handed back afterwards.
+pub fn run_inherited(command: &mut Command) -> Result<ExitStatus> {
+    command.stdin(Stdio::inherit()).stdout(Stdio::inherit()).stderr(Stdio::inherit());
+
+    #[cfg(unix)]
+    let foreground = std::io::stdin().is_terminal() && signals::in_foreground();
+    #[cfg(unix)]
+    signals::own_process_group(command, foreground);
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run {}: {}", command.get_program().to_string_lossy(), e))?;
+
+    #[cfg(unix)]
+    let _forwarding = signals::Forward::to(child.id(), foreground);
+
+    Ok(child.wait()?)
+}
+
+/// Run a command, prefixing every line of its output
+///
+/// Uses a pseudo-terminal when the parent is interactive so the child keeps
+/// colors and prompts; stdout and stderr are merged in that case. The end of
+/// the output is also kept in `tail`, for reports.
+pub fn run_prefixed(command: &mut Command, input: &Input, prefix: &str, tail: &Tail) -> Result<ExitStatus> {
+    #[cfg(unix)]
+    if is_interactive() && *input == Input::Inherit {
+        return pty::run(command, tail.tee(LinePrefixer::new(std::io::stdout(), prefix)));
+    }
+
+    run_piped(
+        command,
+        input,
+        tail.tee(LinePrefixer::new(std::io::stdout(), prefix)),
+        tail.tee(LinePrefixer::new(std::io::stderr(), prefix)),
+    )
+}
+
+/// The last few kilobytes of a run's combined output
+#[derive(Debug, Clone, Default)]
+pub struct Tail {
+    bytes: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+}
+
+impl Tail {
+    /// Output kept per run; enough for the lines a report shows
+    const LIMIT: usize = 16 * 1024;
+
+    /// Wrap a writer so everything written to it is also kept here
+    pub fn tee<W: Write>(&self, inner: W) -> Tee<W> {
+        Tee { inner, tail: self.clone() }
+    }
+
+    fn push(&self, buf: &[u8]) {
+        let mut bytes = self.bytes.lock().unwrap_or_else(|e| e.into_inner());
+        bytes.extend_from_slice(buf);
+        if bytes.len() > Self::LIMIT {
+            let excess = bytes.len() - Self::LIMIT;
+            bytes.drain(..excess);
+        }
+    }
+
+    /// The last `count` lines, without terminal escape sequences
+    pub fn lines(&self, count: usize) -> Vec<String> {
+        static ESCAPES: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+        let escapes = ESCAPES.get_or_init(|| regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]|\r").unwrap());
+
+        let bytes = self.bytes.lock().unwrap_or_else(|e| e.into_inner());
+        let text = String::from_utf8_lossy(&bytes);
+        let text = escapes.replace_all(&text, "");
+        let lines: Vec<&str> = text.lines().collect();
+        lines[lines.len().saturating_sub(count)..].iter().map(|l| l.to_string()).collect()
+    }
+}
+
+/// Writer copying everything to a `Tail` on its way through
+pub struct Tee<W: Write> {
+    inner: W,
+    tail: Tail,
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.tail.push(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Run a command with piped output, copying stdout and stderr to the given writers
+pub fn run_piped<O, E>(command: &mut Command, input: &Input, mut out: O, mut err: E) -> Result<ExitStatus>
+where
+    O: Write + Send + 'static,
+    E: Write + Send + 'static,
+{
+    let stdin = match input {
+        Input::Inherit => Stdio::inherit(),
+        Input::Bytes(_) => Stdio::piped(),
+        Input::Null => Stdio::null(),
+    };
+
+    command.stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    #[cfg(unix)]
+    signals::own_process_group(command, false);
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run {}: {}", command.get_program().to_string_lossy(), e))?;
+
+    #[cfg(unix)]
+    let _forwarding = signals::Forward::to(child.id(), false);
+
+    // Feed stdin from a thread so a child producing lots of output can't deadlock us
+    let writer = match (input, child.stdin.take()) {
+        (Input::Bytes(bytes), Some(mut stdin)) => {
+            let bytes = bytes.clone();
+            // The child may exit without reading everything; that's fine
+            Some(std::thread::spawn(move || stdin.write_all(&bytes).ok()))
+        }
+        _ => None,
+    };
+
+    let mut child_out = child.stdout.take().ok_or_else(|| anyhow!("stdout not captured"))?;
+    let mut child_err = child.stderr.take().ok_or_else(|| anyhow!("stderr not captured"))?;
+    let err_thread = std::thread::spawn(move || std::io::copy(&mut child_err, &mut err).map(|_| ()));
+    std::io::copy(&mut child_out, &mut out)?;
+    err_thread.join().map_err(|_| anyhow!("stderr reader panicked"))??;
+
+    if let Some(writer) = writer {
+        writer.join().ok();
+    }
+
+    Ok(child.wait()?)
+}
+
+/// Run a program whose output is wanted (a PHP binary being probed), giving up after `timeout`
+///
+/// `Ok(None)` when it didn't finish in time. The program gets no stdin and its
+/// stderr is captured, so it can't wait for input or scribble on the
+/// terminal; it runs in a process group of its own, which is killed when the
+/// time is up or the program exits, taking anything it started along.
+pub fn output_within(command: &mut Command, timeout: Duration) -> std::io::Result<Option<Output>> {
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    #[cfg(unix)]
+    signals::own_process_group(command, false);
+
+    let mut child = command.spawn()?;
+    let deadline = Instant::now() + timeout;
+
+    // Read from threads so a chatty program (`php -i`) can't fill a pipe and stall
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let status = loop {
+        match child.try_wait()? {
+            Some(status) => break Some(status),
+            None if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(5)),
+            None => break None,
+        }
+    };
+
+    #[cfg(unix)]
+    signals::kill_group(child.id());
+    let Some(status) = status else {
+        child.kill().ok();
+        child.wait().ok();
+        return Ok(None);
+    };
+
+    // Something that left its own process group may still hold a pipe open
+    let remaining = || deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(50));
+    let Ok(stdout) = stdout.recv_timeout(remaining()) else { return Ok(None) };
+    let stderr = stderr.recv_timeout(remaining()).unwrap_or_default();
+    Ok(Some(Output { status, stdout, stderr }))
+}
+
+/// Read a pipe to its end on a thread of its own, handing over the bytes when done
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> std::sync::mpsc::Receiver<Vec<u8>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut buffer).ok();
+        }
+        sender.send(buffer).ok();
+    });
+    receiver
+}
+
+/// Exit code for a finished child, using the shell convention 128+N for signal deaths
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    status.code().unwrap_or(1)
+}
+
+/// Exit php-switcher the same way the child did
+///
+/// A child killed by a signal makes php-switcher die from the same signal, so
+/// CI wrappers and supervisors see the real cause rather than a plain exit code.
+pub fn exit_like(status: ExitStatus) -> ! {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            // SAFETY: restoring the default action and signalling ourselves
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::kill(libc::getpid(), signal);
+            }
+        }
+    }
+
+    std::process::exit(exit_code(status))
+}
+
+/// Writer that puts a prefix in front of every line
+///
+/// Partial lines (like prompts) are passed through immediately rather than
+/// waiting for a newline.
+pub struct LinePrefixer<W: Write> {
+    inner: W,
+    prefix: String,
+    at_line_start: bool,
+}
+
+impl<W: Write> LinePrefixer<W> {
+    pub fn new(inner: W, prefix: &str) -> Self {
+        Self { inner, prefix: prefix.to_string(), at_line_start: true }
+    }
+}
+
+impl<W: Write> Write for LinePrefixer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                self.inner.write_all(self.prefix.as_bytes())?;
+            }
+            self.inner.write_all(line)?;
+            self.at_line_start = line.ends_with(b"\n");
+        }
+        self.inner.flush()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(unix)]
+pub(crate) mod signals {
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// Signals relayed to the child's process group
+    const FORWARDED: [libc::c_int; 4] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT];
+
+    /// Process group receiving forwarded signals (0 = none)
+    static CHILD_GROUP: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn forward(signal: libc::c_int) {
+        let group = CHILD_GROUP.load(Ordering::SeqCst);
+        if group > 0 {
+            // SAFETY: kill(2) is async-signal-safe
+            unsafe {
+                libc::kill(-group, signal);
+            }
+        }
+    }
+
+    /// Start the command in a new process group, optionally taking the terminal foreground
+    pub fn own_process_group(command: &mut Command, foreground: bool) {
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setpgid(0, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if foreground {
+                    take_terminal(libc::getpid());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Kill what's left of a process group started with `own_process_group`
+    pub fn kill_group(child: u32) {
+        // SAFETY: kill(2) on the group the child leads; fails harmlessly once it's empty
+        unsafe {
+            libc::kill(-(child as libc::pid_t), libc::SIGKILL);
+        }
+    }
+
+    /// Check whether php-switcher itself owns the terminal (i.e. isn't a background job)
+    pub fn in_foreground() -> bool {
+        // SAFETY: plain queries without side effects
+        unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() }
+    }
+
+    /// Make a process group the terminal's foreground group
+    ///
+    /// SIGTTOU is ignored around the call, as a background group would
+    /// otherwise be stopped for touching the terminal.
+    fn take_terminal(group: libc::pid_t) {
+        // SAFETY: async-signal-safe calls on stdin's terminal
+        unsafe {
+            let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+            libc::tcsetpgrp(libc::STDIN_FILENO, group);
+            libc::signal(libc::SIGTTOU, previous);
+        }
+    }
+
+    /// Forwarding of signals to a child's process group, undone when dropped
+    pub struct Forward {
+        previous: Vec<(libc::c_int, libc::sighandler_t)>,
+        foreground: bool,
+    }
+
+    impl Forward {
+        pub fn to(child: u32, foreground: bool) -> Self {
+            let group = child as libc::pid_t;
+            CHILD_GROUP.store(group, Ordering::SeqCst);
+
+            if foreground {
+                // Also done in the child; whichever runs first wins the race
+                take_terminal(group);
+            }
+
+            let handler = forward as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            // SAFETY: installing a handler that only calls kill(2)
+            let previous = FORWARDED
+                .iter()
+                .map(|&signal| (signal, unsafe { libc::signal(signal, handler) }))
+                .collect();
+
+            Self { previous, foreground }
+        }
+    }
+
+    impl Drop for Forward {
+        fn drop(&mut self) {
+            for &(signal, handler) in &self.previous {
+                // SAFETY: restoring the handlers that were installed before
+                unsafe {
+                    libc::signal(signal, handler);
+                }
+            }
+            CHILD_GROUP.store(0, Ordering::SeqCst);
+
+            if self.foreground {
+                // SAFETY: getpgrp(2) always succeeds
+                take_terminal(unsafe { libc::getpgrp() });
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod pty {
+    use super::*;
+    use std::fs::File;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::process::CommandExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Run a command on a fresh pseudo-terminal, copying its output to `out`
+    ///
+    /// Keystrokes are forwarded from the parent terminal, which is put in raw
+    /// mode for the duration so line editing happens in the child.
+    pub fn run<W: Write>(command: &mut Command, mut out: W) -> Result<ExitStatus> {
+        let (mut master, slave) = open()?;
+        copy_window_size(master.as_raw_fd());
+
+        command
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                // New session with the pty as its controlling terminal
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let spawned = command.spawn();
+
+        // Drop our copies of the slave so reading the master ends when the child exits
+        command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        let mut child = spawned.map_err(|e| anyhow!("Failed to run {}: {}", command.get_program().to_string_lossy(), e))?;
+
+        // setsid() made the child the leader of its own process group
+        let _forwarding = signals::Forward::to(child.id(), false);
+        let raw_mode = RawMode::enable();
+        let stop = Arc::new(AtomicBool::new(false));
+        let forwarder = {
+            let stop = Arc::clone(&stop);
+            let master = master.try_clone()?;
+            std::thread::spawn(move || forward_stdin(master, &stop))
+        };
+
+        let mut buf = [0u8; 4096];
+        loop {
+            match master.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => out.write_all(&buf[..n])?,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                // Linux reports EIO once the last slave descriptor is closed
+                Err(_) => break,
+            }
+        }
+
+        let status = child.wait()?;
+        stop.store(true, Ordering::Relaxed);
+        forwarder.join().ok();
+        drop(raw_mode);
+
+        Ok(status)
+    }
+
+    /// Open a pty pair, returning (master, slave)
+    fn open() -> Result<(File, File)> {
+        // SAFETY: plain libc calls; every returned descriptor is checked
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            if master == -1 {
+                return Err(anyhow!("Failed to allocate a pty: {}", std::io::Error::last_os_error()));
+            }
+            let master_file = File::from_raw_fd(master);
+
+            if libc::grantpt(master) == -1 || libc::unlockpt(master) == -1 {
+                return Err(anyhow!("Failed to set up pty: {}", std::io::Error::last_os_error()));
+            }
+
+            let name = libc::ptsname(master);
+            if name.is_null() {
+                return Err(anyhow!("Failed to name pty: {}", std::io::Error::last_os_error()));
+            }
+            let name = std::ffi::CStr::from_ptr(name).to_owned();
+
+            let slave = libc::open(name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY);
+            if slave == -1 {
+                return Err(anyhow!("Failed to open pty: {}", std::io::Error::last_os_error()));
+            }
+
+            Ok((master_file, File::from_raw_fd(slave)))
+        }
+    }
+
+    /// Give the pty the same size as the parent terminal
+    fn copy_window_size(master: i32) {
+        // SAFETY: winsize is plain data; failures leave the default size
+        unsafe {
+            let mut size: libc::winsize = std::mem::zeroed();
+            if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 {
+                libc::ioctl(master, libc::TIOCSWINSZ, &size);
+            }
+        }
+    }
+
+    /// Copy keystrokes to the child until told to stop
+    fn forward_stdin(mut master: File, stop: &AtomicBool) {
+        let mut buf = [0u8; 1024];
+        while !stop.load(Ordering::Relaxed) {
+            let mut poll = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+            // SAFETY: one valid pollfd; a short timeout lets us notice `stop`
+            let ready = unsafe { libc::poll(&mut poll, 1, 100) };
+            if ready <= 0 {
+                continue;
+            }
+
+            // SAFETY: reading into a local buffer of the given length
+            let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 || master.write_all(&buf[..n as usize]).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Raw mode on the parent terminal, restored when dropped
+    struct RawMode(Option<libc::termios>);
+
+    impl RawMode {
+        fn enable() -> Self {
+            if !std::io::stdin().is_terminal() {
+                return RawMode(None);
+            }
+
+            // SAFETY: termios is plain data filled in by tcgetattr
+            unsafe {
+                let mut original: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                    return RawMode(None);
+                }
+                let mut raw = original;
+                libc::cfmakeraw(&mut raw);
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+                RawMode(Some(original))
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            if let Some(original) = &self.0 {
+                // SAFETY: restoring settings previously read from the same fd
+                unsafe {
+                    libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_child_sees_a_terminal() {
+            let mut output = Vec::new();
+            let status = run(
+                Command::new("sh").args(["-c", "test -t 0 && test -t 1 && echo on-a-tty"]),
+                &mut output,
+            )
+            .unwrap();
+
+            assert!(status.success());
+            assert!(String::from_utf8_lossy(&output).contains("on-a-tty"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Shared buffer that can be handed to a reader thread
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_line_prefixer() {
+        let mut prefixer = LinePrefixer::new(Vec::new(), "[8.2] ");
+        prefixer.write_all(b"one\ntw").unwrap();
+        prefixer.write_all(b"o\nprompt> ").unwrap();
+
+        assert_eq!(String::from_utf8(prefixer.inner).unwrap(), "[8.2] one\n[8.2] two\n[8.2] prompt> ");
+    }
+
+    #[test]
+    fn test_tail_keeps_last_lines() {
+        let tail = Tail::default();
+        let mut out = tail.tee(LinePrefixer::new(Vec::new(), "[8.2] "));
+        out.write_all(b"one\r\n\x1b[31mtwo\x1b[0m\nthree\n").unwrap();
+
+        assert_eq!(tail.lines(2), vec!["two", "three"]);
+        assert_eq!(String::from_utf8(out.inner.inner).unwrap(), "[8.2] one\r\n[8.2] \x1b[31mtwo\x1b[0m\n[8.2] three\n");
+    }
+
+    #[test]
+    fn test_run_piped_streams_input() {
+        let (out, err) = (Shared::default(), Shared::default());
+        let input = Input::Bytes(b"hello\nworld\n".to_vec());
+
+        let status = run_piped(
+            Command::new("sh").args(["-c", "cat; echo oops >&2; exit 3"]),
+            &input,
+            LinePrefixer::new(out.clone(), "> "),
+            err.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(out.text(), "> hello\n> world\n");
+        assert_eq!(err.text(), "oops\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exit_code_reproduces_signal_deaths() {
+        let status = Command::new("sh").args(["-c", "exit 7"]).status().unwrap();
+        assert_eq!(exit_code(status), 7);
+
+        let status = Command::new("sh").args(["-c", "kill -TERM $$"]).status().unwrap();
+        assert_eq!(exit_code(status), 128 + libc::SIGTERM);
+    }
+
+    #[test]
+    fn test_children_get_their_own_process_group() {
+        let out = Shared::default();
+        let status = run_piped(
+            Command::new("sh").args(["-c", "ps -o pgid= -p $$; echo $$"]),
+            &Input::Null,
+            out.clone(),
+            Shared::default(),
+        )
+        .unwrap();
+        assert!(status.success());
+
+        // The shell's process group id equals its pid
+        let text = out.text();
+        let ids: Vec<&str> = text.split_whitespace().collect();
+        if ids.len() == 2 {
+            assert_eq!(ids[0], ids[1]);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_output_within_kills_hung_programs() {
+        let output =
+            output_within(Command::new("sh").args(["-c", "echo PHP 8.2.12; echo warning >&2"]), Duration::from_secs(5)).unwrap().unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "PHP 8.2.12\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "warning\n");
+
+        let started = Instant::now();
+        let hung = output_within(Command::new("sh").args(["-c", "sleep 30; echo late"]), Duration::from_millis(200)).unwrap();
+        assert!(hung.is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_run_piped_with_null_input() {
+        let out = Shared::default();
+        let status = run_piped(Command::new("cat").arg("-"), &Input::Null, out.clone(), Shared::default()).unwrap();
+
+        assert!(status.success());
+        assert_eq!(out.text(), "");
+    }
+}
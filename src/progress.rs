@@ -0,0 +1,113 @@
+// Scan progress module
+//
+// A full scan lists a few dozen directories and runs `-v` on every PHP binary
+// it finds, which takes a while on machines with many builds, and looks like
+// a hang when nothing is printed. The detector reports each step here; it
+// costs an atomic load unless `scan` installed a listener (a progress bar on
+// a terminal, or a line per candidate with `--verbose`).
+
+use crate::version::PhpVersion;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// A step of a scan
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanEvent {
+    /// A directory was listed
+    Directory(PathBuf),
+    /// This many binaries are about to be run (the rest were answered from the cache)
+    Probing { total: usize },
+    /// One of them answered (or didn't)
+    Probed,
+    /// What became of a candidate binary
+    Candidate { path: PathBuf, verdict: Verdict },
+}
+
+/// Why a candidate was kept or left out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Accepted(PhpVersion),
+    /// Another path leads to the same binary, and stands for it
+    Alias(PathBuf),
+    /// An `exclude_paths` or `exclude_versions` entry matched
+    Excluded(String),
+    /// It couldn't be run or isn't PHP
+    Rejected(String),
+}
+
+impl Verdict {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Verdict::Accepted(_))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Verdict::Accepted(version) => format!("PHP {}", version),
+            Verdict::Alias(path) => format!("alias of {}", path.display()),
+            Verdict::Excluded(rule) => format!("excluded ({})", rule),
+            Verdict::Rejected(reason) => reason.clone(),
+        }
+    }
+}
+
+type Listener = Box<dyn Fn(&ScanEvent) + Send + Sync>;
+
+static LISTENING: AtomicBool = AtomicBool::new(false);
+static LISTENER: RwLock<Option<Listener>> = RwLock::new(None);
+
+/// Send scan events to `listener` until `stop` (probes run on several threads, so it must be `Sync`)
+pub fn listen(listener: impl Fn(&ScanEvent) + Send + Sync + 'static) {
+    if let Ok(mut slot) = LISTENER.write() {
+        *slot = Some(Box::new(listener));
+        LISTENING.store(true, Ordering::Release);
+    }
+}
+
+/// Remove the listener
+pub fn stop() {
+    LISTENING.store(false, Ordering::Release);
+    if let Ok(mut slot) = LISTENER.write() {
+        *slot = None;
+    }
+}
+
+/// Check whether anyone listens, to skip building events nobody sees
+pub fn is_listening() -> bool {
+    LISTENING.load(Ordering::Acquire)
+}
+
+/// Pass an event to the listener, if there is one
+pub fn report(event: impl FnOnce() -> ScanEvent) {
+    if !is_listening() {
+        return;
+    }
+    if let Ok(slot) = LISTENER.read() {
+        if let Some(listener) = slot.as_ref() {
+            listener(&event());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_listen_and_stop() {
+        let event = |path: &str| ScanEvent::Candidate { path: PathBuf::from(path), verdict: Verdict::Rejected("test".to_string()) };
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        listen(move |event| sink.lock().unwrap().push(event.clone()));
+        report(|| event("/listening"));
+        stop();
+        report(|| event("/stopped"));
+
+        // Scans in other tests may report too, while the listener is installed
+        let seen = seen.lock().unwrap();
+        assert!(seen.contains(&event("/listening")));
+        assert!(!seen.contains(&event("/stopped")));
+        assert_eq!(Verdict::Alias(PathBuf::from("/usr/bin/php8.2")).describe(), "alias of /usr/bin/php8.2");
+    }
+}
@@ -0,0 +1,368 @@
+// Project pin module
+//
+// A project pins its PHP version with a `.php-version` file, or the `php`
+// line of an asdf/mise `.tool-versions`. In a monorepo every package may have
+// its own pin, so the nearest file walking up from the working directory
+// wins, and the workspace (the enclosing git repository) can be searched for
+// all of its pins.
+
+use crate::config::Config;
+use crate::switcher;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the file pinning a project's PHP version
+pub const PIN_FILE: &str = ".php-version";
+
+/// Name of the asdf/mise file pinning versions of several tools
+pub const TOOL_VERSIONS_FILE: &str = ".tool-versions";
+
+/// Kinds of files a project can pin its version with
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PinFile {
+    PhpVersion,
+    ToolVersions,
+}
+
+/// Pin files looked for in each directory, first match wins (see `settings.pin_files`)
+pub const DEFAULT_PIN_FILES: &[PinFile] = &[PinFile::PhpVersion, PinFile::ToolVersions];
+
+impl PinFile {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            PinFile::PhpVersion => PIN_FILE,
+            PinFile::ToolVersions => TOOL_VERSIONS_FILE,
+        }
+    }
+
+    /// Extract the PHP version from the file's contents
+    pub fn parse(&self, contents: &str) -> Option<String> {
+        match self {
+            PinFile::PhpVersion => switcher::parse_version_spec(contents),
+            PinFile::ToolVersions => parse_tool_versions(contents),
+        }
+    }
+}
+
+/// Extract the version from the `php` line of a `.tool-versions` file
+///
+/// asdf allows fallbacks (`php 8.3.4 8.2.12`) and comments after `#`; only
+/// the first version is used, and `system` means no pin.
+pub fn parse_tool_versions(contents: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or(line);
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("php") {
+            return None;
+        }
+        fields.next().filter(|version| *version != "system").map(str::to_string)
+    })
+}
+
+/// Directories never searched for pins
+const SKIPPED_DIRS: &[&str] = &[".git", "vendor", "node_modules"];
+
+/// A `.php-version` file and the version it asks for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pin {
+    /// Directory containing the pin file
+    pub dir: PathBuf,
+    pub version: String,
+}
+
+/// Read the first of `files` in a directory that pins a version
+pub fn read_pin(dir: &Path, files: &[PinFile]) -> Option<Pin> {
+    files.iter().find_map(|file| {
+        let contents = std::fs::read_to_string(dir.join(file.file_name())).ok()?;
+        let version = file.parse(&contents)?;
+        Some(Pin { dir: dir.to_path_buf(), version })
+    })
+}
+
+/// Find the nearest pin, starting at `start` and walking up
+///
+/// Inside a git repository the search stops at its root, so a pin in some
+/// enclosing directory never leaks into a project that doesn't have one.
+/// A directory's files are tried in the order of `files`.
+pub fn find_pin(start: &Path, files: &[PinFile]) -> Option<Pin> {
+    for dir in start.ancestors() {
+        if let Some(pin) = read_pin(dir, files) {
+            return Some(pin);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Find the pin recorded in the config for `start` or its nearest pinned ancestor
+pub fn central_pin(config: &Config, start: &Path) -> Option<Pin> {
+    start.ancestors().find_map(|dir| {
+        let pin = config.pins.iter().find(|p| p.dir == dir)?;
+        Some(Pin { dir: pin.dir.clone(), version: pin.version.clone() })
+    })
+}
+
+/// Find the pin that applies to `start`: a pin in the config wins over pin files
+///
+/// Config pins (`php-switcher pin`) are for projects that can't commit a
+/// version file, so they override whatever file the project has.
+pub fn nearest_pin(config: &Config, start: &Path) -> Option<Pin> {
+    central_pin(config, start).or_else(|| find_pin(start, config.settings.pin_files()))
+}
+
+/// Pin a directory to a tracked version by writing its `.php-version`
+///
+/// The version must match a tracked installation. It's written as given, so
+/// `8.2` keeps following patch releases; labels are machine-specific and are
+/// replaced by the version they stand for.
+pub fn write_pin(config: &Config, dir: &Path, version: &str) -> Result<Pin> {
+    let entry = config
+        .find_version(version)
+        .ok_or_else(|| anyhow!("No PHP installation found matching '{}'. Run 'php-switcher scan' first.", version))?;
+
+    let version = if entry.labels.iter().any(|l| l == version) { entry.version.clone() } else { version.to_string() };
+    let path = dir.join(PIN_FILE);
+    std::fs::write(&path, format!("{}\n", version))
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+
+    Ok(Pin { dir: dir.to_path_buf(), version })
+}
+
+/// Remove a directory's `.php-version`, returning the pin it held
+pub fn remove_pin(dir: &Path) -> Result<Option<Pin>> {
+    let Some(pin) = read_pin(dir, &[PinFile::PhpVersion]) else { return Ok(None) };
+    let path = dir.join(PIN_FILE);
+    std::fs::remove_file(&path).map_err(|e| anyhow!("Failed to remove {}: {}", path.display(), e))?;
+    Ok(Some(pin))
+}
+
+/// The version a directory should use, as reported by `__resolve`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    pub pin: Pin,
+    /// Tracked version the pin matches, if it's installed
+    pub resolved: Option<String>,
+}
+
+impl Resolution {
+    /// Format as the single `__resolve` output line: `<pin>\t<resolved or ->\t<dir>`
+    ///
+    /// Shell hooks split on tabs, so this format must stay stable.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}",
+            self.pin.version,
+            self.resolved.as_deref().unwrap_or("-"),
+            self.pin.dir.display()
+        )
+    }
+}
+
+/// Resolve the version for a directory from its nearest pin and the cached config
+///
+/// Only reads the pin files and the already-loaded config, so it's cheap
+/// enough to run on every `cd`.
+pub fn resolve(config: &Config, dir: &Path) -> Option<Resolution> {
+    let pin = nearest_pin(config, dir)?;
+    let resolved = config.find_version(&pin.version).map(|e| e.version.clone());
+    Some(Resolution { pin, resolved })
+}
+
+/// Find the root of the workspace containing `start`
+///
+/// That's the nearest ancestor holding a `.git` entry, falling back to
+/// `start` itself outside of a repository.
+pub fn workspace_root(start: &Path) -> PathBuf {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(start)
+        .to_path_buf()
+}
+
+/// Find every pin below a directory, sorted by path
+pub fn list_pins(root: &Path, files: &[PinFile]) -> Vec<Pin> {
+    let mut pins = Vec::new();
+    collect_pins(root, files, &mut pins);
+    pins.sort_by(|a, b| a.dir.cmp(&b.dir));
+    pins
+}
+
+fn collect_pins(dir: &Path, files: &[PinFile], pins: &mut Vec<Pin>) {
+    pins.extend(read_pin(dir, files));
+
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else { continue };
+        let skipped = SKIPPED_DIRS.iter().any(|name| entry.file_name() == *name);
+
+        // Not following symlinks keeps us from looping or leaving the workspace
+        if file_type.is_dir() && !skipped {
+            collect_pins(&entry.path(), files, pins);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pin(dir: &Path, version: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(PIN_FILE), format!("{}\n", version)).unwrap();
+    }
+
+    #[test]
+    fn test_nearest_pin_wins() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        pin(root, "8.3");
+        pin(&root.join("packages/legacy"), "7.4");
+        std::fs::create_dir_all(root.join("packages/legacy/src/deep")).unwrap();
+        std::fs::create_dir_all(root.join("packages/api")).unwrap();
+
+        assert_eq!(find_pin(&root.join("packages/legacy/src/deep"), DEFAULT_PIN_FILES).unwrap().version, "7.4");
+        assert_eq!(find_pin(&root.join("packages/api"), DEFAULT_PIN_FILES).unwrap().version, "8.3");
+    }
+
+    #[test]
+    fn test_pin_search_stops_at_repo_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        pin(root, "8.3");
+        std::fs::create_dir_all(root.join("projects/app/.git")).unwrap();
+        std::fs::create_dir_all(root.join("projects/app/src")).unwrap();
+        std::fs::create_dir_all(root.join("scratch")).unwrap();
+
+        assert_eq!(find_pin(&root.join("projects/app/src"), DEFAULT_PIN_FILES), None);
+        assert_eq!(find_pin(&root.join("scratch"), DEFAULT_PIN_FILES).unwrap().version, "8.3");
+    }
+
+    #[test]
+    fn test_resolve_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        pin(root, "8.2");
+        pin(&root.join("legacy"), "7.4");
+
+        let config = Config {
+            versions: vec![crate::config::VersionEntry {
+                version: "8.2.12".to_string(),
+                paths: vec![PathBuf::from("/usr/bin/php8.2")],
+                source: "auto".to_string(),
+                ..crate::config::VersionEntry::default()
+            }],
+            ..Config::default()
+        };
+
+        let line = resolve(&config, root).unwrap().to_line();
+        assert_eq!(line, format!("8.2\t8.2.12\t{}", root.display()));
+
+        let line = resolve(&config, &root.join("legacy")).unwrap().to_line();
+        assert_eq!(line, format!("7.4\t-\t{}", root.join("legacy").display()));
+    }
+
+    #[test]
+    fn test_config_pin_wins_over_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        pin(&root.join("app"), "8.3");
+        std::fs::create_dir_all(root.join("app/src")).unwrap();
+        std::fs::create_dir_all(root.join("other")).unwrap();
+
+        let mut config = Config::default();
+        config.set_pin(root, "7.4");
+
+        let found = nearest_pin(&config, &root.join("app/src")).unwrap();
+        assert_eq!((found.dir.as_path(), found.version.as_str()), (root, "7.4"));
+        assert_eq!(nearest_pin(&config, &root.join("other")).unwrap().version, "7.4");
+        assert_eq!(nearest_pin(&Config::default(), &root.join("app/src")).unwrap().version, "8.3");
+    }
+
+    #[test]
+    fn test_parse_tool_versions() {
+        assert_eq!(parse_tool_versions("nodejs 20.11.0\nphp 8.3.4 8.2.12 # fallback\n").as_deref(), Some("8.3.4"));
+        assert_eq!(parse_tool_versions("# php 7.4\nphp system\n"), None);
+        assert_eq!(parse_tool_versions("phpunit 10\n"), None);
+    }
+
+    #[test]
+    fn test_tool_versions_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        pin(root, "8.2");
+        std::fs::write(root.join(TOOL_VERSIONS_FILE), "php 8.3.4\n").unwrap();
+        std::fs::create_dir_all(root.join("app")).unwrap();
+        std::fs::write(root.join("app").join(TOOL_VERSIONS_FILE), "php 7.4.33\n").unwrap();
+
+        let mut config = Config::default();
+        assert_eq!(nearest_pin(&config, root).unwrap().version, "8.2");
+        // The nearest directory wins whatever file it uses
+        assert_eq!(nearest_pin(&config, &root.join("app")).unwrap().version, "7.4.33");
+
+        config.settings.pin_files = Some(vec![PinFile::ToolVersions, PinFile::PhpVersion]);
+        assert_eq!(nearest_pin(&config, root).unwrap().version, "8.3.4");
+
+        config.settings.pin_files = Some(vec![PinFile::PhpVersion]);
+        assert_eq!(nearest_pin(&config, &root.join("app")).unwrap().version, "8.2");
+    }
+
+    #[test]
+    fn test_write_and_remove_pin() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let config = Config {
+            versions: vec![crate::config::VersionEntry {
+                version: "8.2.12".to_string(),
+                paths: vec![PathBuf::from("/usr/bin/php8.2")],
+                source: "auto".to_string(),
+                labels: vec!["legacy".to_string()],
+                ..crate::config::VersionEntry::default()
+            }],
+            ..Config::default()
+        };
+
+        assert!(write_pin(&config, dir, "7.4").is_err());
+        assert!(!dir.join(PIN_FILE).exists());
+
+        write_pin(&config, dir, "8.2").unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join(PIN_FILE)).unwrap(), "8.2\n");
+        assert_eq!(write_pin(&config, dir, "legacy").unwrap().version, "8.2.12");
+
+        assert_eq!(remove_pin(dir).unwrap().unwrap().version, "8.2.12");
+        assert_eq!(remove_pin(dir).unwrap(), None);
+    }
+
+    #[test]
+    fn test_list_pins_in_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        pin(root, "8.3");
+        pin(&root.join("packages/legacy"), "7.4");
+        pin(&root.join("packages/api"), "8.2");
+        pin(&root.join("vendor/some/lib"), "5.6");
+
+        assert_eq!(workspace_root(&root.join("packages/api")), root);
+
+        let pins: Vec<(PathBuf, String)> = list_pins(root, DEFAULT_PIN_FILES)
+            .into_iter()
+            .map(|p| (p.dir.strip_prefix(root).unwrap().to_path_buf(), p.version))
+            .collect();
+
+        assert_eq!(
+            pins,
+            vec![
+                (PathBuf::from(""), "8.3".to_string()),
+                (PathBuf::from("packages/api"), "8.2".to_string()),
+                (PathBuf::from("packages/legacy"), "7.4".to_string()),
+            ]
+        );
+    }
+}
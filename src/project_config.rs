@@ -0,0 +1,220 @@
+// Project config module
+//
+// A project can commit a `.php-switcher.toml` deciding which tools get shims
+// when someone switches PHP inside it, so "phpunit always runs under the
+// project's PHP" holds on every machine, not just where the user config
+// happens to say so:
+//
+//   [tools]
+//   shim = ["phpunit", "psalm"]   # shimmed even with `#!/usr/bin/env php`
+//   never = ["php-cs-fixer"]      # never shimmed, an existing shim is removed
+//
+//   [tools.pin]
+//   composer = "2"                # shimmed to a tool version, as in a toolchain
+//
+// The rules are merged over the user's `[tools]` config at switch time.
+
+use crate::tools::{self, PhpTool};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the committed project config file
+pub const PROJECT_CONFIG_FILE: &str = ".php-switcher.toml";
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub tools: ProjectTools,
+}
+
+/// Tool rules of a project
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ProjectTools {
+    /// Tools that must be shimmed
+    #[serde(default)]
+    pub shim: Vec<String>,
+    /// Tools that must never be shimmed
+    #[serde(default)]
+    pub never: Vec<String>,
+    /// Tools shimmed to a version (or a path)
+    #[serde(default)]
+    pub pin: BTreeMap<String, String>,
+}
+
+impl ProjectTools {
+    /// Names that must have a shim, required or pinned
+    pub fn required(&self) -> impl Iterator<Item = &String> {
+        self.shim.iter().chain(self.pin.keys())
+    }
+}
+
+/// A project config and the directory it applies to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub root: PathBuf,
+    pub config: ProjectConfig,
+}
+
+/// Find the nearest project config walking up from `start`, stopping at the git root
+pub fn find(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let path = dir.join(PROJECT_CONFIG_FILE);
+        if path.is_file() {
+            return Some(path);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Parse a project config, rejecting tools that are both required and denied
+pub fn parse(contents: &str) -> Result<ProjectConfig> {
+    let config: ProjectConfig = toml::from_str(contents).map_err(|e| anyhow!("Failed to parse project config: {}", e))?;
+
+    if let Some(tool) = config.tools.required().find(|tool| config.tools.never.contains(tool)) {
+        return Err(anyhow!("Tool '{}' is both required and listed under `never`", tool));
+    }
+    Ok(config)
+}
+
+/// Load the project config that applies to `start`, if any
+pub fn load(start: &Path) -> Result<Option<Project>> {
+    let Some(path) = find(start) else { return Ok(None) };
+    let contents = std::fs::read_to_string(&path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let config = parse(&contents).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    let root = path.parent().unwrap_or(start).to_path_buf();
+    Ok(Some(Project { root, config }))
+}
+
+/// The tools to shim once a project's rules are applied
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolPlan {
+    pub tools: Vec<PhpTool>,
+    /// Tools whose shims must go
+    pub denied: Vec<String>,
+    /// Required tools that couldn't be found
+    pub warnings: Vec<String>,
+}
+
+/// Apply a project's rules to the tools the user config would shim
+///
+/// Required tools are looked for in the project's `vendor/bin`, then among
+/// `known` (the user's managed tools), then in `search_dirs`. Pins are
+/// resolved like toolchain pins.
+pub fn plan_tools(project: &Project, base: Vec<PhpTool>, known: &[PhpTool], search_dirs: &[PathBuf]) -> ToolPlan {
+    let rules = &project.config.tools;
+    let mut plan = ToolPlan {
+        tools: base.into_iter().filter(|tool| !rules.never.contains(&tool.name) && !rules.pin.contains_key(&tool.name)).collect(),
+        denied: rules.never.clone(),
+        warnings: Vec::new(),
+    };
+
+    let vendor_bin = project.root.join("vendor/bin");
+    let index = crate::which::cached(search_dirs);
+    for name in &rules.shim {
+        if plan.tools.iter().any(|tool| &tool.name == name) {
+            continue;
+        }
+        let found = Some(vendor_bin.join(name))
+            .filter(|path| path.is_file())
+            .or_else(|| known.iter().find(|tool| &tool.name == name).map(|tool| tool.original_path.clone()))
+            .or_else(|| index.lookup(name).map(Path::to_path_buf));
+        match found {
+            Some(path) => plan.tools.push(tool_at(name, path)),
+            None => plan.warnings.push(format!("{} requires a {} shim, but {} wasn't found", PROJECT_CONFIG_FILE, name, name)),
+        }
+    }
+
+    for (name, pin) in &rules.pin {
+        match crate::toolchain::find_pinned_tool(name, pin, search_dirs) {
+            Some(path) => plan.tools.push(tool_at(name, path)),
+            None => plan.warnings.push(format!("{} pins {} {}, but it wasn't found", PROJECT_CONFIG_FILE, name, pin)),
+        }
+    }
+
+    plan
+}
+
+fn tool_at(name: &str, original_path: PathBuf) -> PhpTool {
+    let shebang = tools::read_shebang(&original_path).unwrap_or_default();
+    PhpTool { name: name.to_string(), original_path, shebang }
+}
+
+/// Whether the bin directory already follows a project's rules
+pub fn shims_current(project: &Project, bin_dir: &Path) -> bool {
+    let rules = &project.config.tools;
+    rules.required().all(|name| bin_dir.join(name).is_file()) && !rules.never.iter().any(|name| bin_dir.join(name).exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_and_find() {
+        let config = parse("[tools]\nshim = [\"phpunit\"]\nnever = [\"php-cs-fixer\"]\n\n[tools.pin]\ncomposer = \"2\"\n").unwrap();
+        assert_eq!(config.tools.required().collect::<Vec<_>>(), ["phpunit", "composer"]);
+        assert_eq!(config.tools.never, ["php-cs-fixer"]);
+
+        let conflict = parse("[tools]\nshim = [\"phpunit\"]\nnever = [\"phpunit\"]\n").unwrap_err();
+        assert!(conflict.to_string().contains("'phpunit' is both required"));
+
+        let temp_dir = TempDir::new().unwrap();
+        let app = temp_dir.path().join("packages/app");
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::write(temp_dir.path().join(".git"), "").unwrap();
+        assert_eq!(find(&app), None);
+
+        std::fs::write(temp_dir.path().join(PROJECT_CONFIG_FILE), "").unwrap();
+        assert_eq!(find(&app), Some(temp_dir.path().join(PROJECT_CONFIG_FILE)));
+        assert_eq!(load(&app).unwrap().unwrap().root, temp_dir.path());
+    }
+
+    #[test]
+    fn test_plan_tools() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("app");
+        let path_dir = temp_dir.path().join("bin");
+        std::fs::create_dir_all(root.join("vendor/bin")).unwrap();
+        std::fs::create_dir_all(&path_dir).unwrap();
+        std::fs::write(root.join("vendor/bin/phpunit"), "#!/usr/bin/env php\n").unwrap();
+        std::fs::write(path_dir.join("composer1"), "#!/usr/bin/php\n").unwrap();
+
+        let project = Project {
+            root: root.clone(),
+            config: parse("[tools]\nshim = [\"phpunit\", \"psalm\"]\nnever = [\"php-cs-fixer\"]\n\n[tools.pin]\ncomposer = \"1\"\n")
+                .unwrap(),
+        };
+        let base = vec![
+            tool_at("php-cs-fixer", path_dir.join("php-cs-fixer")),
+            tool_at("composer", path_dir.join("composer")),
+            tool_at("phpstan", path_dir.join("phpstan")),
+        ];
+
+        let plan = plan_tools(&project, base, &[], std::slice::from_ref(&path_dir));
+        let tools: Vec<_> = plan.tools.iter().map(|t| (t.name.as_str(), t.original_path.clone())).collect();
+        assert_eq!(
+            tools,
+            [
+                ("phpstan", path_dir.join("phpstan")),
+                ("phpunit", root.join("vendor/bin/phpunit")),
+                ("composer", path_dir.join("composer1")),
+            ]
+        );
+        assert_eq!(plan.denied, ["php-cs-fixer"]);
+        assert_eq!(plan.warnings, [".php-switcher.toml requires a psalm shim, but psalm wasn't found"]);
+
+        let bin_dir = temp_dir.path().join("shims");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        assert!(!shims_current(&project, &bin_dir));
+        for name in ["phpunit", "psalm", "composer"] {
+            std::fs::write(bin_dir.join(name), "").unwrap();
+        }
+        assert!(shims_current(&project, &bin_dir));
+    }
+}
@@ -0,0 +1,169 @@
+// Retention module
+//
+// Installations php-switcher manages itself live under
+// `~/.php-switcher/versions/<version>`. Once several patch releases of the
+// same minor version pile up there, `prune` removes all but the newest
+// `settings.keep_patches` of them. Package-manager and manual installs are
+// never touched, and neither is the active version.
+
+use crate::config::{self, Config, VersionEntry};
+use anyhow::{anyhow, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// Patch releases kept per minor version when `keep_patches` isn't set
+pub const DEFAULT_KEEP_PATCHES: usize = 2;
+
+/// A managed installation selected for removal
+#[derive(Debug, Clone, PartialEq)]
+pub struct PruneCandidate {
+    pub version: String,
+    /// Directory that will be deleted
+    pub root: PathBuf,
+}
+
+/// Get the directory holding php-switcher managed installations
+pub fn get_installs_dir() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join("versions"))
+}
+
+/// Get the directory of a managed installation (`<installs_dir>/<name>`)
+///
+/// Returns `None` for anything outside the installs directory, which keeps
+/// pruning from ever deleting package-manager or manual installs, and for
+/// binaries sitting directly in it, which have no directory of their own.
+pub fn managed_root(entry: &VersionEntry, installs_dir: &Path) -> Option<PathBuf> {
+    let primary = entry.primary_path()?;
+    let relative = primary.strip_prefix(installs_dir).ok()?;
+    let mut components = relative.components();
+    let name = match components.next()? {
+        Component::Normal(name) => name,
+        _ => return None,
+    };
+    // The binary must sit below the root, or the root would be the binary itself
+    components.next()?;
+    Some(installs_dir.join(name))
+}
+
+/// Work out which managed installations exceed the retention policy
+///
+/// Within each minor version the newest `keep` managed releases survive; the
+/// active version is always kept and doesn't count against the limit.
+pub fn plan_prune(config: &Config, keep: usize, active: Option<&str>, installs_dir: &Path) -> Vec<PruneCandidate> {
+    let mut candidates = Vec::new();
+
+    for group in config.grouped_by_minor() {
+        let managed = group.entries.iter().filter_map(|entry| {
+            let root = managed_root(entry, installs_dir)?;
+            (Some(entry.version.as_str()) != active).then(|| PruneCandidate { version: entry.version.clone(), root })
+        });
+
+        candidates.extend(managed.skip(keep));
+    }
+
+    candidates
+}
+
+/// Delete pruned installations and drop them from the config
+pub fn prune(config: &mut Config, candidates: &[PruneCandidate]) -> Result<()> {
+    for candidate in candidates {
+        if candidate.root.exists() {
+            std::fs::remove_dir_all(&candidate.root)
+                .map_err(|e| anyhow!("Failed to remove {}: {}", candidate.root.display(), e))?;
+        }
+        forget_installation(config, &candidate.root);
+    }
+
+    config::save_config(config)
+}
+
+/// Drop the entries of a deleted installation from the config
+///
+/// Matched by path, not version: a package-manager build of the same version stays.
+fn forget_installation(config: &mut Config, root: &Path) {
+    config.versions.retain(|entry| !entry.paths.iter().any(|path| path.starts_with(root)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(version: &str, dir: &str) -> VersionEntry {
+        VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(format!("{}/bin/php", dir))],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            versions: vec![
+                entry("8.2.10", "/home/u/.php-switcher/versions/8.2.10"),
+                entry("8.2.20", "/home/u/.php-switcher/versions/8.2.20"),
+                entry("8.2.15", "/home/u/.php-switcher/versions/8.2.15"),
+                entry("8.2.5", "/usr"),
+                entry("8.3.4", "/home/u/.php-switcher/versions/8.3.4"),
+            ],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_managed_root() {
+        let installs_dir = Path::new("/home/u/.php-switcher/versions");
+
+        assert_eq!(
+            managed_root(&entry("8.2.10", "/home/u/.php-switcher/versions/8.2.10"), installs_dir),
+            Some(installs_dir.join("8.2.10"))
+        );
+        assert_eq!(managed_root(&entry("8.2.5", "/usr"), installs_dir), None);
+    }
+
+    #[test]
+    fn test_managed_root_needs_a_directory() {
+        let installs_dir = Path::new("/home/u/.php-switcher/versions");
+        let loose = VersionEntry {
+            version: "8.2.1".to_string(),
+            paths: vec![installs_dir.join("php8.2")],
+            ..VersionEntry::default()
+        };
+        assert_eq!(managed_root(&loose, installs_dir), None);
+
+        let escaping = VersionEntry {
+            version: "8.2.2".to_string(),
+            paths: vec![installs_dir.join("../bin/php")],
+            ..VersionEntry::default()
+        };
+        assert_eq!(managed_root(&escaping, installs_dir), None);
+    }
+
+    #[test]
+    fn test_forget_installation_keeps_same_version_elsewhere() {
+        let mut config = Config {
+            versions: vec![entry("8.2.12", "/home/u/.php-switcher/versions/8.2.12"), entry("8.2.12", "/usr")],
+            ..Config::default()
+        };
+
+        forget_installation(&mut config, Path::new("/home/u/.php-switcher/versions/8.2.12"));
+        assert_eq!(config.versions.len(), 1);
+        assert_eq!(config.versions[0].paths, vec![PathBuf::from("/usr/bin/php")]);
+    }
+
+    #[test]
+    fn test_plan_prune_keeps_newest_managed() {
+        let installs_dir = Path::new("/home/u/.php-switcher/versions");
+        let config = config();
+
+        let versions = |candidates: Vec<PruneCandidate>| -> Vec<String> {
+            candidates.into_iter().map(|c| c.version).collect()
+        };
+
+        // The /usr install is older but never a candidate
+        assert_eq!(versions(plan_prune(&config, 2, None, installs_dir)), vec!["8.2.10"]);
+        assert_eq!(versions(plan_prune(&config, 1, None, installs_dir)), vec!["8.2.15", "8.2.10"]);
+
+        // The active version survives even when it's beyond the limit
+        assert_eq!(versions(plan_prune(&config, 1, Some("8.2.10"), installs_dir)), vec!["8.2.15"]);
+    }
+}
@@ -0,0 +1,131 @@
+// Repair module
+//
+// `brew upgrade` removes the Cellar directory of the old patch release, and
+// package manager upgrades move tools around. Links and shims still pointing
+// at the old paths then fail with "No such file or directory" until someone
+// notices. Commands start with a cheap check of the bin directory (a readlink
+// and a stat per entry); when something broke, a rescan and a switch to the
+// same version (or the newest patch release of its minor version) rewrites
+// the links and shims.
+
+use crate::config::{Config, ToolEntry};
+use crate::history::Trigger;
+use crate::switcher::{self, SwitchOptions, SwitchOutcome};
+use crate::{detector, link};
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+/// What broke in the bin directory since the last switch
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Damage {
+    /// Links whose target is gone
+    pub broken_links: Vec<String>,
+    /// Shimmed tools whose original script is gone
+    pub missing_tools: Vec<String>,
+    /// The version the `php` link belonged to, per the config
+    pub active: Option<String>,
+}
+
+impl Damage {
+    pub fn is_empty(&self) -> bool {
+        self.broken_links.is_empty() && self.missing_tools.is_empty()
+    }
+}
+
+/// Look for links and shims whose targets disappeared
+pub fn inspect(config: &Config, bin_dir: &Path) -> Damage {
+    let mut broken_links: Vec<String> = std::fs::read_dir(bin_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| link::link_target(&entry.path()).is_some_and(|target| !target.exists()))
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    broken_links.sort();
+
+    let missing_tools = config
+        .tools
+        .managed
+        .iter()
+        .filter(|tool| tool.shim_created && !tool.original_path.exists())
+        .map(|tool| tool.name.clone())
+        .collect();
+
+    Damage {
+        broken_links,
+        missing_tools,
+        active: switcher::active_entry(config, bin_dir).map(|entry| entry.version.clone()),
+    }
+}
+
+/// Rescan and switch again to the version that was active, rewriting links and shims
+///
+/// The exact version is gone after an upgrade more often than not, so the
+/// newest patch release of the same minor version stands in for it.
+pub fn repair(config: &mut Config, bin_dir: &Path, damage: &Damage) -> Result<SwitchOutcome> {
+    let version = damage
+        .active
+        .clone()
+        .ok_or_else(|| anyhow!("Can't tell which version was active; run 'php-switcher use <version>'"))?;
+
+    switcher::rescan(config)?;
+    if !damage.missing_tools.is_empty() && config.tools.scan_for_tools {
+        config.tools.managed = detector::find_all_php_tools(&config.tools)?
+            .into_iter()
+            .map(|tool| ToolEntry { name: tool.name, original_path: tool.original_path, shebang: tool.shebang, shim_created: false })
+            .collect();
+    }
+
+    // A rescan that finds nothing keeps the old entries, so check the binary is still there
+    let usable = |entry: &&crate::config::VersionEntry| entry.primary_path().is_some_and(|path| path.exists());
+    let entry = config
+        .find_version(&version)
+        .filter(usable)
+        .or_else(|| config.same_minor(&version).filter(usable))
+        .cloned()
+        .ok_or_else(|| anyhow!("PHP {} is gone and no other release of it is installed", version))?;
+
+    let options = SwitchOptions { trigger: Some(Trigger::Repair), ..Default::default() };
+    switcher::activate(config, &entry, bin_dir, &options)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::config::VersionEntry;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_inspect() {
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        let cellar = temp_dir.path().join("Cellar/php@8.2/8.2.12/bin");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        std::fs::create_dir_all(&cellar).unwrap();
+        std::fs::write(cellar.join("php"), "").unwrap();
+        std::os::unix::fs::symlink(cellar.join("php"), bin_dir.join("php")).unwrap();
+        std::os::unix::fs::symlink(cellar.join("php-cgi"), bin_dir.join("php-cgi")).unwrap();
+
+        let mut config = Config::default();
+        config.versions.push(VersionEntry { version: "8.2.12".to_string(), paths: vec![cellar.join("php")], ..Default::default() });
+        config.tools.managed.push(ToolEntry {
+            name: "composer".to_string(),
+            original_path: PathBuf::from("/nonexistent/composer"),
+            shebang: "#!/usr/bin/php".to_string(),
+            shim_created: true,
+        });
+
+        let damage = inspect(&config, &bin_dir);
+        assert_eq!(damage.broken_links, ["php-cgi"]);
+        assert_eq!(damage.missing_tools, ["composer"]);
+        assert_eq!(damage.active.as_deref(), Some("8.2.12"));
+
+        // After `brew upgrade` the whole directory is gone
+        std::fs::remove_dir_all(temp_dir.path().join("Cellar")).unwrap();
+        assert_eq!(inspect(&config, &bin_dir).broken_links, ["php", "php-cgi"]);
+        assert!(Damage::default().is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+// Run report module
+//
+// `each --report FILE` summarizes a multi-version run for CI: a JUnit XML
+// file for dashboards that understand test results, or Markdown to paste
+// into (or attach to) a pull request. Each run becomes one test case with
+// its outcome, duration and the end of its output.
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// Output lines kept per run in a report
+pub const TAIL_LINES: usize = 20;
+
+/// Report file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Junit,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Guess the format from a file extension (`.xml` or `.md`)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "xml" => Some(ReportFormat::Junit),
+            "md" | "markdown" => Some(ReportFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// How one run ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed { exit_code: i32 },
+    /// The requested version isn't installed, so nothing ran
+    NotInstalled,
+}
+
+/// One run of the command under a version
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunResult {
+    pub label: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+    /// Last lines of output, without terminal escapes
+    pub output: Vec<String>,
+}
+
+impl RunResult {
+    fn summary(&self) -> String {
+        match &self.outcome {
+            Outcome::Passed => "passed".to_string(),
+            Outcome::Failed { exit_code } => format!("exited with status {}", exit_code),
+            Outcome::NotInstalled => "not installed".to_string(),
+        }
+    }
+}
+
+/// Render results as a JUnit XML test suite
+pub fn junit(command: &str, results: &[RunResult]) -> String {
+    let failures = results.iter().filter(|r| matches!(r.outcome, Outcome::Failed { .. })).count();
+    let errors = results.iter().filter(|r| r.outcome == Outcome::NotInstalled).count();
+    let total: Duration = results.iter().map(|r| r.duration).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        escape_xml(&format!("php-switcher each: {}", command)),
+        results.len(),
+        failures,
+        errors,
+        total.as_secs_f64()
+    ));
+
+    for result in results {
+        xml.push_str(&format!(
+            "  <testcase classname=\"php-switcher.each\" name=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&result.label),
+            result.duration.as_secs_f64()
+        ));
+        match result.outcome {
+            Outcome::Passed => {}
+            Outcome::Failed { .. } => xml.push_str(&format!("    <failure message=\"{}\"/>\n", escape_xml(&result.summary()))),
+            Outcome::NotInstalled => xml.push_str(&format!("    <error message=\"{}\"/>\n", escape_xml(&result.summary()))),
+        }
+        if !result.output.is_empty() {
+            xml.push_str(&format!("    <system-out>{}</system-out>\n", escape_xml(&result.output.join("\n"))));
+        }
+        xml.push_str("  </testcase>\n");
+    }
+
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// Render results as a Markdown table, with the output of failed runs below it
+pub fn markdown(command: &str, results: &[RunResult]) -> String {
+    let passed = results.iter().filter(|r| r.outcome == Outcome::Passed).count();
+    let mut md = format!("## php-switcher each: `{}`\n\n", command.replace('`', "'"));
+    md.push_str(&format!("{} of {} runs passed.\n\n", passed, results.len()));
+    md.push_str("| Run | Result | Duration |\n|---|---|---|\n");
+
+    for result in results {
+        let icon = match result.outcome {
+            Outcome::Passed => "✅",
+            Outcome::Failed { .. } => "❌",
+            Outcome::NotInstalled => "⚠️",
+        };
+        md.push_str(&format!(
+            "| {} | {} {} | {:.1}s |\n",
+            result.label.replace('|', "\\|"),
+            icon,
+            result.summary(),
+            result.duration.as_secs_f64()
+        ));
+    }
+
+    for result in results.iter().filter(|r| matches!(r.outcome, Outcome::Failed { .. })) {
+        md.push_str(&format!(
+            "\n<details><summary>{} output (last {} lines)</summary>\n\n```\n{}\n```\n\n</details>\n",
+            result.label,
+            TAIL_LINES,
+            result.output.join("\n").replace("```", "'''")
+        ));
+    }
+
+    md
+}
+
+/// Write a report file in the given format
+pub fn write(path: &Path, format: ReportFormat, command: &str, results: &[RunResult]) -> Result<()> {
+    let contents = match format {
+        ReportFormat::Junit => junit(command, results),
+        ReportFormat::Markdown => markdown(command, results),
+    };
+    std::fs::write(path, contents).map_err(|e| anyhow!("Failed to write report {}: {}", path.display(), e))
+}
+
+/// Escape text for XML attributes and content, dropping characters XML can't hold
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .filter(|&c| c == '\t' || c == '\n' || c >= ' ')
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Vec<RunResult> {
+        vec![
+            RunResult {
+                label: "8.3.4".to_string(),
+                outcome: Outcome::Passed,
+                duration: Duration::from_millis(1500),
+                output: vec!["OK (12 tests)".to_string()],
+            },
+            RunResult {
+                label: "8.2.12".to_string(),
+                outcome: Outcome::Failed { exit_code: 1 },
+                duration: Duration::from_millis(250),
+                output: vec!["Failed asserting that <a> & \u{1b}b".to_string()],
+            },
+            RunResult {
+                label: "7.4".to_string(),
+                outcome: Outcome::NotInstalled,
+                duration: Duration::ZERO,
+                output: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let xml = junit("vendor/bin/phpunit", &results());
+
+        assert!(xml.contains("tests=\"3\" failures=\"1\" errors=\"1\" time=\"1.750\""));
+        assert!(xml.contains("<testcase classname=\"php-switcher.each\" name=\"8.2.12\" time=\"0.250\">"));
+        assert!(xml.contains("<failure message=\"exited with status 1\"/>"));
+        assert!(xml.contains("<system-out>Failed asserting that &lt;a&gt; &amp; b</system-out>"));
+        assert!(xml.contains("<error message=\"not installed\"/>"));
+    }
+
+    #[test]
+    fn test_markdown_report() {
+        let md = markdown("composer test", &results());
+
+        assert!(md.starts_with("## php-switcher each: `composer test`\n\n1 of 3 runs passed.\n"));
+        assert!(md.contains("| 8.3.4 | ✅ passed | 1.5s |"));
+        assert!(md.contains("| 8.2.12 | ❌ exited with status 1 | 0.2s |"));
+        assert!(md.contains("<summary>8.2.12 output (last 20 lines)</summary>"));
+        assert!(!md.contains("8.3.4 output"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ReportFormat::from_path(Path::new("junit.xml")), Some(ReportFormat::Junit));
+        assert_eq!(ReportFormat::from_path(Path::new("summary.md")), Some(ReportFormat::Markdown));
+        assert_eq!(ReportFormat::from_path(Path::new("report")), None);
+    }
+}
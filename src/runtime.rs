@@ -0,0 +1,241 @@
+// Application server runtime module
+//
+// FrankenPHP embeds its own PHP build and RoadRunner runs workers with the
+// PHP named in `.rr.yaml`, so a project deployed on either can run a
+// different PHP than the CLI php-switcher manages. Runtimes are detected
+// during scans, and a project's runtime is compared with the CLI version
+// after switches.
+
+use crate::config::{Config, RuntimeEntry, RuntimeKind};
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const KINDS: [RuntimeKind; 2] = [RuntimeKind::FrankenPhp, RuntimeKind::RoadRunner];
+
+/// Directories searched besides PATH
+const SCAN_DIRS: &[&str] = &["/usr/bin", "/usr/local/bin", "/opt/homebrew/bin", "/home/linuxbrew/.linuxbrew/bin"];
+
+/// The runtime a project deploys with and the PHP version it uses there
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectRuntime {
+    pub kind: RuntimeKind,
+    /// File the runtime was detected from
+    pub source: PathBuf,
+    pub php_version: Option<String>,
+}
+
+/// Parse `frankenphp version` (e.g. "FrankenPHP v1.2.5 PHP 8.3.9 Caddy v2.8.4")
+/// into the runtime and embedded PHP versions
+pub fn parse_frankenphp_version(output: &str) -> Option<(String, Option<String>)> {
+    let re = Regex::new(r"FrankenPHP v?(\S+)(?:\s+PHP\s+(\d+\.\d+\.\d+))?").ok()?;
+    let caps = re.captures(output)?;
+    Some((caps[1].to_string(), caps.get(2).map(|m| m.as_str().to_string())))
+}
+
+/// Parse `rr --version` (e.g. "rr version 2024.1.5 (build time: ...)")
+pub fn parse_roadrunner_version(output: &str) -> Option<String> {
+    let re = Regex::new(r"rr version v?(\S+)").ok()?;
+    Some(re.captures(output)?[1].to_string())
+}
+
+/// Identify a runtime binary by running it
+pub fn probe_runtime(kind: RuntimeKind, path: &Path) -> Option<RuntimeEntry> {
+    let arg = match kind {
+        RuntimeKind::FrankenPhp => "version",
+        RuntimeKind::RoadRunner => "--version",
+    };
+    let output = Command::new(path).arg(arg).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let (version, php_version) = match kind {
+        RuntimeKind::FrankenPhp => parse_frankenphp_version(&stdout)?,
+        RuntimeKind::RoadRunner => (parse_roadrunner_version(&stdout)?, None),
+    };
+
+    Some(RuntimeEntry {
+        kind,
+        version,
+        path: path.to_path_buf(),
+        php_version,
+    })
+}
+
+/// Find runtime binaries in a directory
+pub fn scan_directory_for_runtimes(dir: &Path) -> Vec<RuntimeEntry> {
+    KINDS
+        .iter()
+        .map(|kind| (*kind, dir.join(kind.binary_name())))
+        .filter(|(_, path)| path.is_file())
+        .filter_map(|(kind, path)| probe_runtime(kind, &path))
+        .collect()
+}
+
+/// Find all FrankenPHP and RoadRunner binaries on PATH and in the usual directories
+pub fn find_all_runtimes() -> Vec<RuntimeEntry> {
+    crate::timing::measure(|| "scan for runtimes".to_string(), scan_for_runtimes)
+}
+
+fn scan_for_runtimes() -> Vec<RuntimeEntry> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    dirs.extend(SCAN_DIRS.iter().map(PathBuf::from));
+
+    let mut seen = HashSet::new();
+    let mut runtimes = Vec::new();
+    for dir in dirs {
+        for runtime in scan_directory_for_runtimes(&dir) {
+            let canonical = runtime.path.canonicalize().unwrap_or_else(|_| runtime.path.clone());
+            if seen.insert(canonical) {
+                runtimes.push(runtime);
+            }
+        }
+    }
+
+    runtimes
+}
+
+/// Detect the runtime a project deploys with, starting at `start` and walking up
+///
+/// FrankenPHP is recognized from a Dockerfile based on `dunglas/frankenphp`
+/// (whose `-phpX.Y` tag names the PHP version) or a Caddyfile using the
+/// `frankenphp` directive (the tracked FrankenPHP binary's PHP version);
+/// RoadRunner from `.rr.yaml` (the PHP in `server.command`).
+pub fn detect_project_runtime(start: &Path, config: &Config) -> Option<ProjectRuntime> {
+    for dir in start.ancestors() {
+        let dockerfile = dir.join("Dockerfile");
+        if let Some(tag) = read(&dockerfile).as_deref().and_then(frankenphp_image_tag) {
+            return Some(ProjectRuntime {
+                kind: RuntimeKind::FrankenPhp,
+                source: dockerfile,
+                php_version: php_version_from_tag(&tag).or_else(|| tracked_php_version(config)),
+            });
+        }
+
+        let caddyfile = dir.join("Caddyfile");
+        if read(&caddyfile).is_some_and(|contents| contents.contains("frankenphp")) {
+            return Some(ProjectRuntime {
+                kind: RuntimeKind::FrankenPhp,
+                source: caddyfile,
+                php_version: tracked_php_version(config),
+            });
+        }
+
+        for name in [".rr.yaml", ".rr.yml"] {
+            let rr_config = dir.join(name);
+            if let Some(contents) = read(&rr_config) {
+                let php_version = roadrunner_php_command(&contents)
+                    .and_then(|php| crate::detector::get_version_from_binary(php).ok())
+                    .map(|version| version.to_string());
+                return Some(ProjectRuntime {
+                    kind: RuntimeKind::RoadRunner,
+                    source: rr_config,
+                    php_version,
+                });
+            }
+        }
+
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+fn read(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+fn tracked_php_version(config: &Config) -> Option<String> {
+    config
+        .runtimes
+        .iter()
+        .find(|r| r.kind == RuntimeKind::FrankenPhp)
+        .and_then(|r| r.php_version.clone())
+}
+
+/// Get the image tag of a `FROM dunglas/frankenphp[:tag]` line
+pub fn frankenphp_image_tag(dockerfile: &str) -> Option<String> {
+    dockerfile.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        if !words.next()?.eq_ignore_ascii_case("FROM") {
+            return None;
+        }
+        let image = words.find(|w| !w.starts_with("--"))?;
+        let rest = image.strip_prefix("dunglas/frankenphp")?;
+        Some(rest.strip_prefix(':').unwrap_or("").to_string())
+    })
+}
+
+/// Get the PHP version named by an image tag such as `1-php8.3-alpine`
+pub fn php_version_from_tag(tag: &str) -> Option<String> {
+    let re = Regex::new(r"php(\d+\.\d+(?:\.\d+)?)").ok()?;
+    Some(re.captures(tag)?[1].to_string())
+}
+
+/// Get the PHP executable of the `command:` in `.rr.yaml`'s `server` section
+pub fn roadrunner_php_command(rr_config: &str) -> Option<String> {
+    let re = Regex::new(r#"(?m)^\s+command:\s*["']?(\S+)"#).ok()?;
+    let command = re.captures(rr_config)?[1].trim_matches(['"', '\'']).to_string();
+    let name = Path::new(&command).file_name()?.to_str()?;
+    name.starts_with("php").then_some(command)
+}
+
+/// Check whether the CLI version and a runtime's PHP differ in major.minor
+pub fn diverges(cli_version: &str, runtime_version: &str) -> bool {
+    let minor = |v: &str| v.split('.').take(2).map(str::to_string).collect::<Vec<_>>();
+    minor(cli_version) != minor(runtime_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(
+            parse_frankenphp_version("FrankenPHP v1.2.5 PHP 8.3.9 Caddy v2.8.4 h1:abc\n"),
+            Some(("1.2.5".to_string(), Some("8.3.9".to_string())))
+        );
+        assert_eq!(
+            parse_roadrunner_version("rr version 2024.1.5 (build time: 2024-06-20T19:10:36+0000, go1.22.4), OS: linux\n"),
+            Some("2024.1.5".to_string())
+        );
+        assert_eq!(parse_roadrunner_version("PHP 8.3.9 (cli)"), None);
+    }
+
+    #[test]
+    fn test_project_markers() {
+        assert_eq!(
+            frankenphp_image_tag("# app\nFROM --platform=linux/amd64 dunglas/frankenphp:1-php8.2-alpine AS base\n"),
+            Some("1-php8.2-alpine".to_string())
+        );
+        assert_eq!(frankenphp_image_tag("FROM php:8.3-fpm\n"), None);
+        assert_eq!(php_version_from_tag("1-php8.2-alpine"), Some("8.2".to_string()));
+        assert_eq!(php_version_from_tag("latest"), None);
+
+        let rr = "version: '3'\nserver:\n  command: \"php8.1 worker.php\"\nhttp:\n  address: 0.0.0.0:8080\n";
+        assert_eq!(roadrunner_php_command(rr), Some("php8.1".to_string()));
+        assert_eq!(roadrunner_php_command("server:\n  command: ./bin/worker\n"), None);
+
+        assert!(diverges("8.3.4", "8.2"));
+        assert!(!diverges("8.2.12", "8.2.4"));
+    }
+
+    #[test]
+    fn test_detect_project_runtime_from_dockerfile() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join(".git")).unwrap();
+        std::fs::write(temp_dir.path().join("Dockerfile"), "FROM dunglas/frankenphp:1-php8.2\n").unwrap();
+        let nested = temp_dir.path().join("src/Controller");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let runtime = detect_project_runtime(&nested, &Config::default()).unwrap();
+        assert_eq!(runtime.kind, RuntimeKind::FrankenPhp);
+        assert_eq!(runtime.php_version.as_deref(), Some("8.2"));
+    }
+}
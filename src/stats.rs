@@ -0,0 +1,163 @@
+// Local usage statistics module
+//
+// Strictly opt-in (`settings.stats_enabled`) and local-only: counters live in
+// `~/.php-switcher/stats.toml` and are never sent anywhere.
+
+use crate::config::{self, Config};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Stats {
+    /// Number of switches per version
+    #[serde(default)]
+    pub switches: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub scans: ScanStats,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScanStats {
+    #[serde(default)]
+    pub count: u64,
+    #[serde(default)]
+    pub total_ms: u64,
+    #[serde(default)]
+    pub last_ms: u64,
+}
+
+impl Stats {
+    pub fn record_switch(&mut self, version: &str) {
+        *self.switches.entry(version.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn record_scan(&mut self, duration: Duration) {
+        let ms = duration.as_millis() as u64;
+        self.scans.count += 1;
+        self.scans.total_ms += ms;
+        self.scans.last_ms = ms;
+    }
+
+    /// Average scan duration in milliseconds
+    pub fn average_scan_ms(&self) -> Option<u64> {
+        self.scans.total_ms.checked_div(self.scans.count)
+    }
+
+    /// Versions ordered by switch count, most used first
+    pub fn most_used(&self) -> Vec<(&str, u64)> {
+        let mut versions: Vec<(&str, u64)> = self.switches.iter().map(|(v, c)| (v.as_str(), *c)).collect();
+        versions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        versions
+    }
+}
+
+/// Get the path to the stats file
+pub fn get_stats_path() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join("stats.toml"))
+}
+
+/// Load stats from a file (empty if the file doesn't exist)
+pub fn load_stats_from_file<P: AsRef<Path>>(path: P) -> Result<Stats> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Ok(Stats::default());
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read stats file: {}", e))?;
+
+    toml::from_str(&contents).map_err(|e| anyhow!("Failed to parse stats: {}", e))
+}
+
+/// Save stats to a file
+pub fn save_stats_to_file<P: AsRef<Path>>(stats: &Stats, path: P) -> Result<()> {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Failed to create stats directory: {}", e))?;
+    }
+
+    let toml_str =
+        toml::to_string_pretty(stats).map_err(|e| anyhow!("Failed to serialize stats: {}", e))?;
+
+    std::fs::write(path, toml_str).map_err(|e| anyhow!("Failed to write stats file: {}", e))
+}
+
+/// Load stats from the default location
+pub fn load_stats() -> Result<Stats> {
+    load_stats_from_file(get_stats_path()?)
+}
+
+/// Apply an update to the stored stats if collection is enabled
+///
+/// Errors are swallowed: stats must never break the command being measured.
+pub fn update<F: FnOnce(&mut Stats)>(config: &Config, update: F) {
+    if !config.settings.stats_enabled || config::is_read_only() {
+        return;
+    }
+
+    if let Ok(path) = get_stats_path() {
+        if let Ok(mut stats) = load_stats_from_file(&path) {
+            update(&mut stats);
+            save_stats_to_file(&stats, &path).ok();
+        }
+    }
+}
+
+/// Delete all collected stats
+pub fn reset() -> Result<()> {
+    let path = get_stats_path()?;
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| anyhow!("Failed to remove stats file: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_switch_and_most_used() {
+        let mut stats = Stats::default();
+        stats.record_switch("8.2.12");
+        stats.record_switch("8.3.4");
+        stats.record_switch("8.3.4");
+
+        assert_eq!(stats.most_used(), vec![("8.3.4", 2), ("8.2.12", 1)]);
+    }
+
+    #[test]
+    fn test_record_scan() {
+        let mut stats = Stats::default();
+        assert_eq!(stats.average_scan_ms(), None);
+
+        stats.record_scan(Duration::from_millis(100));
+        stats.record_scan(Duration::from_millis(300));
+
+        assert_eq!(stats.scans.count, 2);
+        assert_eq!(stats.scans.last_ms, 300);
+        assert_eq!(stats.average_scan_ms(), Some(200));
+    }
+
+    #[test]
+    fn test_save_and_load_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let stats_file = temp_dir.path().join("stats.toml");
+
+        assert_eq!(load_stats_from_file(&stats_file).unwrap(), Stats::default());
+
+        let mut stats = Stats::default();
+        stats.record_switch("8.1.0");
+        stats.record_scan(Duration::from_millis(42));
+        save_stats_to_file(&stats, &stats_file).unwrap();
+
+        assert_eq!(load_stats_from_file(&stats_file).unwrap(), stats);
+    }
+}
@@ -0,0 +1,104 @@
+// Output style module
+//
+// Text output marks status with glyphs (✓ ✗ ⚠ ● →) and color. Screen readers
+// skip or mangle those, and dumb terminals may not render them at all. In
+// accessible mode (`--accessible`, `settings.accessible`, or `TERM=dumb`)
+// every glyph is replaced by a word, color is turned off, and trees and
+// aligned columns are printed as plain indented lines.
+
+use std::ffi::OsStr;
+
+/// Set (to anything but `0`) to turn on accessible output, also for child processes
+pub const ACCESSIBLE_ENV: &str = "PHP_SWITCHER_ACCESSIBLE";
+
+/// Set once a loaded config asks for accessible output (`settings.accessible`)
+static ACCESSIBLE_SETTING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Check whether output should avoid glyphs, color and box drawing
+pub fn is_accessible() -> bool {
+    ACCESSIBLE_SETTING.load(std::sync::atomic::Ordering::Relaxed)
+        || accessible_from(std::env::var_os(ACCESSIBLE_ENV).as_deref(), std::env::var_os("TERM").as_deref())
+}
+
+fn accessible_from(flag: Option<&OsStr>, term: Option<&OsStr>) -> bool {
+    flag.is_some_and(|v| !v.is_empty() && v != "0") || term.is_some_and(|term| term == "dumb")
+}
+
+/// Turn accessible mode on for this process and its children, and turn color off
+pub fn enable_accessible() {
+    std::env::set_var(ACCESSIBLE_ENV, "1");
+    colored::control::set_override(false);
+}
+
+/// Turn accessible mode on for this process when the config asks for it
+///
+/// Called when the config is loaded, possibly with other threads running, so
+/// unlike `enable_accessible` it leaves the environment alone; child
+/// processes read the same config.
+pub fn apply_accessible_setting(accessible: bool) {
+    if accessible {
+        ACCESSIBLE_SETTING.store(true, std::sync::atomic::Ordering::Relaxed);
+        colored::control::set_override(false);
+    }
+}
+
+/// A glyph, or the word standing in for it in accessible mode
+pub fn symbol(glyph: &'static str, word: &'static str) -> &'static str {
+    if is_accessible() {
+        word
+    } else {
+        glyph
+    }
+}
+
+/// Something succeeded or is fine
+pub fn ok() -> &'static str {
+    symbol("✓", "ok:")
+}
+
+/// Something failed
+pub fn fail() -> &'static str {
+    symbol("✗", "error:")
+}
+
+pub fn warn() -> &'static str {
+    symbol("⚠", "warning:")
+}
+
+/// The active version
+pub fn active() -> &'static str {
+    symbol("●", "ACTIVE")
+}
+
+/// An available, inactive version
+pub fn inactive() -> &'static str {
+    symbol("○", "available")
+}
+
+/// Between two values, e.g. a link and its target
+pub fn arrow() -> &'static str {
+    symbol("→", "to")
+}
+
+/// In front of a follow-up hint
+pub fn note() -> &'static str {
+    symbol("→", "note:")
+}
+
+pub fn bullet() -> &'static str {
+    symbol("•", "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accessible_from() {
+        let os = |s: &'static str| Some(OsStr::new(s));
+        assert!(accessible_from(os("1"), os("xterm-256color")));
+        assert!(accessible_from(None, os("dumb")));
+        assert!(!accessible_from(os("0"), os("xterm")));
+        assert!(!accessible_from(os(""), None));
+    }
+}
@@ -1,10 +1,119 @@
 // Version switching module
 
-use crate::{config, detector, hints, platform};
+use crate::config::{Config, HookEvent, LinkStrategyKind, VersionEntry};
+use crate::hooks::{self, HookPayload};
+use crate::version::PhpVersion;
+use crate::events::{self, Event};
+use crate::history::{self, HistoryEntry, Trigger};
+use crate::link::{self, LinkStrategy};
+use crate::{composer, config, confirm, detector, hints, managers, opcache, platform, project_config, runtime, stats, style};
 use anyhow::Result;
 use colored::Colorize;
 use std::path::{Path, PathBuf};
 
+/// A binary linked into the switcher bin directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedBinary {
+    pub name: String,
+    pub target: PathBuf,
+    /// Variables set before running it (only strategies writing scripts can)
+    pub env: Vec<(String, String)>,
+}
+
+/// How a switch affected one entry of the bin directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Updated,
+    Unchanged,
+    /// Only for tool shims a project denies
+    Removed,
+}
+
+/// A planned link compared against what the bin directory held before the switch
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkDelta {
+    pub name: String,
+    pub target: PathBuf,
+    /// Previous symlink target, if the entry was a symlink
+    pub previous: Option<PathBuf>,
+    pub change: Change,
+}
+
+/// A tool shim compared against its previous contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShimDelta {
+    pub name: String,
+    pub change: Change,
+}
+
+/// Health of a single link in the bin directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryStatus {
+    /// Resolves to a binary of the active installation
+    Ok,
+    /// The link target no longer exists
+    Broken,
+    /// Resolves to a binary of a different installation
+    Foreign,
+}
+
+/// Verification result for one link in the bin directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryCheck {
+    pub name: String,
+    pub target: PathBuf,
+    pub status: BinaryStatus,
+}
+
+/// Result of activating a PHP version
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchOutcome {
+    pub version: String,
+    pub primary_path: PathBuf,
+    pub links: Vec<LinkDelta>,
+    pub shims: Vec<ShimDelta>,
+    pub verified: Option<PhpVersion>,
+    /// Status of every link in the bin directory after the switch
+    pub checks: Vec<BinaryCheck>,
+    /// Failures of hooks configured to warn rather than abort
+    pub hook_warnings: Vec<String>,
+}
+
+/// Options controlling how a switch is performed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SwitchOptions {
+    /// Switch even if the version is not allowed by the configured policy
+    pub force: bool,
+    /// What caused the switch (detected from the environment when unset)
+    pub trigger: Option<Trigger>,
+    /// Do nothing if the version is already fully active
+    pub if_needed: bool,
+    /// Check opcache and the preload script after switching
+    pub check_opcache: bool,
+    /// Link strategy for this switch only, instead of `settings.link_strategy`
+    pub strategy: Option<LinkStrategyKind>,
+}
+
+/// The link strategy a switch uses
+fn strategy_for_switch(config: &Config, options: &SwitchOptions) -> Box<dyn LinkStrategy> {
+    options.strategy.map(|kind| link::strategy_for(kind, config.settings.shim_mode())).unwrap_or_else(|| link::strategy_for_config(config))
+}
+
+fn show_opcache_report(report: &opcache::OpcacheReport) {
+    println!("\n{}", "OPcache:".dimmed());
+    for problem in &report.problems {
+        println!("  {} {}", style::warn().yellow(), problem.yellow());
+    }
+
+    if report.problems.is_empty() {
+        match &report.preload {
+            Some(preload) => println!("  {} preload script {} loads", style::ok().green(), preload.display()),
+            None => println!("  {} loaded, no preload script configured", style::ok().green()),
+        }
+    }
+}
+
 /// Switch to a specified PHP version
 ///
 /// This function:
@@ -12,50 +121,57 @@ use std::path::{Path, PathBuf};
 /// 2. If not found, automatically scans the system
 /// 3. If still not found, shows installation hints
 /// 4. Creates symlinks for all related binaries (php, php-cgi, etc.)
-pub fn switch_version(version_pattern: &str) -> Result<()> {
-    println!("Switching to PHP {}...", version_pattern.bold());
-
+pub fn switch_version(version_pattern: &str, options: &SwitchOptions) -> Result<()> {
     // Load config
     let mut config = config::load_config()?;
 
-    // Try to find matching version in cache
-    let mut paths = config.get_installation_by_version(version_pattern);
+    // Fast path for hooks and CI: skip all work when nothing would change
+    if options.if_needed {
+        if let Some(entry) = config.find_version(version_pattern) {
+            if is_up_to_date_with(&config, entry, &get_bin_dir()?, strategy_for_switch(&config, options).as_ref()) {
+                println!("{} Already on PHP {}", style::ok().green(), entry.version.bold());
+                return Ok(());
+            }
+        }
+    }
+
+    println!("Switching to PHP {}...", version_pattern.bold());
 
-    // If not found, auto-scan the system
-    if paths.is_none() {
+    // If not found in cache, auto-scan the system
+    if config.find_version(version_pattern).is_none() {
         println!(
             "{}",
             format!("PHP {} not found in cache, scanning system...", version_pattern)
                 .yellow()
         );
 
-        let installations = detector::find_all_php_installations()?;
+        let found = rescan(&mut config)?;
 
-        if installations.is_empty() {
+        if found == 0 {
+            if let Some(broken) = config.find_broken(version_pattern) {
+                return Err(anyhow::anyhow!("{}", broken.describe()));
+            }
             println!("{}", "No PHP installations found on system.".red());
             let detected_platform = platform::Platform::detect();
             hints::show_installation_hints(version_pattern, detected_platform);
             return Err(anyhow::anyhow!("No PHP installations found"));
         }
 
-        // Update config with newly found installations
-        config.update_from_installations(&installations);
-        config::save_config(&config)?;
-
         println!(
             "{} Scan complete, found {} installation(s)",
-            "✓".green(),
-            installations.len()
+            style::ok().green(),
+            found
         );
-
-        // Try to find the version again
-        paths = config.get_installation_by_version(version_pattern);
     }
 
     // If still not found after scanning, show installation hints
-    let paths = match paths {
-        Some(p) if !p.is_empty() => p,
+    let entry = match config.find_version(version_pattern) {
+        Some(entry) if !entry.paths.is_empty() => entry.clone(),
         _ => {
+            // Installing it again won't help if the one found doesn't run
+            if let Some(broken) = config.find_broken(version_pattern) {
+                return Err(anyhow::anyhow!("{}", broken.describe()));
+            }
             let detected_platform = platform::Platform::detect();
             hints::show_installation_hints(version_pattern, detected_platform);
             return Err(anyhow::anyhow!(
@@ -65,78 +181,444 @@ pub fn switch_version(version_pattern: &str) -> Result<()> {
         }
     };
 
-    // Get primary path for verification
-    let primary_path = paths
-        .iter()
-        .find(|p| p.file_name().and_then(|n| n.to_str()) == Some("php"))
-        .or_else(|| paths.first())
-        .ok_or_else(|| anyhow::anyhow!("No primary PHP binary found"))?;
+    if let Some(primary_path) = entry.primary_path() {
+        println!("{} Found PHP at: {}", style::ok().green(), primary_path.display());
+    }
+    println!("  {} related binaries to symlink", entry.paths.len());
 
-    println!("{} Found PHP at: {}", "✓".green(), primary_path.display());
-    println!("  {} related binaries to symlink", paths.len());
+    if options.force && !config.policy.allows(&entry.version) {
+        println!(
+            "{}",
+            format!("{} PHP {} is not allowed by policy, switching anyway (--force)", style::warn(), entry.version).yellow()
+        );
+    }
 
-    // Create symlinks for all related binaries
     let bin_dir = get_bin_dir()?;
-    let symlink_count = create_symlinks(&paths, &bin_dir)?;
-
-    // Verify the switch using the primary binary
-    verify_switch(&bin_dir)?;
-
-    // Create shims for PHP tools if scanning is enabled
-    let shim_count = if config.tools.scan_for_tools && !config.tools.managed.is_empty() {
-        println!("\n{}", "Creating tool shims...".dimmed());
+    let outcome = activate(&mut config, &entry, &bin_dir, options)?;
 
-        let tools: Vec<crate::tools::PhpTool> = config.tools.managed.iter().map(|entry| {
-            crate::tools::PhpTool {
-                name: entry.name.clone(),
-                original_path: entry.original_path.clone(),
-                shebang: entry.shebang.clone(),
-            }
-        }).collect();
-
-        let count = create_shims_for_tools(&tools, &bin_dir)?;
+    println!();
+    for link in &outcome.links {
+        print_link_delta(link);
+    }
+    if let Some(version) = &outcome.verified {
+        println!("\n{} Verified: {}", style::ok().green(), version.to_string().bold());
+    }
 
-        if count > 0 {
-            for tool in &tools {
-                if crate::tools::needs_shim(&tool.shebang) {
-                    println!("  {} {} → uses switched PHP", "✓".green(), tool.name.dimmed());
-                }
+    if outcome.checks.len() > 1 || outcome.checks.iter().any(|c| c.status != BinaryStatus::Ok) {
+        println!("\n{}", "Binaries:".dimmed());
+        for check in &outcome.checks {
+            match check.status {
+                BinaryStatus::Ok => println!("  {} {}", style::ok().green(), check.name.dimmed()),
+                BinaryStatus::Broken => println!(
+                    "  {} {} {} {} (target missing)",
+                    style::fail().red(),
+                    check.name,
+                    style::arrow(),
+                    check.target.display()
+                ),
+                BinaryStatus::Foreign => println!(
+                    "  {} {} {} {} (belongs to another installation)",
+                    style::warn().yellow(),
+                    check.name,
+                    style::arrow(),
+                    check.target.display()
+                ),
             }
         }
+    }
 
-        // Update config to mark shims as created
-        for entry in &mut config.tools.managed {
-            entry.shim_created = crate::tools::needs_shim(&entry.shebang);
+    for warning in &outcome.hook_warnings {
+        println!("{} {}", style::warn().yellow(), warning.yellow());
+    }
+
+    if !outcome.shims.is_empty() {
+        println!("\n{}", "Tool shims:".dimmed());
+        for shim in &outcome.shims {
+            let (marker, note) = match shim.change {
+                Change::Added => (style::symbol("+", "added:").green(), "created"),
+                Change::Updated => (style::symbol("~", "updated:").yellow(), "updated"),
+                Change::Unchanged => (style::symbol("=", "unchanged:").dimmed(), "unchanged"),
+                Change::Removed => (style::symbol("-", "removed:").red(), "removed, denied by the project"),
+            };
+            println!("  {} {} {}", marker, shim.name, format!("({})", note).dimmed());
         }
-        config::save_config(&config)?;
+    }
 
-        count
-    } else {
-        0
-    };
+    if options.check_opcache || config.settings.check_opcache {
+        show_opcache_report(&opcache::check(&outcome.primary_path));
+    }
 
     // Show success message
     println!("\n{}", "PHP version switched successfully!".green().bold());
-    println!("  {} PHP symlinks created", symlink_count);
-    if shim_count > 0 {
-        println!("  {} tool shims created", shim_count);
-    } else if !config.tools.scan_for_tools {
-        let tip = "💡 Tip: Enable tool scanning to auto-shim composer, phpunit, etc.";
+    println!("  Links: {}", summarize_changes(outcome.links.iter().map(|l| l.change)));
+    if !outcome.shims.is_empty() {
+        println!("  Tool shims: {}", summarize_changes(outcome.shims.iter().map(|s| s.change)));
+    }
+
+    // Environment implications: PATH setup, and shells that cached a `php`
+    // from elsewhere before the bin dir had one
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let env_ok = bin_dir_takes_precedence(&path_var, &bin_dir);
+    if env_ok {
+        println!("  Environment: no changes needed ({} is first on PATH)", bin_dir.display());
+        if outcome.links.iter().any(|l| l.name == "php" && l.change == Change::Added) {
+            show_rehash_hint();
+        }
+    }
+
+    if outcome.shims.is_empty() && !config.tools.scan_for_tools {
+        let tip = format!("{} Enable tool scanning to auto-shim composer, phpunit, etc.", style::symbol("💡 Tip:", "Tip:"));
         let cmd = "   Run: php-switcher tools enable && php-switcher tools scan";
         println!("\n{}", tip.dimmed());
         println!("{}", cmd.dimmed());
     }
 
-    show_path_instructions(&bin_dir);
+    if !env_ok {
+        match managers::detect(&path_var, &bin_dir) {
+            Some(competitor) => handle_competitor(&mut config, &entry, &bin_dir, &competitor)?,
+            None => show_path_instructions(&bin_dir),
+        }
+    }
 
     Ok(())
 }
 
-/// Create symlinks for all PHP binaries in the target directory
-fn create_symlinks(source_paths: &[PathBuf], bin_dir: &Path) -> Result<usize> {
-    std::fs::create_dir_all(bin_dir)?;
+/// Explain which mechanism shadows the bin directory, then adopt it or say how to neutralize it
+///
+/// Adoption is only offered while no link strategy is configured; an explicit
+/// choice in the config is never overridden.
+fn handle_competitor(
+    config: &mut Config,
+    entry: &VersionEntry,
+    bin_dir: &Path,
+    competitor: &managers::Competitor,
+) -> Result<()> {
+    let manager = competitor.manager;
+    println!(
+        "\n{}",
+        format!(
+            "{} `php` still resolves to {}, managed by {}, which comes before {} on PATH.",
+            style::warn(),
+            competitor.path.display(),
+            manager.name(),
+            bin_dir.display()
+        )
+        .yellow()
+    );
+    println!("  The switch above won't be visible in new shells until that's resolved.");
+
+    if let Some(kind) = manager.adoptable_strategy().filter(|_| config.settings.link_strategy.is_none()) {
+        let question = format!("Switch through {} instead of the bin directory?", manager.name());
+        if confirm::confirm(config, &question, false)? {
+            // The switch itself is done; only expose it through the adopted strategy
+            link::strategy_for(kind, config.settings.shim_mode()).apply(&plan_entry_links(entry)?, bin_dir)?;
+            config.settings.link_strategy = Some(kind);
+            config::save_config(config)?;
+            println!(
+                "{} Adopted {}; future switches use it (settings.link_strategy)",
+                style::ok().green(),
+                manager.name()
+            );
+            return Ok(());
+        }
+    }
+
+    println!("\nTo let php-switcher take over instead:");
+    for step in manager.neutralize_steps(bin_dir) {
+        println!("  {}", step);
+    }
+    Ok(())
+}
+
+/// Print one line of the link delta
+fn print_link_delta(link: &LinkDelta) {
+    let target = link.target.display().to_string();
+    match link.change {
+        Change::Added => println!("  {} {} {} {}", style::symbol("+", "added:").green(), link.name, style::arrow(), target.dimmed()),
+        Change::Updated => println!(
+            "  {} {} {} {} {}",
+            style::symbol("~", "updated:").yellow(),
+            link.name,
+            style::arrow(),
+            target.dimmed(),
+            link.previous
+                .as_ref()
+                .map(|p| format!("(was {})", p.display()))
+                .unwrap_or_default()
+                .dimmed()
+        ),
+        Change::Unchanged => println!("  {} {} {}", style::symbol("=", "unchanged:").dimmed(), link.name.dimmed(), "(unchanged)".dimmed()),
+        Change::Removed => println!("  {} {}", style::symbol("-", "removed:").red(), link.name),
+    }
+}
 
-    let mut symlink_count = 0;
+/// Summarize changes as e.g. "1 added, 2 updated, 3 unchanged"
+fn summarize_changes(changes: impl Iterator<Item = Change>) -> String {
+    let (mut added, mut updated, mut unchanged, mut removed) = (0, 0, 0, 0);
+    for change in changes {
+        match change {
+            Change::Added => added += 1,
+            Change::Updated => updated += 1,
+            Change::Unchanged => unchanged += 1,
+            Change::Removed => removed += 1,
+        }
+    }
+    let summary = format!("{} added, {} updated, {} unchanged", added, updated, unchanged);
+    if removed > 0 {
+        format!("{}, {} removed", summary, removed)
+    } else {
+        summary
+    }
+}
+
+/// Check whether the bin directory comes before any other directory with a `php` on PATH
+pub fn bin_dir_takes_precedence(path_var: &std::ffi::OsStr, bin_dir: &Path) -> bool {
+    for dir in std::env::split_paths(path_var) {
+        if dir == bin_dir {
+            return true;
+        }
+        if dir.join("php").is_file() {
+            return false;
+        }
+    }
+    false
+}
+
+/// A version picked for an unpinned composer project
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintMatch {
+    pub manifest: PathBuf,
+    /// The `require.php` constraint that was used
+    pub constraint: String,
+    pub version: String,
+}
+
+/// Pick the newest tracked version satisfying the nearest composer.json's `require.php`
+///
+/// `None` when there's no composer.json or it has no PHP constraint; an
+/// error when nothing installed satisfies the constraint.
+pub fn resolve_composer_constraint(config: &Config, start: &Path) -> Result<Option<ConstraintMatch>> {
+    let Some(manifest) = composer::find_composer_json(start) else { return Ok(None) };
+    let contents = composer::load(&manifest)?;
+    let Some(constraint) = composer::php_constraint(&contents) else { return Ok(None) };
+
+    let entry = composer::newest_satisfying(config, constraint)?.ok_or_else(|| {
+        anyhow::anyhow!("No installed PHP version satisfies require.php {} ({})", constraint, manifest.display())
+    })?;
+
+    Ok(Some(ConstraintMatch {
+        constraint: constraint.to_string(),
+        version: entry.version.clone(),
+        manifest,
+    }))
+}
+
+/// Extract a version from text like the contents of a `.php-version` file
+///
+/// Uses the first line that isn't blank or a `#` comment.
+pub fn parse_version_spec(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+}
+
+/// Rescan the system and store the results in the config, returning the number found
+pub fn rescan(config: &mut Config) -> Result<usize> {
+    let started = std::time::Instant::now();
+    let detector::ScanReport { installations, broken, .. } = detector::scan_system(&config.settings.scan_options());
+    stats::update(config, |s| s.record_scan(started.elapsed()));
+
+    config.broken = broken;
+    if !installations.is_empty() {
+        config.update_from_installations(&installations);
+        config.runtimes = runtime::find_all_runtimes();
+        config.containers = crate::container::find_images();
+        // In read-only mode the results are only kept in memory
+        if !config::is_read_only() {
+            config::save_config(config)?;
+        }
+    }
+
+    events::emit(&Event::ScanFinished { installations: installations.len() });
+
+    Ok(installations.len())
+}
+
+/// Activate an installation without printing anything
+///
+/// Creates the symlinks, verifies the primary binary, refreshes tool shims and
+/// saves the config. Used by `switch_version` and the non-interactive API.
+pub fn activate(config: &mut Config, entry: &VersionEntry, bin_dir: &Path, options: &SwitchOptions) -> Result<SwitchOutcome> {
+    if config::is_read_only() {
+        return Err(anyhow::anyhow!("Read-only mode: switching is disabled"));
+    }
+
+    check_policy(config, &entry.version, options)?;
+
+    let primary_path = entry
+        .primary_path()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("No primary PHP binary found"))?;
+
+    if entry.is_garbage_collected() {
+        return Err(anyhow::anyhow!(
+            "The Nix store path {} no longer exists (garbage-collected?); reinstall PHP {} or run 'php-switcher scan'",
+            primary_path.display(),
+            entry.version
+        ));
+    }
+
+    // Verification can't tell "wrong CPU" from "no version output", so check the header first
+    if !options.force {
+        crate::binary::check_runnable(&primary_path).map_err(|e| anyhow::anyhow!("{} (use --force to switch anyway)", e))?;
+    }
+
+    let previous = active_entry(config, bin_dir).map(|e| e.version.clone());
+    let trigger = options.trigger.unwrap_or_else(Trigger::detect);
+
+    let mut payload = HookPayload {
+        event: HookEvent::PreSwitch,
+        old_version: previous.clone(),
+        new_version: entry.version.clone(),
+        primary_path: primary_path.clone(),
+        paths: entry.paths.clone(),
+        trigger,
+    };
+    let mut hook_warnings = hooks::run_hooks(&config.hooks, HookEvent::PreSwitch, &payload)?;
+
+    // Expose all related binaries using the configured link strategy,
+    // remembering what was there before so the summary can show a delta
+    let strategy = strategy_for_switch(config, options);
+    let planned = plan_entry_links(entry)?;
+    let links = diff_links(&planned, bin_dir, strategy.as_ref());
+    prepare_bin_dir(bin_dir, config.settings.bin_dir_mode())?;
+    strategy.apply(&planned, bin_dir)?;
+
+    // Verify the switch using the primary binary, then every other link
+    let verified = verify_switch(bin_dir)?;
+    let checks = verify_bin_dir(bin_dir, entry);
+
+    // Create shims for PHP tools if scanning is enabled, then apply the project's tool rules
+    let managed: Vec<crate::tools::PhpTool> = config.tools.managed.iter().map(|entry| {
+        crate::tools::PhpTool {
+            name: entry.name.clone(),
+            original_path: entry.original_path.clone(),
+            shebang: entry.shebang.clone(),
+        }
+    }).collect();
+    let mut shimmed: Vec<crate::tools::PhpTool> = if config.tools.scan_for_tools {
+        managed.iter().filter(|tool| crate::tools::needs_shim(&tool.shebang)).cloned().collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut denied = Vec::new();
+    match std::env::current_dir().map_err(anyhow::Error::from).and_then(|cwd| project_config::load(&cwd)) {
+        Ok(Some(project)) => {
+            let plan = project_config::plan_tools(&project, shimmed, &managed, &crate::toolchain::search_dirs(config, bin_dir));
+            shimmed = plan.tools;
+            denied = plan.denied;
+            hook_warnings.extend(plan.warnings);
+        }
+        Ok(None) => {}
+        Err(e) => hook_warnings.push(format!("Ignoring project config: {}", e)),
+    }
+
+    let mut shims = Vec::new();
+    if !shimmed.is_empty() {
+        let before: Vec<Option<Vec<u8>>> =
+            shimmed.iter().map(|tool| std::fs::read(bin_dir.join(&tool.name)).ok()).collect();
+
+        let mut settings = crate::tools::ShimSettings::from_config(&config.tools, &config::get_config_dir()?)?;
+        settings.mode = config.settings.shim_mode();
+        for tool in &shimmed {
+            crate::tools::create_shim_with(tool, bin_dir, &settings)?;
+        }
+
+        shims = shimmed
+            .iter()
+            .zip(before)
+            .map(|(tool, before)| {
+                let change = match before {
+                    None => Change::Added,
+                    Some(old) if std::fs::read(bin_dir.join(&tool.name)).is_ok_and(|new| new == old) => Change::Unchanged,
+                    Some(_) => Change::Updated,
+                };
+                ShimDelta { name: tool.name.clone(), change }
+            })
+            .collect();
+    }
+
+    // Denied tools lose their shims, but never one of the version's own links
+    for name in denied {
+        let shim = bin_dir.join(&name);
+        if planned.iter().all(|link| link.name != name) && shim.is_file() {
+            std::fs::remove_file(&shim).map_err(|e| anyhow::anyhow!("Failed to remove {}: {}", shim.display(), e))?;
+            shims.push(ShimDelta { name, change: Change::Removed });
+        }
+    }
+
+    if config.tools.scan_for_tools && !config.tools.managed.is_empty() {
+        // Update config to mark shims as created
+        for entry in &mut config.tools.managed {
+            entry.shim_created = crate::tools::needs_shim(&entry.shebang);
+        }
+    }
+    config.settings.record_selection(&entry.version, previous.as_deref());
+    config::save_config(config)?;
+
+    events::emit(&Event::Switched {
+        version: entry.version.clone(),
+        primary_path: primary_path.clone(),
+    });
+
+    stats::update(config, |s| s.record_switch(&entry.version));
+
+    // History is best-effort: a read-only config dir must not fail the switch
+    history::record(&HistoryEntry::now(previous, &entry.version, trigger)).ok();
+
+    payload.event = HookEvent::PostSwitch;
+    hook_warnings.extend(hooks::run_hooks(&config.hooks, HookEvent::PostSwitch, &payload)?);
+
+    Ok(SwitchOutcome {
+        version: entry.version.clone(),
+        primary_path,
+        links,
+        shims,
+        verified,
+        checks,
+        hook_warnings,
+    })
+}
+
+/// Refuse versions excluded by `[policy] allowed_versions` unless forced
+pub fn check_policy(config: &Config, version: &str, options: &SwitchOptions) -> Result<()> {
+    if options.force || config.policy.allows(version) {
+        return Ok(());
+    }
+
+    Err(anyhow::anyhow!(
+        "PHP {} is not allowed by policy (allowed: {}). Use --force to override.",
+        version,
+        config.policy.allowed_versions.join(", ")
+    ))
+}
+
+/// Find the config entry that the switcher's `php` symlink currently points to
+pub fn active_entry<'a>(config: &'a Config, bin_dir: &Path) -> Option<&'a VersionEntry> {
+    let target = link::link_target(&bin_dir.join("php"))?;
+    // Links made before a rescan may point at what is now recorded as an alias
+    config.versions.iter().find(|entry| entry.paths.contains(&target) || entry.aliases.contains(&target))
+}
+
+/// Work out which links the bin directory should contain for an installation
+///
+/// The primary binary always becomes `php`; related binaries get standardized
+/// names (e.g. `php81-cgi` -> `php-cgi`).
+pub fn plan_links(source_paths: &[PathBuf]) -> Result<Vec<LinkedBinary>> {
+    let mut links = Vec::new();
 
     // Find the primary PHP binary (the one named "php" or the first one)
     let primary_path = source_paths
@@ -146,23 +628,11 @@ fn create_symlinks(source_paths: &[PathBuf], bin_dir: &Path) -> Result<usize> {
         .ok_or_else(|| anyhow::anyhow!("No PHP binary found"))?;
 
     // Always create a standard "php" symlink to the primary binary
-    let php_symlink = bin_dir.join("php");
-    if php_symlink.exists() || php_symlink.symlink_metadata().is_ok() {
-        std::fs::remove_file(&php_symlink).ok();
-    }
-
-    #[cfg(unix)]
-    {
-        std::os::unix::fs::symlink(primary_path, &php_symlink)?;
-    }
-
-    symlink_count += 1;
-    println!(
-        "  {} {} → {}",
-        "✓".green(),
-        "php".dimmed(),
-        primary_path.display().to_string().dimmed()
-    );
+    links.push(LinkedBinary {
+        name: "php".to_string(),
+        target: primary_path.clone(),
+        env: Vec::new(),
+    });
 
     // Create symlinks for related binaries (php-cgi, php-fpm, etc.)
     for path in source_paths {
@@ -176,9 +646,8 @@ fn create_symlinks(source_paths: &[PathBuf], bin_dir: &Path) -> Result<usize> {
 
             // For versioned binaries like "php81", "php81-cgi", create symlinks with standard names
             // e.g., php81 -> skip (primary already handled), php81-cgi -> php-cgi
-            let standardized_name = if filename_str.starts_with("php") {
+            let standardized_name = if let Some(without_prefix) = filename_str.strip_prefix("php") {
                 // Remove version numbers from the name (e.g., php81-cgi -> php-cgi)
-                let without_prefix = &filename_str[3..]; // Skip "php"
                 let rest = without_prefix.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
 
                 // If only a version number (like "php81"), skip it since we already handled primary
@@ -192,59 +661,246 @@ fn create_symlinks(source_paths: &[PathBuf], bin_dir: &Path) -> Result<usize> {
                 filename_str.to_string()
             };
 
-            let symlink_path = bin_dir.join(&standardized_name);
+            links.push(LinkedBinary {
+                name: standardized_name,
+                target: path.clone(),
+                env: Vec::new(),
+            });
+        }
+    }
 
-            // Remove existing symlink if it exists
-            if symlink_path.exists() || symlink_path.symlink_metadata().is_ok() {
-                std::fs::remove_file(&symlink_path).ok();
-            }
+    Ok(links)
+}
+
+/// Plan the links of a tracked version, carrying its `env` to every binary
+pub fn plan_entry_links(entry: &VersionEntry) -> Result<Vec<LinkedBinary>> {
+    let env: Vec<(String, String)> = entry.env.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+    let mut links = plan_links(&entry.paths)?;
+    for link in &mut links {
+        link.env = env.clone();
+    }
+    Ok(links)
+}
+
+/// Compare planned links against the current contents of the bin directory
+pub fn diff_links(links: &[LinkedBinary], bin_dir: &Path, strategy: &dyn LinkStrategy) -> Vec<LinkDelta> {
+    links
+        .iter()
+        .map(|link| {
+            let path = bin_dir.join(&link.name);
+            let change = if strategy.is_linked(link, bin_dir) {
+                Change::Unchanged
+            } else if path.symlink_metadata().is_ok() {
+                Change::Updated
+            } else {
+                Change::Added
+            };
 
-            // Create symlink
-            #[cfg(unix)]
-            {
-                std::os::unix::fs::symlink(path, &symlink_path)?;
+            LinkDelta {
+                name: link.name.clone(),
+                target: link.target.clone(),
+                previous: std::fs::read_link(&path).ok(),
+                change,
             }
+        })
+        .collect()
+}
 
-            symlink_count += 1;
-            println!(
-                "  {} {} → {}",
-                "✓".green(),
-                standardized_name.dimmed(),
-                path.display().to_string().dimmed()
-            );
-        }
-    }
+/// Create symlinks for all PHP binaries in the target directory
+#[cfg(test)]
+fn create_symlinks(source_paths: &[PathBuf], bin_dir: &Path) -> Result<Vec<LinkedBinary>> {
+    let links = plan_links(source_paths)?;
+    link::Symlink::default().apply(&links, bin_dir)?;
+    Ok(links)
+}
+
+/// Check whether an installation is already fully active in the bin directory
+///
+/// True when every planned symlink points at the right target and every tool
+/// shim that would be created already exists.
+pub fn is_up_to_date(config: &Config, entry: &VersionEntry, bin_dir: &Path) -> bool {
+    is_up_to_date_with(config, entry, bin_dir, link::strategy_for_config(config).as_ref())
+}
+
+fn is_up_to_date_with(config: &Config, entry: &VersionEntry, bin_dir: &Path, strategy: &dyn LinkStrategy) -> bool {
+    let Ok(links) = plan_entry_links(entry) else { return false };
+
+    let links_current = links.iter().all(|link| strategy.is_linked(link, bin_dir));
+
+    let shims_current = !config.tools.scan_for_tools
+        || config
+            .tools
+            .managed
+            .iter()
+            .filter(|tool| crate::tools::needs_shim(&tool.shebang))
+            .all(|tool| bin_dir.join(&tool.name).is_file());
+    let project_current = match std::env::current_dir().ok().and_then(|cwd| project_config::load(&cwd).ok().flatten()) {
+        Some(project) => project_config::shims_current(&project, bin_dir),
+        None => true,
+    };
 
-    Ok(symlink_count)
+    links_current && shims_current && project_current
 }
 
 /// Verify that the switch was successful by checking the primary PHP binary
-fn verify_switch(bin_dir: &Path) -> Result<()> {
+fn verify_switch(bin_dir: &Path) -> Result<Option<PhpVersion>> {
     let primary_symlink = bin_dir.join("php");
     if primary_symlink.exists() {
         if let Ok(version) = detector::get_version_from_binary(&primary_symlink) {
-            println!("\n{} Verified: {}", "✓".green(), version.to_string().bold());
+            return Ok(Some(version));
         }
     }
-    Ok(())
+    Ok(None)
+}
+
+/// Check that every link in the bin directory resolves into the active installation
+///
+/// A partially removed package or a previous version with more SAPIs can leave
+/// links behind (e.g. a stale `php-fpm`) that still point at another version.
+/// Links count as belonging to the installation only when they lead to one of
+/// its binaries or aliases: distributions keep every version in `/usr/bin`, so
+/// sharing a directory says nothing. Shims and wrappers are followed to the
+/// binary they run; hard links and copies of other versions can't be told
+/// from tool shims, so only those of this version are reported.
+pub fn verify_bin_dir(bin_dir: &Path, entry: &VersionEntry) -> Vec<BinaryCheck> {
+    let Ok(dir_entries) = std::fs::read_dir(bin_dir) else { return Vec::new() };
+
+    let own: Vec<PathBuf> = entry.paths.iter().chain(&entry.aliases).filter_map(|p| p.canonicalize().ok()).collect();
+
+    let mut checks: Vec<BinaryCheck> = dir_entries
+        .flatten()
+        .filter_map(|dir_entry| {
+            let name = dir_entry.file_name().to_string_lossy().to_string();
+            let Some(target) = link::link_target(&dir_entry.path()) else {
+                // Hard links and copies can only be told apart from tool shims by comparing with the binaries
+                let target = link::identify_copy(&dir_entry.path(), &entry.paths)?;
+                return Some(BinaryCheck { name, target, status: BinaryStatus::Ok });
+            };
+
+            let status = match target.canonicalize() {
+                Err(_) => BinaryStatus::Broken,
+                Ok(_) if entry.paths.contains(&target) || entry.aliases.contains(&target) => BinaryStatus::Ok,
+                Ok(resolved) if own.contains(&resolved) => BinaryStatus::Ok,
+                Ok(_) => BinaryStatus::Foreign,
+            };
+
+            Some(BinaryCheck { name, target, status })
+        })
+        .collect();
+
+    checks.sort_by(|a, b| a.name.cmp(&b.name));
+    checks
 }
 
 /// Get the bin directory where symlinks will be created
-fn get_bin_dir() -> Result<PathBuf> {
+pub fn get_bin_dir() -> Result<PathBuf> {
     let switcher_dir = config::get_config_dir()?;
     Ok(switcher_dir.join("bin"))
 }
 
+/// Get a bin directory exposing one version, without switching globally
+///
+/// Used to run commands under a specific version (`each`): prepending the
+/// directory to the child's PATH makes `php` resolve to that version. The
+/// links are refreshed on every call, with the platform's default strategy
+/// (cmd shims on Windows) rather than a global one like `update-alternatives`.
+pub fn version_bin_dir(config: &Config, entry: &VersionEntry) -> Result<PathBuf> {
+    let dir = config::get_config_dir()?.join("envs").join(&entry.version);
+    if config::is_read_only() {
+        return Err(anyhow::anyhow!("Cannot prepare {} in read-only mode", dir.display()));
+    }
+
+    let links = plan_links(&entry.paths)?;
+    link::strategy_for(platform::Platform::detect().default_link_strategy(), config.settings.shim_mode()).apply(&links, &dir)?;
+    Ok(dir)
+}
+
+/// The environment a version's `env` table sets, for commands run under it
+///
+/// Fails on names `sh` can't export, like the `shim` strategy does.
+pub fn version_env(entry: &VersionEntry) -> Result<Vec<(String, String)>> {
+    if let Some(name) = entry.env.keys().find(|name| !link::is_env_name(name)) {
+        return Err(anyhow::anyhow!("Invalid environment variable name '{}' in the env of PHP {}", name, entry.version));
+    }
+    Ok(entry.env.iter().map(|(name, value)| (name.clone(), value.clone())).collect())
+}
+
+/// Create the bin directory and apply the configured permissions
+///
+/// Permissions are re-applied on every switch so changing `bin_dir_mode`
+/// takes effect without recreating the directory.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn prepare_bin_dir(bin_dir: &Path, mode: u32) -> Result<()> {
+    std::fs::create_dir_all(bin_dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = platform::apply_umask(mode);
+        std::fs::set_permissions(bin_dir, std::fs::Permissions::from_mode(mode))
+            .map_err(|e| anyhow::anyhow!("Failed to set permissions on {}: {}", bin_dir.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// Tell already open shells to drop their cached location of `php`
+///
+/// Inside tmux/screen every other pane is such a shell, so say so and, where
+/// possible, offer a command reaching all of them at once.
+pub fn show_rehash_hint() {
+    let shell = platform::ShellKind::detect();
+    let Some(rehash) = shell.rehash_command() else { return };
+
+    match platform::Multiplexer::detect() {
+        Some(multiplexer) => {
+            let hint = format!("  Other {} panes may still run the old php: run '{}' in each", multiplexer.name(), rehash);
+            println!("{}", hint.dimmed());
+            if let Some(broadcast) = multiplexer.broadcast_command(shell) {
+                println!("{}", "  or reach every idle shell at once:".dimmed());
+                println!("{}", format!("    {}", broadcast).dimmed());
+            }
+        }
+        None => {
+            let hint = format!("  Run '{}' in already open shells to pick up the new php", rehash);
+            println!("{}", hint.dimmed());
+        }
+    }
+}
+
 /// Show instructions for adding the bin directory to PATH
 fn show_path_instructions(bin_dir: &Path) {
     println!(
         "\n{}",
         "IMPORTANT: Ensure the switcher bin directory is first in your PATH:".yellow()
     );
+    if platform::Platform::detect() == platform::Platform::Windows {
+        let commands = platform::windows::powershell_path_instructions(bin_dir);
+        println!("  {}", commands[0]);
+        println!("\nTo keep it for new terminals, run once in PowerShell:\n  {}", commands[1]);
+        return;
+    }
     println!("  export PATH=\"{}:$PATH\"", bin_dir.display());
     println!("\nAdd this to your ~/.bashrc or ~/.zshrc and run: source ~/.bashrc");
 }
 
+/// Create shims for PHP tools that need them
+pub fn create_shims_for_tools<P: AsRef<Path>>(tools: &[crate::tools::PhpTool], bin_dir: P, settings: &crate::tools::ShimSettings) -> Result<usize> {
+    use crate::tools;
+
+    let mut created = 0;
+
+    for tool in tools {
+        // Only create shims for tools with hardcoded PHP paths
+        if tools::needs_shim(&tool.shebang) {
+            tools::create_shim_with(tool, bin_dir.as_ref(), settings)?;
+            created += 1;
+        }
+    }
+
+    Ok(created)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -288,7 +944,7 @@ mod tests {
         let paths = vec![php81_path.clone()];
         let result = create_symlinks(&paths, &bin_dir);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), 1); // Should create 1 symlink (php -> php81)
+        assert_eq!(result.unwrap().len(), 1); // Should create 1 symlink (php -> php81)
 
         // Verify the "php" symlink was created and points to php81
         let php_symlink = bin_dir.join("php");
@@ -316,7 +972,9 @@ mod tests {
         let result = create_symlinks(&paths, &bin_dir);
         assert!(result.is_ok());
         // Should create 2 symlinks: php -> php81, php-cgi -> php81-cgi
-        assert_eq!(result.unwrap(), 2);
+        let links = result.unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[1].name, "php-cgi");
 
         // Verify symlinks
         let php_symlink = bin_dir.join("php");
@@ -325,6 +983,265 @@ mod tests {
         assert!(php_cgi_symlink.exists());
     }
 
+    #[test]
+    fn test_parse_version_spec() {
+        assert_eq!(parse_version_spec("8.2\n"), Some("8.2".to_string()));
+        assert_eq!(parse_version_spec("# pinned\n\n  8.3.4  \n8.1\n"), Some("8.3.4".to_string()));
+        assert_eq!(parse_version_spec("\n# nothing here\n"), None);
+    }
+
+    #[test]
+    fn test_check_policy() {
+        let mut config = Config::default();
+        config.policy.allowed_versions = vec!["8.2".to_string()];
+
+        assert!(check_policy(&config, "8.2.12", &SwitchOptions::default()).is_ok());
+        assert!(check_policy(&config, "7.4.33", &SwitchOptions::default()).is_err());
+        assert!(check_policy(&config, "7.4.33", &SwitchOptions { force: true, ..Default::default() }).is_ok());
+    }
+
+    #[test]
+    fn test_plan_links_standardizes_names() {
+        let paths = vec![
+            PathBuf::from("/usr/bin/php81-cgi"),
+            PathBuf::from("/usr/bin/php81"),
+            PathBuf::from("/usr/bin/phpize81"),
+        ];
+
+        let links = plan_links(&paths).unwrap();
+        let names: Vec<&str> = links.iter().map(|l| l.name.as_str()).collect();
+
+        // No binary is named exactly "php", so the first one becomes primary
+        assert_eq!(links[0].target, PathBuf::from("/usr/bin/php81-cgi"));
+        assert_eq!(names, vec!["php", "php-cgi", "phpize81"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_is_up_to_date() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let bin_dir = temp_dir.path().join("bin");
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        let php = source_dir.join("php");
+        let php_cgi = source_dir.join("php-cgi");
+        std::fs::write(&php, "").unwrap();
+        std::fs::write(&php_cgi, "").unwrap();
+
+        let entry = VersionEntry {
+            version: "8.2.12".to_string(),
+            paths: vec![php.clone(), php_cgi.clone()],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+        let config = Config::default();
+
+        assert!(!is_up_to_date(&config, &entry, &bin_dir));
+
+        create_symlinks(&entry.paths, &bin_dir).unwrap();
+        assert!(is_up_to_date(&config, &entry, &bin_dir));
+
+        // A stale related link means work is still needed
+        std::fs::remove_file(bin_dir.join("php-cgi")).unwrap();
+        std::os::unix::fs::symlink(&php, bin_dir.join("php-cgi")).unwrap();
+        assert!(!is_up_to_date(&config, &entry, &bin_dir));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_prepare_bin_dir_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+
+        prepare_bin_dir(&bin_dir, 0o700).unwrap();
+        let mode = std::fs::metadata(&bin_dir).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, platform::apply_umask(0o700));
+        assert_eq!(mode & 0o077, 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_diff_links() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+
+        let link = |name: &str, target: &str| LinkedBinary { name: name.to_string(), target: PathBuf::from(target), env: Vec::new() };
+        std::os::unix::fs::symlink("/usr/bin/php8.2", bin_dir.join("php")).unwrap();
+        std::os::unix::fs::symlink("/usr/bin/phpize8.3", bin_dir.join("phpize")).unwrap();
+
+        let planned = vec![
+            link("php", "/usr/bin/php8.3"),
+            link("phpize", "/usr/bin/phpize8.3"),
+            link("php-cgi", "/usr/bin/php-cgi8.3"),
+        ];
+        let deltas = diff_links(&planned, &bin_dir, &link::Symlink::default());
+
+        assert_eq!(deltas[0].change, Change::Updated);
+        assert_eq!(deltas[0].previous, Some(PathBuf::from("/usr/bin/php8.2")));
+        assert_eq!(deltas[1].change, Change::Unchanged);
+        assert_eq!(deltas[2].change, Change::Added);
+        assert_eq!(deltas[2].previous, None);
+    }
+
+    #[test]
+    fn test_version_env() {
+        let mut entry = VersionEntry { version: "8.2.12".to_string(), ..VersionEntry::default() };
+        entry.env.insert("PHPRC".to_string(), "/etc/php/8.2-billing".to_string());
+        assert_eq!(version_env(&entry).unwrap(), vec![("PHPRC".to_string(), "/etc/php/8.2-billing".to_string())]);
+
+        entry.env.insert("PHPRC; rm -rf ~".to_string(), String::new());
+        assert!(version_env(&entry).is_err());
+    }
+
+    #[test]
+    fn test_bin_dir_takes_precedence() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        let system = temp_dir.path().join("usr-bin");
+        std::fs::create_dir_all(&system).unwrap();
+        std::fs::write(system.join("php"), "").unwrap();
+
+        let path = |dirs: &[&Path]| std::env::join_paths(dirs).unwrap();
+        assert!(bin_dir_takes_precedence(&path(&[&bin_dir, &system]), &bin_dir));
+        assert!(!bin_dir_takes_precedence(&path(&[&system, &bin_dir]), &bin_dir));
+        assert!(!bin_dir_takes_precedence(&path(&[&system]), &bin_dir));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_verify_bin_dir_reports_each_binary() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let new_dir = temp_dir.path().join("new");
+        let old_dir = temp_dir.path().join("old");
+        let bin_dir = temp_dir.path().join("bin");
+        for dir in [&new_dir, &old_dir, &bin_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+
+        for path in [new_dir.join("php"), new_dir.join("phpize"), old_dir.join("php-fpm")] {
+            std::fs::write(path, "").unwrap();
+        }
+
+        let entry = VersionEntry {
+            version: "8.3.4".to_string(),
+            paths: vec![new_dir.join("php")],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+
+        std::os::unix::fs::symlink(new_dir.join("php"), bin_dir.join("php")).unwrap();
+        // Same directory as the primary binary, but not a tracked path (e.g. another version's)
+        std::os::unix::fs::symlink(new_dir.join("phpize"), bin_dir.join("phpize")).unwrap();
+        // Left over from a previous version
+        std::os::unix::fs::symlink(old_dir.join("php-fpm"), bin_dir.join("php-fpm")).unwrap();
+        std::os::unix::fs::symlink(old_dir.join("php-cgi"), bin_dir.join("php-cgi")).unwrap();
+        // Regular files (tool shims) are not links and are ignored
+        std::fs::write(bin_dir.join("composer"), "#!/bin/bash").unwrap();
+
+        let checks = verify_bin_dir(&bin_dir, &entry);
+        let statuses: Vec<(&str, BinaryStatus)> = checks.iter().map(|c| (c.name.as_str(), c.status)).collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ("php", BinaryStatus::Ok),
+                ("php-cgi", BinaryStatus::Broken),
+                ("php-fpm", BinaryStatus::Foreign),
+                ("phpize", BinaryStatus::Foreign),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_verify_bin_dir_follows_shims_and_copies() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        let entry = VersionEntry {
+            version: "8.3.4".to_string(),
+            paths: vec![temp_dir.path().join("php8.3"), temp_dir.path().join("php-cgi8.3")],
+            ..VersionEntry::default()
+        };
+        std::fs::write(&entry.paths[0], "php").unwrap();
+        std::fs::write(&entry.paths[1], "php-cgi").unwrap();
+        let links = plan_entry_links(&entry).unwrap();
+
+        link::Shim::default().apply(&links[..1], &bin_dir).unwrap();
+        link::FileCopy.apply(&links[1..], &bin_dir).unwrap();
+        std::fs::write(bin_dir.join("phpize"), "#!/bin/sh\nexec /usr/bin/phpize8.1 \"$@\"\n").unwrap();
+
+        let checks = verify_bin_dir(&bin_dir, &entry);
+        let statuses: Vec<(&str, &Path, BinaryStatus)> = checks.iter().map(|c| (c.name.as_str(), c.target.as_path(), c.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("php", entry.paths[0].as_path(), BinaryStatus::Ok),
+                ("php-cgi8.3", entry.paths[1].as_path(), BinaryStatus::Ok),
+            ]
+        );
+
+        // A shim for another binary is verified, not skipped
+        link::Shim::default().apply(&[LinkedBinary { name: "php".to_string(), target: temp_dir.path().join("php8.1"), env: Vec::new() }], &bin_dir).unwrap();
+        assert_eq!(verify_bin_dir(&bin_dir, &entry)[0].status, BinaryStatus::Broken);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_verify_bin_dir_with_versions_sharing_a_directory() {
+        use tempfile::TempDir;
+
+        // Debian and Ubuntu install every version into /usr/bin
+        let temp_dir = TempDir::new().unwrap();
+        let usr_bin = temp_dir.path().join("usr/bin");
+        let bin_dir = temp_dir.path().join("bin");
+        std::fs::create_dir_all(&usr_bin).unwrap();
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        for name in ["php8.3", "phpize8.3", "phpize8.1", "php-cgi8.3"] {
+            std::fs::write(usr_bin.join(name), "").unwrap();
+        }
+        std::os::unix::fs::symlink(usr_bin.join("php8.3"), usr_bin.join("php")).unwrap();
+
+        let entry = VersionEntry {
+            version: "8.3.4".to_string(),
+            paths: vec![usr_bin.join("php8.3"), usr_bin.join("phpize8.3")],
+            aliases: vec![usr_bin.join("php-cgi8.3")],
+            ..VersionEntry::default()
+        };
+
+        // Linked through another path leading to a tracked binary
+        std::os::unix::fs::symlink(usr_bin.join("php"), bin_dir.join("php")).unwrap();
+        std::os::unix::fs::symlink(usr_bin.join("phpize8.3"), bin_dir.join("phpize8.3")).unwrap();
+        std::os::unix::fs::symlink(usr_bin.join("php-cgi8.3"), bin_dir.join("php-cgi")).unwrap();
+        std::os::unix::fs::symlink(usr_bin.join("phpize8.1"), bin_dir.join("phpize")).unwrap();
+
+        let checks = verify_bin_dir(&bin_dir, &entry);
+        let statuses: Vec<(&str, BinaryStatus)> = checks.iter().map(|c| (c.name.as_str(), c.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("php", BinaryStatus::Ok),
+                ("php-cgi", BinaryStatus::Ok),
+                ("phpize", BinaryStatus::Foreign),
+                ("phpize8.3", BinaryStatus::Ok),
+            ]
+        );
+    }
+
     #[test]
     fn test_verify_switch_with_nonexistent_dir() {
         use tempfile::TempDir;
@@ -360,7 +1277,7 @@ mod tests {
             },
         ];
 
-        let result = create_shims_for_tools(&tools, &bin_dir);
+        let result = create_shims_for_tools(&tools, &bin_dir, &crate::tools::ShimSettings::default());
 
         assert!(result.is_ok());
         let created = result.unwrap();
@@ -390,7 +1307,7 @@ mod tests {
             },
         ];
 
-        let result = create_shims_for_tools(&tools, &bin_dir);
+        let result = create_shims_for_tools(&tools, &bin_dir, &crate::tools::ShimSettings::default());
 
         assert!(result.is_ok());
         let created = result.unwrap();
@@ -422,7 +1339,7 @@ mod tests {
             },
         ];
 
-        let result = create_shims_for_tools(&tools, &bin_dir);
+        let result = create_shims_for_tools(&tools, &bin_dir, &crate::tools::ShimSettings::default());
 
         assert!(result.is_ok());
 
@@ -431,21 +1348,31 @@ mod tests {
         assert!(content.contains(".php-switcher/bin/php"));
         assert!(!content.contains("old shim"));
     }
-}
-
-/// Create shims for PHP tools that need them
-pub fn create_shims_for_tools<P: AsRef<Path>>(tools: &[crate::tools::PhpTool], bin_dir: P) -> Result<usize> {
-    use crate::tools;
 
-    let mut created = 0;
-
-    for tool in tools {
-        // Only create shims for tools with hardcoded PHP paths
-        if tools::needs_shim(&tool.shebang) {
-            tools::create_shim(tool, bin_dir.as_ref())?;
-            created += 1;
-        }
+    #[test]
+    fn test_resolve_composer_constraint() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let entry = |version: &str| VersionEntry {
+            version: version.to_string(),
+            paths: vec![PathBuf::from(format!("/usr/bin/php{}", version))],
+            source: "auto".to_string(),
+            ..VersionEntry::default()
+        };
+        let config = Config {
+            versions: vec![entry("8.3.4"), entry("8.2.12")],
+            ..Config::default()
+        };
+
+        assert_eq!(resolve_composer_constraint(&config, temp_dir.path()).unwrap(), None);
+
+        let manifest = temp_dir.path().join("composer.json");
+        std::fs::write(&manifest, r#"{"require": {"php": ">=8.0 <8.3"}}"#).unwrap();
+        let found = resolve_composer_constraint(&config, temp_dir.path()).unwrap().unwrap();
+        assert_eq!(found.version, "8.2.12");
+        assert_eq!(found.constraint, ">=8.0 <8.3");
+        assert_eq!(found.manifest, manifest);
+
+        std::fs::write(&manifest, r#"{"require": {"php": "^9.0"}}"#).unwrap();
+        assert!(resolve_composer_constraint(&config, temp_dir.path()).is_err());
     }
-
-    Ok(created)
 }
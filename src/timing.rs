@@ -0,0 +1,162 @@
+// Startup timing module
+//
+// Prompt and `cd` hooks run php-switcher constantly, so its startup time is a
+// feature. The expensive steps (config load, scans, running PHP binaries) are
+// wrapped in `measure`, which costs a thread-local check unless
+// `--profile-startup` turned recording on; `report` then prints where the
+// time went.
+//
+// Keep the fast paths (`__resolve`, `__complete`, `use --if-needed`) free of
+// subprocesses and repeated regex compilation: compile patterns once with
+// `OnceLock`, and read files only through the already-loaded config.
+
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static SPANS: RefCell<Option<Vec<Span>>> = const { RefCell::new(None) };
+    static DEPTH: RefCell<usize> = const { RefCell::new(0) };
+    static STARTED: RefCell<Option<Instant>> = const { RefCell::new(None) };
+}
+
+/// One measured step
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub label: String,
+    pub duration: Duration,
+    /// Nesting depth, for steps measured inside other steps
+    pub depth: usize,
+}
+
+/// Start recording spans on this thread, timing the run from `started`
+pub fn enable(started: Instant) {
+    SPANS.with(|spans| *spans.borrow_mut() = Some(Vec::new()));
+    STARTED.with(|s| *s.borrow_mut() = Some(started));
+}
+
+/// Check whether spans are being recorded
+pub fn is_enabled() -> bool {
+    SPANS.with(|spans| spans.borrow().is_some())
+}
+
+/// Record a step measured elsewhere
+pub fn record(label: impl Into<String>, duration: Duration) {
+    let depth = DEPTH.with(|depth| *depth.borrow());
+    SPANS.with(|spans| {
+        if let Some(spans) = spans.borrow_mut().as_mut() {
+            spans.push(Span { label: label.into(), duration, depth });
+        }
+    });
+}
+
+/// Run `f`, recording how long it took when profiling is enabled
+///
+/// The label is only built when recording, so callers can format freely.
+pub fn measure<T, L: FnOnce() -> String>(label: L, f: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return f();
+    }
+
+    // Spans are pushed when they finish, so reserve the slot up front to keep start order
+    let index = SPANS.with(|spans| {
+        let mut spans = spans.borrow_mut();
+        let spans = spans.as_mut().expect("profiling enabled");
+        spans.push(Span { label: label(), duration: Duration::ZERO, depth: DEPTH.with(|d| *d.borrow()) });
+        spans.len() - 1
+    });
+
+    DEPTH.with(|depth| *depth.borrow_mut() += 1);
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    DEPTH.with(|depth| *depth.borrow_mut() -= 1);
+
+    SPANS.with(|spans| {
+        if let Some(span) = spans.borrow_mut().as_mut().and_then(|spans| spans.get_mut(index)) {
+            span.duration = elapsed;
+        }
+    });
+
+    result
+}
+
+/// Take the recorded spans, in the order the steps started
+pub fn take() -> Vec<Span> {
+    SPANS.with(|spans| spans.borrow_mut().as_mut().map(std::mem::take).unwrap_or_default())
+}
+
+/// Stop recording and format the breakdown of the run so far, if profiling was enabled
+pub fn finish() -> Option<String> {
+    let started = STARTED.with(|s| s.borrow_mut().take())?;
+    let spans = take();
+    SPANS.with(|spans| *spans.borrow_mut() = None);
+    Some(report(&spans, started.elapsed()))
+}
+
+/// Format a timing breakdown of the spans and the total run time
+pub fn report(spans: &[Span], total: Duration) -> String {
+    let width = spans.iter().map(|s| s.label.len() + s.depth * 2).max().unwrap_or(0).max(5);
+    let mut lines: Vec<String> = spans
+        .iter()
+        .map(|span| {
+            let label = format!("{}{}", "  ".repeat(span.depth), span.label);
+            format!("  {:<width$}  {:>9}", label, format_duration(span.duration), width = width)
+        })
+        .collect();
+
+    let measured: Duration = spans.iter().filter(|s| s.depth == 0).map(|s| s.duration).sum();
+    lines.push(format!(
+        "  {:<width$}  {:>9}",
+        "other",
+        format_duration(total.saturating_sub(measured)),
+        width = width
+    ));
+    lines.push(format!("  {:<width$}  {:>9}", "total", format_duration(total), width = width));
+    lines.join("\n")
+}
+
+/// Format a duration in milliseconds with microsecond precision
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_only_records_when_enabled() {
+        assert_eq!(measure(|| "skipped".to_string(), || 1), 1);
+        assert!(take().is_empty());
+
+        enable(Instant::now());
+        let value = measure(
+            || "outer".to_string(),
+            || measure(|| "inner".to_string(), || 2) + 1,
+        );
+        record("manual", Duration::from_millis(1));
+
+        assert_eq!(value, 3);
+        let spans = take();
+        let labels: Vec<(&str, usize)> = spans.iter().map(|s| (s.label.as_str(), s.depth)).collect();
+        assert_eq!(labels, vec![("outer", 0), ("inner", 1), ("manual", 0)]);
+        assert!(spans[0].duration >= spans[1].duration);
+
+        assert!(finish().unwrap().contains("total"));
+        assert!(!is_enabled());
+    }
+
+    #[test]
+    fn test_report() {
+        let spans = vec![
+            Span { label: "config load".to_string(), duration: Duration::from_micros(1500), depth: 0 },
+            Span { label: "php -v".to_string(), duration: Duration::from_micros(250), depth: 1 },
+        ];
+        let report = report(&spans, Duration::from_millis(2));
+
+        assert_eq!(
+            report,
+            "  config load    1.500ms\n    php -v       0.250ms\n  other          0.500ms\n  total          2.000ms"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+// Toolchain module
+//
+// A toolchain bundles a PHP version with pinned tool versions and an ini
+// profile (`[toolchain.legacy]` in the config) so `use-toolchain legacy`
+// switches all of them together. Everything is resolved before anything is
+// touched, so a missing tool or profile leaves the current setup intact.
+//
+// Ini profiles are directories under `~/.php-switcher/ini/`. The active one
+// is exposed as `~/.php-switcher/ini/active` (a symlink, or a copy where
+// directory symlinks need elevation), which PHP scans once `PHP_INI_SCAN_DIR`
+// includes it.
+
+use crate::config::{self, Config, Toolchain, VersionEntry};
+use crate::switcher::{self, SwitchOptions, SwitchOutcome};
+use crate::tools::{self, PhpTool};
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+/// Directory holding the ini profiles, relative to the config directory
+pub const INI_PROFILES_DIR: &str = "ini";
+
+/// Name of the link to the active ini profile inside the profiles directory
+pub const ACTIVE_PROFILE: &str = "active";
+
+/// A toolchain with every part resolved to something on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedToolchain {
+    pub entry: VersionEntry,
+    pub tools: Vec<PhpTool>,
+    pub ini_profile: Option<PathBuf>,
+}
+
+/// What applying a toolchain changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolchainOutcome {
+    pub switch: SwitchOutcome,
+    pub tools: Vec<PhpTool>,
+    pub ini_profile: Option<PathBuf>,
+}
+
+/// Get the directory holding the ini profiles
+pub fn get_profiles_dir() -> Result<PathBuf> {
+    Ok(config::get_config_dir()?.join(INI_PROFILES_DIR))
+}
+
+/// Find the executable a tool pin refers to
+///
+/// A pin is either a path, or a version looked up as `<tool><pin>`,
+/// `<tool>-<pin>` or `<tool><pin>.phar` (e.g. `composer1`) in `search_dirs`.
+pub fn find_pinned_tool(name: &str, pin: &str, search_dirs: &[PathBuf]) -> Option<PathBuf> {
+    let path = Path::new(pin);
+    if path.is_absolute() {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    let candidates = [format!("{}{}", name, pin), format!("{}-{}", name, pin), format!("{}{}.phar", name, pin)];
+    search_dirs
+        .iter()
+        .flat_map(|dir| candidates.iter().map(move |candidate| dir.join(candidate)))
+        .find(|path| path.is_file())
+}
+
+/// Resolve every part of a toolchain without changing anything
+pub fn resolve(config: &Config, toolchain: &Toolchain, search_dirs: &[PathBuf], profiles_dir: &Path) -> Result<ResolvedToolchain> {
+    let entry = config
+        .find_version(&toolchain.php)
+        .cloned()
+        .ok_or_else(|| anyhow!("No PHP installation found matching '{}'", toolchain.php))?;
+
+    let mut tools = Vec::new();
+    for (name, pin) in &toolchain.tools {
+        let original_path = find_pinned_tool(name, pin, search_dirs)
+            .ok_or_else(|| anyhow!("Tool {} {} not found (looked for {}{} and {}-{})", name, pin, name, pin, name, pin))?;
+        let shebang = tools::read_shebang(&original_path).unwrap_or_default();
+        tools.push(PhpTool { name: name.clone(), original_path, shebang });
+    }
+
+    let ini_profile = match &toolchain.ini {
+        Some(profile) => {
+            let dir = profiles_dir.join(profile);
+            if !dir.is_dir() {
+                return Err(anyhow!("Ini profile '{}' not found (expected {})", profile, dir.display()));
+            }
+            Some(dir)
+        }
+        None => None,
+    };
+
+    Ok(ResolvedToolchain { entry, tools, ini_profile })
+}
+
+/// Directories searched for pinned tools: PATH plus `tools.custom_search_paths`
+///
+/// The switcher's own bin directory is skipped, since it only holds shims.
+pub fn search_dirs(config: &Config, bin_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    dirs.extend(config.tools.custom_search_paths.iter().cloned());
+    dirs.retain(|dir| dir != bin_dir);
+    dirs
+}
+
+/// Point the active ini profile link at a profile, or remove it for none
+pub fn set_active_profile(profiles_dir: &Path, profile: Option<&Path>) -> Result<()> {
+    let link = profiles_dir.join(ACTIVE_PROFILE);
+    match link.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&link),
+        Ok(_) => std::fs::remove_file(&link),
+        Err(_) => Ok(()),
+    }
+    .map_err(|e| anyhow!("Failed to remove {}: {}", link.display(), e))?;
+
+    let Some(profile) = profile else { return Ok(()) };
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(profile, &link).map_err(|e| anyhow!("Failed to link ini profile: {}", e))
+    }
+
+    // Directory symlinks need elevation on Windows
+    #[cfg(not(unix))]
+    {
+        copy_profile(profile, &link)
+    }
+}
+
+/// Copy the `.ini` files of a profile into `dest`
+#[cfg(any(not(unix), test))]
+fn copy_profile(profile: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest).map_err(|e| anyhow!("Failed to create {}: {}", dest.display(), e))?;
+    let entries = std::fs::read_dir(profile).map_err(|e| anyhow!("Failed to read ini profile {}: {}", profile.display(), e))?;
+    for entry in entries.flatten().filter(|entry| entry.path().is_file()) {
+        std::fs::copy(entry.path(), dest.join(entry.file_name()))
+            .map_err(|e| anyhow!("Failed to copy {}: {}", entry.path().display(), e))?;
+    }
+    Ok(())
+}
+
+/// Switch to a named toolchain: PHP version, pinned tool shims and ini profile
+pub fn apply(config: &mut Config, name: &str, bin_dir: &Path, options: &SwitchOptions) -> Result<ToolchainOutcome> {
+    let toolchain = config
+        .toolchains
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("No toolchain named '{}' in the config", name))?;
+
+    let profiles_dir = get_profiles_dir()?;
+    let resolved = resolve(config, &toolchain, &search_dirs(config, bin_dir), &profiles_dir)?;
+
+    let switch = switcher::activate(config, &resolved.entry, bin_dir, options)?;
+
+    let mut settings = tools::ShimSettings::from_config(&config.tools, &config::get_config_dir()?)?;
+    settings.mode = config.settings.shim_mode();
+    for tool in &resolved.tools {
+        tools::create_shim_with(tool, bin_dir, &settings)?;
+    }
+
+    std::fs::create_dir_all(&profiles_dir).map_err(|e| anyhow!("Failed to create {}: {}", profiles_dir.display(), e))?;
+    set_active_profile(&profiles_dir, resolved.ini_profile.as_deref())?;
+
+    Ok(ToolchainOutcome {
+        switch,
+        tools: resolved.tools,
+        ini_profile: resolved.ini_profile,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_pinned_tool() {
+        let temp_dir = TempDir::new().unwrap();
+        let dirs = vec![temp_dir.path().join("empty"), temp_dir.path().to_path_buf()];
+        std::fs::write(temp_dir.path().join("composer1"), "#!/usr/bin/env php\n").unwrap();
+        std::fs::write(temp_dir.path().join("phpunit-9"), "#!/usr/bin/env php\n").unwrap();
+
+        assert_eq!(find_pinned_tool("composer", "1", &dirs), Some(temp_dir.path().join("composer1")));
+        assert_eq!(find_pinned_tool("phpunit", "9", &dirs), Some(temp_dir.path().join("phpunit-9")));
+        assert_eq!(find_pinned_tool("composer", "2", &dirs), None);
+
+        let absolute = temp_dir.path().join("phpunit-9");
+        assert_eq!(find_pinned_tool("phpunit", absolute.to_str().unwrap(), &[]), Some(absolute));
+    }
+
+    #[test]
+    fn test_resolve_fails_before_changing_anything() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("legacy")).unwrap();
+        std::fs::write(temp_dir.path().join("composer1"), "#!/usr/bin/php\n").unwrap();
+        let dirs = vec![temp_dir.path().to_path_buf()];
+
+        let config = Config {
+            versions: vec![VersionEntry {
+                version: "7.4.33".to_string(),
+                paths: vec![PathBuf::from("/usr/bin/php7.4")],
+                source: "auto".to_string(),
+                ..VersionEntry::default()
+            }],
+            ..Config::default()
+        };
+        let toolchain = Toolchain {
+            php: "7.4".to_string(),
+            ini: Some("legacy".to_string()),
+            tools: BTreeMap::from([("composer".to_string(), "1".to_string())]),
+        };
+
+        let resolved = resolve(&config, &toolchain, &dirs, temp_dir.path()).unwrap();
+        assert_eq!(resolved.entry.version, "7.4.33");
+        assert_eq!(resolved.tools[0].shebang, "#!/usr/bin/php");
+        assert_eq!(resolved.ini_profile, Some(temp_dir.path().join("legacy")));
+
+        let missing_tool = Toolchain { tools: BTreeMap::from([("composer".to_string(), "2".to_string())]), ..toolchain.clone() };
+        assert!(resolve(&config, &missing_tool, &dirs, temp_dir.path()).is_err());
+
+        let missing_profile = Toolchain { ini: Some("modern".to_string()), ..toolchain };
+        assert!(resolve(&config, &missing_profile, &dirs, temp_dir.path()).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_set_active_profile() {
+        let temp_dir = TempDir::new().unwrap();
+        let legacy = temp_dir.path().join("legacy");
+        std::fs::create_dir(&legacy).unwrap();
+
+        set_active_profile(temp_dir.path(), Some(&legacy)).unwrap();
+        assert_eq!(std::fs::read_link(temp_dir.path().join(ACTIVE_PROFILE)).unwrap(), legacy);
+
+        set_active_profile(temp_dir.path(), None).unwrap();
+        assert!(temp_dir.path().join(ACTIVE_PROFILE).symlink_metadata().is_err());
+
+        // A copied profile is replaced like a link
+        std::fs::write(legacy.join("memory.ini"), "memory_limit = 1G\n").unwrap();
+        copy_profile(&legacy, &temp_dir.path().join(ACTIVE_PROFILE)).unwrap();
+        assert_eq!(std::fs::read_to_string(temp_dir.path().join(ACTIVE_PROFILE).join("memory.ini")).unwrap(), "memory_limit = 1G\n");
+        set_active_profile(temp_dir.path(), Some(&legacy)).unwrap();
+        assert_eq!(std::fs::read_link(temp_dir.path().join(ACTIVE_PROFILE)).unwrap(), legacy);
+    }
+}
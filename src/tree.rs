@@ -0,0 +1,171 @@
+// Installation tree module
+//
+// On machines with several package managers (Homebrew next to apt, phpbrew
+// builds, a hand-compiled PHP in /opt) the flat version list doesn't say
+// where anything lives. `list --tree` groups tracked versions by what
+// installed them, then by install prefix, then lists each version's binaries:
+//
+//   Homebrew
+//   └── /opt/homebrew/Cellar/php/8.3.1
+//       └── 8.3.1 [ACTIVE]
+//           ├── php
+//           └── php-fpm
+
+use crate::config::{Config, VersionEntry};
+use crate::detector::InstallSource;
+use std::path::{Path, PathBuf};
+
+/// A version and its binaries
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionNode {
+    pub version: String,
+    pub binaries: Vec<PathBuf>,
+    pub active: bool,
+}
+
+/// Versions sharing an install prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefixNode {
+    pub prefix: PathBuf,
+    pub versions: Vec<VersionNode>,
+}
+
+/// Prefixes sharing a source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceNode {
+    pub source: InstallSource,
+    pub prefixes: Vec<PrefixNode>,
+}
+
+/// Group the tracked versions by source and prefix, both sorted
+pub fn build(config: &Config, active: Option<&str>) -> Vec<SourceNode> {
+    let mut sources: Vec<SourceNode> = Vec::new();
+
+    for entry in &config.versions {
+        let Some(primary) = entry.primary_path() else { continue };
+        let resolved = std::fs::canonicalize(primary).unwrap_or_else(|_| primary.to_path_buf());
+        let source = InstallSource::classify(&resolved);
+        let prefix = prefix_of(entry, primary);
+        let node = VersionNode {
+            version: entry.version.clone(),
+            binaries: entry.paths.clone(),
+            active: active == Some(entry.version.as_str()),
+        };
+
+        let source_node = match sources.iter().position(|s| s.source == source) {
+            Some(i) => &mut sources[i],
+            None => {
+                sources.push(SourceNode { source, prefixes: Vec::new() });
+                sources.last_mut().unwrap()
+            }
+        };
+        match source_node.prefixes.iter_mut().find(|p| p.prefix == prefix) {
+            Some(prefix_node) => prefix_node.versions.push(node),
+            None => source_node.prefixes.push(PrefixNode { prefix, versions: vec![node] }),
+        }
+    }
+
+    sources.sort_by_key(|s| s.source);
+    for source in &mut sources {
+        source.prefixes.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+    }
+    sources
+}
+
+/// The recorded install prefix, else the directory above the binary's `bin`
+fn prefix_of(entry: &VersionEntry, primary: &Path) -> PathBuf {
+    if let Some(prefix) = &entry.layout.prefix {
+        return prefix.clone();
+    }
+    let bin_dir = primary.parent().unwrap_or(primary);
+    match bin_dir.file_name() {
+        Some(name) if name == "bin" || name == "sbin" => bin_dir.parent().unwrap_or(bin_dir).to_path_buf(),
+        _ => bin_dir.to_path_buf(),
+    }
+}
+
+/// Draw the tree with box-drawing branches, one line per node; `plain` indents without them
+pub fn render(sources: &[SourceNode], plain: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for source in sources {
+        lines.push(source.source.name().to_string());
+        for (i, prefix) in source.prefixes.iter().enumerate() {
+            let (branch, indent) = branches(i, source.prefixes.len(), "", plain);
+            lines.push(format!("{}{}", branch, prefix.prefix.display()));
+
+            for (j, version) in prefix.versions.iter().enumerate() {
+                let (branch, indent) = branches(j, prefix.versions.len(), &indent, plain);
+                let marker = if version.active { " [ACTIVE]" } else { "" };
+                lines.push(format!("{}{}{}", branch, version.version, marker));
+
+                for (k, binary) in version.binaries.iter().enumerate() {
+                    let (branch, _) = branches(k, version.binaries.len(), &indent, plain);
+                    let name = binary.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                    lines.push(format!("{}{}", branch, name));
+                }
+            }
+        }
+    }
+
+    lines
+}
+
+/// The branch for child `i` of `count` under `indent`, and the indent for its own children
+fn branches(i: usize, count: usize, indent: &str, plain: bool) -> (String, String) {
+    if plain {
+        let indent = format!("{}    ", indent);
+        (indent.clone(), indent)
+    } else if i + 1 == count {
+        (format!("{}└── ", indent), format!("{}    ", indent))
+    } else {
+        (format!("{}├── ", indent), format!("{}│   ", indent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::InstallLayout;
+
+    fn entry(version: &str, paths: &[&str]) -> VersionEntry {
+        VersionEntry {
+            version: version.to_string(),
+            paths: paths.iter().map(PathBuf::from).collect(),
+            ..VersionEntry::default()
+        }
+    }
+
+    #[test]
+    fn test_build_and_render() {
+        let mut config = Config::default();
+        config.versions.push(entry("8.2.12", &["/usr/bin/php8.2", "/usr/sbin/php-fpm8.2"]));
+        config.versions.push(entry("7.4.33", &["/opt/php-7.4/bin/php"]));
+        config.versions.push(entry("8.1.27", &["/usr/bin/php8.1"]));
+        config.versions.push(VersionEntry {
+            layout: InstallLayout { prefix: Some(PathBuf::from("/opt/homebrew/Cellar/php/8.3.1")), ..Default::default() },
+            ..entry("8.3.1", &["/opt/homebrew/Cellar/php/8.3.1/bin/php"])
+        });
+
+        let tree = build(&config, Some("8.2.12"));
+        let sources: Vec<_> = tree.iter().map(|s| s.source).collect();
+        assert_eq!(sources, [InstallSource::Homebrew, InstallSource::System, InstallSource::Manual]);
+        assert_eq!(tree[1].prefixes.len(), 1);
+        assert_eq!(tree[1].prefixes[0].prefix, PathBuf::from("/usr"));
+        assert_eq!(tree[2].prefixes[0].prefix, PathBuf::from("/opt/php-7.4"));
+
+        assert_eq!(
+            render(&tree[1..2], false),
+            [
+                "system packages",
+                "└── /usr",
+                "    ├── 8.2.12 [ACTIVE]",
+                "    │   ├── php8.2",
+                "    │   └── php-fpm8.2",
+                "    └── 8.1.27",
+                "        └── php8.1",
+            ]
+        );
+        assert_eq!(render(&tree[2..], true), ["manual builds", "    /opt/php-7.4", "        7.4.33", "            php"]);
+    }
+}
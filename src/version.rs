@@ -2,8 +2,10 @@
 
 use anyhow::{anyhow, Result};
 use regex::Regex;
+use semver::{Version, VersionReq};
 use std::cmp::Ordering;
 use std::fmt;
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PhpVersion {
@@ -22,8 +24,9 @@ impl PhpVersion {
     }
 
     pub fn from_php_output(output: &str) -> Result<Self> {
-        // Regex to match PHP version like "PHP 8.2.12" or "PHP 8.4.0-dev"
-        let re = Regex::new(r"PHP\s+(\d+)\.(\d+)\.(\d+)").unwrap();
+        // Regex to match PHP version like "PHP 8.2.12" or "PHP 8.4.0-dev", compiled once per run
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| Regex::new(r"PHP\s+(\d+)\.(\d+)\.(\d+)").unwrap());
 
         if let Some(captures) = re.captures(output) {
             let major = captures[1].parse::<u32>()
@@ -39,7 +42,12 @@ impl PhpVersion {
         }
     }
 
+    /// Check whether this version matches a prefix (`8`, `8.2`, `8.2.12`) or a range (see `is_range`)
     pub fn matches(&self, pattern: &str) -> bool {
+        if is_range(pattern) {
+            return self.satisfies(pattern).unwrap_or(false);
+        }
+
         let parts: Vec<&str> = pattern.split('.').collect();
 
         match parts.len() {
@@ -81,6 +89,56 @@ impl PhpVersion {
     pub fn short_version(&self) -> String {
         format!("{}.{}", self.major, self.minor)
     }
+
+    /// Check whether this version satisfies a composer-style constraint
+    ///
+    /// Supports what PHP projects use in practice: `||` alternatives, space or
+    /// comma separated ranges, `^`, `~`, wildcards and plain comparisons.
+    pub fn satisfies(&self, constraint: &str) -> Result<bool> {
+        let version = Version::new(self.major as u64, self.minor as u64, self.patch as u64);
+
+        for alternative in constraint.split('|').map(str::trim).filter(|a| !a.is_empty()) {
+            if to_version_req(alternative)?.matches(&version) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// Check whether a pattern is a range (`^8.1`, `~8.2.3`, `>=8.0`, `8.*`, `^7.4 || ^8.0`)
+/// rather than a plain version prefix
+///
+/// Ranges can match several minor versions, so callers should pick the
+/// newest match instead of the first.
+pub fn is_range(pattern: &str) -> bool {
+    pattern.starts_with(['^', '~', '>', '<', '='])
+        || pattern.contains(['*', '|', ' ', ','])
+        || pattern.split('.').any(|part| part.eq_ignore_ascii_case("x"))
+}
+
+/// Translate one composer range (no `||`) into a semver requirement
+fn to_version_req(range: &str) -> Result<VersionReq> {
+    let invalid = || anyhow!("Unsupported PHP constraint '{}'", range);
+    let mut comparators = Vec::new();
+
+    for part in range.split([' ', ',']).filter(|p| !p.is_empty()) {
+        // Stability flags (`@dev`) don't matter for PHP itself
+        let part = part.split('@').next().unwrap_or(part);
+
+        // Composer's `~8.2` allows any later 8.x; semver's only 8.2.x
+        match part.strip_prefix('~') {
+            Some(rest) if rest.split('.').count() == 2 => {
+                let major = rest.split('.').next().unwrap_or(rest);
+                comparators.push(format!(">={}", rest));
+                comparators.push(format!("<{}", major.parse::<u64>().map_err(|_| invalid())? + 1));
+            }
+            _ => comparators.push(part.trim_start_matches('v').to_string()),
+        }
+    }
+
+    VersionReq::parse(&comparators.join(", ")).map_err(|_| invalid())
 }
 
 impl fmt::Display for PhpVersion {
@@ -148,6 +206,23 @@ mod tests {
         assert_eq!(version.patch, 0);
     }
 
+    #[test]
+    fn test_matches_ranges() {
+        let version = PhpVersion::new(8, 2, 12);
+
+        assert!(version.matches("^8.1"));
+        assert!(version.matches("~8.2.3"));
+        assert!(!version.matches("~8.2.13"));
+        assert!(version.matches(">=8.0"));
+        assert!(version.matches("8.*"));
+        assert!(version.matches("8.x"));
+        assert!(!version.matches("^7.4"));
+        assert!(!version.matches("^nonsense"));
+
+        assert!(is_range("^8.1") && is_range("8.*") && is_range(">=8.0 <8.3"));
+        assert!(!is_range("8.2") && !is_range("8"));
+    }
+
     #[test]
     fn test_parse_invalid_version() {
         let output = "Not a PHP version";
@@ -182,4 +257,21 @@ mod tests {
         let version = PhpVersion::new(8, 2, 12);
         assert_eq!(version.short_version(), "8.2");
     }
+
+    #[test]
+    fn test_satisfies_composer_constraints() {
+        let v = |major, minor, patch| PhpVersion::new(major, minor, patch);
+
+        assert!(v(8, 3, 4).satisfies("^8.1").unwrap());
+        assert!(!v(7, 4, 33).satisfies("^8.1").unwrap());
+        assert!(v(7, 4, 33).satisfies("^7.4 || ^8.0").unwrap());
+        assert!(v(8, 4, 0).satisfies("~8.2").unwrap());
+        assert!(!v(8, 3, 0).satisfies("~8.2.1").unwrap());
+        assert!(v(8, 3, 0).satisfies(">=8.1 <8.4").unwrap());
+        assert!(!v(8, 3, 0).satisfies(">=8.1, <8.3").unwrap());
+        assert!(v(8, 2, 12).satisfies("8.2.*").unwrap());
+        assert!(v(8, 2, 0).satisfies("^7.4|^8.0").unwrap());
+        assert!(v(5, 6, 0).satisfies("*").unwrap());
+        assert!(v(8, 2, 0).satisfies("nonsense").is_err());
+    }
 }
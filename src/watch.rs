@@ -0,0 +1,122 @@
+// Project watch module
+//
+// `php-switcher watch` keeps a long-lived dev session on the version a
+// project asks for. It watches the project directory (and the directories
+// holding its nearest pin file and composer.json, when those are further
+// up) and calls back whenever a change there resolves to a different
+// version than before.
+
+use crate::config::{self, Config};
+use crate::project::{self, PinFile};
+use crate::{composer, switcher};
+use anyhow::{anyhow, Result};
+use notify::{RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Editors save in bursts (temp file, rename, chmod); changes this close together are one change
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// The version `use` would pick in `dir`: its pin, else composer.json's `require.php`
+pub fn wanted_version(config: &Config, dir: &Path) -> Result<Option<String>> {
+    if let Some(pin) = project::nearest_pin(config, dir) {
+        return Ok(Some(pin.version));
+    }
+    Ok(switcher::resolve_composer_constraint(config, dir)?.map(|found| found.version))
+}
+
+/// Whether a changed file can affect the wanted version
+pub fn is_relevant(path: &Path, pin_files: &[PinFile]) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return false };
+    name == composer::COMPOSER_FILE || pin_files.iter().any(|file| file.file_name() == name)
+}
+
+/// Directories to watch for `dir`: itself and wherever its pin and composer.json live
+pub fn watched_dirs(config: &Config, dir: &Path) -> BTreeSet<PathBuf> {
+    let mut dirs = BTreeSet::from([dir.to_path_buf()]);
+    if let Some(pin) = project::find_pin(dir, config.settings.pin_files()) {
+        dirs.insert(pin.dir);
+    }
+    if let Some(parent) = composer::find_composer_json(dir).as_deref().and_then(Path::parent) {
+        dirs.insert(parent.to_path_buf());
+    }
+    dirs
+}
+
+/// Watch `dir` until interrupted, calling `on_change` with each newly wanted version
+///
+/// Errors resolving the version (a constraint nothing satisfies, say) are
+/// passed to `on_error` and watching goes on; the next edit may fix them.
+pub fn watch(
+    dir: &Path,
+    mut on_change: impl FnMut(&str) -> Result<()>,
+    mut on_error: impl FnMut(anyhow::Error),
+) -> Result<()> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|e| anyhow!("Failed to start watching {}: {}", dir.display(), e))?;
+
+    let config = config::load_config()?;
+    for watched in watched_dirs(&config, dir) {
+        watcher
+            .watch(&watched, RecursiveMode::NonRecursive)
+            .map_err(|e| anyhow!("Failed to watch {}: {}", watched.display(), e))?;
+    }
+
+    let mut current = wanted_version(&config, dir)?;
+    while let Ok(event) = events.recv() {
+        let mut relevant = event.is_ok_and(|e| e.paths.iter().any(|p| is_relevant(p, config.settings.pin_files())));
+        while let Ok(event) = events.recv_timeout(SETTLE_TIME) {
+            relevant |= event.is_ok_and(|e| e.paths.iter().any(|p| is_relevant(p, config.settings.pin_files())));
+        }
+        if !relevant {
+            continue;
+        }
+
+        // Re-read the config too: `php-switcher pin` may have changed alongside
+        let wanted = config::load_config().and_then(|config| wanted_version(&config, dir));
+        match wanted {
+            Ok(Some(version)) if current.as_deref() != Some(version.as_str()) => {
+                on_change(&version).unwrap_or_else(&mut on_error);
+                current = Some(version);
+            }
+            Ok(_) => {}
+            Err(e) => on_error(e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::DEFAULT_PIN_FILES;
+
+    #[test]
+    fn test_is_relevant() {
+        assert!(is_relevant(Path::new("/work/app/.php-version"), DEFAULT_PIN_FILES));
+        assert!(is_relevant(Path::new("/work/app/.tool-versions"), DEFAULT_PIN_FILES));
+        assert!(is_relevant(Path::new("/work/app/composer.json"), DEFAULT_PIN_FILES));
+        assert!(!is_relevant(Path::new("/work/app/.tool-versions"), &[PinFile::PhpVersion]));
+        assert!(!is_relevant(Path::new("/work/app/composer.lock"), DEFAULT_PIN_FILES));
+    }
+
+    #[test]
+    fn test_wanted_version_and_watched_dirs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let app = root.join("packages/app");
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::write(root.join(".php-version"), "8.2\n").unwrap();
+
+        let config = Config::default();
+        assert_eq!(wanted_version(&config, &app).unwrap(), Some("8.2".to_string()));
+        assert_eq!(watched_dirs(&config, &app), BTreeSet::from([root.to_path_buf(), app.clone()]));
+
+        std::fs::write(app.join(".php-version"), "8.3\n").unwrap();
+        assert_eq!(wanted_version(&config, &app).unwrap(), Some("8.3".to_string()));
+    }
+}
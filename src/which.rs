@@ -0,0 +1,101 @@
+// PATH index module
+//
+// Looking for a tool by joining its name onto every PATH directory costs one
+// stat per tool per directory, and the tool scan asks for dozens of names
+// over PATHs that are often 20+ entries long. The index lists each directory
+// once instead, and lookups are hash lookups. Indexes are cached for a few
+// seconds, so a command that scans several times only reads PATH once.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a cached index is reused for the same directories
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// The files in a list of directories, by name, in directory order
+#[derive(Debug, Default)]
+pub struct PathIndex {
+    files: HashMap<OsString, Vec<PathBuf>>,
+}
+
+impl PathIndex {
+    /// Read each directory once; missing or unreadable ones are skipped
+    pub fn build(dirs: &[PathBuf]) -> Self {
+        let mut files: HashMap<OsString, Vec<PathBuf>> = HashMap::new();
+
+        for dir in dirs {
+            let Ok(entries) = std::fs::read_dir(dir) else { continue };
+            for entry in entries.flatten() {
+                // Follows symlinks, which is how most tools land on PATH
+                if !entry.path().is_file() {
+                    continue;
+                }
+                files.entry(entry.file_name()).or_default().push(entry.path());
+            }
+        }
+
+        PathIndex { files }
+    }
+
+    /// The first file with this name, as a PATH lookup would find it
+    pub fn lookup(&self, name: &str) -> Option<&Path> {
+        self.all(name).first().map(PathBuf::as_path)
+    }
+
+    /// Every file with this name, in directory order
+    pub fn all(&self, name: &str) -> &[PathBuf] {
+        self.files.get(std::ffi::OsStr::new(name)).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+struct Cached {
+    built: Instant,
+    index: Arc<PathIndex>,
+}
+
+static CACHE: Mutex<Option<HashMap<Vec<PathBuf>, Cached>>> = Mutex::new(None);
+
+/// An index of these directories, reused if one was built for them moments ago
+pub fn cached(dirs: &[PathBuf]) -> Arc<PathIndex> {
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let cache = cache.get_or_insert_with(HashMap::new);
+    cache.retain(|_, cached| cached.built.elapsed() < CACHE_TTL);
+    if let Some(cached) = cache.get(dirs) {
+        return cached.index.clone();
+    }
+
+    let index = Arc::new(PathIndex::build(dirs));
+    cache.insert(dirs.to_vec(), Cached { built: Instant::now(), index: index.clone() });
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_order_and_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let first = temp_dir.path().join("first");
+        let second = temp_dir.path().join("second");
+        std::fs::create_dir_all(first.join("composer")).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        std::fs::write(second.join("composer"), "").unwrap();
+        std::fs::write(second.join("phpunit"), "").unwrap();
+        std::fs::write(first.join("phpunit"), "").unwrap();
+
+        let dirs = vec![first.clone(), temp_dir.path().join("missing"), second.clone()];
+        let index = PathIndex::build(&dirs);
+        // Directories don't count, so `composer` comes from the second one
+        assert_eq!(index.lookup("composer"), Some(second.join("composer").as_path()));
+        assert_eq!(index.all("phpunit"), [first.join("phpunit"), second.join("phpunit")]);
+        assert_eq!(index.lookup("psalm"), None);
+
+        let cached_index = cached(&dirs);
+        assert!(Arc::ptr_eq(&cached_index, &cached(&dirs)));
+        assert!(!Arc::ptr_eq(&cached_index, &cached(&dirs[..1])));
+    }
+}